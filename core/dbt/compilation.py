@@ -436,13 +436,13 @@ class Linker:
         index 0, 1, 2,..., n-1 for compactness"""
         graph_nodes = dict()
         index_dict = dict()
-        for node_index, node_name in enumerate(self.graph.nodes()):
+        for node_index, node_name in enumerate(self.graph.nodes_ordered()):
             index_dict[node_name] = node_index
             data = manifest.expect(node_name).to_dict(omit_none=True)
             graph_nodes[node_index] = {"name": node_name, "type": data["resource_type"]}
 
         for node_index, node in graph_nodes.items():
-            successors = [index_dict[n] for n in self.graph.successors(node["name"])]
+            successors = [index_dict[n] for n in self.graph.successors_ordered(node["name"])]
             if successors:
                 node["succ"] = successors
 
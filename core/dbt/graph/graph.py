@@ -1,4 +1,4 @@
-from typing import Iterable, Iterator, List, NewType, Optional, Set
+from typing import Any, Dict, Iterable, Iterator, List, NewType, Optional, Set, Tuple
 
 from dbt_common.exceptions import DbtInternalError
 import dbt_rs
@@ -38,9 +38,10 @@ class Graph:
         self.graph = graph
 
     @classmethod
-    def empty(cls) -> "Graph":
-        """Create an empty graph."""
-        return cls(dbt_rs.DbtGraph())
+    def empty(cls, acyclic: bool = False) -> "Graph":
+        """Create an empty graph. In acyclic mode, adding an edge that would
+        create a cycle raises immediately."""
+        return cls(dbt_rs.DbtGraph(acyclic))
 
     @classmethod
     def from_json(cls, json_str: str) -> "Graph":
@@ -54,6 +55,11 @@ class Graph:
         rust_graph = dbt_rs.build_graph_from_global_manifest()
         return cls(rust_graph)
 
+    @classmethod
+    def from_manifest_handle(cls, manifest: "dbt_rs.DbtManifest") -> "Graph":
+        """Build graph from a manifest handle returned by dbt_rs.load_manifest."""
+        return cls(dbt_rs.build_graph_from_manifest_handle(manifest))
+
     def find_cycle(self):
         """Detect cycle in graph. Returns cycle path or None."""
         return self.graph.find_cycle()
@@ -65,7 +71,30 @@ class Graph:
         return EdgeView(self.graph.edges())
 
     def __iter__(self) -> Iterator[UniqueId]:
-        return iter(self.graph.nodes())
+        return iter(self.graph.nodes_ordered())
+
+    # Ordered variants return lists sorted by unique_id (order="unique_id") or
+    # topologically with unique_id tie-breaking (order="topological"), so
+    # output built from them is reproducible between runs.
+
+    def nodes_ordered(self, order: str = "unique_id") -> List[UniqueId]:
+        return self.graph.nodes_ordered(order)
+
+    def edges_ordered(self, order: str = "unique_id") -> List[Tuple[UniqueId, UniqueId]]:
+        return self.graph.edges_ordered(order)
+
+    def ancestors_ordered(
+        self, node: UniqueId, max_depth: Optional[int] = None, order: str = "unique_id"
+    ) -> List[UniqueId]:
+        return self.graph.ancestors_ordered(node, max_depth, order)
+
+    def descendants_ordered(
+        self, node: UniqueId, max_depth: Optional[int] = None, order: str = "unique_id"
+    ) -> List[UniqueId]:
+        return self.graph.descendants_ordered(node, max_depth, order)
+
+    def successors_ordered(self, node: UniqueId, order: str = "unique_id") -> List[UniqueId]:
+        return self.graph.successors_ordered(node, order)
 
     def ancestors(self, node: UniqueId, max_depth: Optional[int] = None) -> Set[UniqueId]:
         """Returns all nodes having a path to `node` in `graph`"""
@@ -139,3 +168,37 @@ class Graph:
 
     def __len__(self):
         return self.graph.number_of_nodes()
+
+    def node_metrics(self) -> List[Dict[str, Any]]:
+        """Per-node fan-in/out, depth, height, reach and betweenness."""
+        return self.graph.node_metrics()
+
+    def package_metrics(self) -> List[Dict[str, Any]]:
+        """Structure metrics aggregated per package."""
+        return self.graph.package_metrics()
+
+    def hotspots(self, top_n: int = 20) -> List[Dict[str, Any]]:
+        """Nodes whose change impacts the most downstream nodes."""
+        return self.graph.hotspots(top_n)
+
+    def metrics_json(self) -> str:
+        return self.graph.metrics_json()
+
+    def partition(
+        self,
+        shard_count: int,
+        selected: Optional[Iterable[UniqueId]] = None,
+        weights: Optional[Dict[UniqueId, float]] = None,
+    ) -> Dict[str, Any]:
+        """Split the graph into dependency-respecting shards for parallel CI runs.
+
+        Returns per-shard node lists (in execution order), the cross-shard
+        edges, and the (upstream, downstream) shard pairs they imply.
+        """
+        nodes = set(selected) if selected is not None else None
+        return self.graph.partition(shard_count, nodes, weights)
+
+    def diff(self, other: "Graph") -> Dict[str, Any]:
+        """Structural changes going from this graph to `other`, including the
+        nodes whose upstream lineage changed."""
+        return dbt_rs.diff_graphs(self.graph, other.graph)
@@ -0,0 +1,1056 @@
+//! Column-level lineage from compiled SQL.
+//!
+//! Each node's `compiled_code` is parsed with `sqlparser` in the adapter's
+//! dialect, and every output column is traced to the upstream columns it
+//! reads, following CTEs, subqueries and set operations until a table that
+//! `RelationIndex` resolves to a manifest node or source. `*` expands to the
+//! upstream's lineage columns, or its documented `columns`.
+//!
+//! A node's lineage is `complete: false` when its SQL does not parse, or
+//! when part of it cannot be traced, such as a `*` over a table with
+//! unknown columns.
+
+use crate::manifest::OxideManifest;
+use crate::sql_analysis::{name_parts, parse_sql, Dialect, RelationIndex};
+use serde::Serialize;
+use sqlparser::ast::{
+    ExcludeSelectItem, Expr, FunctionArg, FunctionArgExpr, FunctionArguments, Ident, ObjectName,
+    Query, Select, SelectItem, SetExpr, Statement, TableAlias, TableFactor, TableWithJoins, Visit,
+    Visitor, WildcardAdditionalOptions,
+};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::ControlFlow;
+
+/// How an output column is derived from an upstream column, from weakest
+/// to strongest. Through CTEs the strongest step wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transformation {
+    Passthrough,
+    Rename,
+    Expression,
+    Aggregate,
+}
+
+impl Transformation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transformation::Passthrough => "passthrough",
+            Transformation::Rename => "rename",
+            Transformation::Expression => "expression",
+            Transformation::Aggregate => "aggregate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct ColumnRef {
+    pub unique_id: String,
+    pub column: String,
+}
+
+impl ColumnRef {
+    pub fn new(unique_id: &str, column: &str) -> Self {
+        ColumnRef {
+            unique_id: unique_id.to_string(),
+            column: column.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnSource {
+    #[serde(flatten)]
+    pub column: ColumnRef,
+    pub kind: Transformation,
+}
+
+/// An output column of a query, lowercased, with the upstream columns it
+/// reads.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputColumn {
+    pub name: String,
+    pub sources: Vec<ColumnSource>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NodeLineage {
+    pub columns: Vec<OutputColumn>,
+    /// False when part of the SQL could not be traced, e.g. a `*` over a
+    /// table with unknown columns or an ambiguous unqualified column.
+    pub complete: bool,
+}
+
+/// An edge of the column graph: `target` is derived from `source`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ColumnEdge {
+    pub source: ColumnRef,
+    pub target: ColumnRef,
+    pub kind: Transformation,
+}
+
+const AGGREGATES: [&str; 41] = [
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "array_agg",
+    "string_agg",
+    "listagg",
+    "group_concat",
+    "median",
+    "mode",
+    "stddev",
+    "stddev_pop",
+    "stddev_samp",
+    "variance",
+    "var_pop",
+    "var_samp",
+    "any_value",
+    "count_if",
+    "countif",
+    "sum_if",
+    "approx_count_distinct",
+    "approx_distinct",
+    "bool_and",
+    "bool_or",
+    "bit_and",
+    "bit_or",
+    "every",
+    "percentile_cont",
+    "percentile_disc",
+    "approx_percentile",
+    "approx_quantiles",
+    "object_agg",
+    "array_concat_agg",
+    "logical_and",
+    "logical_or",
+    "corr",
+    "covar_pop",
+    "covar_samp",
+    "arbitrary",
+    "hll_count",
+];
+
+/// Functions whose first argument may be a bare date part, e.g.
+/// `datediff(day, a, b)`.
+const DATE_PART_FUNCTIONS: [&str; 7] = [
+    "date_trunc",
+    "datediff",
+    "dateadd",
+    "date_part",
+    "timestampdiff",
+    "timestampadd",
+    "last_day",
+];
+
+const NILADIC_FUNCTIONS: [&str; 8] = [
+    "current_timestamp",
+    "current_time",
+    "localtime",
+    "localtimestamp",
+    "current_user",
+    "session_user",
+    "sysdate",
+    "current_schema",
+];
+
+fn lower(ident: &Ident) -> String {
+    ident.value.to_lowercase()
+}
+
+fn is_niladic(ident: &Ident) -> bool {
+    ident.quote_style.is_none() && NILADIC_FUNCTIONS.contains(&lower(ident).as_str())
+}
+
+fn function_name(name: &ObjectName) -> String {
+    name.0.last().map(lower).unwrap_or_default()
+}
+
+/// The column a bare column reference such as `c` or `t.c` names.
+fn column_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) if !is_niladic(ident) => Some(lower(ident)),
+        Expr::CompoundIdentifier(parts) => parts.last().map(lower),
+        _ => None,
+    }
+}
+
+/// Add `source` to `sources`, keeping the strongest kind per column.
+fn add_source(sources: &mut Vec<ColumnSource>, source: ColumnSource) {
+    match sources.iter_mut().find(|s| s.column == source.column) {
+        Some(existing) => existing.kind = existing.kind.max(source.kind),
+        None => sources.push(source),
+    }
+}
+
+fn compose(sources: &[ColumnSource], kind: Transformation) -> Vec<ColumnSource> {
+    sources
+        .iter()
+        .map(|source| ColumnSource {
+            column: source.column.clone(),
+            kind: source.kind.max(kind),
+        })
+        .collect()
+}
+
+/// Rename outputs positionally to an explicit column list, as in
+/// `cte (a, b) as (...)` or `(...) as t (a, b)`.
+fn rename_columns(columns: &mut [OutputColumn], alias: &TableAlias) {
+    for (column, name) in columns.iter_mut().zip(&alias.columns) {
+        let name = lower(&name.name);
+        if column.name != name {
+            column.sources = compose(&column.sources, Transformation::Rename);
+            column.name = name;
+        }
+    }
+}
+
+/// A `FROM` item in scope.
+struct Source {
+    /// The alias, or the table name when there is none.
+    name: Option<String>,
+    /// Known columns and their lineage.
+    columns: Vec<OutputColumn>,
+    /// The manifest resource a table resolves to; any column read from it
+    /// is taken to exist.
+    unique_id: Option<String>,
+}
+
+impl Source {
+    fn column(&self, name: &str) -> Option<Vec<ColumnSource>> {
+        if let Some(column) = self.columns.iter().find(|c| c.name == name) {
+            return Some(column.sources.clone());
+        }
+        let unique_id = self.unique_id.as_ref()?;
+        Some(vec![ColumnSource {
+            column: ColumnRef::new(unique_id, name),
+            kind: Transformation::Passthrough,
+        }])
+    }
+
+    fn has_column(&self, name: &str) -> bool {
+        self.columns.iter().any(|c| c.name == name)
+    }
+}
+
+type Ctes = HashMap<String, Vec<OutputColumn>>;
+
+/// Traces the columns of one node's SQL.
+struct Analyzer<'a> {
+    index: &'a RelationIndex,
+    dialect: Dialect,
+    /// Columns of upstream resources, lowercased.
+    known_columns: &'a dyn Fn(&str) -> Option<Vec<String>>,
+    complete: Cell<bool>,
+}
+
+impl Analyzer<'_> {
+    fn incomplete(&self) {
+        self.complete.set(false);
+    }
+
+    fn query(&self, query: &Query, ctes: &Ctes) -> Vec<OutputColumn> {
+        let Some(with) = &query.with else {
+            return self.set_expression(&query.body, ctes);
+        };
+        let mut ctes = ctes.clone();
+        for cte in &with.cte_tables {
+            let mut columns = self.query(&cte.query, &ctes);
+            rename_columns(&mut columns, &cte.alias);
+            ctes.insert(lower(&cte.alias.name), columns);
+        }
+        self.set_expression(&query.body, &ctes)
+    }
+
+    /// `UNION`/`INTERSECT`/`EXCEPT` branches, merged by position and named
+    /// by the first branch.
+    fn set_expression(&self, body: &SetExpr, ctes: &Ctes) -> Vec<OutputColumn> {
+        match body {
+            SetExpr::Select(select) => self.select(select, ctes),
+            SetExpr::Query(query) => self.query(query, ctes),
+            SetExpr::SetOperation { left, right, .. } => {
+                let mut columns = self.set_expression(left, ctes);
+                for (column, other) in columns.iter_mut().zip(self.set_expression(right, ctes)) {
+                    for source in other.sources {
+                        add_source(&mut column.sources, source);
+                    }
+                }
+                columns
+            }
+            _ => {
+                self.incomplete();
+                Vec::new()
+            }
+        }
+    }
+
+    fn select(&self, select: &Select, ctes: &Ctes) -> Vec<OutputColumn> {
+        let mut sources = Vec::new();
+        for table in &select.from {
+            self.add_table(table, ctes, &mut sources);
+        }
+        let mut columns = Vec::new();
+        for item in &select.projection {
+            let position = columns.len();
+            match item {
+                SelectItem::UnnamedExpr(expr) => {
+                    columns.push(self.projection_item(expr, None, position, &sources, ctes))
+                }
+                SelectItem::ExprWithAlias { expr, alias } => columns.push(self.projection_item(
+                    expr,
+                    Some(lower(alias)),
+                    position,
+                    &sources,
+                    ctes,
+                )),
+                SelectItem::Wildcard(options) => columns.extend(self.star(None, options, &sources)),
+                SelectItem::QualifiedWildcard(name, options) => {
+                    let qualifier = name.0.last().map(lower);
+                    columns.extend(self.star(qualifier, options, &sources))
+                }
+            }
+        }
+        columns
+    }
+
+    fn add_table(&self, table: &TableWithJoins, ctes: &Ctes, sources: &mut Vec<Source>) {
+        self.add_table_factor(&table.relation, ctes, sources);
+        for join in &table.joins {
+            self.add_table_factor(&join.relation, ctes, sources);
+        }
+    }
+
+    fn add_table_factor(&self, factor: &TableFactor, ctes: &Ctes, sources: &mut Vec<Source>) {
+        let empty = || Source {
+            name: None,
+            columns: Vec::new(),
+            unique_id: None,
+        };
+        let (mut source, alias) = match factor {
+            TableFactor::Table {
+                name,
+                alias,
+                args: None,
+                ..
+            } => (self.table(name, ctes), alias),
+            TableFactor::Derived {
+                subquery, alias, ..
+            } => (
+                Source {
+                    name: None,
+                    columns: self.query(subquery, ctes),
+                    unique_id: None,
+                },
+                alias,
+            ),
+            // A parenthesized join: `(a join b on ...)`.
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => {
+                self.add_table(table_with_joins, ctes, sources);
+                return;
+            }
+            // Table functions such as `unnest(...)`.
+            TableFactor::Table { alias, .. }
+            | TableFactor::TableFunction { alias, .. }
+            | TableFactor::Function { alias, .. }
+            | TableFactor::UNNEST { alias, .. }
+            | TableFactor::JsonTable { alias, .. }
+            | TableFactor::OpenJsonTable { alias, .. } => (empty(), alias),
+            _ => {
+                self.incomplete();
+                sources.push(empty());
+                return;
+            }
+        };
+        if let Some(alias) = alias {
+            source.name = Some(lower(&alias.name));
+            rename_columns(&mut source.columns, alias);
+        }
+        sources.push(source);
+    }
+
+    /// A table or CTE read by name.
+    fn table(&self, name: &ObjectName, ctes: &Ctes) -> Source {
+        let parts = name_parts(name, self.dialect);
+        if let [name] = parts.as_slice() {
+            if let Some(columns) = ctes.get(name) {
+                return Source {
+                    name: Some(name.clone()),
+                    columns: columns.clone(),
+                    unique_id: None,
+                };
+            }
+        }
+        let unique_id = self.index.resolve(&parts);
+        let columns = unique_id
+            .as_deref()
+            .and_then(|unique_id| {
+                let names = (self.known_columns)(unique_id)?;
+                Some(
+                    names
+                        .into_iter()
+                        .map(|name| OutputColumn {
+                            sources: vec![ColumnSource {
+                                column: ColumnRef::new(unique_id, &name),
+                                kind: Transformation::Passthrough,
+                            }],
+                            name,
+                        })
+                        .collect(),
+                )
+            })
+            .unwrap_or_default();
+        Source {
+            name: parts.last().cloned(),
+            columns,
+            unique_id,
+        }
+    }
+
+    /// The sources in scope that a column reference can read from.
+    fn resolve_column(
+        &self,
+        qualifier: Option<&str>,
+        column: &str,
+        sources: &[Source],
+    ) -> Vec<ColumnSource> {
+        let found = match qualifier {
+            Some(qualifier) => sources
+                .iter()
+                .find(|s| s.name.as_deref() == Some(qualifier))
+                .and_then(|s| s.column(column)),
+            None => {
+                // The one source known to have the column, else the one
+                // table that may have it, else the only source.
+                let with_column: Vec<&Source> =
+                    sources.iter().filter(|s| s.has_column(column)).collect();
+                let open: Vec<&Source> = sources.iter().filter(|s| s.unique_id.is_some()).collect();
+                let source = match (with_column.as_slice(), open.as_slice()) {
+                    ([source], _) | ([], [source]) => Some(*source),
+                    ([], _) if sources.len() == 1 => sources.first(),
+                    _ => None,
+                };
+                source.and_then(|source| source.column(column))
+            }
+        };
+        found.unwrap_or_else(|| {
+            if !sources.is_empty() {
+                self.incomplete();
+            }
+            Vec::new()
+        })
+    }
+
+    /// Upstream columns an expression reads, and whether it aggregates.
+    fn expression(
+        &self,
+        expr: &Expr,
+        sources: &[Source],
+        ctes: &Ctes,
+    ) -> (Vec<ColumnSource>, bool) {
+        let mut visitor = ExpressionColumns {
+            analyzer: self,
+            sources,
+            ctes,
+            found: Vec::new(),
+            aggregate: false,
+            skip: HashSet::new(),
+            depth: 0,
+        };
+        let _ = expr.visit(&mut visitor);
+        (visitor.found, visitor.aggregate)
+    }
+
+    fn projection_item(
+        &self,
+        expr: &Expr,
+        alias: Option<String>,
+        position: usize,
+        sources: &[Source],
+        ctes: &Ctes,
+    ) -> OutputColumn {
+        let bare = column_name(expr);
+        let (sources_read, aggregate) = self.expression(expr, sources, ctes);
+        let kind = match (&bare, &alias) {
+            (Some(column), Some(alias)) if column != alias => Transformation::Rename,
+            (Some(_), _) => Transformation::Passthrough,
+            _ if aggregate => Transformation::Aggregate,
+            _ => Transformation::Expression,
+        };
+        // Unaliased function calls are named after the function, as in
+        // Postgres: `count(*)` is `count`. Other expressions get a name by
+        // position, which only matters for the first branch of a union.
+        let name = alias
+            .or(bare)
+            .or_else(|| match expr {
+                Expr::Function(function) => Some(function_name(&function.name)),
+                _ => None,
+            })
+            .unwrap_or_else(|| format!("_col{}", position));
+        OutputColumn {
+            name,
+            sources: compose(&sources_read, kind),
+        }
+    }
+
+    /// Expand `*`, `t.*` and `* except (...)`/`* exclude (...)`.
+    fn star(
+        &self,
+        qualifier: Option<String>,
+        options: &WildcardAdditionalOptions,
+        sources: &[Source],
+    ) -> Vec<OutputColumn> {
+        let mut excluded = HashSet::new();
+        match &options.opt_exclude {
+            Some(ExcludeSelectItem::Single(name)) => {
+                excluded.insert(lower(name));
+            }
+            Some(ExcludeSelectItem::Multiple(names)) => excluded.extend(names.iter().map(lower)),
+            None => {}
+        }
+        if let Some(except) = &options.opt_except {
+            excluded.insert(lower(&except.first_element));
+            excluded.extend(except.additional_elements.iter().map(lower));
+        }
+        // `ilike`, `replace` and `rename` change which columns come out, or
+        // how they are derived.
+        if options.opt_ilike.is_some()
+            || options.opt_replace.is_some()
+            || options.opt_rename.is_some()
+        {
+            self.incomplete();
+        }
+        let mut columns = Vec::new();
+        for source in sources {
+            if qualifier.is_some() && source.name != qualifier {
+                continue;
+            }
+            if source.columns.is_empty() {
+                self.incomplete();
+            }
+            columns.extend(
+                source
+                    .columns
+                    .iter()
+                    .filter(|c| !excluded.contains(&c.name))
+                    .cloned(),
+            );
+        }
+        columns
+    }
+}
+
+/// Collects the upstream columns an expression reads. A subquery is traced
+/// on its own and contributes the sources of its first column.
+struct ExpressionColumns<'a, 'b> {
+    analyzer: &'a Analyzer<'b>,
+    sources: &'a [Source],
+    ctes: &'a Ctes,
+    found: Vec<ColumnSource>,
+    aggregate: bool,
+    /// Identifiers that are not column references, such as the `day` of
+    /// `datediff(day, a, b)`.
+    skip: HashSet<*const Expr>,
+    /// How many subqueries deep the visitor is.
+    depth: usize,
+}
+
+impl Visitor for ExpressionColumns<'_, '_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        if self.depth == 0 {
+            let columns = self.analyzer.query(query, self.ctes);
+            for source in columns.into_iter().take(1).flat_map(|c| c.sources) {
+                add_source(&mut self.found, source);
+            }
+        }
+        self.depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if self.depth > 0 || self.skip.contains(&(expr as *const Expr)) {
+            return ControlFlow::Continue(());
+        }
+        let (qualifier, column) = match expr {
+            Expr::Identifier(ident) if !is_niladic(ident) => (None, lower(ident)),
+            Expr::CompoundIdentifier(parts) => match parts.as_slice() {
+                [.., qualifier, column] => (Some(lower(qualifier)), lower(column)),
+                _ => return ControlFlow::Continue(()),
+            },
+            Expr::Function(function) => {
+                let name = function_name(&function.name);
+                if AGGREGATES.contains(&name.as_str()) && function.over.is_none() {
+                    self.aggregate = true;
+                }
+                if let FunctionArguments::List(list) = &function.args {
+                    for (i, arg) in list.args.iter().enumerate() {
+                        match arg {
+                            FunctionArg::ExprNamed { name, .. } => {
+                                self.skip.insert(name);
+                            }
+                            FunctionArg::Unnamed(FunctionArgExpr::Expr(
+                                part @ Expr::Identifier(_),
+                            )) if i == 0 && DATE_PART_FUNCTIONS.contains(&name.as_str()) => {
+                                self.skip.insert(part);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                return ControlFlow::Continue(());
+            }
+            _ => return ControlFlow::Continue(()),
+        };
+        let found = self
+            .analyzer
+            .resolve_column(qualifier.as_deref(), &column, self.sources);
+        for source in found {
+            add_source(&mut self.found, source);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The lineage of a single query. `known_columns` gives the columns of an
+/// upstream unique_id, lowercased, when known. SQL that does not parse, or
+/// is not a query, has no columns.
+pub fn query_lineage(
+    sql: &str,
+    dialect: Dialect,
+    index: &RelationIndex,
+    known_columns: &dyn Fn(&str) -> Option<Vec<String>>,
+) -> NodeLineage {
+    let analyzer = Analyzer {
+        index,
+        dialect,
+        known_columns,
+        complete: Cell::new(true),
+    };
+    let columns = match parse_sql(sql, dialect).ok().as_deref() {
+        Some([Statement::Query(query), ..]) => analyzer.query(query, &Ctes::new()),
+        _ => Vec::new(),
+    };
+    NodeLineage {
+        complete: analyzer.complete.get() && !columns.is_empty(),
+        columns,
+    }
+}
+
+/// Documented column names of a node or source, lowercased.
+fn documented_columns(manifest: &OxideManifest, unique_id: &str) -> Option<Vec<String>> {
+    let extra = match manifest.nodes.get(unique_id) {
+        Some(node) => &node.extra,
+        None => &manifest.sources.get(unique_id)?.extra,
+    };
+    let columns = extra.get("columns")?.as_object()?;
+    (!columns.is_empty()).then(|| columns.keys().map(|name| name.to_lowercase()).collect())
+}
+
+/// Column lineage of manifest nodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ColumnLineage {
+    pub nodes: BTreeMap<String, NodeLineage>,
+}
+
+impl ColumnLineage {
+    /// Trace every node with compiled code, upstream nodes first so that
+    /// `*` can expand to their columns.
+    pub fn build(manifest: &OxideManifest, dialect: Dialect) -> Self {
+        let index = RelationIndex::new(manifest, dialect);
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut ids: Vec<&String> = manifest.nodes.keys().collect();
+        ids.sort();
+        for unique_id in ids {
+            let mut stack = vec![(unique_id.as_str(), false)];
+            while let Some((id, expanded)) = stack.pop() {
+                if expanded {
+                    order.push(id);
+                    continue;
+                }
+                if !visited.insert(id) {
+                    continue;
+                }
+                stack.push((id, true));
+                if let Some(node) = manifest.nodes.get(id) {
+                    for dep in node.depends_on.nodes.iter().rev() {
+                        stack.push((dep.as_str(), false));
+                    }
+                }
+            }
+        }
+
+        let mut lineage = ColumnLineage::default();
+        for unique_id in order {
+            let Some(sql) = manifest
+                .nodes
+                .get(unique_id)
+                .and_then(|node| node.compiled_code.as_deref())
+            else {
+                continue;
+            };
+            let known = |upstream: &str| -> Option<Vec<String>> {
+                match lineage.nodes.get(upstream) {
+                    Some(node) if !node.columns.is_empty() => {
+                        Some(node.columns.iter().map(|c| c.name.clone()).collect())
+                    }
+                    _ => documented_columns(manifest, upstream),
+                }
+            };
+            let node = query_lineage(sql, dialect, &index, &known);
+            lineage.nodes.insert(unique_id.to_string(), node);
+        }
+        lineage
+    }
+
+    pub fn edges(&self) -> Vec<ColumnEdge> {
+        let mut edges: Vec<ColumnEdge> = self
+            .nodes
+            .iter()
+            .flat_map(|(unique_id, node)| {
+                node.columns.iter().flat_map(move |column| {
+                    column.sources.iter().map(move |source| ColumnEdge {
+                        source: source.column.clone(),
+                        target: ColumnRef::new(unique_id, &column.name),
+                        kind: source.kind,
+                    })
+                })
+            })
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    fn traverse(&self, start: ColumnRef, upstream: bool) -> Vec<ColumnEdge> {
+        let edges = self.edges();
+        let mut adjacent: HashMap<&ColumnRef, Vec<&ColumnEdge>> = HashMap::new();
+        for edge in &edges {
+            let key = if upstream { &edge.target } else { &edge.source };
+            adjacent.entry(key).or_default().push(edge);
+        }
+        let mut seen = BTreeSet::new();
+        let mut visited = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(column) = queue.pop_front() {
+            for edge in adjacent.get(&column).into_iter().flatten() {
+                seen.insert((*edge).clone());
+                let next = if upstream { &edge.source } else { &edge.target };
+                if visited.insert(next.clone()) {
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+        seen.into_iter().collect()
+    }
+
+    /// Where a column comes from: every edge on its upstream paths.
+    pub fn upstream(&self, unique_id: &str, column: &str) -> Vec<ColumnEdge> {
+        self.traverse(ColumnRef::new(unique_id, &column.to_lowercase()), true)
+    }
+
+    /// What reads a column: every edge on its downstream paths, e.g. to
+    /// see what breaks if it is dropped.
+    pub fn downstream(&self, unique_id: &str, column: &str) -> Vec<ColumnEdge> {
+        self.traverse(ColumnRef::new(unique_id, &column.to_lowercase()), false)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "metadata": {"adapter_type": "postgres"},
+        "nodes": {
+            "model.p.stg_customers": {"unique_id": "model.p.stg_customers", "name": "stg_customers",
+                "resource_type": "model", "package_name": "p",
+                "relation_name": "\"db\".\"main\".\"stg_customers\"",
+                "depends_on": {"nodes": ["source.p.raw.customers"]},
+                "compiled_code": "select id as customer_id, email, first_name || ' ' || last_name as full_name from \"db\".\"raw\".\"customers\""},
+            "model.p.stg_orders": {"unique_id": "model.p.stg_orders", "name": "stg_orders",
+                "resource_type": "model", "package_name": "p",
+                "relation_name": "\"db\".\"main\".\"stg_orders\"",
+                "depends_on": {"nodes": []},
+                "compiled_code": "select * except (_loaded_at) from (select 1 as id, 2 as customer_id, 3 as amount, now() as _loaded_at) s"},
+            "model.p.orders": {"unique_id": "model.p.orders", "name": "orders",
+                "resource_type": "model", "package_name": "p",
+                "relation_name": "\"db\".\"main\".\"orders\"",
+                "depends_on": {"nodes": ["model.p.stg_customers", "model.p.stg_orders"]},
+                "compiled_code": "with o as (select customer_id, sum(amount) as revenue from \"db\".\"main\".\"stg_orders\" group by 1), c as (select * from \"db\".\"main\".\"stg_customers\") select c.customer_id, c.email as contact, o.revenue, extract(year from current_date) as yr, (select max(amount) from \"db\".\"main\".\"stg_orders\") as top from c left join o on o.customer_id = c.customer_id union all select customer_id, email, 0, 2000, null from \"db\".\"main\".\"stg_customers\""}
+        },
+        "sources": {
+            "source.p.raw.customers": {"unique_id": "source.p.raw.customers", "source_name": "raw",
+                "name": "customers", "package_name": "p", "relation_name": "\"db\".\"raw\".\"customers\"",
+                "columns": {"id": {}, "email": {}, "first_name": {}, "last_name": {}}}
+        }
+    }"#;
+
+    fn sources(lineage: &NodeLineage, name: &str) -> Vec<(String, String, &'static str)> {
+        lineage
+            .columns
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| panic!("no column {}", name))
+            .sources
+            .iter()
+            .map(|s| {
+                (
+                    s.column.unique_id.clone(),
+                    s.column.column.clone(),
+                    s.kind.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn col(unique_id: &str, column: &str, kind: &'static str) -> (String, String, &'static str) {
+        (unique_id.to_string(), column.to_string(), kind)
+    }
+
+    #[test]
+    fn test_build_column_lineage() {
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let lineage = ColumnLineage::build(&manifest, Dialect::Ansi);
+
+        let stg = &lineage.nodes["model.p.stg_customers"];
+        assert!(stg.complete);
+        assert_eq!(
+            sources(stg, "customer_id"),
+            vec![col("source.p.raw.customers", "id", "rename")]
+        );
+        assert_eq!(
+            sources(stg, "full_name"),
+            vec![
+                col("source.p.raw.customers", "first_name", "expression"),
+                col("source.p.raw.customers", "last_name", "expression"),
+            ]
+        );
+
+        let stg_orders = &lineage.nodes["model.p.stg_orders"];
+        let names: Vec<&str> = stg_orders.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "customer_id", "amount"]);
+
+        let orders = &lineage.nodes["model.p.orders"];
+        assert!(orders.complete, "{:?}", orders);
+        let names: Vec<&str> = orders.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["customer_id", "contact", "revenue", "yr", "top"]
+        );
+        assert_eq!(
+            sources(orders, "customer_id"),
+            vec![col("model.p.stg_customers", "customer_id", "passthrough")]
+        );
+        assert_eq!(
+            sources(orders, "contact"),
+            vec![col("model.p.stg_customers", "email", "rename")]
+        );
+        assert_eq!(
+            sources(orders, "revenue"),
+            vec![col("model.p.stg_orders", "amount", "aggregate")]
+        );
+        assert_eq!(sources(orders, "yr"), vec![]);
+        assert_eq!(
+            sources(orders, "top"),
+            vec![col("model.p.stg_orders", "amount", "aggregate")]
+        );
+    }
+
+    #[test]
+    fn test_upstream_and_downstream() {
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let lineage = ColumnLineage::build(&manifest, Dialect::Ansi);
+
+        let upstream: Vec<(String, String)> = lineage
+            .upstream("model.p.orders", "CONTACT")
+            .into_iter()
+            .map(|e| (e.source.unique_id, e.source.column))
+            .collect();
+        assert_eq!(
+            upstream,
+            vec![
+                ("model.p.stg_customers".to_string(), "email".to_string()),
+                ("source.p.raw.customers".to_string(), "email".to_string()),
+            ]
+        );
+
+        let downstream: Vec<(String, String)> = lineage
+            .downstream("model.p.stg_customers", "email")
+            .into_iter()
+            .map(|e| (e.target.unique_id, e.target.column))
+            .collect();
+        assert_eq!(
+            downstream,
+            vec![("model.p.orders".to_string(), "contact".to_string())]
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&lineage.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["nodes"]["model.p.orders"]["columns"][1]["sources"][0],
+            serde_json::json!({"unique_id": "model.p.stg_customers", "column": "email", "kind": "rename"})
+        );
+    }
+    fn lineage(sql: &str, dialect: Dialect) -> NodeLineage {
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let index = RelationIndex::new(&manifest, dialect);
+        let known = |unique_id: &str| documented_columns(&manifest, unique_id);
+        query_lineage(sql, dialect, &index, &known)
+    }
+
+    fn names(lineage: &NodeLineage) -> Vec<&str> {
+        lineage.columns.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    const RAW: &str = "source.p.raw.customers";
+
+    #[test]
+    fn test_transformation_kinds() {
+        let node = lineage(
+            "select id, email as contact, upper(first_name) as first, count(distinct email) as n, \
+             sum(id) over (partition by last_name) as running, \
+             datediff(day, first_name, last_name) as d, lower(email), 1 + 1 \
+             from \"db\".\"raw\".\"customers\" group by 1",
+            Dialect::Ansi,
+        );
+        assert!(node.complete, "{:?}", node);
+        assert_eq!(
+            names(&node),
+            vec!["id", "contact", "first", "n", "running", "d", "lower", "_col7"]
+        );
+        assert_eq!(sources(&node, "id"), vec![col(RAW, "id", "passthrough")]);
+        assert_eq!(sources(&node, "contact"), vec![col(RAW, "email", "rename")]);
+        assert_eq!(
+            sources(&node, "first"),
+            vec![col(RAW, "first_name", "expression")]
+        );
+        assert_eq!(sources(&node, "n"), vec![col(RAW, "email", "aggregate")]);
+        assert_eq!(
+            sources(&node, "running"),
+            vec![
+                col(RAW, "id", "expression"),
+                col(RAW, "last_name", "expression"),
+            ]
+        );
+        assert_eq!(
+            sources(&node, "d"),
+            vec![
+                col(RAW, "first_name", "expression"),
+                col(RAW, "last_name", "expression"),
+            ]
+        );
+        assert_eq!(sources(&node, "_col7"), vec![]);
+    }
+
+    #[test]
+    fn test_star_expansion() {
+        let node = lineage("select * from \"db\".\"raw\".\"customers\"", Dialect::Ansi);
+        assert!(node.complete);
+        assert_eq!(names(&node), vec!["id", "email", "first_name", "last_name"]);
+        assert_eq!(
+            sources(&node, "email"),
+            vec![col(RAW, "email", "passthrough")]
+        );
+
+        let node = lineage(
+            "select c.* exclude (email, last_name), 1 as one \
+             from \"db\".\"raw\".\"customers\" c, (select 2 as two) t",
+            Dialect::Snowflake,
+        );
+        assert!(node.complete, "{:?}", node);
+        assert_eq!(names(&node), vec!["id", "first_name", "one"]);
+
+        let node = lineage(
+            "select * except (id) from \"db\".\"raw\".\"customers\"",
+            Dialect::Ansi,
+        );
+        assert_eq!(names(&node), vec!["email", "first_name", "last_name"]);
+    }
+
+    #[test]
+    fn test_union() {
+        let node = lineage(
+            "select id, email from \"db\".\"raw\".\"customers\" \
+             union all select id, first_name as name from \"db\".\"raw\".\"customers\" \
+             union (select 0, last_name || '!' from \"db\".\"raw\".\"customers\")",
+            Dialect::Ansi,
+        );
+        assert!(node.complete, "{:?}", node);
+        assert_eq!(names(&node), vec!["id", "email"]);
+        assert_eq!(sources(&node, "id"), vec![col(RAW, "id", "passthrough")]);
+        assert_eq!(
+            sources(&node, "email"),
+            vec![
+                col(RAW, "email", "passthrough"),
+                col(RAW, "first_name", "rename"),
+                col(RAW, "last_name", "expression"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_subqueries() {
+        let node = lineage(
+            "with c (key, contact) as (select id, email from \"db\".\"raw\".\"customers\") \
+             select x.customer_id, x.b, (select max(contact) from c) as top \
+             from (select y.id as customer_id, y.b \
+                   from (select id, first_name from \"db\".\"raw\".\"customers\") as y (id, b)) as x \
+             where exists (select 1 from c where c.key = x.customer_id)",
+            Dialect::Ansi,
+        );
+        assert_eq!(names(&node), vec!["customer_id", "b", "top"]);
+        assert_eq!(
+            sources(&node, "customer_id"),
+            vec![col(RAW, "id", "rename")]
+        );
+        assert_eq!(sources(&node, "b"), vec![col(RAW, "first_name", "rename")]);
+        assert_eq!(sources(&node, "top"), vec![col(RAW, "email", "aggregate")]);
+    }
+
+    #[test]
+    fn test_incomplete_lineage() {
+        // SQL that does not parse.
+        let node = lineage("select from where", Dialect::Ansi);
+        assert!(!node.complete);
+        assert!(node.columns.is_empty());
+
+        // Not a query.
+        let node = lineage("delete from \"db\".\"raw\".\"customers\"", Dialect::Ansi);
+        assert!(!node.complete);
+
+        // `*` over a model whose columns are unknown.
+        let node = lineage(
+            "select * from \"db\".\"main\".\"stg_orders\"",
+            Dialect::Ansi,
+        );
+        assert!(!node.complete);
+        assert!(node.columns.is_empty());
+
+        // An unqualified column that either table may have.
+        let node = lineage(
+            "select o.id, amount from \"db\".\"main\".\"stg_orders\" o \
+             join \"db\".\"main\".\"stg_customers\" c on o.customer_id = c.customer_id",
+            Dialect::Ansi,
+        );
+        assert!(!node.complete);
+        assert_eq!(
+            sources(&node, "id"),
+            vec![col("model.p.stg_orders", "id", "passthrough")]
+        );
+        assert_eq!(sources(&node, "amount"), vec![]);
+    }
+}
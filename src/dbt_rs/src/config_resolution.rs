@@ -0,0 +1,607 @@
+//! Node config resolution: merging the path-scoped blocks of
+//! `dbt_project.yml` with properties (`schema.yml`) and `config()` calls the
+//! way `ContextConfigGenerator.calculate_node_config` does.
+//!
+//! Layers apply in this order, later ones winning:
+//! 1. the config class defaults for the resource type,
+//! 2. the node's own project, from the top of e.g. `models:` down along its
+//!    fqn (only `+`-prefixed keys and non-dict values are configs),
+//! 3. the `config:` of its properties entry,
+//! 4. its `config()` calls,
+//! 5. the root project's blocks, when the node belongs to a package.
+//!
+//! Most keys are clobbered. `tags`, `pre-hook`, `post-hook` and `packages`
+//! are appended, `meta`, `quoting`, `column_types`, `docs` and `contract`
+//! are shallowly updated, and `grants` lists are replaced per privilege, or
+//! extended when the privilege is written with a leading `+`.
+
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeBehavior {
+    Clobber,
+    Append,
+    Update,
+    DictKeyAppend,
+}
+
+fn is_node_type(resource_type: &str) -> bool {
+    matches!(resource_type, "model" | "seed" | "snapshot" | "analysis")
+}
+
+/// How `key` merges for `resource_type`, per the field metadata of its
+/// config class.
+pub fn merge_behavior(resource_type: &str, key: &str) -> MergeBehavior {
+    match key {
+        "meta" => MergeBehavior::Update,
+        // SourceConfig declares `tags` without a merge behavior.
+        "tags" if resource_type == "source" => MergeBehavior::Clobber,
+        "tags" => MergeBehavior::Append,
+        "pre-hook" | "post-hook" | "packages" if is_node_type(resource_type) => {
+            MergeBehavior::Append
+        }
+        "quoting" | "column_types" | "docs" | "contract" if is_node_type(resource_type) => {
+            MergeBehavior::Update
+        }
+        "grants" if is_node_type(resource_type) => MergeBehavior::DictKeyAppend,
+        _ => MergeBehavior::Clobber,
+    }
+}
+
+/// The `dbt_project.yml` section holding configs for `resource_type`.
+pub fn project_config_key(resource_type: &str) -> &'static str {
+    match resource_type {
+        "seed" => "seeds",
+        "snapshot" => "snapshots",
+        "source" => "sources",
+        "test" => "data_tests",
+        "metric" => "metrics",
+        "semantic_model" => "semantic_models",
+        "saved_query" => "saved_queries",
+        "exposure" => "exposures",
+        "unit_test" => "unit_tests",
+        _ => "models",
+    }
+}
+
+/// Defaults of the config class for `resource_type`, leaving out the
+/// fields that default to None.
+pub fn default_config(resource_type: &str) -> Map<String, Value> {
+    let defaults = match resource_type {
+        "model" | "seed" | "snapshot" | "analysis" => {
+            let materialized = match resource_type {
+                "seed" => "seed",
+                "snapshot" => "snapshot",
+                _ => "view",
+            };
+            let mut defaults = json!({
+                "enabled": true,
+                "tags": [],
+                "meta": {},
+                "materialized": materialized,
+                "lookback": 1,
+                "persist_docs": {},
+                "post-hook": [],
+                "pre-hook": [],
+                "quoting": {},
+                "column_types": {},
+                "on_schema_change": "ignore",
+                "on_configuration_change": "apply",
+                "grants": {},
+                "packages": [],
+                "docs": {"show": true, "node_color": null},
+                "contract": {"enforced": false, "alias_types": true},
+            });
+            if resource_type == "model" {
+                defaults["access"] = json!("protected");
+            }
+            if resource_type == "seed" {
+                defaults["delimiter"] = json!(",");
+            }
+            defaults
+        }
+        "test" => json!({
+            "enabled": true,
+            "tags": [],
+            "meta": {},
+            "schema": "dbt_test__audit",
+            "materialized": "test",
+            "severity": "ERROR",
+            "fail_calc": "count(*)",
+            "warn_if": "!= 0",
+            "error_if": "!= 0",
+        }),
+        _ => json!({"enabled": true, "tags": [], "meta": {}}),
+    };
+    match defaults {
+        Value::Object(defaults) => defaults,
+        _ => unreachable!(),
+    }
+}
+
+fn listify(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        value => vec![value],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The node's package is not among the loaded projects.
+    UnknownProject { name: String, found: Vec<String> },
+    /// An `Update` or `DictKeyAppend` key was given something other than a
+    /// dict.
+    NotAMapping { key: String, layer: ConfigLayer },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::UnknownProject { name, found } => write!(
+                f,
+                "Project name {} not found in dependencies (found {:?})",
+                name, found
+            ),
+            ConfigError::NotAMapping { key, layer } => {
+                write!(f, "Config '{}' must be a dict (set by {})", key, layer)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Merge `value` into `current` following `behavior`.
+fn merge_value(behavior: MergeBehavior, current: Value, value: Value) -> Result<Value, Value> {
+    match behavior {
+        MergeBehavior::Clobber => Ok(value),
+        MergeBehavior::Append => {
+            let mut items = listify(current);
+            items.extend(listify(value));
+            Ok(Value::Array(items))
+        }
+        MergeBehavior::Update => match (current, value) {
+            (Value::Object(mut current), Value::Object(value)) => {
+                current.extend(value);
+                Ok(Value::Object(current))
+            }
+            (_, value) => Err(value),
+        },
+        MergeBehavior::DictKeyAppend => match (current, value) {
+            (Value::Object(current), Value::Object(value)) => {
+                let mut merged: Map<String, Value> = current
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::Array(listify(value))))
+                    .collect();
+                for (key, value) in value {
+                    let (name, extend) = match key.strip_prefix('+') {
+                        Some(_) => (key.trim_start_matches('+').to_string(), true),
+                        None => (key, false),
+                    };
+                    match merged.get_mut(&name) {
+                        Some(Value::Array(items)) if extend => items.extend(listify(value)),
+                        _ => {
+                            merged.insert(name, Value::Array(listify(value)));
+                        }
+                    }
+                }
+                Ok(Value::Object(merged))
+            }
+            (_, value) => Err(value),
+        },
+    }
+}
+
+/// Merge the options of a later `config()` call into those of earlier ones,
+/// as `merge_config_dicts` does.
+pub fn merge_config_dicts(
+    resource_type: &str,
+    into: &mut Map<String, Value>,
+    from: &Map<String, Value>,
+) -> Result<(), ConfigError> {
+    for (key, value) in from {
+        let merged = match into.get_mut(key).map(Value::take) {
+            None => value.clone(),
+            Some(current) => {
+                merge_value(merge_behavior(resource_type, key), current, value.clone()).map_err(
+                    |_| ConfigError::NotAMapping {
+                        key: key.clone(),
+                        layer: ConfigLayer::ConfigCall,
+                    },
+                )?
+            }
+        };
+        into.insert(key.clone(), merged);
+    }
+    Ok(())
+}
+
+/// Where a config value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLayer {
+    Default,
+    /// A block of the node's own project; `path` is the fqn prefix it is
+    /// nested under, empty for the top of the section.
+    Project {
+        project: String,
+        path: Vec<String>,
+    },
+    Properties,
+    ConfigCall,
+    /// A block of the root project, applied last to package nodes.
+    RootProject {
+        project: String,
+        path: Vec<String>,
+    },
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLayer::Default => write!(f, "default"),
+            ConfigLayer::Project { project, path } | ConfigLayer::RootProject { project, path } => {
+                let root = if matches!(self, ConfigLayer::RootProject { .. }) {
+                    "root project "
+                } else {
+                    ""
+                };
+                write!(f, "{}{} dbt_project.yml", root, project)?;
+                if !path.is_empty() {
+                    write!(f, " at {}", path.join("."))?;
+                }
+                Ok(())
+            }
+            ConfigLayer::Properties => write!(f, "properties"),
+            ConfigLayer::ConfigCall => write!(f, "config()"),
+        }
+    }
+}
+
+/// A resolved config and, for every key, the layers that contributed to
+/// it: the last one for clobbered keys, all of them for merged keys.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ResolvedConfig {
+    pub config: Map<String, Value>,
+    pub origins: BTreeMap<String, Vec<ConfigLayer>>,
+}
+
+impl ResolvedConfig {
+    fn apply(
+        &mut self,
+        resource_type: &str,
+        layer: ConfigLayer,
+        partial: Map<String, Value>,
+    ) -> Result<(), ConfigError> {
+        for (key, value) in partial {
+            // Misspelled hook names have always been accepted.
+            let key = match key.as_str() {
+                "pre_hook" => "pre-hook".to_string(),
+                "post_hook" => "post-hook".to_string(),
+                _ => key,
+            };
+            let behavior = merge_behavior(resource_type, &key);
+            let origins = self.origins.entry(key.clone()).or_default();
+            let merged = match self.config.get_mut(&key).map(Value::take) {
+                Some(current) => {
+                    merge_value(behavior, current, value).map_err(|_| ConfigError::NotAMapping {
+                        key: key.clone(),
+                        layer: layer.clone(),
+                    })?
+                }
+                None => value,
+            };
+            if behavior == MergeBehavior::Clobber {
+                origins.clear();
+            }
+            origins.push(layer.clone());
+            self.config.insert(key, merged);
+        }
+        Ok(())
+    }
+
+    /// Drop None values and normalize hooks to `{"sql", "transaction",
+    /// "index"}`, as the manifest stores them.
+    fn finalize(&mut self) {
+        self.config.retain(|_, value| !value.is_null());
+        for key in ["pre-hook", "post-hook"] {
+            if let Some(Value::Array(hooks)) = self.config.get_mut(key) {
+                for hook in hooks.iter_mut() {
+                    let mut dict = match hook.take() {
+                        Value::Object(dict) => dict,
+                        Value::String(sql) => match serde_json::from_str(&sql) {
+                            Ok(Value::Object(dict)) => dict,
+                            _ => Map::from_iter([("sql".to_string(), Value::String(sql))]),
+                        },
+                        other => Map::from_iter([("sql".to_string(), other)]),
+                    };
+                    dict.entry("transaction").or_insert(Value::Bool(true));
+                    dict.entry("index").or_insert(Value::Null);
+                    *hook = Value::Object(dict);
+                }
+            }
+        }
+    }
+}
+
+/// Resolves node configs against a root project and its dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigResolver {
+    root_project: String,
+    /// Parsed, rendered `dbt_project.yml` contents by project name.
+    projects: HashMap<String, Map<String, Value>>,
+}
+
+/// The node whose config is being resolved.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigTarget<'a> {
+    pub package_name: &'a str,
+    pub resource_type: &'a str,
+    pub fqn: &'a [String],
+}
+
+impl ConfigResolver {
+    pub fn new(root_project: &str) -> Self {
+        ConfigResolver {
+            root_project: root_project.to_string(),
+            projects: HashMap::new(),
+        }
+    }
+
+    /// Add a project's `dbt_project.yml`; the root project must be added too.
+    pub fn add_project(&mut self, name: &str, project: Map<String, Value>) {
+        self.projects.insert(name.to_string(), project);
+    }
+
+    /// The config blocks along `fqn` within `project`'s section for the
+    /// resource type, outermost first, as in `fqn_search`.
+    fn project_layers(
+        &self,
+        project: &str,
+        target: ConfigTarget,
+    ) -> Vec<(Vec<String>, Map<String, Value>)> {
+        let Some(dict) = self.projects.get(project) else {
+            return Vec::new();
+        };
+        let key = project_config_key(target.resource_type);
+        // `tests:` is the old name of `data_tests:`.
+        let section = dict
+            .get(key)
+            .or_else(|| (key == "data_tests").then(|| dict.get("tests")).flatten());
+        let Some(Value::Object(root)) = section else {
+            return Vec::new();
+        };
+        let mut level = root;
+        let mut layers = Vec::new();
+        let mut path = Vec::new();
+        loop {
+            let configs = level
+                .iter()
+                .filter_map(|(key, value)| match key.strip_prefix('+') {
+                    Some(name) => Some((name.trim().to_string(), value.clone())),
+                    None if !value.is_object() => Some((key.clone(), value.clone())),
+                    None => None,
+                })
+                .collect();
+            layers.push((path.clone(), configs));
+            let Some(name) = target.fqn.get(path.len()) else {
+                break;
+            };
+            let Some(Value::Object(next)) = level.get(name) else {
+                break;
+            };
+            path.push(name.clone());
+            level = next;
+        }
+        layers
+    }
+
+    /// Resolve the config of `target`, given the `config:` of its properties
+    /// entry and its merged `config()` calls.
+    pub fn resolve(
+        &self,
+        target: ConfigTarget,
+        properties: Option<&Map<String, Value>>,
+        config_call: Option<&Map<String, Value>>,
+    ) -> Result<ResolvedConfig, ConfigError> {
+        if !self.projects.contains_key(target.package_name) {
+            let mut found: Vec<String> = self.projects.keys().cloned().collect();
+            found.sort();
+            return Err(ConfigError::UnknownProject {
+                name: target.package_name.to_string(),
+                found,
+            });
+        }
+        let resource_type = target.resource_type;
+        let mut resolved = ResolvedConfig::default();
+        resolved.apply(
+            resource_type,
+            ConfigLayer::Default,
+            default_config(resource_type),
+        )?;
+        for (path, configs) in self.project_layers(target.package_name, target) {
+            let layer = ConfigLayer::Project {
+                project: target.package_name.to_string(),
+                path,
+            };
+            resolved.apply(resource_type, layer, configs)?;
+        }
+        if let Some(properties) = properties.filter(|properties| !properties.is_empty()) {
+            resolved.apply(resource_type, ConfigLayer::Properties, properties.clone())?;
+        }
+        if let Some(config_call) = config_call {
+            resolved.apply(resource_type, ConfigLayer::ConfigCall, config_call.clone())?;
+        }
+        if target.package_name != self.root_project {
+            for (path, configs) in self.project_layers(&self.root_project, target) {
+                let layer = ConfigLayer::RootProject {
+                    project: self.root_project.clone(),
+                    path,
+                };
+                resolved.apply(resource_type, layer, configs)?;
+            }
+        }
+        resolved.finalize();
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("expected an object"),
+        }
+    }
+
+    fn fqn(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|part| part.to_string()).collect()
+    }
+
+    fn resolver() -> ConfigResolver {
+        let mut resolver = ConfigResolver::new("jaffle");
+        resolver.add_project(
+            "jaffle",
+            object(json!({
+                "name": "jaffle",
+                "models": {
+                    "+tags": "root",
+                    "+meta": {"owner": "data", "tier": 1},
+                    "+grants": {"select": ["reporter"]},
+                    "jaffle": {
+                        "+materialized": "table",
+                        "staging": {
+                            "+materialized": "view",
+                            "+tags": ["staging"],
+                            "pre_hook": "select 1",
+                            "+grants": {"+select": ["loader"]},
+                        },
+                    },
+                    "utils": {"+schema": "utils"},
+                },
+            })),
+        );
+        resolver.add_project(
+            "utils",
+            object(json!({
+                "name": "utils",
+                "models": {"utils": {"+materialized": "ephemeral", "+tags": ["pkg"]}},
+            })),
+        );
+        resolver
+    }
+
+    #[test]
+    fn test_project_layers_properties_and_config_calls() {
+        let resolver = resolver();
+        let node_fqn = fqn(&["jaffle", "staging", "stg_orders"]);
+        let target = ConfigTarget {
+            package_name: "jaffle",
+            resource_type: "model",
+            fqn: &node_fqn,
+        };
+        let properties = object(json!({"meta": {"tier": 2}, "tags": "props"}));
+        let mut config_call = object(json!({"tags": ["call"]}));
+        merge_config_dicts(
+            "model",
+            &mut config_call,
+            &object(json!({"tags": "call2", "materialized": "incremental"})),
+        )
+        .unwrap();
+        let resolved = resolver
+            .resolve(target, Some(&properties), Some(&config_call))
+            .unwrap();
+        let config = &resolved.config;
+
+        assert_eq!(config["materialized"], json!("incremental"));
+        assert_eq!(
+            config["tags"],
+            json!(["root", "staging", "props", "call", "call2"])
+        );
+        assert_eq!(config["meta"], json!({"owner": "data", "tier": 2}));
+        assert_eq!(config["grants"], json!({"select": ["reporter", "loader"]}));
+        assert_eq!(
+            config["pre-hook"],
+            json!([{"sql": "select 1", "transaction": true, "index": null}])
+        );
+        assert!(!config.contains_key("pre_hook"));
+
+        assert_eq!(
+            resolved.origins["materialized"],
+            vec![ConfigLayer::ConfigCall]
+        );
+        assert_eq!(
+            resolved.origins["meta"],
+            vec![
+                ConfigLayer::Default,
+                ConfigLayer::Project {
+                    project: "jaffle".to_string(),
+                    path: vec![],
+                },
+                ConfigLayer::Properties,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_package_nodes_get_root_project_configs_last() {
+        let resolver = resolver();
+        let node_fqn = fqn(&["utils", "dates"]);
+        let target = ConfigTarget {
+            package_name: "utils",
+            resource_type: "model",
+            fqn: &node_fqn,
+        };
+        let config_call = object(json!({"schema": "mine"}));
+        let resolved = resolver.resolve(target, None, Some(&config_call)).unwrap();
+        assert_eq!(resolved.config["materialized"], json!("ephemeral"));
+        assert_eq!(resolved.config["schema"], json!("utils"));
+        assert_eq!(resolved.config["tags"], json!(["pkg", "root"]));
+        assert_eq!(
+            resolved.origins["schema"][0].to_string(),
+            "root project jaffle dbt_project.yml at utils"
+        );
+
+        let target = ConfigTarget {
+            package_name: "missing",
+            ..target
+        };
+        assert!(matches!(
+            resolver.resolve(target, None, None),
+            Err(ConfigError::UnknownProject { .. })
+        ));
+    }
+
+    #[test]
+    fn test_merge_behaviors() {
+        assert_eq!(merge_behavior("source", "tags"), MergeBehavior::Clobber);
+        assert_eq!(merge_behavior("test", "tags"), MergeBehavior::Append);
+        assert_eq!(merge_behavior("test", "pre-hook"), MergeBehavior::Clobber);
+        assert_eq!(
+            merge_value(
+                MergeBehavior::DictKeyAppend,
+                json!({"select": "a", "insert": ["b"]}),
+                json!({"select": ["c"], "+insert": "d", "+delete": "e"}),
+            )
+            .unwrap(),
+            json!({"select": ["c"], "insert": ["b", "d"], "delete": ["e"]})
+        );
+        let mut resolved = ResolvedConfig::default();
+        resolved
+            .apply("model", ConfigLayer::Properties, object(json!({"meta": 1})))
+            .unwrap();
+        assert!(matches!(
+            resolved.apply(
+                "model",
+                ConfigLayer::ConfigCall,
+                object(json!({"meta": {}}))
+            ),
+            Err(ConfigError::NotAMapping { .. })
+        ));
+    }
+}
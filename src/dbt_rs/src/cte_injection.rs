@@ -0,0 +1,638 @@
+use crate::graph::OxideGraph;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// An ephemeral model's CTE, as `InjectedCTE`: `sql` is
+/// ` <cte name> as (\n<compiled sql>\n)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedCte {
+    pub id: String,
+    pub sql: String,
+}
+
+/// A node's compiled SQL before CTE injection, with the ephemeral models it
+/// refs in call order (`CompiledNode::ephemeral_refs`, dbt's `extra_ctes`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompiledSql {
+    pub compiled_code: String,
+    pub ephemeral_refs: Vec<String>,
+}
+
+/// An ephemeral model: its relation identifier and its compiled SQL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EphemeralModel {
+    pub identifier: String,
+    pub compiled: CompiledSql,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CteError {
+    /// Ephemeral models that depend on each other.
+    Cycle { unique_id: String },
+    /// A ref to a model that is not a known ephemeral model.
+    Unresolved { unique_id: String },
+}
+
+impl fmt::Display for CteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CteError::Cycle { unique_id } => write!(
+                f,
+                "Found a cycle between ephemeral models involving {}",
+                unique_id
+            ),
+            CteError::Unresolved { unique_id } => write!(
+                f,
+                "During compilation, found a cte reference that could not be resolved: {}",
+                unique_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CteError {}
+
+/// dbt's `add_ephemeral_prefix`.
+pub fn ephemeral_cte_name(identifier: &str) -> String {
+    format!("__dbt__cte__{}", identifier)
+}
+
+/// Position after the whitespace at `pos` and, with `comments`, any `--`,
+/// `#` and `/* */` comments around it.
+fn skip_trivia(sql: &str, mut pos: usize, comments: bool) -> usize {
+    loop {
+        let rest = &sql[pos..];
+        let trimmed = rest.trim_start();
+        pos += rest.len() - trimmed.len();
+        if !comments {
+            return pos;
+        }
+        if trimmed.starts_with("--") || trimmed.starts_with('#') {
+            pos += trimmed.find('\n').map_or(trimmed.len(), |end| end + 1);
+        } else if trimmed.starts_with("/*") {
+            pos += trimmed.find("*/").map_or(trimmed.len(), |end| end + 2);
+        } else {
+            return pos;
+        }
+        if pos >= sql.len() {
+            return pos;
+        }
+    }
+}
+
+/// Position after `keyword` if `sql` has it at `pos` as a whole word,
+/// ignoring case.
+fn keyword_at(sql: &str, pos: usize, keyword: &str) -> Option<usize> {
+    let end = pos + keyword.len();
+    let word = sql.get(pos..end)?;
+    let boundary = sql[end..]
+        .chars()
+        .next()
+        .is_none_or(|c| !(c.is_alphanumeric() || c == '_' || c == '$'));
+    (word.eq_ignore_ascii_case(keyword) && boundary).then_some(end)
+}
+
+/// Splice CTEs into `sql`, as `inject_ctes_into_sql`.
+///
+/// If the statement starts with `with` (or `with recursive`), the CTEs go
+/// right after it, followed by `, ` and the existing CTEs. Otherwise
+/// `with <ctes> ` is put before the first token. As in dbt, a leading
+/// comment counts as that first token, so it ends up after the CTEs.
+pub fn inject_ctes_into_sql(sql: &str, ctes: &[InjectedCte]) -> String {
+    if ctes.is_empty() {
+        return sql.to_string();
+    }
+    let joined = ctes
+        .iter()
+        .map(|cte| cte.sql.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let start = skip_trivia(sql, 0, true);
+    if let Some(mut end) = keyword_at(sql, start, "with") {
+        let after_with = skip_trivia(sql, end, true);
+        if let Some(recursive_end) = keyword_at(sql, after_with, "recursive") {
+            end = recursive_end;
+        }
+        // sqlparse inserts after the whitespace that follows the keyword.
+        let insert_at = skip_trivia(sql, end, false);
+        return format!("{}{}, {}", &sql[..insert_at], joined, &sql[insert_at..]);
+    }
+
+    let first = skip_trivia(sql, 0, false);
+    format!("{}with{} {}", &sql[..first], joined, &sql[first..])
+}
+
+/// dbt's `_add_prepended_cte`: the first occurrence keeps its position and
+/// takes the newest SQL.
+fn add_cte(ctes: &mut Vec<InjectedCte>, new: InjectedCte) {
+    match ctes.iter_mut().find(|cte| cte.id == new.id) {
+        Some(existing) => existing.sql = new.sql,
+        None => ctes.push(new),
+    }
+}
+
+/// Builds the CTE chains of nodes, remembering the chain of every ephemeral
+/// model it walks through.
+pub struct CteChains<'a> {
+    ephemeral: &'a HashMap<String, EphemeralModel>,
+    chains: HashMap<String, Vec<InjectedCte>>,
+}
+
+impl<'a> CteChains<'a> {
+    pub fn new(ephemeral: &'a HashMap<String, EphemeralModel>) -> Self {
+        CteChains {
+            ephemeral,
+            chains: HashMap::new(),
+        }
+    }
+
+    /// The CTEs to inject into a node with `ephemeral_refs`, as
+    /// `_recursively_prepend_ctes`: for each ref in call order, the chain of
+    /// the ephemeral model followed by its own CTE, the first occurrence of
+    /// a CTE keeping its position.
+    pub fn chain(&mut self, ephemeral_refs: &[String]) -> Result<Vec<InjectedCte>, CteError> {
+        self.prepend(ephemeral_refs, &mut HashSet::new())
+    }
+
+    fn prepend(
+        &mut self,
+        ephemeral_refs: &[String],
+        visiting: &mut HashSet<String>,
+    ) -> Result<Vec<InjectedCte>, CteError> {
+        let mut chain = Vec::new();
+        for unique_id in ephemeral_refs {
+            for cte in self.ephemeral_chain(unique_id, visiting)? {
+                add_cte(&mut chain, cte);
+            }
+            let model = &self.ephemeral[unique_id];
+            let sql = format!(
+                " {} as (\n{}\n)",
+                ephemeral_cte_name(&model.identifier),
+                model.compiled.compiled_code
+            );
+            add_cte(
+                &mut chain,
+                InjectedCte {
+                    id: unique_id.clone(),
+                    sql,
+                },
+            );
+        }
+        Ok(chain)
+    }
+
+    fn ephemeral_chain(
+        &mut self,
+        unique_id: &str,
+        visiting: &mut HashSet<String>,
+    ) -> Result<Vec<InjectedCte>, CteError> {
+        if let Some(chain) = self.chains.get(unique_id) {
+            return Ok(chain.clone());
+        }
+        let Some(model) = self.ephemeral.get(unique_id) else {
+            return Err(CteError::Unresolved {
+                unique_id: unique_id.to_string(),
+            });
+        };
+        if !visiting.insert(unique_id.to_string()) {
+            return Err(CteError::Cycle {
+                unique_id: unique_id.to_string(),
+            });
+        }
+        let chain = self.prepend(&model.compiled.ephemeral_refs, visiting)?;
+        visiting.remove(unique_id);
+        self.chains.insert(unique_id.to_string(), chain.clone());
+        Ok(chain)
+    }
+}
+
+/// The ephemeral models among `unique_id`'s parents in `graph`, sorted by
+/// unique_id.
+///
+/// dbt prepends CTEs in `ref()` call order (`extra_ctes`), which the graph
+/// does not record, so this is for nodes whose call order is unknown. The
+/// CTEs are valid either way: each chain still comes before the CTE that
+/// needs it.
+pub fn ephemeral_parents(
+    graph: &OxideGraph,
+    is_ephemeral: impl Fn(&str) -> bool,
+    unique_id: &str,
+) -> Vec<String> {
+    let mut parents: Vec<String> = graph
+        .predecessors(unique_id)
+        .into_iter()
+        .filter(|parent| is_ephemeral(parent))
+        .collect();
+    parents.sort();
+    parents
+}
+
+/// A node's SQL with its ephemeral CTEs injected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedNode {
+    pub compiled_code: String,
+    pub extra_ctes: Vec<InjectedCte>,
+}
+
+/// Inject ephemeral CTEs into every node of `compiled`, by unique_id.
+pub fn inject_ephemeral_ctes(
+    ephemeral: &HashMap<String, EphemeralModel>,
+    compiled: &BTreeMap<String, CompiledSql>,
+) -> Result<BTreeMap<String, InjectedNode>, CteError> {
+    let mut chains = CteChains::new(ephemeral);
+    compiled
+        .iter()
+        .map(|(unique_id, node)| {
+            let extra_ctes = chains.chain(&node.ephemeral_refs)?;
+            let node = InjectedNode {
+                compiled_code: inject_ctes_into_sql(&node.compiled_code, &extra_ctes),
+                extra_ctes,
+            };
+            Ok((unique_id.clone(), node))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Like dbt's `assertEqualIgnoreWhitespace`.
+    fn norm(s: &str) -> String {
+        s.split_whitespace().collect()
+    }
+
+    fn cte(id: &str, sql: &str) -> InjectedCte {
+        InjectedCte {
+            id: id.to_string(),
+            sql: sql.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_inject_ctes_into_sql() {
+        let base = cte(
+            "model.test.base",
+            " __dbt__cte__base as (\n\n\nselect * from seed\n)",
+        );
+        let cases = [
+            (
+                "select * from __dbt__cte__base",
+                vec![base.clone()],
+                "with __dbt__cte__base as ( select * from seed ) select * from __dbt__cte__base",
+            ),
+            (
+                "select * from __dbt__cte__ephemeral",
+                vec![
+                    cte("model.test.two", " __dbt__cte__two as (\n\nselect * from src\n)"),
+                    cte(
+                        "model.test.ephemeral",
+                        " __dbt__cte__ephemeral as (\n\nselect * from __dbt__cte__two\n)",
+                    ),
+                ],
+                "with __dbt__cte__two as ( select * from src ), __dbt__cte__ephemeral as ( \
+                 select * from __dbt__cte__two ) select * from __dbt__cte__ephemeral",
+            ),
+            (
+                "\n   with internal_cte as (select * from sessions)\n   select * from internal_cte\n",
+                vec![
+                    cte("cte_id_1", "__dbt__cte__ephemeral as (select * from table)"),
+                    cte("cte_id_2", "__dbt__cte__events as (select id, type from events)"),
+                ],
+                "with __dbt__cte__ephemeral as (select * from table), \
+                 __dbt__cte__events as (select id, type from events), \
+                 internal_cte as (select * from sessions) select * from internal_cte",
+            ),
+            (
+                "\n    --- This is sql with a comment\n    select * from __dbt__cte__base\n",
+                vec![base.clone()],
+                "with __dbt__cte__base as ( select * from seed ) \
+                 --- This is sql with a comment select * from __dbt__cte__base",
+            ),
+            (
+                "\n  with recursive t(n) as (\n select * from __dbt__cte__base\n union all \
+                 select n+1 from t where n < 100\n )\n select sum(n) from t\n",
+                vec![base.clone()],
+                "with recursive __dbt__cte__base as ( select * from seed ), t(n) as ( \
+                 select * from __dbt__cte__base union all select n+1 from t where n < 100 ) \
+                 select sum(n) from t",
+            ),
+            (
+                "/* header */ WITH x as (select 1) select * from x, __dbt__cte__base",
+                vec![base.clone()],
+                "/* header */ WITH __dbt__cte__base as ( select * from seed ), \
+                 x as (select 1) select * from x, __dbt__cte__base",
+            ),
+            (
+                "select 1 as without",
+                vec![],
+                "select 1 as without",
+            ),
+        ];
+        for (sql, ctes, expected) in cases {
+            assert_eq!(
+                norm(&inject_ctes_into_sql(sql, &ctes)),
+                norm(expected),
+                "{}",
+                sql
+            );
+        }
+        // `withdrawals` is not the `with` keyword.
+        assert!(inject_ctes_into_sql("withdrawals", &[base]).starts_with("with "));
+    }
+
+    fn refs(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn ephemeral_models(models: &[(&str, &str, &[&str])]) -> HashMap<String, EphemeralModel> {
+        models
+            .iter()
+            .map(|(unique_id, sql, ephemeral_refs)| {
+                let model = EphemeralModel {
+                    identifier: unique_id.rsplit('.').next().unwrap().to_string(),
+                    compiled: CompiledSql {
+                        compiled_code: sql.to_string(),
+                        ephemeral_refs: refs(ephemeral_refs),
+                    },
+                };
+                (unique_id.to_string(), model)
+            })
+            .collect()
+    }
+
+    fn compiled_nodes(nodes: &[(&str, &str, &[&str])]) -> BTreeMap<String, CompiledSql> {
+        nodes
+            .iter()
+            .map(|(unique_id, sql, ephemeral_refs)| {
+                let node = CompiledSql {
+                    compiled_code: sql.to_string(),
+                    ephemeral_refs: refs(ephemeral_refs),
+                };
+                (unique_id.to_string(), node)
+            })
+            .collect()
+    }
+
+    fn inject(
+        ephemeral: &[(&str, &str, &[&str])],
+        nodes: &[(&str, &str, &[&str])],
+    ) -> BTreeMap<String, InjectedNode> {
+        inject_ephemeral_ctes(&ephemeral_models(ephemeral), &compiled_nodes(nodes)).unwrap()
+    }
+
+    // The cases of dbt's test_compiler.py.
+
+    #[test]
+    fn test_prepend_ctes() {
+        let injected = inject(
+            &[("model.root.ephemeral", "select * from source_table", &[])],
+            &[(
+                "model.root.view",
+                "select * from __dbt__cte__ephemeral",
+                &["model.root.ephemeral"],
+            )],
+        );
+        let view = &injected["model.root.view"];
+        assert_eq!(
+            view.extra_ctes,
+            vec![cte(
+                "model.root.ephemeral",
+                " __dbt__cte__ephemeral as (\nselect * from source_table\n)"
+            )]
+        );
+        assert_eq!(
+            norm(&view.compiled_code),
+            norm(
+                "with __dbt__cte__ephemeral as (select * from source_table) \
+                 select * from __dbt__cte__ephemeral"
+            )
+        );
+    }
+
+    #[test]
+    fn test_prepend_ctes_already_has_cte() {
+        let injected = inject(
+            &[("model.root.ephemeral", "select * from source_table", &[])],
+            &[(
+                "model.root.view",
+                "with cte as (select * from something_else) select * from __dbt__cte__ephemeral",
+                &["model.root.ephemeral"],
+            )],
+        );
+        assert_eq!(
+            norm(&injected["model.root.view"].compiled_code),
+            norm(
+                "with __dbt__cte__ephemeral as (select * from source_table), \
+                 cte as (select * from something_else) select * from __dbt__cte__ephemeral"
+            )
+        );
+    }
+
+    #[test]
+    fn test_prepend_ctes_no_ctes() {
+        let with_cte = "with cte as (select * from something_else) select * from source_table";
+        let injected = inject(
+            &[],
+            &[
+                ("model.root.view", with_cte, &[]),
+                ("model.root.view_no_cte", "select * from source_table", &[]),
+            ],
+        );
+        assert_eq!(injected["model.root.view"].compiled_code, with_cte);
+        assert!(injected["model.root.view"].extra_ctes.is_empty());
+        assert_eq!(
+            injected["model.root.view_no_cte"].compiled_code,
+            "select * from source_table"
+        );
+    }
+
+    #[test]
+    fn test_prepend_ctes_multiple_levels() {
+        let injected = inject(
+            &[
+                (
+                    "model.root.ephemeral",
+                    "select * from __dbt__cte__ephemeral_level_two",
+                    &["model.root.ephemeral_level_two"],
+                ),
+                (
+                    "model.root.ephemeral_level_two",
+                    "select * from source_table",
+                    &[],
+                ),
+            ],
+            &[
+                (
+                    "model.root.view",
+                    "select * from __dbt__cte__ephemeral",
+                    &["model.root.ephemeral"],
+                ),
+                (
+                    "model.root.ephemeral",
+                    "select * from __dbt__cte__ephemeral_level_two",
+                    &["model.root.ephemeral_level_two"],
+                ),
+            ],
+        );
+        assert_eq!(
+            norm(&injected["model.root.view"].compiled_code),
+            norm(
+                "with __dbt__cte__ephemeral_level_two as (select * from source_table), \
+                 __dbt__cte__ephemeral as (select * from __dbt__cte__ephemeral_level_two) \
+                 select * from __dbt__cte__ephemeral"
+            )
+        );
+        // The ephemeral model's CTE holds its SQL from before its own
+        // injection, so the view gets no nested `with`.
+        assert_eq!(
+            norm(&injected["model.root.ephemeral"].compiled_code),
+            norm(
+                "with __dbt__cte__ephemeral_level_two as (select * from source_table) \
+                 select * from __dbt__cte__ephemeral_level_two"
+            )
+        );
+    }
+
+    #[test]
+    fn test_prepend_ctes_follow_ref_order() {
+        // As in the ephemeral_multi project of test_compilation.py.
+        let injected = inject(
+            &[
+                ("model.t.base", "select * from seed", &[]),
+                (
+                    "model.t.base_copy",
+                    "select * from __dbt__cte__base",
+                    &["model.t.base"],
+                ),
+                (
+                    "model.t.female_only",
+                    "select * from __dbt__cte__base_copy where gender = 'Female'",
+                    &["model.t.base_copy"],
+                ),
+            ],
+            &[
+                (
+                    "model.t.double_dependent",
+                    "select * from __dbt__cte__female_only union all select * from __dbt__cte__base_copy",
+                    &["model.t.female_only", "model.t.base_copy"],
+                ),
+                (
+                    "model.t.zz_first",
+                    "select * from __dbt__cte__female_only, __dbt__cte__base",
+                    &["model.t.female_only", "model.t.base"],
+                ),
+            ],
+        );
+        let ids = |unique_id: &str| -> Vec<String> {
+            injected[unique_id]
+                .extra_ctes
+                .iter()
+                .map(|c| c.id.clone())
+                .collect()
+        };
+        assert_eq!(
+            ids("model.t.double_dependent"),
+            vec!["model.t.base", "model.t.base_copy", "model.t.female_only"]
+        );
+        assert_eq!(
+            norm(&injected["model.t.double_dependent"].compiled_code),
+            norm(
+                "with __dbt__cte__base as ( select * from seed ), \
+                 __dbt__cte__base_copy as ( select * from __dbt__cte__base ), \
+                 __dbt__cte__female_only as ( select * from __dbt__cte__base_copy where gender = 'Female' ) \
+                 select * from __dbt__cte__female_only union all select * from __dbt__cte__base_copy"
+            )
+        );
+
+        // Refs are visited in call order, not by unique_id.
+        let injected = inject(
+            &[
+                ("model.p.zeta", "select 1", &[]),
+                ("model.p.alpha", "select 2", &[]),
+            ],
+            &[(
+                "model.p.view",
+                "select * from __dbt__cte__zeta, __dbt__cte__alpha",
+                &["model.p.zeta", "model.p.alpha"],
+            )],
+        );
+        let ids: Vec<&str> = injected["model.p.view"]
+            .extra_ctes
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["model.p.zeta", "model.p.alpha"]);
+    }
+
+    #[test]
+    fn test_ephemeral_parents() {
+        let mut graph = OxideGraph::new();
+        for (source, target) in [
+            ("model.p.zeta", "model.p.view"),
+            ("model.p.alpha", "model.p.view"),
+            ("model.p.table", "model.p.view"),
+            ("model.p.base", "model.p.alpha"),
+        ] {
+            graph.add_edge(source, target, None).unwrap();
+        }
+        let mut ephemeral = ephemeral_models(&[
+            ("model.p.zeta", "select 1", &[]),
+            ("model.p.alpha", "select * from __dbt__cte__base", &[]),
+            ("model.p.base", "select 2", &[]),
+        ]);
+        let is_ephemeral = |unique_id: &str| ephemeral.contains_key(unique_id);
+        assert_eq!(
+            ephemeral_parents(&graph, is_ephemeral, "model.p.view"),
+            refs(&["model.p.alpha", "model.p.zeta"])
+        );
+        assert!(ephemeral_parents(&graph, is_ephemeral, "model.p.base").is_empty());
+
+        let parents = ephemeral_parents(&graph, is_ephemeral, "model.p.alpha");
+        ephemeral
+            .get_mut("model.p.alpha")
+            .unwrap()
+            .compiled
+            .ephemeral_refs = parents;
+        let mut compiled = compiled_nodes(&[(
+            "model.p.view",
+            "select * from __dbt__cte__zeta, __dbt__cte__alpha",
+            &[],
+        )]);
+        compiled.get_mut("model.p.view").unwrap().ephemeral_refs =
+            ephemeral_parents(&graph, |id| ephemeral.contains_key(id), "model.p.view");
+        let injected = inject_ephemeral_ctes(&ephemeral, &compiled).unwrap();
+        let ids: Vec<&str> = injected["model.p.view"]
+            .extra_ctes
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["model.p.base", "model.p.alpha", "model.p.zeta"]);
+    }
+
+    #[test]
+    fn test_prepend_ctes_errors() {
+        let err = inject_ephemeral_ctes(
+            &ephemeral_models(&[]),
+            &compiled_nodes(&[("model.p.view", "select 1", &["model.p.missing"])]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            CteError::Unresolved {
+                unique_id: "model.p.missing".to_string()
+            }
+        );
+
+        let err = inject_ephemeral_ctes(
+            &ephemeral_models(&[
+                ("model.p.a", "select * from __dbt__cte__b", &["model.p.b"]),
+                ("model.p.b", "select * from __dbt__cte__a", &["model.p.a"]),
+            ]),
+            &compiled_nodes(&[("model.p.view", "select 1", &["model.p.a"])]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, CteError::Cycle { .. }), "{}", err);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::graph::OxideGraph;
 use crate::manifest::OxideManifest;
+use std::collections::BTreeSet;
 
 #[allow(dead_code)]
 pub fn build_graph_from_manifest(manifest: &OxideManifest) -> OxideGraph {
@@ -63,6 +64,93 @@ pub fn build_graph_from_manifest(manifest: &OxideManifest) -> OxideGraph {
     graph
 }
 
+/// The `depends_on.nodes` of a graph member, or `None` when `unique_id` is
+/// not in one of the collections `build_graph_from_manifest` adds.
+fn graph_parents<'a>(manifest: &'a OxideManifest, unique_id: &str) -> Option<&'a [String]> {
+    if manifest.sources.contains_key(unique_id) {
+        return Some(&[]);
+    }
+    let parents = manifest
+        .nodes
+        .get(unique_id)
+        .map(|n| &n.depends_on.nodes)
+        .or_else(|| {
+            manifest
+                .exposures
+                .get(unique_id)
+                .map(|e| &e.depends_on.nodes)
+        })
+        .or_else(|| manifest.metrics.get(unique_id).map(|m| &m.depends_on.nodes))
+        .or_else(|| {
+            manifest
+                .semantic_models
+                .get(unique_id)
+                .map(|sm| &sm.depends_on.nodes)
+        })
+        .or_else(|| {
+            manifest
+                .saved_queries
+                .get(unique_id)
+                .map(|sq| &sq.depends_on.nodes)
+        })
+        .or_else(|| {
+            manifest
+                .unit_tests
+                .get(unique_id)
+                .map(|ut| &ut.depends_on.nodes)
+        })?;
+    Some(parents)
+}
+
+/// Bring a graph built by `build_graph_from_manifest` up to date after the
+/// manifest was patched: `removed` unique_ids are dropped and the incoming
+/// edges of `changed` ones are rebuilt from their current `depends_on`. The
+/// result matches a full rebuild, including nodes that are only referenced.
+#[allow(dead_code)]
+pub fn update_graph_from_manifest(
+    graph: &mut OxideGraph,
+    manifest: &OxideManifest,
+    changed: &[String],
+    removed: &[String],
+) -> Result<(), String> {
+    let mut relink: BTreeSet<String> = BTreeSet::new();
+    let mut former_parents: BTreeSet<String> = BTreeSet::new();
+    for unique_id in removed.iter().chain(changed) {
+        if graph_parents(manifest, unique_id).is_some() {
+            relink.insert(unique_id.clone());
+            continue;
+        }
+        // No longer a member, e.g. deleted or disabled. Children keep
+        // referencing it, so relink them to recreate it as a bare node.
+        for child in graph.successors(unique_id) {
+            if graph_parents(manifest, &child).is_some() {
+                relink.insert(child);
+            }
+        }
+        former_parents.extend(graph.predecessors(unique_id));
+        graph.remove_node(unique_id);
+    }
+    for unique_id in relink {
+        let parents = graph_parents(manifest, &unique_id).unwrap_or_default();
+        former_parents.extend(graph.remove_incoming_edges(&unique_id));
+        graph.add_node(unique_id.clone());
+        for parent in parents {
+            graph.add_edge(parent, &unique_id, None)?;
+        }
+    }
+    // Drop nodes that were only there because a removed or changed node
+    // referenced them.
+    for unique_id in former_parents {
+        if graph_parents(manifest, &unique_id).is_none()
+            && graph.out_degree(&unique_id) == Some(0)
+            && graph.in_degree(&unique_id) == Some(0)
+        {
+            graph.remove_node(&unique_id);
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,4 +231,61 @@ mod tests {
             .ancestors("exposure.test.e", None)
             .contains("model.test.m"));
     }
+
+    fn assert_same_graph(a: &OxideGraph, b: &OxideGraph) {
+        let mut a_edges = a.edges();
+        let mut b_edges = b.edges();
+        a_edges.sort();
+        b_edges.sort();
+        assert_eq!(a.nodes(), b.nodes());
+        assert_eq!(a_edges, b_edges);
+    }
+
+    #[test]
+    fn test_update_graph_matches_rebuild() {
+        use crate::manifest_patch::PatchTarget;
+
+        let json = r#"{
+            "nodes": {
+                "model.test.a": {"unique_id":"model.test.a","name":"a","resource_type":"model","package_name":"test",
+                                 "depends_on":{"nodes":["source.test.raw.tbl"]}},
+                "model.test.b": {"unique_id":"model.test.b","name":"b","resource_type":"model","package_name":"test",
+                                 "depends_on":{"nodes":["model.test.a","model.test.ghost"]}},
+                "model.test.c": {"unique_id":"model.test.c","name":"c","resource_type":"model","package_name":"test",
+                                 "depends_on":{"nodes":["model.test.b"]}}
+            },
+            "sources": {"source.test.raw.tbl": {"unique_id":"source.test.raw.tbl","source_name":"raw","name":"tbl","package_name":"test"}}
+        }"#;
+        let mut manifest = OxideManifest::from_json_str(json).unwrap();
+        let mut graph = build_graph_from_manifest(&manifest);
+
+        // b stops depending on the unknown ghost node and on a.
+        let changed = manifest
+            .upsert_json(
+                PatchTarget::Nodes,
+                r#"{"model.test.b": {"unique_id":"model.test.b","name":"b","resource_type":"model","package_name":"test",
+                                     "depends_on":{"nodes":["source.test.raw.tbl"]}}}"#,
+            )
+            .unwrap();
+        update_graph_from_manifest(&mut graph, &manifest, &changed, &[]).unwrap();
+        assert_same_graph(&graph, &build_graph_from_manifest(&manifest));
+        assert!(!graph.nodes().contains("model.test.ghost"));
+
+        // Removing b leaves c referencing it as a bare node.
+        let removed = manifest.remove_entries(PatchTarget::Nodes, &["model.test.b".to_string()]);
+        update_graph_from_manifest(&mut graph, &manifest, &[], &removed).unwrap();
+        assert_same_graph(&graph, &build_graph_from_manifest(&manifest));
+        assert_eq!(graph.in_degree("model.test.b"), Some(0));
+
+        // Adding it back restores its parents.
+        let changed = manifest
+            .upsert_json(
+                PatchTarget::Nodes,
+                r#"{"model.test.b": {"unique_id":"model.test.b","name":"b","resource_type":"model","package_name":"test",
+                                     "depends_on":{"nodes":["model.test.a"]}}}"#,
+            )
+            .unwrap();
+        update_graph_from_manifest(&mut graph, &manifest, &changed, &[]).unwrap();
+        assert_same_graph(&graph, &build_graph_from_manifest(&manifest));
+    }
 }
@@ -0,0 +1,89 @@
+//! `.dbtignore` matching with git's wildmatch rules, as dbt builds it with
+//! `pathspec.PathSpec.from_lines(GitWildMatchPattern, ...)`.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct IgnoreSpec {
+    matcher: Gitignore,
+}
+
+impl IgnoreSpec {
+    /// Build a spec from `.dbtignore` lines; lines that are not valid
+    /// patterns are skipped.
+    pub fn from_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut builder = GitignoreBuilder::new("");
+        for line in lines {
+            let _ = builder.add_line(None, line);
+        }
+        IgnoreSpec {
+            matcher: builder.build().unwrap_or_else(|_| Gitignore::empty()),
+        }
+    }
+
+    /// Read `<project_root>/.dbtignore`, like `generate_dbt_ignore_spec`.
+    /// `None` when the file does not exist.
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn from_project_root(project_root: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(project_root.join(".dbtignore")) {
+            Ok(contents) => Ok(Some(IgnoreSpec::from_lines(contents.lines()))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// True when the file at `path`, relative to the project root, or one of
+    /// its directories is ignored. The last matching pattern decides.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        let normalized = path.replace('\\', "/");
+        let relative: Vec<&str> = normalized
+            .split('/')
+            .filter(|s| !s.is_empty() && *s != ".")
+            .collect();
+        if relative.is_empty() {
+            return false;
+        }
+        self.matcher
+            .matched_path_or_any_parents(relative.join("/"), false)
+            .is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_names_and_anchors() {
+        let spec = IgnoreSpec::from_lines(["# comment", "", "scratch.sql", "/models/tmp", "*.bak"]);
+        assert!(spec.is_ignored("models/scratch.sql"));
+        assert!(spec.is_ignored("models/deep/scratch.sql"));
+        assert!(spec.is_ignored("models/tmp/a.sql"));
+        assert!(!spec.is_ignored("other/models/tmp/a.sql"));
+        assert!(spec.is_ignored("macros/x.sql.bak"));
+        assert!(!spec.is_ignored("models/a.sql"));
+    }
+
+    #[test]
+    fn test_directories_double_star_and_negation() {
+        let spec = IgnoreSpec::from_lines([
+            "target/",
+            "models/**/legacy_*.sql",
+            "seeds/**",
+            "!seeds/keep.csv",
+            "data[0-9].csv",
+        ]);
+        assert!(spec.is_ignored("models/target/a.sql"));
+        assert!(spec.is_ignored("models/legacy_a.sql"));
+        assert!(spec.is_ignored("models/x/y/legacy_b.sql"));
+        assert!(!spec.is_ignored("models/x/current.sql"));
+        assert!(spec.is_ignored("seeds/a.csv"));
+        assert!(!spec.is_ignored("seeds/keep.csv"));
+        assert!(spec.is_ignored("seeds/sub/data1.csv"));
+        assert!(!spec.is_ignored("seeds_other/dataX.csv"));
+        assert!(spec.is_ignored("./seeds\\a.csv"));
+    }
+}
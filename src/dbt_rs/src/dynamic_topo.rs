@@ -0,0 +1,178 @@
+use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+use petgraph::Direction;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Topological order maintained incrementally as edges are added
+/// (Pearce & Kelly, "A Dynamic Topological Sort Algorithm for Directed
+/// Acyclic Graphs", 2006).
+///
+/// Only the nodes between the endpoints of an out-of-order edge are visited
+/// and reassigned, so inserting an edge is cheap on large graphs and a cycle
+/// is detected at the moment it would be introduced.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DynamicTopoOrder {
+    ord: HashMap<NodeIndex, usize>,
+    slots: Vec<Option<NodeIndex>>,
+}
+
+impl DynamicTopoOrder {
+    /// Compute an initial order for `graph`. On failure returns the nodes of
+    /// one cycle, with the first node repeated at the end.
+    pub fn from_graph<E>(graph: &StableDiGraph<String, E>) -> Result<Self, Vec<NodeIndex>> {
+        let mut in_degree: HashMap<NodeIndex, usize> = graph
+            .node_indices()
+            .map(|idx| {
+                (
+                    idx,
+                    graph.neighbors_directed(idx, Direction::Incoming).count(),
+                )
+            })
+            .collect();
+        let mut queue: VecDeque<NodeIndex> = graph
+            .node_indices()
+            .filter(|idx| in_degree[idx] == 0)
+            .collect();
+
+        let mut order = DynamicTopoOrder::default();
+        while let Some(idx) = queue.pop_front() {
+            order.push(idx);
+            for child in graph.neighbors_directed(idx, Direction::Outgoing) {
+                let degree = in_degree.get_mut(&child).expect("node in graph");
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        if order.ord.len() == graph.node_count() {
+            return Ok(order);
+        }
+        // Every unprocessed node has an unprocessed parent: walk parents until
+        // a node repeats to recover a cycle.
+        let mut current = graph
+            .node_indices()
+            .find(|idx| !order.ord.contains_key(idx))
+            .expect("unprocessed node");
+        let mut seen = Vec::new();
+        loop {
+            if let Some(pos) = seen.iter().position(|n| *n == current) {
+                let mut cycle: Vec<NodeIndex> = seen[pos..].to_vec();
+                cycle.reverse();
+                cycle.push(cycle[0]);
+                return Err(cycle);
+            }
+            seen.push(current);
+            current = graph
+                .neighbors_directed(current, Direction::Incoming)
+                .find(|p| !order.ord.contains_key(p))
+                .expect("unprocessed parent");
+        }
+    }
+
+    /// Append a new node at the end of the order.
+    pub fn push(&mut self, idx: NodeIndex) {
+        if !self.ord.contains_key(&idx) {
+            self.ord.insert(idx, self.slots.len());
+            self.slots.push(Some(idx));
+        }
+    }
+
+    pub fn remove(&mut self, idx: NodeIndex) {
+        if let Some(pos) = self.ord.remove(&idx) {
+            self.slots[pos] = None;
+        }
+        if self.slots.len() > 64 && self.ord.len() < self.slots.len() / 2 {
+            self.compact();
+        }
+    }
+
+    /// Nodes in topological order.
+    pub fn iter(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.slots.iter().filter_map(|slot| *slot)
+    }
+
+    /// Update the order for a new edge `source -> target` that has already
+    /// been added to `graph`. On failure returns the cycle the edge closes as
+    /// `source, target, ..., source`; the order is left unchanged.
+    pub fn add_edge<E>(
+        &mut self,
+        graph: &StableDiGraph<String, E>,
+        source: NodeIndex,
+        target: NodeIndex,
+    ) -> Result<(), Vec<NodeIndex>> {
+        if source == target {
+            return Err(vec![source, source]);
+        }
+        let lower = self.ord[&target];
+        let upper = self.ord[&source];
+        if lower > upper {
+            return Ok(());
+        }
+
+        // Forward search from target over nodes ordered before source.
+        let mut forward = vec![target];
+        let mut came_from: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut visited: HashSet<NodeIndex> = HashSet::from([target]);
+        let mut stack = vec![target];
+        while let Some(node) = stack.pop() {
+            for child in graph.neighbors_directed(node, Direction::Outgoing) {
+                if child == source {
+                    let mut path = vec![source];
+                    let mut current = node;
+                    while current != target {
+                        path.push(current);
+                        current = came_from[&current];
+                    }
+                    path.push(target);
+                    path.push(source);
+                    path.reverse();
+                    return Err(path);
+                }
+                if self.ord[&child] < upper && visited.insert(child) {
+                    came_from.insert(child, node);
+                    forward.push(child);
+                    stack.push(child);
+                }
+            }
+        }
+
+        // Backward search from source over nodes ordered after target.
+        let mut backward = vec![source];
+        let mut visited: HashSet<NodeIndex> = HashSet::from([source]);
+        let mut stack = vec![source];
+        while let Some(node) = stack.pop() {
+            for parent in graph.neighbors_directed(node, Direction::Incoming) {
+                if self.ord[&parent] > lower && visited.insert(parent) {
+                    backward.push(parent);
+                    stack.push(parent);
+                }
+            }
+        }
+
+        // Reuse the affected positions: ancestors of source first, then
+        // descendants of target, each keeping their relative order.
+        backward.sort_by_key(|n| self.ord[n]);
+        forward.sort_by_key(|n| self.ord[n]);
+        let mut positions: Vec<usize> = backward
+            .iter()
+            .chain(&forward)
+            .map(|n| self.ord[n])
+            .collect();
+        positions.sort_unstable();
+        for (node, pos) in backward.into_iter().chain(forward).zip(positions) {
+            self.ord.insert(node, pos);
+            self.slots[pos] = Some(node);
+        }
+        Ok(())
+    }
+
+    fn compact(&mut self) {
+        self.slots.retain(|slot| slot.is_some());
+        for (pos, slot) in self.slots.iter().enumerate() {
+            if let Some(idx) = slot {
+                self.ord.insert(*idx, pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,500 @@
+//! Finding and hashing project files for parsing, as dbt's
+//! `ReadFilesFromFileSystem` does, with the directory walks and hashing
+//! spread over threads.
+//!
+//! Files are found like `find_matching`: every file under a resource path
+//! whose name matches `[!.#~]*<extension>` (ignoring case) and whose
+//! project-relative path is not excluded by `.dbtignore`. Symlinked
+//! directories are not followed.
+
+use crate::dbtignore::IgnoreSpec;
+use crate::partial_parse::{join_path, FileHash, SourceFileHash};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Seeds larger than this are tracked by path instead of contents
+/// (`MAXIMUM_SEED_SIZE`).
+pub const MAXIMUM_SEED_SIZE: u64 = 1024 * 1024;
+
+/// The resource paths and extensions searched for one parse file type, as
+/// in `get_file_types_for_project`.
+#[derive(Debug, Clone)]
+pub struct FileTypeSearch {
+    pub parse_file_type: String,
+    pub paths: Vec<String>,
+    pub extensions: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectSearch {
+    pub project_name: String,
+    pub project_root: PathBuf,
+    pub file_types: Vec<FileTypeSearch>,
+    pub ignore: Option<IgnoreSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedFile {
+    /// `project_name://original_file_path`
+    pub file_id: String,
+    pub project_name: String,
+    pub searched_path: String,
+    pub relative_path: String,
+    pub original_file_path: String,
+    pub parse_file_type: String,
+    /// Seconds since the epoch, as `os.path.getmtime`.
+    pub modification_time: f64,
+    pub checksum: FileHash,
+}
+
+#[derive(Debug)]
+pub enum ScanError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// dbt reads project files as strict UTF-8.
+    Encoding {
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ScanError::Encoding { path } => {
+                write!(f, "{}: file is not valid UTF-8", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+/// Scanned files compared with the hashes of a previous parse, by file_id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDiff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+/// A file found by a walk, before it is read.
+struct FoundFile {
+    project: usize,
+    parse_file_type: String,
+    searched_path: String,
+    relative_path: String,
+    absolute_path: PathBuf,
+}
+
+/// A pool of `threads` workers (at least one) for the parse steps that run
+/// in parallel.
+pub(crate) fn thread_pool(threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .expect("failed to start parse threads")
+}
+
+/// `fnmatch("[!.#~]*" + extension)`, case-insensitively.
+fn matches_extension(file_name: &str, extension: &str) -> bool {
+    let name = file_name.to_lowercase();
+    let extension = extension.to_lowercase();
+    name.len() > extension.len() && name.ends_with(&extension) && !name.starts_with(['.', '#', '~'])
+}
+
+/// Walk `dir` like `os.walk`, collecting files relative to the search root.
+/// Unreadable directories are skipped, as `os.walk` does by default.
+fn walk(dir: &Path, relative_dir: &str, out: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative_path = if relative_dir.is_empty() {
+            name
+        } else {
+            format!("{}/{}", relative_dir, name)
+        };
+        if file_type.is_dir() {
+            walk(&entry.path(), &relative_path, out);
+        } else if file_type.is_symlink() && entry.path().is_dir() {
+            // os.walk lists symlinked directories but does not descend.
+            continue;
+        } else {
+            out.push((relative_path, entry.path()));
+        }
+    }
+}
+
+fn find_files(
+    projects: &[ProjectSearch],
+    project: usize,
+    file_type: &FileTypeSearch,
+    searched_path: &str,
+) -> Vec<FoundFile> {
+    let search = &projects[project];
+    let mut walked = Vec::new();
+    walk(&search.project_root.join(searched_path), "", &mut walked);
+    walked
+        .into_iter()
+        .filter(|(relative_path, _)| {
+            let file_name = relative_path.rsplit('/').next().unwrap_or_default();
+            file_type
+                .extensions
+                .iter()
+                .any(|extension| matches_extension(file_name, extension))
+        })
+        .filter(|(relative_path, _)| {
+            search
+                .ignore
+                .as_ref()
+                .is_none_or(|ignore| !ignore.is_ignored(&join_path(searched_path, relative_path)))
+        })
+        .filter(|(relative_path, _)| {
+            // Generic tests and fixtures live under the test paths but are
+            // not singular tests.
+            file_type.parse_file_type != "singular_test"
+                || !matches!(
+                    relative_path.split('/').next(),
+                    Some("generic" | "fixtures")
+                )
+        })
+        .map(|(relative_path, absolute_path)| FoundFile {
+            project,
+            parse_file_type: file_type.parse_file_type.clone(),
+            searched_path: searched_path.to_string(),
+            relative_path,
+            absolute_path,
+        })
+        .collect()
+}
+
+pub(crate) fn is_python_whitespace(c: char) -> bool {
+    c.is_whitespace() || ('\x1c'..='\x1f').contains(&c)
+}
+
+/// dbt's checksum of a project file: the path for seeds larger than
+/// `MAXIMUM_SEED_SIZE`, else `FileHash.from_contents` of the contents loaded
+/// with `strip=True`. `contents` is only called when the contents are hashed.
+pub fn file_checksum<S: AsRef<str>, E>(
+    parse_file_type: &str,
+    original_file_path: &str,
+    size: u64,
+    contents: impl FnOnce() -> Result<S, E>,
+) -> Result<FileHash, E> {
+    if parse_file_type == "seed" && size > MAXIMUM_SEED_SIZE {
+        return Ok(FileHash {
+            name: "path".to_string(),
+            checksum: original_file_path.to_string(),
+        });
+    }
+    let contents = contents()?;
+    Ok(FileHash::from_contents(
+        contents.as_ref().trim_matches(is_python_whitespace),
+    ))
+}
+
+fn read_contents(path: &Path) -> Result<String, ScanError> {
+    let bytes = fs::read(path).map_err(|error| ScanError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    String::from_utf8(bytes).map_err(|_| ScanError::Encoding {
+        path: path.to_path_buf(),
+    })
+}
+
+fn read_file(projects: &[ProjectSearch], found: &FoundFile) -> Result<ScannedFile, ScanError> {
+    let io_error = |error| ScanError::Io {
+        path: found.absolute_path.clone(),
+        error,
+    };
+    let metadata = fs::metadata(&found.absolute_path).map_err(io_error)?;
+    let modification_time = metadata
+        .modified()
+        .map_err(io_error)?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64());
+    let original_file_path = join_path(&found.searched_path, &found.relative_path);
+    let checksum = file_checksum(
+        &found.parse_file_type,
+        &original_file_path,
+        metadata.len(),
+        || read_contents(&found.absolute_path),
+    )?;
+    let project_name = projects[found.project].project_name.clone();
+    Ok(ScannedFile {
+        file_id: format!("{}://{}", project_name, original_file_path),
+        project_name,
+        searched_path: found.searched_path.clone(),
+        relative_path: found.relative_path.clone(),
+        original_file_path,
+        parse_file_type: found.parse_file_type.clone(),
+        modification_time,
+        checksum,
+    })
+}
+
+/// Find, stat and hash the files of every project on up to `threads`
+/// threads. When several searches find the same file_id the last one wins,
+/// as in dbt's `files` dict. Results are sorted by file_id.
+pub fn scan_projects(
+    projects: &[ProjectSearch],
+    threads: usize,
+) -> Result<Vec<ScannedFile>, ScanError> {
+    let searches: Vec<(usize, &FileTypeSearch, &str)> = projects
+        .iter()
+        .enumerate()
+        .flat_map(|(project, search)| {
+            search.file_types.iter().flat_map(move |file_type| {
+                file_type
+                    .paths
+                    .iter()
+                    .map(move |path| (project, file_type, path.as_str()))
+            })
+        })
+        .collect();
+    let pool = thread_pool(threads);
+    let found: Vec<FoundFile> = pool.install(|| {
+        searches
+            .par_iter()
+            .flat_map_iter(|(project, file_type, path)| {
+                find_files(projects, *project, file_type, path)
+            })
+            .collect()
+    });
+    let scanned: Vec<ScannedFile> = pool.install(|| {
+        found
+            .par_iter()
+            .map(|found| read_file(projects, found))
+            .collect::<Result<_, _>>()
+    })?;
+
+    let mut files = BTreeMap::new();
+    for file in scanned {
+        files.insert(file.file_id.clone(), file);
+    }
+    Ok(files.into_values().collect())
+}
+
+/// Compare a scan with the file hashes of a previous parse. Only previous
+/// files of the scanned projects and parse file types can be deleted.
+/// Checksums named "none" were never computed and so never match.
+pub fn diff_files(
+    projects: &[ProjectSearch],
+    scanned: &[ScannedFile],
+    previous: &BTreeMap<String, SourceFileHash>,
+) -> FileDiff {
+    let mut diff = FileDiff::default();
+    for file in scanned {
+        match previous.get(&file.file_id) {
+            None => diff.added.push(file.file_id.clone()),
+            Some(old) if old.checksum == file.checksum && old.checksum.name != "none" => {
+                diff.unchanged.push(file.file_id.clone())
+            }
+            Some(_) => diff.changed.push(file.file_id.clone()),
+        }
+    }
+    let scopes: BTreeSet<(&str, &str)> = projects
+        .iter()
+        .flat_map(|search| {
+            search.file_types.iter().map(|file_type| {
+                (
+                    search.project_name.as_str(),
+                    file_type.parse_file_type.as_str(),
+                )
+            })
+        })
+        .collect();
+    let scanned_ids: HashSet<&str> = scanned.iter().map(|file| file.file_id.as_str()).collect();
+    diff.deleted = previous
+        .iter()
+        .filter(|(file_id, _)| !scanned_ids.contains(file_id.as_str()))
+        .filter(|(_, old)| {
+            let project = old.project_name.as_deref().unwrap_or_default();
+            let parse_file_type = old.parse_file_type.as_deref().unwrap_or_default();
+            scopes.contains(&(project, parse_file_type))
+        })
+        .map(|(file_id, _)| file_id.clone())
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempProject(PathBuf);
+
+    impl TempProject {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("dbt_rs_scan_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            TempProject(root)
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn file_type(parse_file_type: &str, paths: &[&str], extensions: &[&str]) -> FileTypeSearch {
+        FileTypeSearch {
+            parse_file_type: parse_file_type.to_string(),
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn search(project: &TempProject, ignore: &[&str]) -> ProjectSearch {
+        ProjectSearch {
+            project_name: "p".to_string(),
+            project_root: project.0.clone(),
+            file_types: vec![
+                file_type("model", &["models"], &[".sql", ".py"]),
+                file_type("schema", &["models"], &[".yml", ".yaml"]),
+                file_type("singular_test", &["tests"], &[".sql"]),
+                file_type("seed", &["seeds"], &[".csv"]),
+            ],
+            ignore: Some(IgnoreSpec::from_lines(ignore.iter().copied())),
+        }
+    }
+
+    #[test]
+    fn test_scan_matches_dbt_search() {
+        let project = TempProject::new("search");
+        project.write("models/a.sql", "  select 1\n\n");
+        project.write("models/staging/B.SQL", "select 2");
+        project.write("models/staging/schema.yml", "version: 2");
+        project.write("models/.hidden.sql", "x");
+        project.write("models/#draft.sql", "x");
+        project.write("models/.sql", "x");
+        project.write("models/scratch/c.sql", "x");
+        project.write("models/notes.txt", "x");
+        project.write("tests/t.sql", "select 1");
+        project.write("tests/generic/g.sql", "x");
+        project.write("tests/fixtures/f.sql", "x");
+        project.write("seeds/s.csv", "id\n1\n");
+
+        let files = scan_projects(&[search(&project, &["scratch/"])], 4).unwrap();
+        let ids: Vec<&str> = files.iter().map(|f| f.file_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "p://models/a.sql",
+                "p://models/staging/B.SQL",
+                "p://models/staging/schema.yml",
+                "p://seeds/s.csv",
+                "p://tests/t.sql",
+            ]
+        );
+        let a = &files[0];
+        assert_eq!(a.searched_path, "models");
+        assert_eq!(a.relative_path, "a.sql");
+        assert_eq!(a.parse_file_type, "model");
+        assert!(a.modification_time > 0.0);
+        assert_eq!(a.checksum.name, "sha256");
+        assert_eq!(
+            a.checksum.checksum,
+            "822ae07d4783158bc1912bb623e5107cc9002d519e1143a9c200ed6ee18b6d0f"
+        );
+        assert_eq!(files[2].parse_file_type, "schema");
+    }
+
+    #[test]
+    fn test_large_seeds_and_encoding_errors() {
+        let project = TempProject::new("seeds");
+        project.write("seeds/big.csv", &"x".repeat(MAXIMUM_SEED_SIZE as usize + 1));
+        let files = scan_projects(&[search(&project, &[])], 2).unwrap();
+        assert_eq!(
+            files[0].checksum,
+            FileHash {
+                name: "path".to_string(),
+                checksum: "seeds/big.csv".to_string()
+            }
+        );
+
+        project.write("models/bad.sql", "");
+        fs::write(project.0.join("models/bad.sql"), [0xff, 0xfe]).unwrap();
+        let err = scan_projects(&[search(&project, &[])], 2).unwrap_err();
+        assert!(matches!(err, ScanError::Encoding { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_file_checksum() {
+        let contents = |s: &'static str| move || Ok::<_, ScanError>(s);
+        assert_eq!(
+            file_checksum("model", "models/a.sql", 12, contents("\n select 1 \n")).unwrap(),
+            FileHash::from_contents("select 1")
+        );
+        let big = MAXIMUM_SEED_SIZE + 1;
+        let unread = || -> Result<&str, ScanError> { panic!("contents read") };
+        assert_eq!(
+            file_checksum("seed", "seeds/big.csv", big, unread)
+                .unwrap()
+                .checksum,
+            "seeds/big.csv"
+        );
+    }
+
+    #[test]
+    fn test_diff_against_previous_hashes() {
+        let project = TempProject::new("diff");
+        project.write("models/same.sql", "select 1");
+        project.write("models/edited.sql", "select 2");
+        project.write("models/new.sql", "select 3");
+        let searches = [search(&project, &[])];
+        let files = scan_projects(&searches, 2).unwrap();
+
+        let hash = |checksum: &str, parse_file_type: &str| SourceFileHash {
+            project_name: Some("p".to_string()),
+            original_file_path: String::new(),
+            parse_file_type: Some(parse_file_type.to_string()),
+            checksum: FileHash {
+                name: "sha256".to_string(),
+                checksum: checksum.to_string(),
+            },
+        };
+        let previous = BTreeMap::from([
+            (
+                "p://models/same.sql".to_string(),
+                hash(&FileHash::from_contents("select 1").checksum, "model"),
+            ),
+            ("p://models/edited.sql".to_string(), hash("old", "model")),
+            ("p://models/gone.sql".to_string(), hash("x", "model")),
+            (
+                "p://docs/overview.md".to_string(),
+                hash("x", "documentation"),
+            ),
+        ]);
+        let diff = diff_files(&searches, &files, &previous);
+        assert_eq!(diff.added, vec!["p://models/new.sql"]);
+        assert_eq!(diff.changed, vec!["p://models/edited.sql"]);
+        assert_eq!(diff.unchanged, vec!["p://models/same.sql"]);
+        assert_eq!(diff.deleted, vec!["p://models/gone.sql"]);
+    }
+}
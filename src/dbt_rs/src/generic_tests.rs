@@ -0,0 +1,1416 @@
+//! Generic test nodes from the `data_tests:` of properties files, replacing
+//! `TestBuilder` and `SchemaGenericTestParser.parse_generic_test`.
+//!
+//! Names, unique_ids, `raw_code` and `test_metadata` are built as dbt does.
+//! Test kwargs are not rendered with the full Jinja context: the `ref()` and
+//! `source()` calls they contain are read statically, which is what parsing
+//! needs them for. Mappings keep the YAML's key order, as Python dicts do;
+//! only the metadata hashed into a unique_id is key-sorted.
+
+use crate::config_resolution::{ConfigError, ConfigTarget};
+use crate::jinja_render::render_template;
+use crate::lossless;
+use crate::macro_resolution::MacroResolveContext;
+use crate::manifest::{OxideManifest, OxideNode, OxideNodeVersion};
+use crate::node_construction::{fqn_prefix, generate_schema_name, BuildContext};
+use crate::properties::{
+    ColumnProperties, NodeProperties, SourceProperties, SourceTableProperties,
+};
+use crate::python_repr::{python_repr, python_repr_str, python_str, python_type};
+use crate::static_parser::extract_static_calls;
+use md5::{Digest, Md5};
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// Test entry keys that are configs rather than test arguments.
+const CONFIG_ARGS: [&str; 14] = [
+    "severity",
+    "tags",
+    "enabled",
+    "where",
+    "limit",
+    "warn_if",
+    "error_if",
+    "fail_calc",
+    "store_failures",
+    "store_failures_as",
+    "meta",
+    "database",
+    "schema",
+    "alias",
+];
+
+/// Tests whose macros dbt does not render at parse time.
+const SHORTCUT_MACROS: [&str; 2] = ["macro.dbt.test_not_null", "macro.dbt.test_unique"];
+
+const HASH_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenericTestError {
+    /// The test entry cannot be turned into a test.
+    Definition {
+        original_file_path: String,
+        target: String,
+        message: String,
+    },
+    Config {
+        unique_id: String,
+        error: ConfigError,
+    },
+    /// The assembled node does not validate.
+    Invalid { unique_id: String, message: String },
+}
+
+impl fmt::Display for GenericTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenericTestError::Definition {
+                original_file_path,
+                target,
+                message,
+            } => write!(
+                f,
+                "Invalid test config given in {}:\n\t{}\n\t@: {}",
+                original_file_path, message, target
+            ),
+            GenericTestError::Config { unique_id, error } => write!(f, "{}: {}", unique_id, error),
+            GenericTestError::Invalid { unique_id, message } => {
+                write!(f, "Invalid node {}: {}", unique_id, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenericTestError {}
+
+/// Settings shared by every generic test of a parse.
+#[derive(Debug, Clone, Copy)]
+pub struct GenericTestContext<'a> {
+    pub build: BuildContext<'a>,
+    /// Used to find the `test_<name>` macros.
+    pub macros: MacroResolveContext<'a>,
+    /// Context for rendering Jinja in test configs, e.g. `{"var": ...}`
+    /// values.
+    pub render_context: &'a Map<String, Value>,
+    /// The `require_generic_test_arguments_property` flag: test arguments
+    /// may be nested under `arguments:`.
+    pub arguments_property: bool,
+}
+
+/// The properties entry that declares the tests.
+#[derive(Debug, Clone, Copy)]
+pub enum TestTarget<'a> {
+    /// An entry of `models:`, `seeds:`, `snapshots:` or `analyses:`.
+    Node {
+        yaml_key: &'a str,
+        properties: &'a NodeProperties,
+    },
+    Source {
+        source: &'a SourceProperties,
+        table: &'a SourceTableProperties,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GenericTestInput<'a> {
+    pub target: TestTarget<'a>,
+    /// The package of the properties file.
+    pub package_name: &'a str,
+    /// The properties file, e.g. `models/schema.yml`.
+    pub original_file_path: &'a str,
+}
+
+impl TestTarget<'_> {
+    /// `target.name`; sources are named `<source>_<table>`.
+    fn name(&self) -> String {
+        match self {
+            TestTarget::Node { properties, .. } => properties.name.clone(),
+            TestTarget::Source { source, table } => format!("{}_{}", source.name, table.name),
+        }
+    }
+
+    fn is_model(&self) -> bool {
+        matches!(self, TestTarget::Node { yaml_key, .. } if *yaml_key == "models")
+    }
+
+    fn quote_columns(&self) -> Option<bool> {
+        match self {
+            TestTarget::Node { properties, .. } => properties
+                .extra
+                .get("quote_columns")
+                .and_then(Value::as_bool),
+            TestTarget::Source { source, table } => {
+                let column_quoting = |extra: &Map<String, Value>| {
+                    extra
+                        .get("quoting")
+                        .and_then(|quoting| quoting.get("column"))
+                        .and_then(Value::as_bool)
+                };
+                column_quoting(&table.extra).or_else(|| column_quoting(&source.extra))
+            }
+        }
+    }
+
+    fn file_key_name(&self) -> String {
+        match self {
+            TestTarget::Node {
+                yaml_key,
+                properties,
+            } => format!("{}.{}", yaml_key, properties.name),
+            TestTarget::Source { source, .. } => format!("sources.{}", source.name),
+        }
+    }
+
+    /// `get_where_subquery(...)` argument and dependency call of the target.
+    fn model_call(&self, version: Option<&OxideNodeVersion>) -> String {
+        match self {
+            TestTarget::Node { properties, .. } => match version {
+                Some(version) if self.is_model() => {
+                    format!("ref('{}', version='{}')", properties.name, version)
+                }
+                _ => format!("ref('{}')", properties.name),
+            },
+            TestTarget::Source { source, table } => {
+                format!("source('{}', '{}')", source.name, table.name)
+            }
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn version_truthy(version: &OxideNodeVersion) -> bool {
+    match version {
+        OxideNodeVersion::Int(v) => *v != 0,
+        OxideNodeVersion::Float(v) => *v != 0.0,
+        OxideNodeVersion::Str(v) => !v.is_empty(),
+    }
+}
+
+/// `re.sub("[^0-9a-zA-Z_]+", "_", arg)`.
+fn clean_arg(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut in_run = false;
+    for c in arg.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            out.push(c);
+            in_run = false;
+        } else if !in_run {
+            out.push('_');
+            in_run = true;
+        }
+    }
+    out
+}
+
+/// dbt's `utils.md5`: the hex MD5 of a string.
+fn md5_hex(s: &str) -> String {
+    format!("{:x}", Md5::digest(s.as_bytes()))
+}
+
+/// dbt's `synthesize_generic_test_names`: the short name used for the
+/// compiled file and alias (at most 63 characters, hashed when the full
+/// name is longer) and the full name used for the unique_id and fqn.
+pub fn synthesize_generic_test_names(
+    test_type: &str,
+    test_name: &str,
+    args: &Map<String, Value>,
+) -> (String, String) {
+    let mut flat_args = Vec::new();
+    let mut arg_names: Vec<&String> = args.keys().collect();
+    arg_names.sort();
+    for arg_name in arg_names {
+        // The model is already part of the name.
+        if arg_name == "model" {
+            continue;
+        }
+        match &args[arg_name] {
+            Value::Object(map) => flat_args.extend(map.values().map(python_str)),
+            Value::Array(items) => flat_args.extend(items.iter().map(python_str)),
+            other => flat_args.push(python_str(other)),
+        }
+    }
+    let unique = flat_args
+        .iter()
+        .map(|arg| clean_arg(arg))
+        .collect::<Vec<_>>()
+        .join("__");
+
+    let test_identifier = format!("{}_{}", test_type, test_name);
+    let full_name = format!("{}_{}", test_identifier, unique);
+    if full_name.chars().count() >= 64 {
+        let truncated: String = test_identifier.chars().take(30).collect();
+        let short_name = format!("{}_{}", truncated, md5_hex(&full_name));
+        (short_name, full_name)
+    } else {
+        (full_name.clone(), full_name)
+    }
+}
+
+/// `TEST_NAME_PATTERN`: an optionally namespaced identifier at the start of
+/// `test_name`.
+fn parse_test_name(test_name: &str) -> Option<(Option<&str>, &str)> {
+    fn identifier(s: &str) -> Option<&str> {
+        let first = s.chars().next()?;
+        if !(first.is_ascii_alphabetic() || first == '_') {
+            return None;
+        }
+        let end = s
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(s.len());
+        Some(&s[..end])
+    }
+    let first = identifier(test_name)?;
+    let namespaced = test_name[first.len()..]
+        .strip_prefix('.')
+        .and_then(identifier);
+    Some(match namespaced {
+        Some(name) => (Some(first), name),
+        None => (None, first),
+    })
+}
+
+/// Whether dbt's `add_rendered_test_kwargs` wraps a kwarg in `{{ }}`:
+/// `^\s*(env_var|ref|var|source|doc)\s*\(.+\)\s*$`.
+fn looks_like_function(value: &str) -> bool {
+    let trimmed = value.trim_start();
+    let Some(rest) = ["env_var", "ref", "var", "source", "doc"]
+        .iter()
+        .find_map(|name| trimmed.strip_prefix(name))
+    else {
+        return false;
+    };
+    let Some(rest) = rest.trim_start().strip_prefix('(') else {
+        return false;
+    };
+    let rest = rest.trim_end();
+    match rest.strip_suffix(')') {
+        Some(inner) => !inner.is_empty() && !inner.contains('\n'),
+        None => false,
+    }
+}
+
+/// `TestBuilder`: a test entry split into name, arguments and configs.
+struct TestBuilder {
+    name: String,
+    namespace: Option<String>,
+    args: Map<String, Value>,
+    /// In dbt's order: the top-level config keys, then `config:`.
+    config: Vec<(String, Value)>,
+    description: String,
+    compiled_name: String,
+    fqn_name: String,
+}
+
+impl TestBuilder {
+    fn new(
+        ctx: &GenericTestContext,
+        data_test: &Value,
+        target: &TestTarget,
+        column_name: Option<&str>,
+        version: Option<&OxideNodeVersion>,
+    ) -> Result<Self, String> {
+        let (test_name, mut args) =
+            extract_test_args(data_test, column_name, ctx.arguments_property)?;
+        if args.contains_key("model") {
+            return Err("Test arguments include \"model\", which is a reserved argument".into());
+        }
+        let version = version.filter(|version| version_truthy(version));
+        args.insert(
+            "model".to_string(),
+            json!(format!(
+                "{{{{ get_where_subquery({}) }}}}",
+                target.model_call(version)
+            )),
+        );
+        let (namespace, name) = parse_test_name(&test_name).ok_or_else(|| {
+            format!(
+                "Test name string did not match expected pattern: {}",
+                test_name
+            )
+        })?;
+        let (namespace, name) = (namespace.map(str::to_string), name.to_string());
+
+        let mut builder = TestBuilder {
+            name,
+            namespace,
+            args,
+            config: Vec::new(),
+            description: String::new(),
+            compiled_name: String::new(),
+            fqn_name: String::new(),
+        };
+        let legacy = builder.process_legacy_args()?;
+        builder.config = builder.render_values(ctx, legacy)?;
+        match builder.args.shift_remove("config") {
+            None => {}
+            Some(Value::Object(nested)) => {
+                for (key, value) in builder.render_values(ctx, nested.into_iter().collect())? {
+                    match builder.config.iter_mut().find(|(k, _)| *k == key) {
+                        Some(entry) => entry.1 = value,
+                        None => builder.config.push((key, value)),
+                    }
+                }
+            }
+            Some(other) => {
+                return Err(format!(
+                    "test config must be a dict, got {} (value {})",
+                    python_type(&other),
+                    python_str(&other)
+                ))
+            }
+        }
+
+        if let Some(description) = builder.args.shift_remove("description") {
+            builder.description = python_str(&description);
+        }
+        if let Some(name) = builder.args.shift_remove("name") {
+            builder.compiled_name = python_str(&name);
+            builder.fqn_name = builder.compiled_name.clone();
+        } else {
+            let (short_name, full_name) = builder.synthetic_names(target, version);
+            if short_name != full_name && !builder.config.iter().any(|(k, _)| k == "alias") {
+                builder
+                    .config
+                    .push(("alias".to_string(), json!(short_name.clone())));
+            }
+            builder.compiled_name = short_name;
+            builder.fqn_name = full_name;
+        }
+        Ok(builder)
+    }
+
+    /// `_process_legacy_args`: config keys given next to the test arguments,
+    /// falling back to `config:` for unset (falsy) ones.
+    fn process_legacy_args(&mut self) -> Result<Vec<(String, Value)>, String> {
+        let mut config = Vec::new();
+        for key in CONFIG_ARGS {
+            let mut value = self.args.shift_remove(key).unwrap_or(Value::Null);
+            if let Some(nested) = self.args.get_mut("config") {
+                let Value::Object(nested) = nested else {
+                    return Err(format!(
+                        "test config must be a dict, got {} (value {})",
+                        python_type(nested),
+                        python_str(nested)
+                    ));
+                };
+                if truthy(&value) && nested.contains_key(key) {
+                    return Err(
+                        "Test cannot have the same key at the top-level and in config".into(),
+                    );
+                }
+                if !truthy(&value) {
+                    value = nested.shift_remove(key).unwrap_or(Value::Null);
+                }
+            }
+            config.push((key.to_string(), value));
+        }
+        Ok(config)
+    }
+
+    /// `_render_values`: render string configs natively and drop `None`s.
+    fn render_values(
+        &self,
+        ctx: &GenericTestContext,
+        config: Vec<(String, Value)>,
+    ) -> Result<Vec<(String, Value)>, String> {
+        let mut rendered = Vec::new();
+        for (key, value) in config {
+            let value = match value {
+                Value::String(s) if s.contains('{') => {
+                    render_template(&s, ctx.render_context, true).map_err(|e| {
+                        format!(
+                            "The config '{}' of test '{}' could not be rendered: {}",
+                            key, self.name, e
+                        )
+                    })?
+                }
+                value => value,
+            };
+            if !value.is_null() {
+                rendered.push((key, value));
+            }
+        }
+        Ok(rendered)
+    }
+
+    fn synthetic_names(
+        &self,
+        target: &TestTarget,
+        version: Option<&OxideNodeVersion>,
+    ) -> (String, String) {
+        let mut target_name = target.name();
+        let mut name = match target {
+            TestTarget::Source { .. } => format!("source_{}", self.name),
+            TestTarget::Node { .. } => self.name.clone(),
+        };
+        if let (Some(version), true) = (version, target.is_model()) {
+            target_name = format!("{}_v{}", target_name, version);
+        }
+        if let Some(namespace) = &self.namespace {
+            name = format!("{}_{}", namespace, name);
+        }
+        synthesize_generic_test_names(&name, &target_name, &self.args)
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let tags = match self.config.iter().find(|(key, _)| key == "tags") {
+            None => return Ok(Vec::new()),
+            Some((_, tags)) => tags,
+        };
+        match tags {
+            Value::String(tag) => Ok(vec![tag.clone()]),
+            Value::Array(tags) => tags
+                .iter()
+                .map(|tag| match tag {
+                    Value::String(tag) => Ok(tag.clone()),
+                    other => Err(format!(
+                        "got {} ({}) for tag, expected a str",
+                        python_str(other),
+                        python_type(other)
+                    )),
+                })
+                .collect(),
+            other => Err(format!(
+                "got {} ({}) for tags, expected a list of strings",
+                python_str(other),
+                python_type(other)
+            )),
+        }
+    }
+
+    fn macro_name(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}.test_{}", namespace, self.name),
+            None => format!("test_{}", self.name),
+        }
+    }
+
+    /// `build_raw_code`: the test macro call and its `config()` call.
+    fn raw_code(&self) -> String {
+        let configs = self
+            .config
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(s) => format!("{}=\"{}\"", key, s.replace('"', "\\\"")),
+                other => format!("{}={}", key, python_str(other)),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let config = if configs.is_empty() {
+            String::new()
+        } else {
+            format!("{{{{ config({}) }}}}", configs)
+        };
+        format!(
+            "{{{{ {}(**_dbt_generic_test_kwargs) }}}}{}",
+            self.macro_name(),
+            config
+        )
+    }
+}
+
+/// `extract_test_args`: the test name and a copy of its arguments.
+fn extract_test_args(
+    data_test: &Value,
+    column_name: Option<&str>,
+    arguments_property: bool,
+) -> Result<(String, Map<String, Value>), String> {
+    let data_test = match data_test {
+        // A bare name is a test without arguments.
+        Value::String(name) => {
+            return extract_test_args(&json!({ name: {} }), column_name, arguments_property)
+        }
+        Value::Object(data_test) => data_test,
+        other => {
+            return Err(format!(
+                "test must be dict or str, got {} (value {})",
+                python_type(other),
+                python_str(other)
+            ))
+        }
+    };
+    let (test_name, test_args) = match data_test.get("test_name") {
+        Some(test_name) => {
+            let mut args = data_test.clone();
+            args.shift_remove("test_name");
+            (test_name.clone(), Value::Object(args))
+        }
+        None if data_test.len() == 1 => {
+            let (name, args) = data_test.iter().next().expect("one key");
+            (json!(name), args.clone())
+        }
+        None => {
+            let items: Vec<String> = data_test
+                .iter()
+                .map(|(k, v)| format!("({}, {})", python_repr_str(k), python_repr(v)))
+                .collect();
+            return Err(format!(
+                "test definition dictionary must have exactly one key, got [{}] instead ({} keys)",
+                items.join(", "),
+                data_test.len()
+            ));
+        }
+    };
+    let Value::Object(mut test_args) = test_args else {
+        return Err(format!(
+            "test arguments must be a dict, got {} (value {})",
+            python_type(&test_args),
+            python_str(&test_args)
+        ));
+    };
+    let Value::String(test_name) = test_name else {
+        return Err(format!(
+            "test name must be a str, got {} (value {})",
+            python_type(&test_name),
+            python_str(&test_name)
+        ));
+    };
+    if let Some(column_name) = column_name {
+        test_args.insert("column_name".to_string(), json!(column_name));
+    }
+    if arguments_property {
+        if let Some(Value::Object(arguments)) = test_args.shift_remove("arguments") {
+            test_args.extend(arguments);
+        }
+    }
+    Ok((test_name, test_args))
+}
+
+/// `get_hashable_md`: mappings key-sorted, lists kept, everything else
+/// `str()`ed.
+fn hashable_metadata(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), hashable_metadata(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(hashable_metadata).collect()),
+        other => Value::String(python_str(other)),
+    }
+}
+
+fn column_tags(column: &ColumnProperties) -> Vec<String> {
+    let mut tags: Vec<String> = column
+        .extra
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag.as_str().map(str::to_string))
+        .collect();
+    match column.config.get("tags") {
+        Some(Value::String(tag)) => tags.push(tag.clone()),
+        Some(Value::Array(config_tags)) => tags.extend(
+            config_tags
+                .iter()
+                .filter_map(|tag| tag.as_str().map(str::to_string)),
+        ),
+        _ => {}
+    }
+    tags
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.as_str().map(str::to_string))
+        .collect()
+}
+
+/// `ref()` and `source()` calls made while rendering the kwargs, the
+/// implicit `model` argument last as dbt adds it last.
+fn kwarg_calls(
+    args: &Map<String, Value>,
+    target: &TestTarget,
+    version: Option<&OxideNodeVersion>,
+    refs: &mut Vec<Value>,
+    sources: &mut Vec<Value>,
+) {
+    fn walk(value: &Value, refs: &mut Vec<Value>, sources: &mut Vec<Value>) {
+        match value {
+            Value::String(s) => {
+                let calls = if looks_like_function(s) {
+                    extract_static_calls(&format!("{{{{ {} }}}}", s))
+                } else {
+                    extract_static_calls(s)
+                };
+                refs.extend(
+                    calls.refs.iter().map(
+                        |r| json!({"name": r.name, "package": r.package, "version": r.version}),
+                    ),
+                );
+                sources.extend(calls.sources.iter().map(|s| json!(s)));
+            }
+            Value::Array(items) => items.iter().for_each(|item| walk(item, refs, sources)),
+            Value::Object(map) => map.values().for_each(|item| walk(item, refs, sources)),
+            _ => {}
+        }
+    }
+    for (key, value) in args {
+        // Column names are never rendered.
+        if key != "column_name" && key != "model" {
+            walk(value, refs, sources);
+        }
+    }
+    target_call(target, version, refs, sources);
+}
+
+fn target_call(
+    target: &TestTarget,
+    version: Option<&OxideNodeVersion>,
+    refs: &mut Vec<Value>,
+    sources: &mut Vec<Value>,
+) {
+    match target {
+        TestTarget::Node { properties, .. } => {
+            refs.push(json!({"name": properties.name, "package": null, "version": version}))
+        }
+        TestTarget::Source { source, table } => sources.push(json!([source.name, table.name])),
+    }
+}
+
+/// `parse_generic_test`: build one test of `input.target`.
+fn build_test(
+    ctx: &GenericTestContext,
+    manifest: &OxideManifest,
+    input: &GenericTestInput,
+    data_test: &Value,
+    column: Option<&ColumnProperties>,
+    version: Option<&OxideNodeVersion>,
+) -> Result<OxideNode, GenericTestError> {
+    let target = &input.target;
+    let package_name = input.package_name;
+    let definition_error = |message: String| GenericTestError::Definition {
+        original_file_path: input.original_file_path.to_string(),
+        target: target.name(),
+        message,
+    };
+
+    let column_name = column.map(|column| {
+        let quote = column.extra.get("quote").and_then(Value::as_bool);
+        if quote.unwrap_or(false) || (quote.is_none() && target.quote_columns() == Some(true)) {
+            let quote_char = ctx.build.quote_char.unwrap_or('"');
+            format!("{}{}{}", quote_char, column.name, quote_char)
+        } else {
+            column.name.clone()
+        }
+    });
+    let mut tags: Vec<String> = match target {
+        TestTarget::Node { .. } => Vec::new(),
+        TestTarget::Source { source, table } => {
+            let mut tags = string_list(source.extra.get("tags"));
+            tags.extend(string_list(table.extra.get("tags")));
+            tags
+        }
+    };
+    tags.extend(column.map(column_tags).unwrap_or_default());
+
+    let builder = TestBuilder::new(ctx, data_test, target, column_name.as_deref(), version)
+        .map_err(definition_error)?;
+    tags.extend(builder.tags().map_err(definition_error)?);
+    tags.sort();
+    tags.dedup();
+
+    // The fqn is the properties file's directory within its resource path.
+    let relative_path = input
+        .original_file_path
+        .split_once('/')
+        .map_or("", |(_, rest)| rest);
+    let mut fqn = fqn_prefix(package_name, relative_path);
+    fqn.push(builder.fqn_name.clone());
+
+    let test_metadata = json!({
+        "namespace": builder.namespace,
+        "name": builder.name,
+        "kwargs": builder.args,
+    });
+    let hash_string = format!(
+        "{}{}",
+        builder.fqn_name,
+        python_repr(&hashable_metadata(&test_metadata))
+    );
+    let test_hash = md5_hex(&hash_string);
+    let unique_id = format!(
+        "test.{}.{}.{}",
+        package_name,
+        builder.fqn_name,
+        &test_hash[test_hash.len() - HASH_LENGTH..]
+    );
+
+    let config_call: Map<String, Value> = builder.config.iter().cloned().collect();
+    let config_target = ConfigTarget {
+        package_name,
+        resource_type: "test",
+        fqn: &fqn,
+    };
+    let config = ctx
+        .build
+        .resolver
+        .resolve(config_target, None, Some(&config_call))
+        .map_err(|error| GenericTestError::Config {
+            unique_id: unique_id.clone(),
+            error,
+        })?
+        .config;
+    for tag in string_list(config.get("tags")) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    let macro_ctx = MacroResolveContext {
+        node_package: Some(package_name),
+        ..ctx.macros
+    };
+    let test_macro = manifest
+        .resolve_macro(&format!("test_{}", builder.name), None, macro_ctx)
+        .map_err(|e| definition_error(e.to_string()))?
+        .unique_id;
+    let mut macros = vec![test_macro.clone()];
+    let mut refs = Vec::new();
+    let mut sources = Vec::new();
+    if SHORTCUT_MACROS.contains(&test_macro.as_str()) {
+        target_call(target, version, &mut refs, &mut sources);
+    } else {
+        kwarg_calls(&builder.args, target, version, &mut refs, &mut sources);
+        if let Ok(resolution) = manifest.resolve_macro("get_where_subquery", None, macro_ctx) {
+            if !macros.contains(&resolution.unique_id) {
+                macros.push(resolution.unique_id);
+            }
+        }
+    }
+
+    let database = config
+        .get("database")
+        .and_then(Value::as_str)
+        .unwrap_or(ctx.build.database)
+        .to_string();
+    let schema = generate_schema_name(
+        ctx.build.schema,
+        config.get("schema").and_then(Value::as_str),
+    );
+    let alias = config
+        .get("alias")
+        .and_then(Value::as_str)
+        .unwrap_or(&builder.fqn_name)
+        .to_string();
+    let mut group = config.get("group").filter(|group| truthy(group)).cloned();
+
+    // Generic tests inherit the group of the node they are attached to.
+    let mut attached_node = None;
+    if let TestTarget::Node { properties, .. } = target {
+        let version = version.map(|version| version.to_string());
+        let found = manifest
+            .find_ref_unique_id(&properties.name, Some(package_name), version.as_deref())
+            .and_then(|unique_id| manifest.nodes.get(&unique_id))
+            .or_else(|| {
+                [properties.name.clone(), properties.name.to_uppercase()]
+                    .iter()
+                    .find_map(|name| manifest.find_disabled_unique_id(name, None))
+                    .and_then(|unique_id| manifest.get_disabled(&unique_id))
+                    .and_then(|nodes| nodes.first())
+            });
+        if let Some(node) = found {
+            attached_node = Some(node.unique_id.clone());
+            group = node.group.clone().map(Value::String);
+        }
+    }
+
+    let mut node = json!({
+        "unique_id": unique_id,
+        "name": builder.fqn_name,
+        "resource_type": "test",
+        "package_name": package_name,
+        "path": format!("{}.sql", builder.compiled_name),
+        "original_file_path": input.original_file_path,
+        "fqn": fqn,
+        "alias": alias,
+        "schema": schema,
+        "database": database,
+        "checksum": {"name": "none", "checksum": ""},
+        "tags": tags,
+        "meta": config.get("meta").filter(|meta| truthy(meta)).cloned().unwrap_or(json!({})),
+        "description": builder.description,
+        "columns": {},
+        "raw_code": builder.raw_code(),
+        "language": "sql",
+        "refs": refs,
+        "sources": sources,
+        "metrics": [],
+        "depends_on": {"macros": macros, "nodes": []},
+        "config_call_dict": config_call,
+        "test_metadata": test_metadata,
+        "column_name": column_name,
+        "file_key_name": target.file_key_name(),
+        "attached_node": attached_node,
+        "group": group,
+        "relation_name": null,
+    });
+    if let Some(docs) = config.get("docs") {
+        node["docs"] = docs.clone();
+    }
+    node["config"] = Value::Object(config);
+
+    lossless::from_value(node).map_err(|e| GenericTestError::Invalid {
+        unique_id,
+        message: e.to_string(),
+    })
+}
+
+/// The `include`/`exclude` element of a version's `columns`.
+fn version_includes(columns: &[Value], name: &str) -> bool {
+    let Some(include_exclude) = columns
+        .iter()
+        .find(|column| column.get("include").is_some())
+    else {
+        return true;
+    };
+    let included = match include_exclude.get("include") {
+        Some(Value::String(all)) => all == "*" || all == "all",
+        Some(Value::Array(names)) => names.iter().any(|n| n.as_str() == Some(name)),
+        _ => false,
+    };
+    let excluded = include_exclude
+        .get("exclude")
+        .and_then(Value::as_array)
+        .is_some_and(|names| names.iter().any(|n| n.as_str() == Some(name)));
+    included && !excluded
+}
+
+/// Build every generic test declared by `input.target`, in dbt's order:
+/// for models and other nodes the column tests, then the entry's own tests
+/// (per version for versioned models); for sources the table's tests, then
+/// its column tests.
+pub fn build_generic_tests(
+    ctx: &GenericTestContext,
+    manifest: &OxideManifest,
+    input: &GenericTestInput,
+) -> Result<Vec<OxideNode>, GenericTestError> {
+    let mut tests = Vec::new();
+    match input.target {
+        TestTarget::Source { table, .. } => {
+            for data_test in &table.data_tests {
+                tests.push(build_test(ctx, manifest, input, data_test, None, None)?);
+            }
+            for column in &table.columns {
+                for data_test in &column.data_tests {
+                    tests.push(build_test(
+                        ctx,
+                        manifest,
+                        input,
+                        data_test,
+                        Some(column),
+                        None,
+                    )?);
+                }
+            }
+        }
+        TestTarget::Node { properties, .. }
+            if input.target.is_model() && !properties.versions.is_empty() =>
+        {
+            for version_entry in &properties.versions {
+                let version: OxideNodeVersion = version_entry
+                    .get("v")
+                    .cloned()
+                    .and_then(|v| serde_json::from_value(v).ok())
+                    .ok_or_else(|| GenericTestError::Definition {
+                        original_file_path: input.original_file_path.to_string(),
+                        target: properties.name.clone(),
+                        message: "versions must have a 'v'".to_string(),
+                    })?;
+                let version_columns: &[Value] = version_entry
+                    .get("columns")
+                    .and_then(Value::as_array)
+                    .map_or(&[], Vec::as_slice);
+                let mut columns: Vec<ColumnProperties> = properties
+                    .columns
+                    .iter()
+                    .filter(|column| version_includes(version_columns, &column.name))
+                    .cloned()
+                    .collect();
+                for column in version_columns {
+                    if column.get("include").is_some() {
+                        continue;
+                    }
+                    columns.push(serde_json::from_value(column.clone()).map_err(|e| {
+                        GenericTestError::Definition {
+                            original_file_path: input.original_file_path.to_string(),
+                            target: properties.name.clone(),
+                            message: e.to_string(),
+                        }
+                    })?);
+                }
+                for column in &columns {
+                    for data_test in &column.data_tests {
+                        tests.push(build_test(
+                            ctx,
+                            manifest,
+                            input,
+                            data_test,
+                            Some(column),
+                            Some(&version),
+                        )?);
+                    }
+                }
+                let version_tests = version_entry
+                    .get("data_tests")
+                    .or_else(|| version_entry.get("tests"))
+                    .and_then(Value::as_array);
+                for data_test in version_tests.unwrap_or(&properties.data_tests) {
+                    tests.push(build_test(
+                        ctx,
+                        manifest,
+                        input,
+                        data_test,
+                        None,
+                        Some(&version),
+                    )?);
+                }
+            }
+        }
+        TestTarget::Node { properties, .. } => {
+            for column in &properties.columns {
+                for data_test in &column.data_tests {
+                    tests.push(build_test(
+                        ctx,
+                        manifest,
+                        input,
+                        data_test,
+                        Some(column),
+                        None,
+                    )?);
+                }
+            }
+            for data_test in &properties.data_tests {
+                tests.push(build_test(ctx, manifest, input, data_test, None, None)?);
+            }
+        }
+    }
+    Ok(tests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_resolution::ConfigResolver;
+    use crate::node_construction::link_dependencies;
+    use crate::properties::parse_properties;
+    use std::collections::{HashMap, HashSet};
+
+    const MANIFEST: &str = r#"{
+        "nodes": {
+            "model.shop.orders": {"unique_id": "model.shop.orders", "name": "orders", "resource_type": "model", "package_name": "shop", "group": "sales"},
+            "model.shop.customers": {"unique_id": "model.shop.customers", "name": "customers", "resource_type": "model", "package_name": "shop"}
+        },
+        "sources": {
+            "source.shop.raw.payments": {"unique_id": "source.shop.raw.payments", "name": "payments", "source_name": "raw", "resource_type": "source", "package_name": "shop"}
+        },
+        "macros": {
+            "macro.dbt.test_unique": {"unique_id": "macro.dbt.test_unique", "name": "test_unique", "package_name": "dbt"},
+            "macro.dbt.test_not_null": {"unique_id": "macro.dbt.test_not_null", "name": "test_not_null", "package_name": "dbt"},
+            "macro.dbt.test_accepted_values": {"unique_id": "macro.dbt.test_accepted_values", "name": "test_accepted_values", "package_name": "dbt"},
+            "macro.dbt.test_relationships": {"unique_id": "macro.dbt.test_relationships", "name": "test_relationships", "package_name": "dbt"},
+            "macro.dbt.get_where_subquery": {"unique_id": "macro.dbt.get_where_subquery", "name": "get_where_subquery", "package_name": "dbt"},
+            "macro.dbt_utils.test_expression_is_true": {"unique_id": "macro.dbt_utils.test_expression_is_true", "name": "test_expression_is_true", "package_name": "dbt_utils"},
+            "macro.shop.test_mapped": {"unique_id": "macro.shop.test_mapped", "name": "test_mapped", "package_name": "shop"}
+        }
+    }"#;
+
+    const PROPERTIES: &str = "
+version: 2
+models:
+  - name: orders
+    columns:
+      - name: id
+        data_tests:
+          - unique
+          - not_null:
+              config:
+                severity: warn
+      - name: status
+        tags: [status]
+        data_tests:
+          - accepted_values:
+              arguments:
+                values: ['placed', 'shipped', 'completed', 'return_pending', 'returned']
+      - name: customer_id
+        data_tests:
+          - relationships:
+              arguments:
+                to: ref('customers')
+                field: id
+    data_tests:
+      - dbt_utils.expression_is_true:
+          arguments:
+            expression: amount >= 0
+          name: positive_amounts
+sources:
+  - name: raw
+    tags: [raw]
+    tables:
+      - name: payments
+        columns:
+          - name: id
+            quote: true
+            data_tests: [unique]
+";
+
+    struct Fixture {
+        resolver: ConfigResolver,
+        internal: Vec<String>,
+        dispatch: HashMap<String, Vec<String>>,
+        render_context: Map<String, Value>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let mut resolver = ConfigResolver::new("shop");
+            let Value::Object(project) = json!({
+                "name": "shop",
+                "data_tests": {"shop": {"staging": {"+severity": "warn"}}},
+            }) else {
+                unreachable!()
+            };
+            resolver.add_project("shop", project);
+            Fixture {
+                resolver,
+                internal: vec!["dbt".to_string()],
+                dispatch: HashMap::new(),
+                render_context: Map::new(),
+            }
+        }
+
+        fn ctx(&self) -> GenericTestContext<'_> {
+            GenericTestContext {
+                build: BuildContext {
+                    resolver: &self.resolver,
+                    database: "analytics",
+                    schema: "dbt_me",
+                    quote_char: Some('"'),
+                },
+                macros: MacroResolveContext {
+                    root_project: "shop",
+                    internal_packages: &self.internal,
+                    adapter_types: &[],
+                    dispatch_search_order: &self.dispatch,
+                    node_package: None,
+                },
+                render_context: &self.render_context,
+                arguments_property: true,
+            }
+        }
+    }
+
+    fn build_all(manifest: &OxideManifest, fixture: &Fixture) -> Vec<OxideNode> {
+        let file = parse_properties("models/staging/schema.yml", PROPERTIES, false).unwrap();
+        let ctx = fixture.ctx();
+        let source = &file.sources[0].value;
+        let targets = [
+            TestTarget::Node {
+                yaml_key: "models",
+                properties: &file.models[0].value,
+            },
+            TestTarget::Source {
+                source,
+                table: &source.tables[0],
+            },
+        ];
+        targets
+            .into_iter()
+            .flat_map(|target| {
+                let input = GenericTestInput {
+                    target,
+                    package_name: "shop",
+                    original_file_path: "models/staging/schema.yml",
+                };
+                build_generic_tests(&ctx, manifest, &input).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_synthesized_names_match_dbt() {
+        let Value::Object(args) = json!({
+            "column_name": "status",
+            "model": "{{ get_where_subquery(ref('orders')) }}",
+            "values": ["placed", "shipped", "completed", "return_pending", "returned"],
+        }) else {
+            unreachable!()
+        };
+        assert_eq!(
+            synthesize_generic_test_names("accepted_values", "orders", &args),
+            (
+                "accepted_values_orders_1ce6ab157c285f7cd2ac656013faf758".to_string(),
+                "accepted_values_orders_status__placed__shipped__completed__return_pending__returned"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse_test_name("dbt_utils.at_least_one"),
+            Some((Some("dbt_utils"), "at_least_one"))
+        );
+        assert_eq!(parse_test_name("unique."), Some((None, "unique")));
+        assert_eq!(parse_test_name("1unique"), None);
+        assert_eq!(clean_arg("ref('customers')"), "ref_customers_");
+        assert!(looks_like_function(" ref('customers') "));
+        assert!(!looks_like_function("reference('customers')"));
+    }
+
+    #[test]
+    fn test_unique_ids_match_python_builder() {
+        // Expected values computed with dbt's TestBuilder and
+        // SchemaGenericTestParser.create_test_node.
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let fixture = Fixture::new();
+        let tests = build_all(&manifest, &fixture);
+        let unique_ids: Vec<&str> = tests.iter().map(|t| t.unique_id.as_str()).collect();
+        assert_eq!(
+            unique_ids,
+            vec![
+                "test.shop.unique_orders_id.1360ecc70e",
+                "test.shop.not_null_orders_id.84ef11d8fe",
+                "test.shop.accepted_values_orders_status__placed__shipped__completed__return_pending__returned.be6b5b5ec3",
+                "test.shop.relationships_orders_customer_id__id__ref_customers_.4282b0c525",
+                "test.shop.positive_amounts.116d688e6f",
+                "test.shop.source_unique_raw_payments__id_.97427b24dc",
+            ]
+        );
+
+        let accepted_values = &tests[2];
+        assert_eq!(
+            accepted_values.extra["path"],
+            json!("accepted_values_orders_1ce6ab157c285f7cd2ac656013faf758.sql")
+        );
+        assert_eq!(
+            accepted_values.extra["alias"],
+            json!("accepted_values_orders_1ce6ab157c285f7cd2ac656013faf758")
+        );
+        assert_eq!(
+            accepted_values.raw_code.as_deref(),
+            Some("{{ test_accepted_values(**_dbt_generic_test_kwargs) }}{{ config(alias=\"accepted_values_orders_1ce6ab157c285f7cd2ac656013faf758\") }}")
+        );
+        assert_eq!(accepted_values.extra["tags"], json!(["status"]));
+        assert_eq!(
+            accepted_values.fqn,
+            vec![
+                "shop",
+                "staging",
+                "accepted_values_orders_status__placed__shipped__completed__return_pending__returned"
+            ]
+        );
+        assert_eq!(
+            accepted_values.extra["schema"],
+            json!("dbt_me_dbt_test__audit")
+        );
+        assert_eq!(
+            accepted_values.extra["attached_node"],
+            json!("model.shop.orders")
+        );
+        assert_eq!(accepted_values.group.as_deref(), Some("sales"));
+        assert_eq!(
+            accepted_values.extra["file_key_name"],
+            json!("models.orders")
+        );
+
+        let not_null = &tests[1];
+        assert_eq!(not_null.config.extra["severity"], json!("warn"));
+        // `+severity` from dbt_project.yml applies to the file's tests.
+        assert_eq!(tests[0].config.extra["severity"], json!("warn"));
+        assert_eq!(
+            not_null.raw_code.as_deref(),
+            Some("{{ test_not_null(**_dbt_generic_test_kwargs) }}{{ config(severity=\"warn\") }}")
+        );
+        assert_eq!(not_null.depends_on.macros, vec!["macro.dbt.test_not_null"]);
+        assert_eq!(
+            not_null.extra["test_metadata"],
+            json!({
+                "name": "not_null",
+                "namespace": null,
+                "kwargs": {"column_name": "id", "model": "{{ get_where_subquery(ref('orders')) }}"},
+            })
+        );
+
+        let relationships = &tests[3];
+        assert_eq!(
+            relationships.depends_on.macros,
+            vec![
+                "macro.dbt.test_relationships",
+                "macro.dbt.get_where_subquery"
+            ]
+        );
+        assert_eq!(
+            relationships.extra["refs"],
+            json!([
+                {"name": "customers", "package": null, "version": null},
+                {"name": "orders", "package": null, "version": null},
+            ])
+        );
+
+        let expression = &tests[4];
+        assert_eq!(
+            expression.extra["test_metadata"]["namespace"],
+            json!("dbt_utils")
+        );
+        assert_eq!(expression.extra["column_name"], Value::Null);
+        assert_eq!(
+            expression.raw_code.as_deref(),
+            Some("{{ dbt_utils.test_expression_is_true(**_dbt_generic_test_kwargs) }}")
+        );
+
+        let source_test = &tests[5];
+        assert_eq!(source_test.extra["column_name"], json!("\"id\""));
+        assert_eq!(source_test.extra["sources"], json!([["raw", "payments"]]));
+        assert_eq!(source_test.extra["tags"], json!(["raw"]));
+        assert_eq!(source_test.extra["attached_node"], Value::Null);
+        assert_eq!(source_test.extra["file_key_name"], json!("sources.raw"));
+    }
+
+    #[test]
+    fn test_register_and_link() {
+        let mut manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let fixture = Fixture::new();
+        let tests = build_all(&manifest, &fixture);
+        let unique_ids: Vec<String> = tests.iter().map(|t| t.unique_id.clone()).collect();
+        for test in tests {
+            manifest.register_node(test);
+        }
+        let errors = link_dependencies(&mut manifest, Some(&unique_ids), "shop", &HashSet::new());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            manifest.nodes[&unique_ids[3]].depends_on.nodes,
+            vec!["model.shop.customers", "model.shop.orders"]
+        );
+        assert_eq!(
+            manifest.nodes[&unique_ids[5]].depends_on.nodes,
+            vec!["source.shop.raw.payments"]
+        );
+    }
+
+    #[test]
+    fn test_versioned_model_tests() {
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let fixture = Fixture::new();
+        let yaml = "models:
+  - name: orders
+    columns:
+      - name: id
+        data_tests: [not_null]
+      - name: status
+    versions:
+      - v: 2
+        columns:
+          - include: all
+            exclude: [status]
+";
+        let file = parse_properties("models/schema.yml", yaml, false).unwrap();
+        let ctx = fixture.ctx();
+        let input = GenericTestInput {
+            target: TestTarget::Node {
+                yaml_key: "models",
+                properties: &file.models[0].value,
+            },
+            package_name: "shop",
+            original_file_path: "models/schema.yml",
+        };
+        let tests = build_generic_tests(&ctx, &manifest, &input).unwrap();
+        assert_eq!(tests.len(), 1);
+        assert_eq!(
+            tests[0].unique_id,
+            "test.shop.not_null_orders_v2_id.468337f0ff"
+        );
+        assert_eq!(
+            tests[0].extra["refs"],
+            json!([{"name": "orders", "package": null, "version": 2}])
+        );
+    }
+
+    #[test]
+    fn test_dict_arguments_keep_yaml_order() {
+        // dbt names the test from the dict's values in YAML order and only
+        // sorts keys in the hashed metadata.
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let fixture = Fixture::new();
+        let yaml = "models:
+  - name: orders
+    columns:
+      - name: id
+        data_tests:
+          - mapped:
+              arguments:
+                mapping: {zeta: 1, alpha: 2}
+";
+        let file = parse_properties("models/schema.yml", yaml, false).unwrap();
+        let ctx = fixture.ctx();
+        let input = GenericTestInput {
+            target: TestTarget::Node {
+                yaml_key: "models",
+                properties: &file.models[0].value,
+            },
+            package_name: "shop",
+            original_file_path: "models/schema.yml",
+        };
+        let tests = build_generic_tests(&ctx, &manifest, &input).unwrap();
+        assert_eq!(
+            tests[0].unique_id,
+            "test.shop.mapped_orders_id__1__2.a6a5187451"
+        );
+        let kwargs = tests[0].extra["test_metadata"]["kwargs"]
+            .as_object()
+            .unwrap();
+        assert_eq!(
+            kwargs.keys().collect::<Vec<_>>(),
+            vec!["column_name", "mapping", "model"]
+        );
+        assert_eq!(python_repr(&kwargs["mapping"]), "{'zeta': 1, 'alpha': 2}");
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        let manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let fixture = Fixture::new();
+        let yaml =
+            "models:\n  - name: orders\n    data_tests:\n      - unique:\n          model: x\n";
+        let file = parse_properties("models/schema.yml", yaml, false).unwrap();
+        let ctx = fixture.ctx();
+        let input = GenericTestInput {
+            target: TestTarget::Node {
+                yaml_key: "models",
+                properties: &file.models[0].value,
+            },
+            package_name: "shop",
+            original_file_path: "models/schema.yml",
+        };
+        let error = build_generic_tests(&ctx, &manifest, &input).unwrap_err();
+        assert!(error.to_string().contains("reserved argument"), "{}", error);
+
+        let mut properties = file.models[0].value.clone();
+        properties.data_tests = vec![json!({"unique": {}, "not_null": {}})];
+        let input = GenericTestInput {
+            target: TestTarget::Node {
+                yaml_key: "models",
+                properties: &properties,
+            },
+            ..input
+        };
+        let error = build_generic_tests(&ctx, &manifest, &input).unwrap_err();
+        assert!(error.to_string().contains("exactly one key"), "{}", error);
+    }
+}
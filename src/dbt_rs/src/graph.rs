@@ -1,16 +1,69 @@
+use crate::dynamic_topo::DynamicTopoOrder;
 use petgraph::stable_graph::{NodeIndex, StableDiGraph};
-use petgraph::visit::EdgeRef;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use petgraph::Direction;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
 const PARENT_TEST_EDGE: &str = "parent_test";
 
+/// Sort order for the `*_ordered` query variants.
+#[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeOrder {
+    /// Lexicographic by unique_id.
+    UniqueId,
+    /// Topological, ties broken by unique_id.
+    Topological,
+}
+
+/// Compact, index-based snapshot of an `OxideGraph` used by the analytics
+/// modules. Node ids are sorted so that indices are stable between runs.
+#[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+pub(crate) struct DenseView {
+    pub ids: Vec<String>,
+    pub children: Vec<Vec<usize>>,
+    pub parents: Vec<Vec<usize>>,
+}
+
+#[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+impl DenseView {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Kahn's algorithm over the dense view. Returns `None` if the view has a cycle.
+    pub fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut in_degree: Vec<usize> = self.parents.iter().map(|p| p.len()).collect();
+        let mut queue: VecDeque<usize> = (0..self.len()).filter(|i| in_degree[*i] == 0).collect();
+        let mut order = Vec::with_capacity(self.len());
+
+        while let Some(idx) = queue.pop_front() {
+            order.push(idx);
+            for &child in &self.children[idx] {
+                in_degree[child] -= 1;
+                if in_degree[child] == 0 {
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        if order.len() == self.len() {
+            Some(order)
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
 #[derive(Clone)]
 pub struct OxideGraph {
     graph: StableDiGraph<String, String>,
     node_map: HashMap<String, NodeIndex>,
+    // Present only in acyclic mode, see `enable_acyclic_mode`.
+    topo: Option<DynamicTopoOrder>,
 }
 
 #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
@@ -19,19 +72,64 @@ impl OxideGraph {
         OxideGraph {
             graph: StableDiGraph::new(),
             node_map: HashMap::new(),
+            topo: None,
+        }
+    }
+
+    /// Create an empty graph in acyclic mode.
+    pub fn new_acyclic() -> Self {
+        OxideGraph {
+            topo: Some(DynamicTopoOrder::default()),
+            ..OxideGraph::new()
+        }
+    }
+
+    /// Switch to acyclic mode: from now on `add_edge` rejects edges that would
+    /// close a cycle, and `topological_sort_grouped` reuses the maintained
+    /// order. Fails with the cycle if the graph already contains one.
+    pub fn enable_acyclic_mode(&mut self) -> Result<(), Vec<(String, String)>> {
+        match DynamicTopoOrder::from_graph(&self.graph) {
+            Ok(order) => {
+                self.topo = Some(order);
+                Ok(())
+            }
+            Err(cycle) => Err(self.path_to_edges(&cycle)),
         }
     }
 
+    pub fn disable_acyclic_mode(&mut self) {
+        self.topo = None;
+    }
+
+    pub fn is_acyclic_mode(&self) -> bool {
+        self.topo.is_some()
+    }
+
+    fn path_to_edges(&self, path: &[NodeIndex]) -> Vec<(String, String)> {
+        path.windows(2)
+            .map(|pair| (self.graph[pair[0]].clone(), self.graph[pair[1]].clone()))
+            .collect()
+    }
+
+    fn ensure_node(&mut self, id: &str) -> NodeIndex {
+        if let Some(idx) = self.node_map.get(id) {
+            return *idx;
+        }
+        let idx = self.graph.add_node(id.to_string());
+        self.node_map.insert(id.to_string(), idx);
+        if let Some(topo) = self.topo.as_mut() {
+            topo.push(idx);
+        }
+        idx
+    }
+
     // Returns true if the edge represents a data dependency (should be traversed)
     fn is_data_edge(&self, edge_weight: &str) -> bool {
         edge_weight != PARENT_TEST_EDGE
     }
 
     pub fn add_node(&mut self, id: String) -> String {
-        if !self.node_map.contains_key(&id) {
-            let idx = self.graph.add_node(id.clone());
-            self.node_map.insert(id.clone(), idx);
-        }
+        self.ensure_node(&id);
         id
     }
 
@@ -41,29 +139,30 @@ impl OxideGraph {
         target: &str,
         edge_type: Option<String>,
     ) -> Result<(), String> {
-        let source_idx = if let Some(idx) = self.node_map.get(source) {
-            *idx
-        } else {
-            let idx = self.graph.add_node(source.to_string());
-            self.node_map.insert(source.to_string(), idx);
-            idx
-        };
-
-        let target_idx = if let Some(idx) = self.node_map.get(target) {
-            *idx
-        } else {
-            let idx = self.graph.add_node(target.to_string());
-            self.node_map.insert(target.to_string(), idx);
-            idx
-        };
+        let source_idx = self.ensure_node(source);
+        let target_idx = self.ensure_node(target);
 
         if let Some(edge) = self.graph.find_edge(source_idx, target_idx) {
             if let Some(w) = self.graph.edge_weight_mut(edge) {
                 *w = edge_type.unwrap_or_default();
             }
-        } else {
-            self.graph
-                .add_edge(source_idx, target_idx, edge_type.unwrap_or_default());
+            return Ok(());
+        }
+
+        let edge = self
+            .graph
+            .add_edge(source_idx, target_idx, edge_type.unwrap_or_default());
+        if let Some(topo) = self.topo.as_mut() {
+            if let Err(cycle) = topo.add_edge(&self.graph, source_idx, target_idx) {
+                self.graph.remove_edge(edge);
+                let path: Vec<&str> = cycle.iter().map(|idx| self.graph[*idx].as_str()).collect();
+                return Err(format!(
+                    "Adding edge {} -> {} would create a cycle: {}",
+                    source,
+                    target,
+                    path.join(" -> ")
+                ));
+            }
         }
         Ok(())
     }
@@ -71,9 +170,32 @@ impl OxideGraph {
     pub fn remove_node(&mut self, node: &str) {
         if let Some(idx) = self.node_map.remove(node) {
             self.graph.remove_node(idx);
+            if let Some(topo) = self.topo.as_mut() {
+                topo.remove(idx);
+            }
         }
     }
 
+    /// Remove every edge into `node` and return the former parents. The
+    /// topological order stays valid, so acyclic mode needs no update.
+    pub fn remove_incoming_edges(&mut self, node: &str) -> Vec<String> {
+        let Some(&idx) = self.node_map.get(node) else {
+            return Vec::new();
+        };
+        let incoming: Vec<_> = self
+            .graph
+            .edges_directed(idx, Direction::Incoming)
+            .map(|edge| (edge.id(), edge.source()))
+            .collect();
+        incoming
+            .into_iter()
+            .map(|(edge, source)| {
+                self.graph.remove_edge(edge);
+                self.graph[source].clone()
+            })
+            .collect()
+    }
+
     pub fn nodes(&self) -> HashSet<String> {
         self.node_map.keys().cloned().collect()
     }
@@ -164,7 +286,11 @@ impl OxideGraph {
     }
 
     pub fn subgraph(&self, nodes: &HashSet<String>) -> OxideGraph {
-        let mut new_graph = OxideGraph::new();
+        let mut new_graph = if self.is_acyclic_mode() {
+            OxideGraph::new_acyclic()
+        } else {
+            OxideGraph::new()
+        };
 
         for node in nodes {
             if self.node_map.contains_key(node) {
@@ -213,6 +339,40 @@ impl OxideGraph {
         new_graph
     }
 
+    /// Build a `DenseView` of the graph. When `data_edges_only` is set,
+    /// `parent_test` edges are left out, matching the traversal methods.
+    pub(crate) fn dense_view(&self, data_edges_only: bool) -> DenseView {
+        let mut ids: Vec<String> = self.node_map.keys().cloned().collect();
+        ids.sort();
+        let position: HashMap<&str, usize> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.as_str(), i))
+            .collect();
+
+        let mut children = vec![Vec::new(); ids.len()];
+        let mut parents = vec![Vec::new(); ids.len()];
+        for edge in self.graph.edge_references() {
+            if data_edges_only && !self.is_data_edge(edge.weight()) {
+                continue;
+            }
+            let source = &self.graph[edge.source()];
+            let target = &self.graph[edge.target()];
+            let (s, t) = (position[source.as_str()], position[target.as_str()]);
+            children[s].push(t);
+            parents[t].push(s);
+        }
+        for list in children.iter_mut().chain(parents.iter_mut()) {
+            list.sort_unstable();
+        }
+
+        DenseView {
+            ids,
+            children,
+            parents,
+        }
+    }
+
     fn get_neighbors(&self, node: &str, direction: Direction) -> HashSet<String> {
         let mut result = HashSet::new();
         if let Some(idx) = self.node_map.get(node) {
@@ -320,6 +480,10 @@ impl OxideGraph {
     }
 
     pub fn topological_sort_grouped(&self) -> Result<Vec<Vec<String>>, String> {
+        if let Some(topo) = &self.topo {
+            return Ok(self.group_by_level(topo));
+        }
+
         let mut in_degree: HashMap<NodeIndex, usize> = HashMap::new();
         let mut queue: Vec<NodeIndex> = Vec::new();
         let mut processed_count = 0;
@@ -381,6 +545,105 @@ impl OxideGraph {
         Ok(result)
     }
 
+    /// Topological order that is independent of insertion order: among the
+    /// nodes ready at any point, the smallest unique_id comes first.
+    pub fn stable_topological_order(&self) -> Result<Vec<String>, String> {
+        let mut in_degree: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut ready = BinaryHeap::new();
+        for node_idx in self.graph.node_indices() {
+            let degree = self
+                .graph
+                .edges_directed(node_idx, Direction::Incoming)
+                .count();
+            in_degree.insert(node_idx, degree);
+            if degree == 0 {
+                ready.push(Reverse((&self.graph[node_idx], node_idx)));
+            }
+        }
+
+        let mut result = Vec::with_capacity(self.graph.node_count());
+        while let Some(Reverse((id, node_idx))) = ready.pop() {
+            result.push(id.clone());
+            for neighbor_idx in self.graph.neighbors_directed(node_idx, Direction::Outgoing) {
+                if let Some(degree) = in_degree.get_mut(&neighbor_idx) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.push(Reverse((&self.graph[neighbor_idx], neighbor_idx)));
+                    }
+                }
+            }
+        }
+
+        if result.len() != self.graph.node_count() {
+            return Err("Cycle detected in graph".to_string());
+        }
+        Ok(result)
+    }
+
+    fn topological_rank(&self) -> Result<HashMap<String, usize>, String> {
+        Ok(self
+            .stable_topological_order()?
+            .into_iter()
+            .enumerate()
+            .map(|(rank, id)| (id, rank))
+            .collect())
+    }
+
+    /// Sort a set of node ids returned by one of the query methods.
+    pub fn order_nodes(
+        &self,
+        nodes: HashSet<String>,
+        order: NodeOrder,
+    ) -> Result<Vec<String>, String> {
+        let mut nodes: Vec<String> = nodes.into_iter().collect();
+        match order {
+            NodeOrder::UniqueId => nodes.sort(),
+            NodeOrder::Topological => {
+                let rank = self.topological_rank()?;
+                nodes.sort_by_key(|id| rank.get(id).copied().unwrap_or(usize::MAX));
+            }
+        }
+        Ok(nodes)
+    }
+
+    pub fn edges_ordered(&self, order: NodeOrder) -> Result<Vec<(String, String)>, String> {
+        let mut edges = self.edges();
+        match order {
+            NodeOrder::UniqueId => edges.sort(),
+            NodeOrder::Topological => {
+                let rank = self.topological_rank()?;
+                edges.sort_by_key(|(s, t)| (rank[s], rank[t]));
+            }
+        }
+        Ok(edges)
+    }
+
+    // Same grouping as Kahn's algorithm: a node's level is the length of the
+    // longest path reaching it, computed in one pass over the maintained order.
+    fn group_by_level(&self, topo: &DynamicTopoOrder) -> Vec<Vec<String>> {
+        let mut level: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut result: Vec<Vec<String>> = Vec::new();
+
+        for node_idx in topo.iter() {
+            let node_level = self
+                .graph
+                .neighbors_directed(node_idx, Direction::Incoming)
+                .map(|parent| level[&parent] + 1)
+                .max()
+                .unwrap_or(0);
+            level.insert(node_idx, node_level);
+            if result.len() <= node_level {
+                result.resize_with(node_level + 1, Vec::new);
+            }
+            result[node_level].push(self.graph[node_idx].clone());
+        }
+
+        for group in result.iter_mut() {
+            group.sort();
+        }
+        result
+    }
+
     fn bfs_traversal(
         &self,
         start_node: &str,
@@ -650,7 +913,7 @@ mod tests {
 
         assert_eq!(g.node_count(), 1);
         assert_eq!(g.edge_count(), 0);
-        assert!(g.node_map.get("A").is_none());
+        assert!(!g.node_map.contains_key("A"));
     }
 
     #[test]
@@ -664,7 +927,7 @@ mod tests {
 
         let nodes = g.nodes();
         assert_eq!(nodes.len(), 3);
-        assert!(nodes.contains(&"A".to_string()));
+        assert!(nodes.contains("A"));
 
         let edges = g.edges();
         assert_eq!(edges.len(), 2);
@@ -687,12 +950,12 @@ mod tests {
 
         let successors = g.successors("A");
         assert_eq!(successors.len(), 2);
-        assert!(successors.contains(&"B".to_string()));
-        assert!(successors.contains(&"C".to_string()));
+        assert!(successors.contains("B"));
+        assert!(successors.contains("C"));
 
         let predecessors = g.predecessors("B");
         assert_eq!(predecessors.len(), 1);
-        assert!(predecessors.contains(&"A".to_string()));
+        assert!(predecessors.contains("A"));
     }
 
     #[test]
@@ -731,4 +994,127 @@ mod tests {
         // Should preserve A -> C via B
         assert!(sub.get_edge_weight("A", "C").is_some());
     }
+
+    #[test]
+    fn test_acyclic_mode_rejects_cycle_with_path() {
+        let mut g = OxideGraph::new_acyclic();
+        g.add_edge("A", "B", None).unwrap();
+        g.add_edge("B", "C", None).unwrap();
+
+        let err = g.add_edge("C", "A", None).unwrap_err();
+        assert_eq!(
+            err,
+            "Adding edge C -> A would create a cycle: C -> A -> B -> C"
+        );
+        // The rejected edge is not kept
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.add_edge("A", "A", None).is_err());
+    }
+
+    #[test]
+    fn test_acyclic_mode_reorders_and_groups() {
+        let mut g = OxideGraph::new_acyclic();
+        // Insert nodes in reverse dependency order to force reordering
+        g.add_node("D".to_string());
+        g.add_node("C".to_string());
+        g.add_node("B".to_string());
+        g.add_node("A".to_string());
+        g.add_edge("C", "D", None).unwrap();
+        g.add_edge("B", "C", None).unwrap();
+        g.add_edge("A", "B", None).unwrap();
+        g.add_edge("A", "C", None).unwrap();
+
+        let expected = vec![
+            vec!["A".to_string()],
+            vec!["B".to_string()],
+            vec!["C".to_string()],
+            vec!["D".to_string()],
+        ];
+        assert_eq!(g.topological_sort_grouped().unwrap(), expected);
+
+        g.disable_acyclic_mode();
+        assert_eq!(g.topological_sort_grouped().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_acyclic_mode_matches_kahn_after_edits() {
+        let mut g = OxideGraph::new();
+        for (s, t) in [("A", "B"), ("A", "C"), ("C", "D"), ("E", "D"), ("B", "F")] {
+            g.add_edge(s, t, None).unwrap();
+        }
+        g.enable_acyclic_mode().unwrap();
+
+        g.add_edge("F", "E", None).unwrap();
+        g.remove_node("C");
+        g.add_edge("G", "A", None).unwrap();
+        let incremental = g.topological_sort_grouped().unwrap();
+
+        g.disable_acyclic_mode();
+        assert_eq!(incremental, g.topological_sort_grouped().unwrap());
+    }
+
+    #[test]
+    fn test_enable_acyclic_mode_on_cyclic_graph() {
+        let mut g = OxideGraph::new();
+        g.add_edge("A", "B", None).unwrap();
+        g.add_edge("B", "A", None).unwrap();
+
+        let cycle = g.enable_acyclic_mode().unwrap_err();
+        assert_eq!(cycle.len(), 2);
+        assert!(!g.is_acyclic_mode());
+    }
+
+    #[test]
+    fn test_ordered_queries_are_insertion_independent() {
+        let mut g1 = OxideGraph::new();
+        g1.add_edge("b", "d", None).unwrap();
+        g1.add_edge("a", "c", None).unwrap();
+        g1.add_edge("c", "d", None).unwrap();
+
+        let mut g2 = OxideGraph::new();
+        g2.add_edge("c", "d", None).unwrap();
+        g2.add_edge("a", "c", None).unwrap();
+        g2.add_edge("b", "d", None).unwrap();
+
+        for g in [&g1, &g2] {
+            assert_eq!(
+                g.order_nodes(g.nodes(), NodeOrder::UniqueId).unwrap(),
+                vec!["a", "b", "c", "d"]
+            );
+            assert_eq!(
+                g.stable_topological_order().unwrap(),
+                vec!["a", "b", "c", "d"]
+            );
+            assert_eq!(
+                g.order_nodes(g.ancestors("d", None), NodeOrder::Topological)
+                    .unwrap(),
+                vec!["a", "b", "c"]
+            );
+        }
+        assert_eq!(
+            g1.edges_ordered(NodeOrder::UniqueId).unwrap(),
+            g2.edges_ordered(NodeOrder::UniqueId).unwrap()
+        );
+        assert_eq!(
+            g1.edges_ordered(NodeOrder::Topological).unwrap(),
+            vec![
+                ("a".to_string(), "c".to_string()),
+                ("b".to_string(), "d".to_string()),
+                ("c".to_string(), "d".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stable_topological_order_prefers_ready_ids() {
+        let mut g = OxideGraph::new();
+        g.add_edge("z", "a", None).unwrap();
+        g.add_node("m".to_string());
+
+        // "a" sorts first but depends on "z"
+        assert_eq!(g.stable_topological_order().unwrap(), vec!["m", "z", "a"]);
+
+        g.add_edge("a", "z", None).unwrap();
+        assert!(g.stable_topological_order().is_err());
+    }
 }
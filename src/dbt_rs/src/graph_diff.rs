@@ -0,0 +1,231 @@
+use crate::graph::OxideGraph;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// An edge present in both graphs whose edge type differs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EdgeTypeChange {
+    pub source: String,
+    pub target: String,
+    pub old_type: String,
+    pub new_type: String,
+}
+
+/// Structural difference between two graphs. All lists are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphDiff {
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub added_edges: Vec<(String, String)>,
+    pub removed_edges: Vec<(String, String)>,
+    pub changed_edge_types: Vec<EdgeTypeChange>,
+    /// Nodes present in both graphs whose upstream lineage differs.
+    pub lineage_changed: Vec<String>,
+}
+
+impl GraphDiff {
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+fn typed_edges(graph: &OxideGraph) -> HashMap<(String, String), String> {
+    graph
+        .edges()
+        .into_iter()
+        .map(|(source, target)| {
+            let edge_type = graph
+                .get_edge_weight(&source, &target)
+                .cloned()
+                .unwrap_or_default();
+            ((source, target), edge_type)
+        })
+        .collect()
+}
+
+/// `seeds` and every node reachable from them, over edges of every type.
+/// Unlike `OxideGraph::descendants`, `parent_test` edges are followed too.
+fn downstream(graph: &OxideGraph, seeds: &[&String]) -> HashSet<String> {
+    let mut reached: HashSet<String> = seeds.iter().map(|id| id.to_string()).collect();
+    let mut queue: Vec<String> = reached.iter().cloned().collect();
+    while let Some(node) = queue.pop() {
+        for child in graph.successors(&node) {
+            if reached.insert(child.clone()) {
+                queue.push(child);
+            }
+        }
+    }
+    reached
+}
+
+/// Compare `old` against `new`, e.g. a `--state` graph against the current one.
+///
+/// A node's lineage changed when one of its incoming edges was added,
+/// removed or changed type, or when that happened to any of its ancestors in
+/// either graph.
+pub fn diff_graphs(old: &OxideGraph, new: &OxideGraph) -> GraphDiff {
+    let old_nodes = old.nodes();
+    let new_nodes = new.nodes();
+    let old_edges = typed_edges(old);
+    let new_edges = typed_edges(new);
+
+    let mut diff = GraphDiff {
+        added_nodes: new_nodes.difference(&old_nodes).cloned().collect(),
+        removed_nodes: old_nodes.difference(&new_nodes).cloned().collect(),
+        ..GraphDiff::default()
+    };
+
+    for (edge, new_type) in &new_edges {
+        match old_edges.get(edge) {
+            None => diff.added_edges.push(edge.clone()),
+            Some(old_type) if old_type != new_type => {
+                diff.changed_edge_types.push(EdgeTypeChange {
+                    source: edge.0.clone(),
+                    target: edge.1.clone(),
+                    old_type: old_type.clone(),
+                    new_type: new_type.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed_edges = old_edges
+        .keys()
+        .filter(|edge| !new_edges.contains_key(*edge))
+        .cloned()
+        .collect();
+
+    let common: HashSet<&String> = old_nodes.intersection(&new_nodes).collect();
+    let targets: Vec<&String> = diff
+        .added_edges
+        .iter()
+        .chain(&diff.removed_edges)
+        .map(|(_, target)| target)
+        .chain(diff.changed_edge_types.iter().map(|change| &change.target))
+        .collect();
+    let lineage_changed: BTreeSet<String> = [old, new]
+        .into_iter()
+        .flat_map(|graph| downstream(graph, &targets))
+        .filter(|id| common.contains(id))
+        .collect();
+    diff.lineage_changed = lineage_changed.into_iter().collect();
+
+    diff.added_nodes.sort();
+    diff.removed_nodes.sort();
+    diff.added_edges.sort();
+    diff.removed_edges.sort();
+    diff.changed_edge_types
+        .sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(s: &str, t: &str) -> (String, String) {
+        (s.to_string(), t.to_string())
+    }
+
+    #[test]
+    fn test_diff_identical_graphs() {
+        let mut g = OxideGraph::new();
+        g.add_edge("A", "B", None).unwrap();
+
+        let diff = diff_graphs(&g, &g.clone());
+        assert_eq!(diff, GraphDiff::default());
+    }
+
+    #[test]
+    fn test_diff_nodes_and_edges() {
+        let mut old = OxideGraph::new();
+        old.add_edge("A", "B", None).unwrap();
+        old.add_edge("B", "C", None).unwrap();
+
+        let mut new = OxideGraph::new();
+        new.add_edge("A", "B", None).unwrap();
+        new.add_edge("D", "C", None).unwrap();
+
+        let diff = diff_graphs(&old, &new);
+        assert_eq!(diff.added_nodes, vec!["D".to_string()]);
+        assert!(diff.removed_nodes.is_empty());
+        assert_eq!(diff.added_edges, vec![edge("D", "C")]);
+        assert_eq!(diff.removed_edges, vec![edge("B", "C")]);
+        assert_eq!(diff.lineage_changed, vec!["C".to_string()]);
+    }
+
+    #[test]
+    fn test_diff_lineage_propagates_downstream() {
+        let mut old = OxideGraph::new();
+        old.add_edge("A", "B", None).unwrap();
+        old.add_edge("B", "C", None).unwrap();
+        old.add_node("X".to_string());
+
+        let mut new = old.clone();
+        new.add_edge("X", "B", None).unwrap();
+
+        let diff = diff_graphs(&old, &new);
+        assert_eq!(diff.added_edges, vec![edge("X", "B")]);
+        assert_eq!(diff.lineage_changed, vec!["B".to_string(), "C".to_string()]);
+    }
+
+    #[test]
+    fn test_diff_removed_node_and_edge_type_change() {
+        let mut old = OxideGraph::new();
+        old.add_edge("A", "B", None).unwrap();
+        old.add_edge("B", "T", None).unwrap();
+
+        let mut new = OxideGraph::new();
+        new.add_edge("B", "T", Some("parent_test".to_string()))
+            .unwrap();
+
+        let diff = diff_graphs(&old, &new);
+        assert_eq!(diff.removed_nodes, vec!["A".to_string()]);
+        assert_eq!(diff.removed_edges, vec![edge("A", "B")]);
+        assert_eq!(
+            diff.changed_edge_types,
+            vec![EdgeTypeChange {
+                source: "B".to_string(),
+                target: "T".to_string(),
+                old_type: String::new(),
+                new_type: "parent_test".to_string(),
+            }]
+        );
+        assert!(diff.lineage_changed.contains(&"B".to_string()));
+    }
+
+    #[test]
+    fn test_diff_lineage_follows_parent_test_edges() {
+        let mut old = OxideGraph::new();
+        old.add_edge("A", "B", None).unwrap();
+        old.add_edge("B", "T1", None).unwrap();
+        old.add_edge("T1", "T2", Some("parent_test".to_string()))
+            .unwrap();
+        old.add_node("X".to_string());
+
+        let mut new = old.clone();
+        new.add_edge("X", "B", None).unwrap();
+
+        let diff = diff_graphs(&old, &new);
+        assert_eq!(diff.lineage_changed, vec!["B", "T1", "T2"]);
+    }
+
+    #[test]
+    fn test_diff_edge_type_change_changes_lineage() {
+        let mut old = OxideGraph::new();
+        old.add_edge("A", "B", None).unwrap();
+        old.add_edge("B", "C", None).unwrap();
+        old.add_edge("C", "D", None).unwrap();
+
+        let mut new = old.clone();
+        new.add_edge("B", "C", Some("parent_test".to_string()))
+            .unwrap();
+
+        let diff = diff_graphs(&old, &new);
+        assert!(diff.added_edges.is_empty());
+        assert!(diff.removed_edges.is_empty());
+        assert_eq!(diff.changed_edge_types.len(), 1);
+        assert_eq!(diff.lineage_changed, vec!["C", "D"]);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::graph::{DenseView, OxideGraph};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Structural metrics for a single graph node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeMetrics {
+    pub unique_id: String,
+    pub package_name: String,
+    pub fan_in: usize,
+    pub fan_out: usize,
+    /// Longest path from any source (node without parents).
+    pub depth: usize,
+    /// Longest path to any leaf (node without children).
+    pub height: usize,
+    pub ancestor_count: usize,
+    pub descendant_count: usize,
+    /// Normalized betweenness centrality (same scaling as networkx for directed graphs).
+    pub betweenness: f64,
+}
+
+/// Metrics aggregated over all nodes belonging to one package.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PackageMetrics {
+    pub package_name: String,
+    pub node_count: usize,
+    /// Edges coming from nodes of other packages.
+    pub edges_in: usize,
+    /// Edges going to nodes of other packages.
+    pub edges_out: usize,
+    pub max_depth: usize,
+    pub max_height: usize,
+    pub max_descendant_count: usize,
+    pub max_betweenness: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphMetrics {
+    pub nodes: Vec<NodeMetrics>,
+    pub packages: Vec<PackageMetrics>,
+}
+
+impl GraphMetrics {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Nodes with the largest downstream impact, ordered by descendant count
+    /// and then by betweenness.
+    pub fn hotspots(&self, top_n: usize) -> Vec<&NodeMetrics> {
+        let mut ranked: Vec<&NodeMetrics> = self.nodes.iter().collect();
+        ranked.sort_by(|a, b| {
+            b.descendant_count
+                .cmp(&a.descendant_count)
+                .then(b.betweenness.total_cmp(&a.betweenness))
+                .then(a.unique_id.cmp(&b.unique_id))
+        });
+        ranked.truncate(top_n);
+        ranked
+    }
+}
+
+/// Package name of a dbt unique_id (`<resource_type>.<package>.<name>...`).
+fn package_of(unique_id: &str) -> &str {
+    unique_id.split('.').nth(1).unwrap_or("")
+}
+
+impl OxideGraph {
+    /// Compute per-node and per-package structure metrics.
+    /// Only data edges are considered; `parent_test` edges are ignored.
+    pub fn compute_metrics(&self) -> Result<GraphMetrics, String> {
+        let view = self.dense_view(true);
+        let order = view
+            .topological_order()
+            .ok_or_else(|| "Cycle detected in graph".to_string())?;
+
+        let n = view.len();
+        let mut depth = vec![0usize; n];
+        for &v in &order {
+            for &c in &view.children[v] {
+                depth[c] = depth[c].max(depth[v] + 1);
+            }
+        }
+        let mut height = vec![0usize; n];
+        for &v in order.iter().rev() {
+            for &p in &view.parents[v] {
+                height[p] = height[p].max(height[v] + 1);
+            }
+        }
+
+        let reach = brandes(&view);
+
+        let nodes: Vec<NodeMetrics> = (0..n)
+            .map(|i| NodeMetrics {
+                unique_id: view.ids[i].clone(),
+                package_name: package_of(&view.ids[i]).to_string(),
+                fan_in: view.parents[i].len(),
+                fan_out: view.children[i].len(),
+                depth: depth[i],
+                height: height[i],
+                ancestor_count: reach.ancestors[i],
+                descendant_count: reach.descendants[i],
+                betweenness: reach.betweenness[i],
+            })
+            .collect();
+
+        let packages = aggregate_packages(&view, &nodes);
+        Ok(GraphMetrics { nodes, packages })
+    }
+}
+
+struct Reachability {
+    ancestors: Vec<usize>,
+    descendants: Vec<usize>,
+    betweenness: Vec<f64>,
+}
+
+/// Brandes' algorithm for unweighted directed graphs. The BFS from each
+/// source visits exactly its descendants, so reachability counts come for free.
+fn brandes(view: &DenseView) -> Reachability {
+    let n = view.len();
+    let mut ancestors = vec![0usize; n];
+    let mut descendants = vec![0usize; n];
+    let mut betweenness = vec![0f64; n];
+
+    let mut sigma = vec![0f64; n];
+    let mut dist = vec![-1i64; n];
+    let mut delta = vec![0f64; n];
+    let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut stack: Vec<usize> = Vec::with_capacity(n);
+    let mut queue = VecDeque::new();
+
+    for s in 0..n {
+        for v in stack.drain(..) {
+            sigma[v] = 0.0;
+            dist[v] = -1;
+            delta[v] = 0.0;
+            preds[v].clear();
+        }
+        sigma[s] = 1.0;
+        dist[s] = 0;
+        queue.push_back(s);
+
+        while let Some(v) = queue.pop_front() {
+            stack.push(v);
+            for &w in &view.children[v] {
+                if dist[w] < 0 {
+                    dist[w] = dist[v] + 1;
+                    queue.push_back(w);
+                }
+                if dist[w] == dist[v] + 1 {
+                    sigma[w] += sigma[v];
+                    preds[w].push(v);
+                }
+            }
+        }
+
+        descendants[s] = stack.len() - 1;
+        for &w in stack.iter().rev() {
+            if w != s {
+                ancestors[w] += 1;
+                betweenness[w] += delta[w];
+            }
+            for &v in &preds[w] {
+                delta[v] += sigma[v] / sigma[w] * (1.0 + delta[w]);
+            }
+        }
+    }
+
+    if n > 2 {
+        let scale = 1.0 / ((n - 1) * (n - 2)) as f64;
+        for b in betweenness.iter_mut() {
+            *b *= scale;
+        }
+    }
+
+    Reachability {
+        ancestors,
+        descendants,
+        betweenness,
+    }
+}
+
+fn aggregate_packages(view: &DenseView, nodes: &[NodeMetrics]) -> Vec<PackageMetrics> {
+    let mut packages: BTreeMap<&str, PackageMetrics> = BTreeMap::new();
+
+    for node in nodes {
+        let entry = packages
+            .entry(node.package_name.as_str())
+            .or_insert_with(|| PackageMetrics {
+                package_name: node.package_name.clone(),
+                node_count: 0,
+                edges_in: 0,
+                edges_out: 0,
+                max_depth: 0,
+                max_height: 0,
+                max_descendant_count: 0,
+                max_betweenness: 0.0,
+            });
+        entry.node_count += 1;
+        entry.max_depth = entry.max_depth.max(node.depth);
+        entry.max_height = entry.max_height.max(node.height);
+        entry.max_descendant_count = entry.max_descendant_count.max(node.descendant_count);
+        entry.max_betweenness = entry.max_betweenness.max(node.betweenness);
+    }
+
+    for (s, children) in view.children.iter().enumerate() {
+        for &t in children {
+            let (source_pkg, target_pkg) = (
+                nodes[s].package_name.as_str(),
+                nodes[t].package_name.as_str(),
+            );
+            if source_pkg != target_pkg {
+                if let Some(p) = packages.get_mut(source_pkg) {
+                    p.edges_out += 1;
+                }
+                if let Some(p) = packages.get_mut(target_pkg) {
+                    p.edges_in += 1;
+                }
+            }
+        }
+    }
+
+    packages.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics_for<'a>(metrics: &'a GraphMetrics, id: &str) -> &'a NodeMetrics {
+        metrics.nodes.iter().find(|m| m.unique_id == id).unwrap()
+    }
+
+    #[test]
+    fn test_metrics_chain() {
+        let mut g = OxideGraph::new();
+        g.add_edge("model.p.a", "model.p.b", None).unwrap();
+        g.add_edge("model.p.b", "model.p.c", None).unwrap();
+
+        let metrics = g.compute_metrics().unwrap();
+        let a = metrics_for(&metrics, "model.p.a");
+        let b = metrics_for(&metrics, "model.p.b");
+        let c = metrics_for(&metrics, "model.p.c");
+
+        assert_eq!((a.depth, a.height), (0, 2));
+        assert_eq!((c.depth, c.height), (2, 0));
+        assert_eq!(a.descendant_count, 2);
+        assert_eq!(c.ancestor_count, 2);
+        assert_eq!((b.fan_in, b.fan_out), (1, 1));
+        // b lies on the only shortest path a -> c: 1 / ((3 - 1) * (3 - 2))
+        assert!((b.betweenness - 0.5).abs() < 1e-12);
+        assert_eq!(a.betweenness, 0.0);
+    }
+
+    #[test]
+    fn test_metrics_ignore_test_edges() {
+        let mut g = OxideGraph::new();
+        g.add_edge("model.p.a", "model.p.b", None).unwrap();
+        g.add_edge("model.p.b", "test.p.t", Some("parent_test".to_string()))
+            .unwrap();
+
+        let metrics = g.compute_metrics().unwrap();
+        assert_eq!(metrics_for(&metrics, "model.p.a").descendant_count, 1);
+        assert_eq!(metrics_for(&metrics, "model.p.b").fan_out, 0);
+    }
+
+    #[test]
+    fn test_metrics_package_aggregates() {
+        let mut g = OxideGraph::new();
+        g.add_edge("source.raw.s.t", "model.core.stg", None)
+            .unwrap();
+        g.add_edge("model.core.stg", "model.core.fct", None)
+            .unwrap();
+        g.add_edge("model.core.fct", "exposure.bi.dash", None)
+            .unwrap();
+
+        let metrics = g.compute_metrics().unwrap();
+        let names: Vec<&str> = metrics
+            .packages
+            .iter()
+            .map(|p| p.package_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["bi", "core", "raw"]);
+
+        let core = &metrics.packages[1];
+        assert_eq!(core.node_count, 2);
+        assert_eq!((core.edges_in, core.edges_out), (1, 1));
+        assert_eq!(core.max_depth, 2);
+    }
+
+    #[test]
+    fn test_metrics_hotspots_and_json() {
+        let mut g = OxideGraph::new();
+        g.add_edge("model.p.root", "model.p.a", None).unwrap();
+        g.add_edge("model.p.root", "model.p.b", None).unwrap();
+        g.add_edge("model.p.a", "model.p.c", None).unwrap();
+
+        let metrics = g.compute_metrics().unwrap();
+        let top = metrics.hotspots(2);
+        assert_eq!(top[0].unique_id, "model.p.root");
+        assert_eq!(top[1].unique_id, "model.p.a");
+
+        let json: serde_json::Value = serde_json::from_str(&metrics.to_json().unwrap()).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 4);
+        assert_eq!(json["packages"][0]["package_name"], "p");
+    }
+
+    #[test]
+    fn test_metrics_cycle_is_error() {
+        let mut g = OxideGraph::new();
+        g.add_edge("A", "B", None).unwrap();
+        g.add_edge("B", "A", None).unwrap();
+        assert!(g.compute_metrics().is_err());
+    }
+}
@@ -0,0 +1,813 @@
+//! Jinja rendering with minijinja, matching dbt's `get_rendered`.
+//!
+//! Values print the way Jinja2 prints Python objects (`True`, `None`), and
+//! the `as_text`, `as_bool`, `as_number` and `as_native` filters follow
+//! dbt_common's native environment: when the whole template is a single
+//! `{{ ... }}` using one of them, the rendered text is converted with
+//! `ast.literal_eval` rules; any other template renders to a string.
+//!
+//! `compile_node` renders a node's `raw_code` with the dbt context functions
+//! that only need the manifest (`ref`, `source`, `var`, `env_var`, `config`,
+//! `is_incremental`, `this`). Other functions, such as adapter-bound macros,
+//! are delegated to a caller-supplied callback by name.
+
+use crate::literal_eval::literal_eval;
+use crate::manifest::{OxideManifest, OxideNode, OxideSource};
+use crate::python_repr;
+use crate::resolution::{Referrer, ResolveContext};
+use minijinja::value::{Kwargs, Object, Rest, Value, ValueKind};
+use minijinja::{Environment, Error, ErrorKind, State, UndefinedBehavior};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderError {
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{} (line {})", self.message, line),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<Error> for RenderError {
+    fn from(e: Error) -> Self {
+        let message = match e.detail() {
+            Some(detail) => format!("{}: {}", e.kind(), detail),
+            None => e.kind().to_string(),
+        };
+        RenderError {
+            message,
+            line: e.line(),
+        }
+    }
+}
+
+impl RenderError {
+    fn new(message: impl Into<String>) -> Self {
+        RenderError {
+            message: message.into(),
+            line: None,
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidOperation, message.into())
+}
+
+/// `str(value)` as Python would print the value Jinja2 holds. The objects
+/// defined here render themselves.
+fn python_str(value: &Value) -> String {
+    if value.is_undefined() {
+        return String::new();
+    }
+    if let Some(s) = value.as_str() {
+        return s.to_string();
+    }
+    let renders_itself = value.downcast_object_ref::<Marker>().is_some()
+        || value.downcast_object_ref::<Relation>().is_some()
+        || value.downcast_object_ref::<Config>().is_some();
+    match serde_json::to_value(value) {
+        Ok(json) if !renders_itself => python_repr::python_str(&json),
+        _ => value.to_string(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Conversion {
+    Text,
+    Bool,
+    Number,
+    Native,
+}
+
+/// A value passed through one of the `as_*` filters in native mode.
+#[derive(Debug)]
+struct Marker {
+    conversion: Conversion,
+    value: Value,
+}
+
+impl Object for Marker {
+    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&python_str(&self.value))
+    }
+}
+
+/// What the formatter wrote: the number of `{{ ... }}` outputs and, for the
+/// last one, its text if it was a native marker.
+#[derive(Debug, Default)]
+struct OutputLog {
+    writes: usize,
+    marker: Option<(Conversion, String)>,
+}
+
+fn split<'a>(parts: impl Iterator<Item = &'a str>) -> Value {
+    parts.map(Value::from).collect()
+}
+
+/// Common Python `str` and `dict` methods that Jinja2 templates call.
+fn python_method(_: &State, value: &Value, method: &str, args: &[Value]) -> Result<Value, Error> {
+    let arg = |i: usize| args.get(i).and_then(Value::as_str);
+    if let Some(s) = value.as_str() {
+        return Ok(match (method, args.len()) {
+            ("upper", 0) => Value::from(s.to_uppercase()),
+            ("lower", 0) => Value::from(s.to_lowercase()),
+            ("strip", 0) => Value::from(s.trim()),
+            ("lstrip", 0) => Value::from(s.trim_start()),
+            ("rstrip", 0) => Value::from(s.trim_end()),
+            ("strip" | "lstrip" | "rstrip", 1) => {
+                let chars: Vec<char> = arg(0).unwrap_or_default().chars().collect();
+                let trimmed = match method {
+                    "strip" => s.trim_matches(chars.as_slice()),
+                    "lstrip" => s.trim_start_matches(chars.as_slice()),
+                    _ => s.trim_end_matches(chars.as_slice()),
+                };
+                Value::from(trimmed)
+            }
+            ("replace", 2) => match (arg(0), arg(1)) {
+                (Some(old), Some(new)) => Value::from(s.replace(old, new)),
+                _ => return Err(invalid("replace() arguments must be strings")),
+            },
+            ("startswith", 1) => Value::from(arg(0).is_some_and(|p| s.starts_with(p))),
+            ("endswith", 1) => Value::from(arg(0).is_some_and(|p| s.ends_with(p))),
+            ("split", 0) => split(s.split_whitespace()),
+            ("split", 1) => match arg(0) {
+                Some(sep) => split(s.split(sep)),
+                None => split(s.split_whitespace()),
+            },
+            _ => return Err(Error::from(ErrorKind::UnknownMethod)),
+        });
+    }
+    if value.kind() == ValueKind::Map {
+        let pairs = || -> Result<Vec<(Value, Value)>, Error> {
+            value
+                .try_iter()?
+                .map(|key| Ok((key.clone(), value.get_item(&key)?)))
+                .collect()
+        };
+        return match (method, args.len()) {
+            ("items", 0) => Ok(Value::from(
+                pairs()?
+                    .into_iter()
+                    .map(|(k, v)| Value::from(vec![k, v]))
+                    .collect::<Vec<_>>(),
+            )),
+            ("keys", 0) => Ok(Value::from(
+                pairs()?.into_iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            )),
+            ("values", 0) => Ok(Value::from(
+                pairs()?.into_iter().map(|(_, v)| v).collect::<Vec<_>>(),
+            )),
+            ("get", 1 | 2) => {
+                let found = value.get_item(&args[0])?;
+                Ok(if found.is_undefined() {
+                    args.get(1).cloned().unwrap_or(Value::from(()))
+                } else {
+                    found
+                })
+            }
+            _ => Err(Error::from(ErrorKind::UnknownMethod)),
+        };
+    }
+    Err(Error::from(ErrorKind::UnknownMethod))
+}
+
+/// An environment configured like dbt's: strict undefined, Python-style
+/// printing and the `as_*` / `is_list` filters.
+fn base_environment<'source>(native: bool) -> (Environment<'source>, Arc<Mutex<OutputLog>>) {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_unknown_method_callback(python_method);
+
+    let log = Arc::new(Mutex::new(OutputLog::default()));
+    let formatter_log = log.clone();
+    env.set_formatter(move |out, _state, value| {
+        let text = python_str(value);
+        let mut log = formatter_log.lock().unwrap();
+        log.writes += 1;
+        log.marker = value
+            .downcast_object_ref::<Marker>()
+            .map(|marker| (marker.conversion, text.clone()));
+        out.write_str(&text).map_err(Error::from)
+    });
+
+    for (name, conversion) in [
+        ("as_text", Conversion::Text),
+        ("as_bool", Conversion::Bool),
+        ("as_number", Conversion::Number),
+        ("as_native", Conversion::Native),
+    ] {
+        env.add_filter(name, move |value: Value| {
+            if native {
+                Value::from_object(Marker { conversion, value })
+            } else {
+                Value::from(python_str(&value))
+            }
+        });
+    }
+    env.add_filter("is_list", |value: Value| value.kind() == ValueKind::Seq);
+    (env, log)
+}
+
+/// dbt_common's `quoted_native_concat`, given the rendered output.
+fn native_result(output: String, log: &OutputLog) -> Result<JsonValue, RenderError> {
+    let raw = match &log.marker {
+        Some((conversion, raw)) if log.writes == 1 && output == *raw => (*conversion, raw),
+        _ => return Ok(JsonValue::String(output)),
+    };
+    let (conversion, raw) = raw;
+    let evaluated = literal_eval(raw);
+    match conversion {
+        Conversion::Text => Ok(JsonValue::String(raw.clone())),
+        Conversion::Native => Ok(evaluated.unwrap_or_else(|_| JsonValue::String(raw.clone()))),
+        Conversion::Bool => match evaluated {
+            Ok(JsonValue::Bool(b)) => Ok(JsonValue::Bool(b)),
+            _ => Err(RenderError::new(format!(
+                "Could not convert value '{}' into type 'bool'",
+                raw
+            ))),
+        },
+        Conversion::Number => match evaluated {
+            Ok(number @ JsonValue::Number(_)) => Ok(number),
+            _ => Err(RenderError::new(format!(
+                "Could not convert value '{}' into type 'number'",
+                raw
+            ))),
+        },
+    }
+}
+
+/// Render a template with a plain context, as `get_rendered`. Text mode
+/// returns a string; native mode may return any JSON value.
+pub fn render_template(
+    source: &str,
+    context: &Map<String, JsonValue>,
+    native: bool,
+) -> Result<JsonValue, RenderError> {
+    let (env, log) = base_environment(native);
+    let output = env.render_str(source, context)?;
+    if !native {
+        return Ok(JsonValue::String(output));
+    }
+    let log = log.lock().unwrap();
+    native_result(output, &log)
+}
+
+/// A relation as `ref`, `source` and `this` return it: prints as its
+/// quoted name and exposes its parts.
+#[derive(Debug)]
+struct Relation {
+    database: Option<String>,
+    schema: Option<String>,
+    identifier: Option<String>,
+    rendered: String,
+}
+
+impl Relation {
+    fn new(
+        database: Option<String>,
+        schema: Option<String>,
+        identifier: Option<String>,
+        relation_name: Option<String>,
+    ) -> Self {
+        let rendered = relation_name.unwrap_or_else(|| {
+            [&database, &schema, &identifier]
+                .into_iter()
+                .flatten()
+                .filter(|part| !part.is_empty())
+                .map(|part| format!("\"{}\"", part))
+                .collect::<Vec<_>>()
+                .join(".")
+        });
+        Relation {
+            database,
+            schema,
+            identifier,
+            rendered,
+        }
+    }
+
+    fn for_node(node: &OxideNode) -> Self {
+        let field = |key: &str| {
+            node.extra
+                .get(key)
+                .and_then(JsonValue::as_str)
+                .map(str::to_string)
+        };
+        Relation::new(
+            field("database"),
+            field("schema"),
+            Some(field("alias").unwrap_or_else(|| node.name.clone())),
+            field("relation_name"),
+        )
+    }
+
+    /// dbt's `add_ephemeral_prefix`: ephemeral models are injected as CTEs.
+    fn for_ephemeral(node: &OxideNode) -> Self {
+        let alias = node.extra.get("alias").and_then(JsonValue::as_str);
+        let identifier = format!("__dbt__cte__{}", alias.unwrap_or(&node.name));
+        Relation::new(None, None, Some(identifier.clone()), Some(identifier))
+    }
+
+    fn for_source(source: &OxideSource) -> Self {
+        let field = |key: &str| {
+            source
+                .extra
+                .get(key)
+                .and_then(JsonValue::as_str)
+                .map(str::to_string)
+        };
+        Relation::new(
+            source.database.clone(),
+            source.schema.clone(),
+            Some(field("identifier").unwrap_or_else(|| source.name.clone())),
+            field("relation_name"),
+        )
+    }
+}
+
+impl Object for Relation {
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        let part = match key.as_str()? {
+            "database" => &self.database,
+            "schema" => &self.schema,
+            "identifier" | "name" | "table" => &self.identifier,
+            _ => return None,
+        };
+        Some(part.as_deref().map_or(Value::from(()), Value::from))
+    }
+
+    fn call_method(
+        self: &Arc<Self>,
+        _: &State<'_, '_>,
+        method: &str,
+        _: &[Value],
+    ) -> Result<Value, Error> {
+        match method {
+            "render" => Ok(Value::from(self.rendered.clone())),
+            _ => Err(Error::from(ErrorKind::UnknownMethod)),
+        }
+    }
+
+    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rendered)
+    }
+}
+
+/// `config` at compile time: calls render to nothing and `get`/`require`
+/// read the node's resolved config.
+#[derive(Debug)]
+struct Config {
+    values: Map<String, JsonValue>,
+}
+
+impl Object for Config {
+    fn call(self: &Arc<Self>, _: &State<'_, '_>, _: &[Value]) -> Result<Value, Error> {
+        Ok(Value::from(""))
+    }
+
+    fn call_method(
+        self: &Arc<Self>,
+        _: &State<'_, '_>,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Value, Error> {
+        let key = args.first().and_then(Value::as_str);
+        match (method, key) {
+            ("get", Some(key)) => Ok(match self.values.get(key) {
+                Some(value) => Value::from_serialize(value),
+                None => args.get(1).cloned().unwrap_or(Value::from(())),
+            }),
+            ("require", Some(key)) => match self.values.get(key) {
+                Some(value) => Ok(Value::from_serialize(value)),
+                None => Err(invalid(format!(
+                    "Required config value '{}' is missing",
+                    key
+                ))),
+            },
+            ("get" | "require", None) => Err(invalid(format!("config.{}() needs a key", method))),
+            _ => Err(Error::from(ErrorKind::UnknownMethod)),
+        }
+    }
+}
+
+/// Calls a function the Rust context does not provide, e.g. an
+/// adapter-bound macro rendered by Python: `(name, args, kwargs)`.
+pub type FunctionCallback = Arc<
+    dyn Fn(&str, &[JsonValue], &Map<String, JsonValue>) -> Result<JsonValue, String> + Send + Sync,
+>;
+
+/// Settings for `compile_node` that come from the project and invocation.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    pub current_project: String,
+    pub restricted_packages: HashSet<String>,
+    /// Resolved `vars` for the node's package.
+    pub vars: Map<String, JsonValue>,
+    /// Values for `env_var`, checked before the process environment.
+    pub env_vars: BTreeMap<String, String>,
+    /// Whether the node's relation exists in the warehouse; with
+    /// `full_refresh` this decides `is_incremental()`.
+    pub relation_exists: bool,
+    pub full_refresh: bool,
+    /// Extra globals, such as `target`.
+    pub context: Map<String, JsonValue>,
+    /// Names of functions delegated to the callback.
+    pub callback_functions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompiledNode {
+    pub compiled_code: String,
+    /// unique_ids of `ref` targets, in call order.
+    pub refs: Vec<String>,
+    pub sources: Vec<String>,
+    /// unique_ids of ephemeral `ref` targets, to be injected as CTEs.
+    pub ephemeral_refs: Vec<String>,
+    /// Names of the environment variables read, with their values.
+    pub env_vars: BTreeMap<String, String>,
+}
+
+fn to_json(value: &Value) -> Result<JsonValue, Error> {
+    serde_json::to_value(value).map_err(|e| invalid(e.to_string()))
+}
+
+fn read(
+    manifest: &RwLock<OxideManifest>,
+) -> Result<std::sync::RwLockReadGuard<'_, OxideManifest>, Error> {
+    manifest
+        .read()
+        .map_err(|_| invalid("manifest lock poisoned"))
+}
+
+/// Render `unique_id`'s raw code with the dbt compile context.
+pub fn compile_node(
+    manifest: &Arc<RwLock<OxideManifest>>,
+    unique_id: &str,
+    options: CompileOptions,
+    callback: Option<FunctionCallback>,
+) -> Result<CompiledNode, RenderError> {
+    let node = read(manifest)?
+        .nodes
+        .get(unique_id)
+        .cloned()
+        .ok_or_else(|| RenderError::new(format!("Node {} not found", unique_id)))?;
+    let raw_code = node.raw_code.clone().unwrap_or_default();
+    let options = Arc::new(options);
+    let node = Arc::new(node);
+    let compiled = Arc::new(Mutex::new(CompiledNode::default()));
+
+    let (mut env, _) = base_environment(false);
+    for (name, value) in &options.context {
+        env.add_global(name.clone(), Value::from_serialize(value));
+    }
+
+    if let Some(callback) = callback {
+        for name in &options.callback_functions {
+            let callback = callback.clone();
+            let function = name.clone();
+            env.add_function(name.clone(), move |args: Rest<Value>, kwargs: Kwargs| {
+                let args = args.iter().map(to_json).collect::<Result<Vec<_>, _>>()?;
+                let mut named = Map::new();
+                for key in kwargs.args() {
+                    named.insert(key.to_string(), to_json(&kwargs.get::<Value>(key)?)?);
+                }
+                let result = callback(&function, &args, &named).map_err(invalid)?;
+                Ok::<_, Error>(Value::from_serialize(result))
+            });
+        }
+    }
+
+    let (m, n, c, o) = (
+        manifest.clone(),
+        node.clone(),
+        compiled.clone(),
+        options.clone(),
+    );
+    env.add_function("ref", move |args: Rest<String>, kwargs: Kwargs| {
+        let (package, name) = match args.as_slice() {
+            [name] => (None, name),
+            [package, name] => (Some(package.as_str()), name),
+            _ => {
+                return Err(invalid(format!(
+                    "ref() takes 1 or 2 arguments, got {}",
+                    args.len()
+                )))
+            }
+        };
+        // dbt accepts both `version=` and its alias `v=`.
+        let version = [
+            kwargs.get::<Option<Value>>("version")?,
+            kwargs.get::<Option<Value>>("v")?,
+        ]
+        .into_iter()
+        .flatten()
+        .find(|v| v.is_true())
+        .map(|v| python_str(&v));
+        kwargs.assert_all_used()?;
+        let manifest = read(&m)?;
+        let referrer = Referrer {
+            package_name: &n.package_name,
+            resource_type: &n.resource_type,
+            group: n.group.as_deref(),
+        };
+        let ctx = ResolveContext {
+            current_project: &o.current_project,
+            restricted_packages: &o.restricted_packages,
+        };
+        let target_id = manifest
+            .resolve_ref(referrer, name, package, version.as_deref(), ctx)
+            .map_err(|e| invalid(e.to_string()))?;
+        let target = &manifest.nodes[&target_id];
+        let mut compiled = c.lock().unwrap();
+        compiled.refs.push(target_id.clone());
+        let relation = if target.config.materialized.as_deref() == Some("ephemeral") {
+            if !compiled.ephemeral_refs.contains(&target_id) {
+                compiled.ephemeral_refs.push(target_id);
+            }
+            Relation::for_ephemeral(target)
+        } else {
+            Relation::for_node(target)
+        };
+        Ok(Value::from_object(relation))
+    });
+
+    let (m, n, c, o) = (
+        manifest.clone(),
+        node.clone(),
+        compiled.clone(),
+        options.clone(),
+    );
+    env.add_function("source", move |source_name: String, table_name: String| {
+        let manifest = read(&m)?;
+        let source_id = manifest
+            .resolve_source(
+                &source_name,
+                &table_name,
+                &n.package_name,
+                &o.current_project,
+            )
+            .map_err(|e| invalid(e.to_string()))?;
+        c.lock().unwrap().sources.push(source_id.clone());
+        Ok::<_, Error>(Value::from_object(Relation::for_source(
+            &manifest.sources[&source_id],
+        )))
+    });
+
+    let o = options.clone();
+    env.add_function("var", move |name: String, default: Option<Value>| {
+        match (o.vars.get(&name), default) {
+            (Some(value), _) => Ok(Value::from_serialize(value)),
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(invalid(format!(
+                "Required var '{}' not found in config",
+                name
+            ))),
+        }
+    });
+
+    let (c, o) = (compiled.clone(), options.clone());
+    env.add_function("env_var", move |name: String, default: Option<String>| {
+        let value = o
+            .env_vars
+            .get(&name)
+            .cloned()
+            .or_else(|| std::env::var(&name).ok());
+        match (value, default) {
+            (Some(value), _) => {
+                c.lock().unwrap().env_vars.insert(name, value.clone());
+                Ok(value)
+            }
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(invalid(format!(
+                "Env var required but not provided: '{}'",
+                name
+            ))),
+        }
+    });
+
+    let config = serde_json::to_value(&node.config)
+        .ok()
+        .and_then(|value| value.as_object().cloned())
+        .unwrap_or_default();
+    env.add_global("config", Value::from_object(Config { values: config }));
+
+    let incremental = node.config.materialized.as_deref() == Some("incremental")
+        && options.relation_exists
+        && !options.full_refresh;
+    env.add_function("is_incremental", move || incremental);
+
+    env.add_global("this", Value::from_object(Relation::for_node(&node)));
+
+    let output = env.render_str(&raw_code, ())?;
+    let mut compiled = std::mem::take(&mut *compiled.lock().unwrap());
+    compiled.compiled_code = output;
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> Map<String, JsonValue> {
+        json!({"a_str": "100", "a_int": 100, "b_str": "hello"})
+            .as_object()
+            .cloned()
+            .unwrap()
+    }
+
+    enum Expect {
+        Returns(JsonValue),
+        Raises,
+    }
+    use Expect::{Raises, Returns};
+
+    /// The template, text-mode result and native-mode result, from dbt's
+    /// `test_jinja.py` cases whose YAML value is a string.
+    fn cases() -> Vec<(&'static str, Expect, Expect)> {
+        let s = |v: &str| Returns(json!(v));
+        vec![
+            ("bar", s("bar"), s("bar")),
+            ("'bar'", s("'bar'"), s("'bar'")),
+            ("\"bar\"", s("\"bar\""), s("\"bar\"")),
+            ("{{ 'bar' | as_text }}", s("bar"), s("bar")),
+            ("{{ 'bar' | as_bool }}", s("bar"), Raises),
+            ("{{ 'bar' | as_number }}", s("bar"), Raises),
+            ("{{ 'bar' | as_native }}", s("bar"), s("bar")),
+            ("1", s("1"), s("1")),
+            ("'1'", s("'1'"), s("'1'")),
+            ("\"1\"", s("\"1\""), s("\"1\"")),
+            ("{{ 1 }}", s("1"), s("1")),
+            ("{{ '1' }}", s("1"), s("1")),
+            ("'{{ 1 }}'", s("'1'"), s("'1'")),
+            ("'{{ '1' }}'", s("'1'"), s("'1'")),
+            ("{{ 1 | as_text }}", s("1"), s("1")),
+            ("{{ 1 | as_bool }}", s("1"), Raises),
+            ("{{ 1 | as_number }}", s("1"), Returns(json!(1))),
+            ("{{ 1 | as_native }}", s("1"), Returns(json!(1))),
+            ("{{ '1' | as_text }}", s("1"), s("1")),
+            ("{{ '1' | as_bool }}", s("1"), Raises),
+            ("{{ '1' | as_number }}", s("1"), Returns(json!(1))),
+            ("{{ '1' | as_native }}", s("1"), Returns(json!(1))),
+            ("{{ True }}", s("True"), s("True")),
+            ("{{ True | as_text }}", s("True"), s("True")),
+            ("{{ True | as_bool }}", s("True"), Returns(json!(true))),
+            ("{{ True | as_number }}", s("True"), Raises),
+            ("{{ True | as_native }}", s("True"), Returns(json!(true))),
+            ("{{ true }}", s("True"), s("True")),
+            ("{{ true | as_text }}", s("True"), s("True")),
+            ("{{ true | as_bool }}", s("True"), Returns(json!(true))),
+            ("{{ true | as_number }}", s("True"), Raises),
+            ("{{ true | as_native }}", s("True"), Returns(json!(true))),
+            ("{{ 'true' | as_text }}", s("true"), s("true")),
+            ("'{{ true }}'", s("'True'"), s("'True'")),
+            ("'{{ true | as_text }}'", s("'True'"), s("'True'")),
+            ("'{{ true | as_bool }}'", s("'True'"), s("'True'")),
+            ("'{{ true | as_number }}'", s("'True'"), s("'True'")),
+            ("'{{ true | as_native }}'", s("'True'"), s("'True'")),
+            ("{{ 'True' | as_text }}", s("True"), s("True")),
+            ("{{ 'True' | as_bool }}", s("True"), Returns(json!(true))),
+            ("'{{ True }}'", s("'True'"), s("'True'")),
+            ("'{{ True | as_bool }}'", s("'True'"), s("'True'")),
+            ("yes", s("yes"), s("yes")),
+            (
+                "{{ (a_int + 100) | as_native }}",
+                s("200"),
+                Returns(json!(200)),
+            ),
+            (
+                "{{ (a_str ~ 100) | as_native }}",
+                s("100100"),
+                Returns(json!(100100)),
+            ),
+            (
+                "{{( a_int ~ 100) | as_native }}",
+                s("100100"),
+                Returns(json!(100100)),
+            ),
+            (
+                "{{ a_str | as_native }}{{ a_str | as_native }}",
+                s("100100"),
+                s("100100"),
+            ),
+            (
+                "{{ a_int | as_native }}{{ a_int | as_native }}",
+                s("100100"),
+                s("100100"),
+            ),
+            (
+                "'{{ a_int | as_native }}{{ a_int | as_native }}'",
+                s("'100100'"),
+                s("'100100'"),
+            ),
+            ("", s(""), s("")),
+            ("{{ '' | as_native }}", s(""), s("")),
+            ("{{ none | as_native }}", s("None"), Returns(json!(null))),
+            ("{# #}hello", s("hello"), s("hello")),
+            ("{% if false %}{% endif %}hello", s("hello"), s("hello")),
+        ]
+    }
+
+    #[test]
+    fn test_dbt_rendering_cases() {
+        for (template, text, native) in cases() {
+            for (expect, is_native) in [(text, false), (native, true)] {
+                let result = render_template(template, &context(), is_native);
+                match expect {
+                    Returns(value) => {
+                        assert_eq!(result, Ok(value), "{} native={}", template, is_native)
+                    }
+                    Raises => assert!(result.is_err(), "{} native={}", template, is_native),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_regular_render_and_methods() {
+        let ctx = Map::new();
+        for (template, expected) in [
+            ("{{ \"some_value\" | as_native }}", "some_value"),
+            ("{{ 1991 | as_native }}", "1991"),
+            ("{{ 1991 | as_text }}", "1991"),
+            ("{{ [1] | is_list }}", "True"),
+            ("{{ 'A,b'.lower().split(',') | join('|') }}", "a|b"),
+            ("{{ {'k': 1}.get('x', 2) }}", "2"),
+            ("{{ ['a', none, 1.5] }}", "['a', None, 1.5]"),
+            ("{{ {'k': [true]} }}", "{'k': [True]}"),
+        ] {
+            assert_eq!(
+                render_template(template, &ctx, false),
+                Ok(json!(expected)),
+                "{}",
+                template
+            );
+        }
+        let err = render_template("{{ missing }}", &ctx, false).unwrap_err();
+        assert_eq!(err.line, Some(1));
+    }
+
+    const MANIFEST: &str = r#"{
+        "nodes": {
+            "model.p.orders": {"unique_id": "model.p.orders", "name": "orders", "resource_type": "model",
+                "package_name": "p", "relation_name": "\"db\".\"main\".\"orders\"",
+                "config": {"materialized": "incremental", "unique_key": "id"},
+                "raw_code": "{{ config(materialized='incremental') }}select * from {{ ref('stg') }} join {{ source('raw', 'pay') }}{% if is_incremental() %} where x > (select max(x) from {{ this }}){% endif %} -- {{ var('v') }} {{ env_var('DBT_RS_TEST_MISSING', 'd') }} {{ config.get('unique_key') }} {{ audit(1, k='x') }}"},
+            "model.p.stg": {"unique_id": "model.p.stg", "name": "stg", "resource_type": "model",
+                "package_name": "p", "config": {"materialized": "ephemeral"}},
+            "model.p.bad": {"unique_id": "model.p.bad", "name": "bad", "resource_type": "model",
+                "package_name": "p", "raw_code": "select * from {{ ref('nope') }}"}
+        },
+        "sources": {
+            "source.p.raw.pay": {"unique_id": "source.p.raw.pay", "source_name": "raw", "name": "pay",
+                "package_name": "p", "database": "db", "schema": "raw_data"}
+        }
+    }"#;
+
+    #[test]
+    fn test_compile_node() {
+        let manifest = Arc::new(RwLock::new(OxideManifest::from_json_str(MANIFEST).unwrap()));
+        let options = CompileOptions {
+            current_project: "p".to_string(),
+            vars: json!({"v": 7}).as_object().cloned().unwrap(),
+            relation_exists: true,
+            callback_functions: vec!["audit".to_string()],
+            ..CompileOptions::default()
+        };
+        let callback: FunctionCallback =
+            Arc::new(|name, args, kwargs| Ok(json!(format!("{}{}{}", name, args[0], kwargs["k"]))));
+        let compiled =
+            compile_node(&manifest, "model.p.orders", options.clone(), Some(callback)).unwrap();
+        assert_eq!(
+            compiled.compiled_code,
+            "select * from __dbt__cte__stg join \"db\".\"raw_data\".\"pay\" \
+             where x > (select max(x) from \"db\".\"main\".\"orders\") -- 7 d id audit1\"x\""
+        );
+        assert_eq!(compiled.refs, vec!["model.p.stg"]);
+        assert_eq!(compiled.ephemeral_refs, vec!["model.p.stg"]);
+        assert_eq!(compiled.sources, vec!["source.p.raw.pay"]);
+
+        let full_refresh = CompileOptions {
+            full_refresh: true,
+            callback_functions: Vec::new(),
+            ..options.clone()
+        };
+        let err = compile_node(&manifest, "model.p.orders", full_refresh, None).unwrap_err();
+        assert!(err.message.contains("audit"), "{}", err);
+
+        let err = compile_node(&manifest, "model.p.bad", options, None).unwrap_err();
+        assert!(err.message.contains("ref('nope') was not found"), "{}", err);
+    }
+}
@@ -1,6 +1,33 @@
+mod column_lineage;
+mod config_resolution;
+mod cte_injection;
 mod data_layer;
+mod dbtignore;
+mod dynamic_topo;
+mod file_scanner;
+mod generic_tests;
 mod graph;
+mod graph_diff;
+mod graph_metrics;
+mod jinja_render;
+mod literal_eval;
+mod lossless;
+mod macro_resolution;
 mod manifest;
+mod manifest_errors;
+mod manifest_patch;
+mod manifest_upgrade;
+mod msgpack;
+mod node_construction;
+mod partial_parse;
+mod partition;
+mod properties;
+mod python_repr;
+mod resolution;
+mod schema_validation;
+mod sql_analysis;
+mod static_parser;
+mod yaml_loader;
 
 #[cfg(feature = "extension-module")]
 mod py_graph;
@@ -11,6 +38,42 @@ mod py_manifest;
 #[cfg(feature = "extension-module")]
 mod py_data_layer;
 
+#[cfg(feature = "extension-module")]
+mod py_views;
+
+#[cfg(feature = "extension-module")]
+mod py_schema;
+
+#[cfg(feature = "extension-module")]
+mod py_scanner;
+
+#[cfg(feature = "extension-module")]
+mod py_static_parser;
+
+#[cfg(feature = "extension-module")]
+mod py_jinja_render;
+
+#[cfg(feature = "extension-module")]
+mod py_cte_injection;
+
+#[cfg(feature = "extension-module")]
+mod py_sql_analysis;
+
+#[cfg(feature = "extension-module")]
+mod py_column_lineage;
+
+#[cfg(feature = "extension-module")]
+mod py_properties;
+
+#[cfg(feature = "extension-module")]
+mod py_config_resolution;
+
+#[cfg(feature = "extension-module")]
+mod py_node_construction;
+
+#[cfg(feature = "extension-module")]
+mod py_generic_tests;
+
 #[cfg(feature = "extension-module")]
 use pyo3::prelude::*;
 
@@ -27,13 +90,27 @@ fn rust_version() -> PyResult<String> {
 /// A Python module implemented in Rust.
 #[cfg(feature = "extension-module")]
 #[pymodule]
-fn dbt_rs(_py: Python, m: &PyModule) -> PyResult<()> {
+fn dbt_rs(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(rust_version, m)?)?;
 
     m.add_class::<DbtGraph>()?;
+    m.add_function(wrap_pyfunction!(py_graph::diff_graphs, m)?)?;
+    m.add_function(wrap_pyfunction!(py_graph::diff_graphs_json, m)?)?;
 
-    py_manifest::register_manifest_module(m)?;
+    py_manifest::register_manifest_module(py, m)?;
     py_data_layer::register_data_layer_module(m)?;
+    py_views::register_views_module(m)?;
+    py_schema::register_schema_module(m)?;
+    py_scanner::register_scanner_module(m)?;
+    py_static_parser::register_static_parser_module(m)?;
+    py_jinja_render::register_jinja_render_module(m)?;
+    py_cte_injection::register_cte_injection_module(m)?;
+    py_sql_analysis::register_sql_analysis_module(m)?;
+    py_column_lineage::register_column_lineage_module(m)?;
+    py_properties::register_properties_module(m)?;
+    py_config_resolution::register_config_resolution_module(m)?;
+    py_node_construction::register_node_construction_module(m)?;
+    py_generic_tests::register_generic_tests_module(m)?;
 
     Ok(())
 }
@@ -0,0 +1,445 @@
+//! Python's `ast.literal_eval` for the literals dbt's native rendering can
+//! produce, evaluated to JSON values.
+//!
+//! Tuples and sets become arrays and non-string dict keys are stringified.
+//! Bytes, complex numbers and integers outside the 64-bit range are
+//! rejected, like any other input `literal_eval` would not accept.
+
+use serde_json::{Map, Number, Value};
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    source: &'a str,
+}
+
+type ParseResult<T> = Result<T, String>;
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error<T>(&self, message: &str) -> ParseResult<T> {
+        Err(format!(
+            "{} at position {} in {:?}",
+            message, self.pos, self.source
+        ))
+    }
+
+    /// Skip whitespace, and newlines inside brackets, and comments.
+    fn skip_space(&mut self, in_brackets: bool) {
+        while let Some(c) = self.peek() {
+            if c == ' ' || c == '\t' || c == '\x0c' || (in_brackets && (c == '\n' || c == '\r')) {
+                self.pos += 1;
+            } else if c == '\\' && matches!(self.chars.get(self.pos + 1), Some('\n')) {
+                self.pos += 2;
+            } else if c == '#' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char, depth: usize) -> ParseResult<()> {
+        self.skip_space(depth > 0);
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", c))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> ParseResult<Value> {
+        self.skip_space(depth > 0);
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                Ok(Value::Array(self.items(']', depth + 1)?))
+            }
+            Some('(') => {
+                self.pos += 1;
+                self.skip_space(true);
+                if self.peek() == Some(')') {
+                    self.pos += 1;
+                    return Ok(Value::Array(Vec::new()));
+                }
+                let first = self.value(depth + 1)?;
+                self.skip_space(true);
+                match self.peek() {
+                    // A parenthesized value, not a tuple.
+                    Some(')') => {
+                        self.pos += 1;
+                        Ok(first)
+                    }
+                    Some(',') => {
+                        self.pos += 1;
+                        let mut items = vec![first];
+                        items.extend(self.items(')', depth + 1)?);
+                        Ok(Value::Array(items))
+                    }
+                    _ => self.error("expected ')' or ','"),
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.dict_or_set(depth + 1)
+            }
+            Some('+' | '-') => {
+                let negative = self.peek() == Some('-');
+                self.pos += 1;
+                match self.value(depth)? {
+                    Value::Number(n) if negative => negate(&n)
+                        .map(Value::Number)
+                        .map_or_else(|| self.error("integer out of range"), Ok),
+                    Value::Number(n) => Ok(Value::Number(n)),
+                    _ => self.error("unary operator on a non-number"),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "True" => Ok(Value::Bool(true)),
+                    "False" => Ok(Value::Bool(false)),
+                    "None" => Ok(Value::Null),
+                    _ if matches!(self.peek(), Some('\'' | '"')) => {
+                        self.pos = start;
+                        self.strings()
+                    }
+                    _ => self.error("malformed node or string"),
+                }
+            }
+            Some('\'' | '"') => self.strings(),
+            _ => self.error("invalid syntax"),
+        }
+    }
+
+    /// Comma-separated values up to `close`, allowing a trailing comma.
+    fn items(&mut self, close: char, depth: usize) -> ParseResult<Vec<Value>> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_space(true);
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(items);
+            }
+            items.push(self.value(depth)?);
+            self.skip_space(true);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == close => {}
+                _ => return self.error(&format!("expected ',' or '{}'", close)),
+            }
+        }
+    }
+
+    fn dict_or_set(&mut self, depth: usize) -> ParseResult<Value> {
+        self.skip_space(true);
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(Map::new()));
+        }
+        let first = self.value(depth)?;
+        self.skip_space(true);
+        if self.peek() != Some(':') {
+            let mut items = vec![first];
+            if self.peek() == Some(',') {
+                self.pos += 1;
+                items.extend(self.items('}', depth)?);
+            } else {
+                self.expect('}', depth)?;
+            }
+            return Ok(Value::Array(items));
+        }
+        let mut map = Map::new();
+        let mut key = first;
+        loop {
+            self.expect(':', depth)?;
+            let value = self.value(depth)?;
+            map.insert(
+                key_string(&key).map_or_else(|| self.error("unhashable key"), Ok)?,
+                value,
+            );
+            self.skip_space(true);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {}
+                _ => return self.error("expected ',' or '}'"),
+            }
+            self.skip_space(true);
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(Value::Object(map));
+            }
+            key = self.value(depth)?;
+        }
+    }
+
+    fn number(&mut self) -> ParseResult<Value> {
+        let start = self.pos;
+        let radix = match (self.peek(), self.chars.get(self.pos + 1)) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('o' | 'O')) => 8,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.pos += 2;
+            let digits_start = self.pos;
+            while self.peek().is_some_and(|c| c.is_digit(radix) || c == '_') {
+                self.pos += 1;
+            }
+            let digits: String = self.chars[digits_start..self.pos]
+                .iter()
+                .filter(|c| **c != '_')
+                .collect();
+            return match u64::from_str_radix(&digits, radix) {
+                Ok(n) => Ok(Value::from(n)),
+                Err(_) => self.error("invalid integer"),
+            };
+        }
+        let mut float = false;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || c == '_' {
+                self.pos += 1;
+            } else if c == '.' && !float {
+                float = true;
+                self.pos += 1;
+            } else if c == 'e' || c == 'E' {
+                float = true;
+                self.pos += 1;
+                if matches!(self.peek(), Some('+' | '-')) {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        if matches!(self.peek(), Some('j' | 'J')) {
+            return self.error("complex numbers are not supported");
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        if float {
+            match text.parse::<f64>().ok().and_then(Number::from_f64) {
+                Some(n) => Ok(Value::Number(n)),
+                None => self.error("invalid float"),
+            }
+        } else if text.len() > 1 && text.starts_with('0') && text.chars().any(|c| c != '0') {
+            self.error("leading zeros in decimal integer literals are not permitted")
+        } else {
+            match text.parse::<u64>() {
+                Ok(n) => Ok(Value::from(n)),
+                Err(_) => self.error("integer out of range"),
+            }
+        }
+    }
+
+    /// One or more adjacent string literals, concatenated.
+    fn strings(&mut self) -> ParseResult<Value> {
+        let mut out = String::new();
+        loop {
+            out.push_str(&self.string()?);
+            self.skip_space(false);
+            let prefixed = self.peek().is_some_and(char::is_alphabetic)
+                && matches!(self.chars.get(self.pos + 1), Some('\'' | '"'));
+            if !(matches!(self.peek(), Some('\'' | '"')) || prefixed) {
+                return Ok(Value::String(out));
+            }
+        }
+    }
+
+    fn string(&mut self) -> ParseResult<String> {
+        let mut raw = false;
+        while let Some(c) = self.peek().filter(|c| c.is_alphabetic()) {
+            match c.to_ascii_lowercase() {
+                'r' => raw = true,
+                'u' => {}
+                'b' => return self.error("bytes are not supported"),
+                _ => return self.error("malformed node or string"),
+            }
+            self.pos += 1;
+        }
+        let quote = self.peek().filter(|c| *c == '\'' || *c == '"');
+        let Some(quote) = quote else {
+            return self.error("expected a string");
+        };
+        let triple = self.chars.get(self.pos..self.pos + 3) == Some(&[quote; 3][..]);
+        self.pos += if triple { 3 } else { 1 };
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return self.error("unterminated string literal");
+            };
+            if c == quote
+                && (!triple || self.chars.get(self.pos..self.pos + 3) == Some(&[quote; 3][..]))
+            {
+                self.pos += if triple { 3 } else { 1 };
+                return Ok(out);
+            }
+            if c == '\n' && !triple {
+                return self.error("unterminated string literal");
+            }
+            self.pos += 1;
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            let Some(next) = self.peek() else {
+                return self.error("unterminated string literal");
+            };
+            self.pos += 1;
+            if raw {
+                out.push('\\');
+                out.push(next);
+                continue;
+            }
+            match next {
+                '\n' => {}
+                '\\' | '\'' | '"' => out.push(next),
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                'a' => out.push('\x07'),
+                'b' => out.push('\x08'),
+                'f' => out.push('\x0c'),
+                'v' => out.push('\x0b'),
+                '0'..='7' => {
+                    let mut code = next.to_digit(8).unwrap_or(0);
+                    for _ in 0..2 {
+                        match self.peek().and_then(|c| c.to_digit(8)) {
+                            Some(d) => {
+                                code = code * 8 + d;
+                                self.pos += 1;
+                            }
+                            None => break,
+                        }
+                    }
+                    out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                'x' | 'u' | 'U' => {
+                    let len = match next {
+                        'x' => 2,
+                        'u' => 4,
+                        _ => 8,
+                    };
+                    let hex: String = self.chars.iter().skip(self.pos).take(len).collect();
+                    let code = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == len);
+                    match code.and_then(char::from_u32) {
+                        Some(c) => out.push(c),
+                        None => return self.error("invalid escape sequence"),
+                    }
+                    self.pos += len;
+                }
+                other => {
+                    out.push('\\');
+                    out.push(other);
+                }
+            }
+        }
+    }
+}
+
+fn negate(n: &Number) -> Option<Number> {
+    if let Some(u) = n.as_u64() {
+        if u <= i64::MAX as u64 {
+            Some(Number::from(-(u as i64)))
+        } else if u == i64::MAX as u64 + 1 {
+            Some(Number::from(i64::MIN))
+        } else {
+            None
+        }
+    } else if let Some(i) = n.as_i64() {
+        i.checked_neg().map(Number::from)
+    } else {
+        n.as_f64().and_then(|f| Number::from_f64(-f))
+    }
+}
+
+/// Dict keys as JSON object keys: strings as-is, other scalars as Python
+/// prints them.
+fn key_string(key: &Value) -> Option<String> {
+    match key {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(true) => Some("True".to_string()),
+        Value::Bool(false) => Some("False".to_string()),
+        Value::Null => Some("None".to_string()),
+        _ => None,
+    }
+}
+
+/// Evaluate `source` like `ast.literal_eval`. The error message describes
+/// why it is not a literal.
+pub fn literal_eval(source: &str) -> Result<Value, String> {
+    // literal_eval strips leading spaces and tabs before parsing.
+    let trimmed = source.trim_start_matches([' ', '\t']);
+    let mut parser = Parser {
+        chars: trimmed.chars().collect(),
+        pos: 0,
+        source,
+    };
+    let value = parser.value(0)?;
+    parser.skip_space(false);
+    while matches!(parser.peek(), Some('\n' | '\r')) {
+        parser.pos += 1;
+        parser.skip_space(false);
+    }
+    if parser.pos != parser.chars.len() {
+        return parser.error("invalid syntax");
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(literal_eval("1").unwrap(), json!(1));
+        assert_eq!(literal_eval("  -1_000").unwrap(), json!(-1000));
+        assert_eq!(literal_eval("0x1f").unwrap(), json!(31));
+        assert_eq!(literal_eval("1.5e2").unwrap(), json!(150.0));
+        assert_eq!(literal_eval("True").unwrap(), json!(true));
+        assert_eq!(literal_eval("None").unwrap(), json!(null));
+        assert_eq!(literal_eval("'a' \"b\"").unwrap(), json!("ab"));
+        assert_eq!(literal_eval(r"'\x41\n' r'\d'").unwrap(), json!("A\n\\d"));
+        assert_eq!(literal_eval("'''x'y'''").unwrap(), json!("x'y"));
+    }
+
+    #[test]
+    fn test_containers() {
+        assert_eq!(
+            literal_eval("[1, (2,), {'a': [True, None]}, {3, 4}, (), {},]").unwrap(),
+            json!([1, [2], {"a": [true, null]}, [3, 4], [], {}])
+        );
+        assert_eq!(literal_eval("{1: 'x'}").unwrap(), json!({"1": "x"}));
+        assert_eq!(literal_eval("(5)").unwrap(), json!(5));
+        assert_eq!(literal_eval("[\n 1,\n 2\n]\n").unwrap(), json!([1, 2]));
+    }
+
+    #[test]
+    fn test_rejects_non_literals() {
+        for source in [
+            "", "bar", "true", "none", "1 + 1", "f(1)", "b'x'", "1j", "[1", "'a", "01", "x.y",
+            "1 2", "-'a'",
+        ] {
+            assert!(literal_eval(source).is_err(), "{}", source);
+        }
+    }
+}
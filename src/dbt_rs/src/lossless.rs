@@ -0,0 +1,262 @@
+//! Lossless JSON round-tripping for manifest entities.
+//!
+//! Entities model only the fields the Rust side needs and keep everything
+//! else in a flattened `extra` map. Modelled fields that were absent from
+//! the input are filled in with defaults on load; their paths are recorded
+//! so that serializing the entity leaves them out again.
+
+use serde::de::{DeserializeOwned, Error as _, MapAccess, Visitor};
+use serde::ser::{Error as _, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+pub trait Lossless: Serialize + DeserializeOwned {
+    /// Dotted paths of modelled fields that have a default.
+    const DEFAULTED_FIELDS: &'static [&'static str];
+
+    /// Defaulted fields that were missing from the input.
+    fn missing_fields(&self) -> &[&'static str];
+
+    fn set_missing_fields(&mut self, missing: Vec<&'static str>);
+}
+
+fn has_path(value: &Value, path: &str) -> bool {
+    let mut current = value;
+    for key in path.split('.') {
+        match current.get(key) {
+            Some(next) => current = next,
+            None => return false,
+        }
+    }
+    true
+}
+
+fn remove_path(value: &mut Value, path: &str) {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (parent.split('.').try_fold(value, |v, k| v.get_mut(k)), key),
+        None => (Some(value), path),
+    };
+    if let Some(Value::Object(map)) = parent {
+        map.shift_remove(key);
+    }
+}
+
+pub fn from_value<T: Lossless>(value: Value) -> Result<T, serde_json::Error> {
+    let missing: Vec<&'static str> = T::DEFAULTED_FIELDS
+        .iter()
+        .copied()
+        .filter(|path| !has_path(&value, path))
+        .collect();
+    let mut item: T = serde_json::from_value(value)?;
+    item.set_missing_fields(missing);
+    Ok(item)
+}
+
+pub fn to_value<T: Lossless>(item: &T) -> Result<Value, serde_json::Error> {
+    let mut value = serde_json::to_value(item)?;
+    for path in item.missing_fields() {
+        remove_path(&mut value, path);
+    }
+    Ok(value)
+}
+
+/// `items` sorted by key, so that written artifacts do not depend on the
+/// `HashMap`'s iteration order.
+fn sorted<T>(items: &HashMap<String, T>) -> Vec<(&String, &T)> {
+    let mut entries: Vec<_> = items.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Reads a map of entities one entry at a time: each value is read as an
+/// `R` and converted before the next one is read, so only one entity is
+/// held as a `Value` at once.
+struct Entries<R, T> {
+    convert: fn(R) -> Result<T, serde_json::Error>,
+    raw: PhantomData<R>,
+}
+
+impl<R, T> Entries<R, T> {
+    fn new(convert: fn(R) -> Result<T, serde_json::Error>) -> Self {
+        Entries {
+            convert,
+            raw: PhantomData,
+        }
+    }
+}
+
+impl<'de, R: Deserialize<'de>, T> Visitor<'de> for Entries<R, T> {
+    type Value = HashMap<String, T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut items = HashMap::with_capacity(access.size_hint().unwrap_or(0));
+        while let Some((key, raw)) = access.next_entry::<String, R>()? {
+            items.insert(key, (self.convert)(raw).map_err(A::Error::custom)?);
+        }
+        Ok(items)
+    }
+}
+
+struct AsValue<'a, T>(&'a T);
+
+impl<T: Lossless> Serialize for AsValue<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        to_value(self.0)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+/// `#[serde(with = "lossless::map")]` for `HashMap<String, T>` fields,
+/// written in key order.
+pub mod map {
+    use super::*;
+
+    pub fn serialize<S, T>(items: &HashMap<String, T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Lossless,
+    {
+        let mut map = serializer.serialize_map(Some(items.len()))?;
+        for (key, item) in sorted(items) {
+            map.serialize_entry(key, &AsValue(item))?;
+        }
+        map.end()
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<HashMap<String, T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Lossless,
+    {
+        deserializer.deserialize_map(Entries::new(from_value))
+    }
+}
+
+/// `#[serde(with = "lossless::map_of_vecs")]` for
+/// `Option<HashMap<String, Vec<T>>>` fields, written in key order. `None` is
+/// `null`.
+pub mod map_of_vecs {
+    use super::*;
+
+    struct Items<'a, T>(&'a [T]);
+
+    impl<T: Lossless> Serialize for Items<'_, T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+            for item in self.0 {
+                seq.serialize_element(&AsValue(item))?;
+            }
+            seq.end()
+        }
+    }
+
+    pub fn serialize<S, T>(
+        items: &Option<HashMap<String, Vec<T>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Lossless,
+    {
+        let Some(items) = items else {
+            return serializer.serialize_none();
+        };
+        let mut map = serializer.serialize_map(Some(items.len()))?;
+        for (key, entries) in sorted(items) {
+            map.serialize_entry(key, &Items(entries))?;
+        }
+        map.end()
+    }
+
+    fn from_values<T: Lossless>(values: Vec<Value>) -> Result<Vec<T>, serde_json::Error> {
+        values.into_iter().map(from_value).collect()
+    }
+
+    struct OrNull<T>(PhantomData<T>);
+
+    impl<'de, T: Lossless> Visitor<'de> for OrNull<T> {
+        type Value = Option<HashMap<String, Vec<T>>>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a map or null")
+        }
+
+        fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer
+                .deserialize_map(Entries::new(from_values))
+                .map(Some)
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(
+        deserializer: D,
+    ) -> Result<Option<HashMap<String, Vec<T>>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Lossless,
+    {
+        deserializer.deserialize_option(OrNull(PhantomData))
+    }
+}
+
+/// `#[serde(with = "lossless::option")]` for `Option<T>` fields.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S, T>(item: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Lossless,
+    {
+        item.as_ref().map(AsValue).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Lossless,
+    {
+        Option::<Value>::deserialize(deserializer)?
+            .map(from_value)
+            .transpose()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_paths() {
+        let mut value = json!({"a": {"b": 1}, "c": null});
+        assert!(has_path(&value, "a.b"));
+        assert!(has_path(&value, "c"));
+        assert!(!has_path(&value, "a.x"));
+        assert!(!has_path(&value, "x.b"));
+
+        remove_path(&mut value, "a.b");
+        remove_path(&mut value, "x.y");
+        assert_eq!(value, json!({"a": {}, "c": null}));
+    }
+}
@@ -0,0 +1,403 @@
+use crate::manifest::OxideManifest;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Macros by name, then package name, mapped to unique_id.
+#[derive(Debug, Clone, Default)]
+pub struct MacroIndex {
+    by_name: HashMap<String, BTreeMap<String, String>>,
+}
+
+impl MacroIndex {
+    pub fn build(manifest: &OxideManifest) -> Self {
+        let mut index = MacroIndex::default();
+        for m in manifest.macros.values() {
+            index
+                .by_name
+                .entry(m.name.clone())
+                .or_default()
+                .insert(m.package_name.clone(), m.unique_id.clone());
+        }
+        index
+    }
+
+    fn has_package(&self, package: &str) -> bool {
+        self.by_name.values().any(|p| p.contains_key(package))
+    }
+}
+
+/// Everything about the calling project that affects macro lookup.
+#[derive(Debug, Clone, Copy)]
+pub struct MacroResolveContext<'a> {
+    pub root_project: &'a str,
+    /// `dbt` and the adapter packages, highest precedence first, e.g.
+    /// `["dbt_redshift", "dbt_postgres", "dbt"]`.
+    pub internal_packages: &'a [String],
+    /// The adapter type followed by its parents, e.g. `["redshift", "postgres"]`.
+    pub adapter_types: &'a [String],
+    /// `dispatch` config from dbt_project.yml: macro_namespace to search_order.
+    pub dispatch_search_order: &'a HashMap<String, Vec<String>>,
+    /// Package of the node making the call. Its own macros take precedence.
+    pub node_package: Option<&'a str>,
+}
+
+impl MacroResolveContext<'_> {
+    fn is_internal(&self, package: &str) -> bool {
+        self.internal_packages.iter().any(|p| p == package)
+    }
+}
+
+/// The macro a call binds to and the candidates it shadowed, best first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacroResolution {
+    pub unique_id: String,
+    pub shadowed: Vec<String>,
+    /// Macro names tried, as `package.name` (or just `name` for an
+    /// unqualified lookup), in order.
+    pub searched: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroResolveError {
+    InvalidName {
+        name: String,
+    },
+    NotFound {
+        target: String,
+        searched: Vec<String>,
+    },
+}
+
+impl MacroResolveError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MacroResolveError::InvalidName { .. } => "invalid_name",
+            MacroResolveError::NotFound { .. } => "not_found",
+        }
+    }
+}
+
+impl fmt::Display for MacroResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MacroResolveError::InvalidName { name } => write!(
+                f,
+                "In adapter.dispatch, got a string packages argument ('{}'), but \
+                 macro names may not contain a '.'",
+                name
+            ),
+            MacroResolveError::NotFound { target, searched } if searched.is_empty() => {
+                write!(f, "{} was not found", target)
+            }
+            MacroResolveError::NotFound { target, searched } => write!(
+                f,
+                "{} was not found, searched: {}",
+                target,
+                searched.join(", ")
+            ),
+        }
+    }
+}
+
+impl OxideManifest {
+    /// Candidates for `name` in precedence order. Without a package this is
+    /// dbt's namespace order: the caller's package, the root project, other
+    /// installed packages, then the internal packages. `dbt` stands for all
+    /// internal packages; any other package is searched alone.
+    fn macro_candidates(
+        &self,
+        name: &str,
+        package: Option<&str>,
+        ctx: &MacroResolveContext,
+    ) -> Vec<String> {
+        let Some(by_package) = self.macro_index().by_name.get(name) else {
+            return Vec::new();
+        };
+        let internal = ctx.internal_packages.iter().map(String::as_str);
+        let packages: Vec<&str> = match package {
+            Some("dbt") => internal.collect(),
+            Some(package) => vec![package],
+            None => {
+                let mut packages = Vec::new();
+                if let Some(local) = ctx.node_package.filter(|p| !ctx.is_internal(p)) {
+                    packages.push(local);
+                }
+                packages.push(ctx.root_project);
+                packages.extend(
+                    by_package
+                        .keys()
+                        .map(String::as_str)
+                        .filter(|p| !ctx.is_internal(p)),
+                );
+                packages.extend(internal);
+                packages
+            }
+        };
+
+        let mut candidates: Vec<String> = Vec::new();
+        for package in packages {
+            if let Some(unique_id) = by_package.get(package) {
+                if !candidates.contains(unique_id) {
+                    candidates.push(unique_id.clone());
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Resolve a macro call `name()` or `package.name()`.
+    pub fn resolve_macro(
+        &self,
+        name: &str,
+        package: Option<&str>,
+        ctx: MacroResolveContext,
+    ) -> Result<MacroResolution, MacroResolveError> {
+        let target = match package {
+            Some(package) => format!("{}.{}", package, name),
+            None => name.to_string(),
+        };
+        let mut candidates = self.macro_candidates(name, package, &ctx).into_iter();
+        match candidates.next() {
+            Some(unique_id) => Ok(MacroResolution {
+                unique_id,
+                shadowed: candidates.collect(),
+                searched: vec![target],
+            }),
+            None => Err(MacroResolveError::NotFound {
+                target: format!("Macro '{}'", target),
+                searched: Vec::new(),
+            }),
+        }
+    }
+
+    /// Resolve `adapter.dispatch(macro_name, macro_namespace)`, trying the
+    /// `<adapter>__` prefixes and then `default__` in each searched package.
+    pub fn resolve_dispatch(
+        &self,
+        macro_name: &str,
+        macro_namespace: Option<&str>,
+        ctx: MacroResolveContext,
+    ) -> Result<MacroResolution, MacroResolveError> {
+        if macro_name.contains('.') {
+            return Err(MacroResolveError::InvalidName {
+                name: macro_name.to_string(),
+            });
+        }
+
+        let search_packages: Vec<Option<&str>> = match macro_namespace {
+            None => vec![None],
+            Some(namespace) => match ctx.dispatch_search_order.get(namespace) {
+                Some(order) => order.iter().map(|p| Some(p.as_str())).collect(),
+                None if !ctx.is_internal(namespace)
+                    && self.macro_index().has_package(namespace) =>
+                {
+                    vec![Some(ctx.root_project), Some(namespace)]
+                }
+                None => vec![None],
+            },
+        };
+        let prefixes = ctx
+            .adapter_types
+            .iter()
+            .map(String::as_str)
+            .chain(["default"]);
+
+        let mut searched = Vec::new();
+        let mut found: Vec<String> = Vec::new();
+        for package in search_packages {
+            for prefix in prefixes.clone() {
+                let name = format!("{}__{}", prefix, macro_name);
+                searched.push(match package {
+                    Some(package) => format!("{}.{}", package, name),
+                    None => name.clone(),
+                });
+                for unique_id in self.macro_candidates(&name, package, &ctx) {
+                    if !found.contains(&unique_id) {
+                        found.push(unique_id);
+                    }
+                }
+            }
+        }
+
+        if found.is_empty() {
+            return Err(MacroResolveError::NotFound {
+                target: format!("Macro '{}' dispatch", macro_name),
+                searched,
+            });
+        }
+        let unique_id = found.remove(0);
+        Ok(MacroResolution {
+            unique_id,
+            shadowed: found,
+            searched,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "macros": {
+            "macro.root.foo": {"unique_id": "macro.root.foo", "name": "foo", "package_name": "root"},
+            "macro.pkg.foo": {"unique_id": "macro.pkg.foo", "name": "foo", "package_name": "pkg"},
+            "macro.dbt.foo": {"unique_id": "macro.dbt.foo", "name": "foo", "package_name": "dbt"},
+            "macro.pkg.bar": {"unique_id": "macro.pkg.bar", "name": "bar", "package_name": "pkg"},
+            "macro.dbt.bar": {"unique_id": "macro.dbt.bar", "name": "bar", "package_name": "dbt"},
+            "macro.dbt.default__cast": {"unique_id": "macro.dbt.default__cast", "name": "default__cast", "package_name": "dbt"},
+            "macro.dbt_postgres.postgres__cast": {"unique_id": "macro.dbt_postgres.postgres__cast", "name": "postgres__cast", "package_name": "dbt_postgres"},
+            "macro.utils.default__pivot": {"unique_id": "macro.utils.default__pivot", "name": "default__pivot", "package_name": "utils"},
+            "macro.root.postgres__pivot": {"unique_id": "macro.root.postgres__pivot", "name": "postgres__pivot", "package_name": "root"},
+            "macro.shim.default__pivot": {"unique_id": "macro.shim.default__pivot", "name": "default__pivot", "package_name": "shim"}
+        }
+    }"#;
+
+    fn manifest() -> OxideManifest {
+        OxideManifest::from_json_str(MANIFEST).unwrap()
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    struct Setup {
+        internal: Vec<String>,
+        adapters: Vec<String>,
+        dispatch: HashMap<String, Vec<String>>,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            Setup {
+                internal: strings(&["dbt_postgres", "dbt"]),
+                adapters: strings(&["postgres"]),
+                dispatch: HashMap::new(),
+            }
+        }
+
+        fn ctx<'a>(&'a self, node_package: Option<&'a str>) -> MacroResolveContext<'a> {
+            MacroResolveContext {
+                root_project: "root",
+                internal_packages: &self.internal,
+                adapter_types: &self.adapters,
+                dispatch_search_order: &self.dispatch,
+                node_package,
+            }
+        }
+    }
+
+    #[test]
+    fn test_bare_macro_precedence() {
+        let m = manifest();
+        let setup = Setup::new();
+
+        let r = m.resolve_macro("foo", None, setup.ctx(None)).unwrap();
+        assert_eq!(r.unique_id, "macro.root.foo");
+        assert_eq!(r.shadowed, strings(&["macro.pkg.foo", "macro.dbt.foo"]));
+
+        // A package's own macros win for its nodes.
+        let r = m
+            .resolve_macro("foo", None, setup.ctx(Some("pkg")))
+            .unwrap();
+        assert_eq!(r.unique_id, "macro.pkg.foo");
+        assert_eq!(r.shadowed, strings(&["macro.root.foo", "macro.dbt.foo"]));
+
+        // Installed packages shadow internal ones.
+        let r = m.resolve_macro("bar", None, setup.ctx(None)).unwrap();
+        assert_eq!(r.unique_id, "macro.pkg.bar");
+        assert_eq!(r.shadowed, strings(&["macro.dbt.bar"]));
+    }
+
+    #[test]
+    fn test_qualified_macro() {
+        let m = manifest();
+        let setup = Setup::new();
+
+        let r = m
+            .resolve_macro("foo", Some("dbt"), setup.ctx(None))
+            .unwrap();
+        assert_eq!(r.unique_id, "macro.dbt.foo");
+        assert!(r.shadowed.is_empty());
+
+        let err = m
+            .resolve_macro("bar", Some("root"), setup.ctx(None))
+            .unwrap_err();
+        assert_eq!(err.kind(), "not_found");
+    }
+
+    #[test]
+    fn test_dispatch_adapter_prefix_first() {
+        let m = manifest();
+        let setup = Setup::new();
+
+        let r = m.resolve_dispatch("cast", None, setup.ctx(None)).unwrap();
+        assert_eq!(r.unique_id, "macro.dbt_postgres.postgres__cast");
+        assert_eq!(r.shadowed, strings(&["macro.dbt.default__cast"]));
+        assert_eq!(r.searched, strings(&["postgres__cast", "default__cast"]));
+
+        let r = m
+            .resolve_dispatch("cast", Some("dbt"), setup.ctx(None))
+            .unwrap();
+        assert_eq!(r.unique_id, "macro.dbt_postgres.postgres__cast");
+    }
+
+    #[test]
+    fn test_dispatch_package_namespace() {
+        let m = manifest();
+        let mut setup = Setup::new();
+
+        // Root project overrides are searched before the namespace itself.
+        let r = m
+            .resolve_dispatch("pivot", Some("utils"), setup.ctx(None))
+            .unwrap();
+        assert_eq!(r.unique_id, "macro.root.postgres__pivot");
+        assert_eq!(r.shadowed, strings(&["macro.utils.default__pivot"]));
+        assert_eq!(
+            r.searched,
+            strings(&[
+                "root.postgres__pivot",
+                "root.default__pivot",
+                "utils.postgres__pivot",
+                "utils.default__pivot",
+            ])
+        );
+
+        // A configured search_order replaces the default one.
+        setup
+            .dispatch
+            .insert("utils".to_string(), strings(&["shim", "utils"]));
+        let r = m
+            .resolve_dispatch("pivot", Some("utils"), setup.ctx(None))
+            .unwrap();
+        assert_eq!(r.unique_id, "macro.shim.default__pivot");
+        assert_eq!(r.shadowed, strings(&["macro.utils.default__pivot"]));
+    }
+
+    #[test]
+    fn test_dispatch_errors() {
+        let m = manifest();
+        let setup = Setup::new();
+
+        let err = m
+            .resolve_dispatch("utils.pivot", None, setup.ctx(None))
+            .unwrap_err();
+        assert_eq!(err.kind(), "invalid_name");
+
+        let err = m
+            .resolve_dispatch("missing", Some("utils"), setup.ctx(None))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            MacroResolveError::NotFound {
+                target: "Macro 'missing' dispatch".to_string(),
+                searched: strings(&[
+                    "root.postgres__missing",
+                    "root.default__missing",
+                    "utils.postgres__missing",
+                    "utils.default__missing",
+                ]),
+            }
+        );
+    }
+}
@@ -1,5 +1,18 @@
+use crate::lossless::{self, Lossless};
+use crate::macro_resolution::MacroIndex;
+use crate::manifest_errors::ManifestParseError;
+use crate::manifest_upgrade::{
+    check_supported, schema_version_field, upgrade_manifest_json, CURRENT_SCHEMA_VERSION,
+};
+use crate::resolution::ResolutionIndex;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 
 fn default_true() -> bool {
     true
@@ -11,14 +24,68 @@ pub struct OxideDependsOn {
     pub nodes: Vec<String>,
     #[serde(default)]
     pub macros: Vec<String>,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OxideNodeConfig {
     #[serde(default)]
     pub materialized: Option<String>,
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// A missing `config` block means an enabled node, same as `"config": {}`.
+impl Default for OxideNodeConfig {
+    fn default() -> Self {
+        OxideNodeConfig {
+            materialized: None,
+            enabled: true,
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OxideSourceConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for OxideSourceConfig {
+    fn default() -> Self {
+        OxideSourceConfig {
+            enabled: true,
+            extra: Map::new(),
+        }
+    }
+}
+
+/// A model version as written in the manifest: `2`, `"2"` or `2.1`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum OxideNodeVersion {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl fmt::Display for OxideNodeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OxideNodeVersion::Int(v) => write!(f, "{}", v),
+            OxideNodeVersion::Float(v) => write!(f, "{:?}", v),
+            OxideNodeVersion::Str(v) => write!(f, "{}", v),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -37,6 +104,37 @@ pub struct OxideNode {
     pub compiled_code: Option<String>,
     #[serde(default)]
     pub config: OxideNodeConfig,
+    #[serde(default)]
+    pub version: Option<OxideNodeVersion>,
+    #[serde(default)]
+    pub latest_version: Option<OxideNodeVersion>,
+    #[serde(default)]
+    pub access: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
+}
+
+impl OxideNode {
+    pub fn is_versioned(&self) -> bool {
+        self.resource_type == "model" && self.version.is_some()
+    }
+
+    pub fn is_latest_version(&self) -> bool {
+        self.version.is_some() && self.version == self.latest_version
+    }
+
+    /// Name used by `ref()` lookups: `name` or `name.v<version>`.
+    pub fn search_name(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}.v{}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -50,6 +148,20 @@ pub struct OxideSource {
     pub schema: Option<String>,
     #[serde(default)]
     pub package_name: String,
+    #[serde(default)]
+    pub config: OxideSourceConfig,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
+}
+
+impl OxideSource {
+    /// Name used by `source()` lookups: `source_name.name`.
+    pub fn search_name(&self) -> String {
+        format!("{}.{}", self.source_name, self.name)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -61,6 +173,11 @@ pub struct OxideMacro {
     pub macro_sql: Option<String>,
     #[serde(default)]
     pub depends_on: OxideDependsOn,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -69,6 +186,11 @@ pub struct OxideExposure {
     pub name: String,
     #[serde(default)]
     pub depends_on: OxideDependsOn,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -77,12 +199,22 @@ pub struct OxideMetric {
     pub name: String,
     #[serde(default)]
     pub depends_on: OxideDependsOn,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OxideGroup {
     pub unique_id: String,
     pub name: String,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -91,6 +223,11 @@ pub struct OxideSemanticModel {
     pub name: String,
     #[serde(default)]
     pub depends_on: OxideDependsOn,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -99,6 +236,11 @@ pub struct OxideSavedQuery {
     pub name: String,
     #[serde(default)]
     pub depends_on: OxideDependsOn,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[allow(dead_code)]
@@ -108,6 +250,11 @@ pub struct OxideUnitTest {
     pub name: String,
     #[serde(default)]
     pub depends_on: OxideDependsOn,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
 }
 
 #[allow(dead_code)]
@@ -117,37 +264,192 @@ pub struct OxideManifestMetadata {
     pub dbt_version: String,
     #[serde(default)]
     pub adapter_type: String,
+    /// Fields not modelled above, kept for lossless round-tripping.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    missing: Vec<&'static str>,
+}
+
+macro_rules! impl_lossless {
+    ($ty:ty, [$($field:literal),* $(,)?]) => {
+        impl Lossless for $ty {
+            const DEFAULTED_FIELDS: &'static [&'static str] = &[$($field),*];
+
+            fn missing_fields(&self) -> &[&'static str] {
+                &self.missing
+            }
+
+            fn set_missing_fields(&mut self, missing: Vec<&'static str>) {
+                self.missing = missing;
+            }
+        }
+    };
 }
 
+impl_lossless!(
+    OxideNode,
+    [
+        "fqn",
+        "depends_on",
+        "depends_on.nodes",
+        "depends_on.macros",
+        "raw_code",
+        "compiled_code",
+        "config",
+        "config.materialized",
+        "config.enabled",
+        "version",
+        "latest_version",
+        "access",
+        "group",
+    ]
+);
+impl_lossless!(
+    OxideSource,
+    [
+        "database",
+        "schema",
+        "package_name",
+        "config",
+        "config.enabled"
+    ]
+);
+impl_lossless!(
+    OxideMacro,
+    [
+        "macro_sql",
+        "depends_on",
+        "depends_on.nodes",
+        "depends_on.macros"
+    ]
+);
+impl_lossless!(
+    OxideExposure,
+    ["depends_on", "depends_on.nodes", "depends_on.macros"]
+);
+impl_lossless!(
+    OxideMetric,
+    ["depends_on", "depends_on.nodes", "depends_on.macros"]
+);
+impl_lossless!(OxideGroup, []);
+impl_lossless!(
+    OxideSemanticModel,
+    ["depends_on", "depends_on.nodes", "depends_on.macros"]
+);
+impl_lossless!(
+    OxideSavedQuery,
+    ["depends_on", "depends_on.nodes", "depends_on.macros"]
+);
+impl_lossless!(
+    OxideUnitTest,
+    ["depends_on", "depends_on.nodes", "depends_on.macros"]
+);
+impl_lossless!(OxideManifestMetadata, ["dbt_version", "adapter_type"]);
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OxideManifest {
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub nodes: HashMap<String, OxideNode>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub sources: HashMap<String, OxideSource>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub macros: HashMap<String, OxideMacro>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub exposures: HashMap<String, OxideExposure>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub metrics: HashMap<String, OxideMetric>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub groups: HashMap<String, OxideGroup>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub semantic_models: HashMap<String, OxideSemanticModel>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub saved_queries: HashMap<String, OxideSavedQuery>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::map")]
     pub unit_tests: HashMap<String, OxideUnitTest>,
-    #[serde(default)]
+    #[serde(default, with = "lossless::option")]
     pub metadata: Option<OxideManifestMetadata>,
+    /// Disabled nodes and sources, keyed by unique_id. `None` when the
+    /// manifest has `"disabled": null`, which the schema allows; it is written
+    /// back as `null`.
+    #[serde(default = "empty_disabled", with = "lossless::map_of_vecs")]
+    pub disabled: Option<HashMap<String, Vec<OxideNode>>>,
+    /// Top-level keys not modelled above (`docs`, `selectors`, `parent_map`, ...).
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    #[serde(skip)]
+    resolution_index: OnceCell<ResolutionIndex>,
+    #[serde(skip)]
+    macro_index: OnceCell<MacroIndex>,
+}
+
+fn empty_disabled() -> Option<HashMap<String, Vec<OxideNode>>> {
+    Some(HashMap::new())
+}
+
+/// The part of manifest.json read before the rest, to decide whether it
+/// must be upgraded.
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    metadata: Option<MetadataProbe>,
+}
+
+#[derive(Deserialize)]
+struct MetadataProbe {
+    #[serde(default)]
+    dbt_schema_version: Option<Value>,
+}
+
+/// Locate a data error by parsing `json` once more as a `Value`.
+fn data_error(json: &str, err: serde_json::Error) -> ManifestParseError {
+    match serde_json::from_str::<Value>(json) {
+        Ok(value) => ManifestParseError::from_value_error(&value, err),
+        Err(err) => ManifestParseError::from_syntax_error(json, err),
+    }
 }
 
 #[allow(dead_code)]
 impl OxideManifest {
-    pub fn from_json_str(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+    /// Parse manifest.json, upgrading schema versions older than the current
+    /// one first. A manifest without `metadata.dbt_schema_version` is read as
+    /// the current version. Errors carry the JSON path of the offending value.
+    ///
+    /// Current manifests are deserialized straight from `json`; only ones
+    /// that need an upgrade are loaded as a `Value` first.
+    pub fn from_json_str(json: &str) -> Result<Self, ManifestParseError> {
+        let probe: VersionProbe = serde_json::from_str(json).map_err(|e| {
+            if e.is_syntax() || e.is_eof() {
+                ManifestParseError::from_syntax_error(json, e)
+            } else {
+                data_error(json, e)
+            }
+        })?;
+        let field = probe.metadata.and_then(|m| m.dbt_schema_version);
+        match schema_version_field(field.as_ref())? {
+            Some(version) if version != CURRENT_SCHEMA_VERSION => {
+                check_supported(version)?;
+                let mut value: Value = serde_json::from_str(json)
+                    .map_err(|e| ManifestParseError::from_syntax_error(json, e))?;
+                upgrade_manifest_json(&mut value, version);
+                OxideManifest::deserialize(&value)
+                    .map_err(|e| ManifestParseError::from_value_error(&value, e))
+            }
+            _ => serde_json::from_str(json).map_err(|e| data_error(json, e)),
+        }
+    }
+
+    /// Serialize back to manifest.json. Fields the manifest was loaded with
+    /// are written back unchanged, including ones not modelled here.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
     }
 
     pub fn get_node(&self, unique_id: &str) -> Option<&OxideNode> {
@@ -157,6 +459,31 @@ impl OxideManifest {
     pub fn node_count(&self) -> usize {
         self.nodes.len()
     }
+
+    pub fn get_disabled(&self, unique_id: &str) -> Option<&Vec<OxideNode>> {
+        self.disabled.as_ref()?.get(unique_id)
+    }
+
+    /// `disabled` for adding entries; a `null` becomes an empty map.
+    pub fn disabled_mut(&mut self) -> &mut HashMap<String, Vec<OxideNode>> {
+        self.disabled.get_or_insert_with(HashMap::new)
+    }
+
+    /// `ref()`/`source()` lookup index, built on first use.
+    pub fn resolution_index(&self) -> &ResolutionIndex {
+        self.resolution_index
+            .get_or_init(|| ResolutionIndex::build(self))
+    }
+
+    pub fn macro_index(&self) -> &MacroIndex {
+        self.macro_index.get_or_init(|| MacroIndex::build(self))
+    }
+
+    /// Drop derived indexes after the manifest was modified.
+    pub fn invalidate_indexes(&mut self) {
+        self.resolution_index = OnceCell::new();
+        self.macro_index = OnceCell::new();
+    }
 }
 
 #[cfg(test)]
@@ -261,10 +588,138 @@ mod tests {
         assert_eq!(manifest.metrics.len(), 1);
     }
 
+    #[test]
+    fn test_parse_null_disabled() {
+        let json = r#"{
+            "nodes": {},
+            "disabled": null,
+            "metadata": {"dbt_schema_version": "https://schemas.getdbt.com/dbt/manifest/v12.json"}
+        }"#;
+        let manifest = OxideManifest::from_json_str(json).unwrap();
+        assert!(manifest.disabled.is_none());
+        let written: Value = serde_json::from_str(&manifest.to_json_string().unwrap()).unwrap();
+        assert_eq!(written.get("disabled"), Some(&Value::Null));
+
+        let manifest = OxideManifest::from_json_str(r#"{"nodes": {}}"#).unwrap();
+        let written: Value = serde_json::from_str(&manifest.to_json_string().unwrap()).unwrap();
+        assert_eq!(written["disabled"], serde_json::json!({}));
+    }
+
     #[test]
     fn test_invalid_json_returns_error() {
         let invalid_json = r#"{"nodes": invalid}"#;
         let result = OxideManifest::from_json_str(invalid_json);
         assert!(result.is_err());
     }
+
+    const V12_MANIFEST: &str =
+        include_str!("../../../tests/functional/artifacts/data/state/v12/manifest.json");
+
+    fn round_trip(json: &str) -> (Value, Value) {
+        let manifest = OxideManifest::from_json_str(json).unwrap();
+        let written: Value = serde_json::from_str(&manifest.to_json_string().unwrap()).unwrap();
+        (serde_json::from_str(json).unwrap(), written)
+    }
+
+    #[test]
+    fn test_round_trip_v12_manifest() {
+        let (original, written) = round_trip(V12_MANIFEST);
+        assert_eq!(original, written);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_absent_fields_absent() {
+        let json = r#"{
+            "nodes": {
+                "seed.p.s": {
+                    "unique_id": "seed.p.s", "name": "s", "resource_type": "seed", "package_name": "p",
+                    "depends_on": {"macros": []}, "config": {"enabled": true, "quote_columns": null}
+                },
+                "model.p.m": {
+                    "unique_id": "model.p.m", "name": "m", "resource_type": "model", "package_name": "p",
+                    "version": null, "depends_on": {"nodes": [], "macros": []}, "checksum": {"name": "sha256", "checksum": "x"}
+                }
+            },
+            "macros": {
+                "macro.p.x": {"unique_id": "macro.p.x", "name": "x", "package_name": "p", "depends_on": {"macros": []}}
+            },
+            "disabled": {
+                "model.p.off": [{"unique_id": "model.p.off", "name": "off", "resource_type": "model", "package_name": "p"}]
+            },
+            "metadata": {"dbt_schema_version": "https://schemas.getdbt.com/dbt/manifest/v12.json"},
+            "parent_map": null
+        }"#;
+        let (original, mut written) = round_trip(json);
+        // Top-level collections are always written.
+        for key in [
+            "sources",
+            "exposures",
+            "metrics",
+            "groups",
+            "semantic_models",
+            "saved_queries",
+            "unit_tests",
+        ] {
+            assert_eq!(written[key], serde_json::json!({}));
+            written.as_object_mut().unwrap().remove(key);
+        }
+        assert_eq!(original, written);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_float_precision() {
+        // The default float parser can land one ULP off, which comparing two
+        // re-parsed Values would never notice.
+        let json = r#"{"macros": {"macro.p.x": {"unique_id": "macro.p.x", "name": "x", "package_name": "p", "created_at": 1729197943.3910441}}}"#;
+        let manifest = OxideManifest::from_json_str(json).unwrap();
+        assert!(manifest
+            .to_json_string()
+            .unwrap()
+            .contains("\"created_at\":1729197943.3910441"));
+    }
+
+    #[test]
+    fn test_collections_are_written_in_key_order() {
+        let node = |id: &str| serde_json::json!({"unique_id": id, "name": id, "resource_type": "model", "package_name": "p"});
+        let ids = [
+            "model.p.c",
+            "model.p.a",
+            "model.p.e",
+            "model.p.b",
+            "model.p.d",
+        ];
+        let manifest = serde_json::json!({
+            "nodes": ids.iter().map(|id| (id.to_string(), node(id))).collect::<Map<_, _>>(),
+            "disabled": ids.iter().map(|id| (id.to_string(), serde_json::json!([node(id)]))).collect::<Map<_, _>>(),
+        });
+        let manifest = OxideManifest::from_json_str(&manifest.to_string()).unwrap();
+        let written = manifest.to_json_string().unwrap();
+        assert_eq!(manifest.to_json_string().unwrap(), written);
+
+        let written: Value = serde_json::from_str(&written).unwrap();
+        let mut sorted = ids.to_vec();
+        sorted.sort_unstable();
+        for key in ["nodes", "disabled"] {
+            let keys: Vec<&str> = written[key]
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(String::as_str)
+                .collect();
+            assert_eq!(keys, sorted);
+        }
+    }
+
+    #[test]
+    fn test_write_json() {
+        let manifest = OxideManifest::from_json_str(V12_MANIFEST).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("dbt_rs_manifest_{}.json", std::process::id()));
+        manifest.write_json(&path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let reloaded = OxideManifest::from_json_str(&written).unwrap();
+        assert_eq!(reloaded.node_count(), manifest.node_count());
+    }
 }
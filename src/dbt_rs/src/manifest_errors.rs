@@ -0,0 +1,491 @@
+//! Locating manifest parse failures: which collection, unique_id and field
+//! a serde error came from, and what type was expected there.
+//!
+//! Manifests are parsed with serde_json's fast path. When that fails, the
+//! offending entity is deserialized once more with `serde_path_to_error`,
+//! through an error type that records what serde expected and found.
+
+use crate::manifest::{
+    OxideExposure, OxideGroup, OxideMacro, OxideManifest, OxideManifestMetadata, OxideMetric,
+    OxideNode, OxideSavedQuery, OxideSemanticModel, OxideSource, OxideUnitTest,
+};
+use crate::manifest_upgrade::SchemaVersionError;
+use serde::de::{self, DeserializeOwned, Expected, Unexpected};
+use serde::Deserialize;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use serde_value::ValueDeserializer;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Not valid JSON.
+    Syntax,
+    /// Valid JSON that does not have the shape of a manifest.
+    Data,
+    /// `metadata.dbt_schema_version` is malformed or unsupported.
+    Version,
+}
+
+impl ParseErrorKind {
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseErrorKind::Syntax => "syntax",
+            ParseErrorKind::Data => "data",
+            ParseErrorKind::Version => "version",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestParseError {
+    pub kind: ParseErrorKind,
+    /// Keys and indexes from the document root to the offending value, e.g.
+    /// `["nodes", "model.x.y", "config", "materialized"]`.
+    pub path: Vec<String>,
+    /// The underlying error, without position.
+    pub message: String,
+    /// What serde expected at `path`, e.g. `a string` or `field `name``.
+    pub expected: Option<Box<str>>,
+    /// What it found instead, e.g. `integer `5``.
+    pub found: Option<Box<str>>,
+    /// Line and column in the input, for syntax errors.
+    pub position: Option<(usize, usize)>,
+}
+
+/// Top-level keys whose values are maps of unique_id to entity.
+const COLLECTIONS: [&str; 10] = [
+    "nodes",
+    "sources",
+    "macros",
+    "exposures",
+    "metrics",
+    "groups",
+    "semantic_models",
+    "saved_queries",
+    "unit_tests",
+    "disabled",
+];
+
+impl ManifestParseError {
+    /// The path as a JSON pointer.
+    pub fn pointer(&self) -> String {
+        self.path
+            .iter()
+            .map(|key| format!("/{}", escape(key)))
+            .collect()
+    }
+
+    pub fn collection(&self) -> Option<&str> {
+        self.path
+            .first()
+            .map(String::as_str)
+            .filter(|key| COLLECTIONS.contains(key))
+    }
+
+    pub fn unique_id(&self) -> Option<&str> {
+        self.collection()?;
+        self.path.get(1).map(String::as_str)
+    }
+
+    /// Dotted field path inside the entity, e.g. `config.materialized`.
+    pub fn field(&self) -> Option<String> {
+        let collection = self.collection()?;
+        // Disabled entries are lists; skip the index.
+        let skip = if collection == "disabled" { 3 } else { 2 };
+        let rest = self.path.get(skip..).filter(|rest| !rest.is_empty())?;
+        Some(rest.join("."))
+    }
+
+    /// A syntax error in `json`, located by the keys open at the error.
+    pub(crate) fn from_syntax_error(json: &str, err: serde_json::Error) -> Self {
+        let offset = byte_offset(json, err.line(), err.column());
+        let mut error = ManifestParseError::data(path_at(json, offset), strip_position(&err));
+        error.kind = ParseErrorKind::Syntax;
+        error.position = Some((err.line(), err.column()));
+        error
+    }
+
+    /// Locate the error raised when deserializing `value`, found at `prefix`
+    /// in the manifest, as a `T`.
+    pub(crate) fn from_entity_error<T: DeserializeOwned>(
+        prefix: Vec<String>,
+        value: &Value,
+        err: serde_json::Error,
+    ) -> Self {
+        locate::<T>(prefix.clone(), value)
+            .unwrap_or_else(|| ManifestParseError::data(prefix, strip_position(&err)))
+    }
+
+    /// Locate the error raised when deserializing `value` as a manifest.
+    pub(crate) fn from_value_error(value: &Value, err: serde_json::Error) -> Self {
+        locate_in_manifest(value)
+            .unwrap_or_else(|| ManifestParseError::data(Vec::new(), strip_position(&err)))
+    }
+
+    pub(crate) fn from_version_error(err: impl fmt::Display) -> Self {
+        ManifestParseError {
+            kind: ParseErrorKind::Version,
+            path: vec!["metadata".to_string(), "dbt_schema_version".to_string()],
+            message: err.to_string(),
+            expected: None,
+            found: None,
+            position: None,
+        }
+    }
+
+    fn data(path: Vec<String>, message: String) -> Self {
+        ManifestParseError {
+            kind: ParseErrorKind::Data,
+            path,
+            message,
+            expected: None,
+            found: None,
+            position: None,
+        }
+    }
+}
+
+impl From<SchemaVersionError> for ManifestParseError {
+    fn from(err: SchemaVersionError) -> Self {
+        ManifestParseError::from_version_error(err)
+    }
+}
+
+impl fmt::Display for ManifestParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = self.pointer();
+        write!(
+            f,
+            "{}: {}",
+            if pointer.is_empty() { "/" } else { &pointer },
+            self.message
+        )?;
+        if let Some((line, column)) = self.position {
+            write!(f, " at line {} column {}", line, column)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ManifestParseError {}
+
+fn strip_position(err: &serde_json::Error) -> String {
+    let message = err.to_string();
+    let suffix = format!(" at line {} column {}", err.line(), err.column());
+    match message.strip_suffix(&suffix) {
+        Some(stripped) if err.line() > 0 => stripped.to_string(),
+        _ => message,
+    }
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// A deserialization error as serde's constructors describe it, keeping
+/// the expected and found parts apart. Messages match serde_json's.
+#[derive(Debug)]
+struct Described {
+    message: String,
+    expected: Option<String>,
+    found: Option<String>,
+    missing_field: Option<&'static str>,
+}
+
+impl Described {
+    fn new(message: String, expected: Option<String>, found: Option<String>) -> Self {
+        Described {
+            message,
+            expected,
+            found,
+            missing_field: None,
+        }
+    }
+}
+
+impl fmt::Display for Described {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Described {}
+
+impl de::Error for Described {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Described::new(msg.to_string(), None, None)
+    }
+
+    fn invalid_type(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        Described::new(
+            format!("invalid type: {}, expected {}", unexp, exp),
+            Some(exp.to_string()),
+            Some(unexp.to_string()),
+        )
+    }
+
+    fn invalid_value(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        Described::new(
+            format!("invalid value: {}, expected {}", unexp, exp),
+            Some(exp.to_string()),
+            Some(unexp.to_string()),
+        )
+    }
+
+    fn invalid_length(len: usize, exp: &dyn Expected) -> Self {
+        Described::new(
+            format!("invalid length {}, expected {}", len, exp),
+            Some(exp.to_string()),
+            Some(format!("length {}", len)),
+        )
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        let names: Vec<String> = expected.iter().map(|v| format!("`{}`", v)).collect();
+        let expected = match names.as_slice() {
+            [] => "no variants".to_string(),
+            [one] => one.clone(),
+            _ => format!("one of {}", names.join(", ")),
+        };
+        Described::new(
+            format!("unknown variant `{}`, expected {}", variant, expected),
+            Some(expected),
+            Some(format!("`{}`", variant)),
+        )
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        let mut described = Described::new(
+            format!("missing field `{}`", field),
+            Some(format!("field `{}`", field)),
+            None,
+        );
+        described.missing_field = Some(field);
+        described
+    }
+}
+
+/// Deserialize `value` as a `T` with paths and described errors. Returns
+/// `None` if it deserializes.
+fn locate<T: DeserializeOwned>(prefix: Vec<String>, value: &Value) -> Option<ManifestParseError> {
+    let value = serde_value::Value::deserialize(value).ok()?;
+    let err = serde_path_to_error::deserialize::<_, T>(ValueDeserializer::<Described>::new(value))
+        .err()?;
+    let mut path = prefix;
+    path.extend(err.path().iter().filter_map(|segment| match segment {
+        Segment::Seq { index } => Some(index.to_string()),
+        Segment::Map { key } => Some(key.clone()),
+        Segment::Enum { variant } => Some(variant.clone()),
+        Segment::Unknown => None,
+    }));
+    let described = err.into_inner();
+    path.extend(described.missing_field.map(str::to_string));
+    Some(ManifestParseError {
+        kind: ParseErrorKind::Data,
+        path,
+        message: described.message,
+        expected: described.expected.map(String::into_boxed_str),
+        found: described.found.map(String::into_boxed_str),
+        position: None,
+    })
+}
+
+fn locate_collection<T: DeserializeOwned>(value: &Value, key: &str) -> Option<ManifestParseError> {
+    value
+        .get(key)?
+        .as_object()?
+        .iter()
+        .find_map(|(unique_id, entity)| {
+            locate::<T>(vec![key.to_string(), unique_id.clone()], entity)
+        })
+}
+
+fn locate_in_manifest(value: &Value) -> Option<ManifestParseError> {
+    let disabled = || {
+        value
+            .get("disabled")?
+            .as_object()?
+            .iter()
+            .find_map(|(unique_id, entries)| {
+                entries
+                    .as_array()?
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, entry)| {
+                        let prefix = vec!["disabled".to_string(), unique_id.clone(), i.to_string()];
+                        locate::<OxideNode>(prefix, entry)
+                    })
+            })
+    };
+    let metadata = || {
+        let metadata = value.get("metadata").filter(|m| !m.is_null())?;
+        locate::<OxideManifestMetadata>(vec!["metadata".to_string()], metadata)
+    };
+    // Entities first, each on its own: a failure above them, e.g.
+    // `"nodes": []`, is located by deserializing the manifest itself.
+    locate_collection::<OxideNode>(value, "nodes")
+        .or_else(|| locate_collection::<OxideSource>(value, "sources"))
+        .or_else(|| locate_collection::<OxideMacro>(value, "macros"))
+        .or_else(|| locate_collection::<OxideExposure>(value, "exposures"))
+        .or_else(|| locate_collection::<OxideMetric>(value, "metrics"))
+        .or_else(|| locate_collection::<OxideGroup>(value, "groups"))
+        .or_else(|| locate_collection::<OxideSemanticModel>(value, "semantic_models"))
+        .or_else(|| locate_collection::<OxideSavedQuery>(value, "saved_queries"))
+        .or_else(|| locate_collection::<OxideUnitTest>(value, "unit_tests"))
+        .or_else(disabled)
+        .or_else(metadata)
+        .or_else(|| locate::<OxideManifest>(Vec::new(), value))
+}
+
+fn byte_offset(json: &str, line: usize, column: usize) -> usize {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        json.match_indices('\n')
+            .nth(line - 2)
+            .map_or(json.len(), |(i, _)| i + 1)
+    };
+    (line_start + column.saturating_sub(1)).min(json.len())
+}
+
+enum Frame {
+    Object { key: Option<String>, in_value: bool },
+    Array { index: usize },
+}
+
+/// The key path at byte `offset` of a (possibly invalid) JSON document.
+fn path_at(json: &str, offset: usize) -> Vec<String> {
+    let bytes = &json.as_bytes()[..offset];
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if let Some(Frame::Object {
+                    key,
+                    in_value: false,
+                }) = stack.last_mut()
+                {
+                    let raw = &json[start..(i + 1).min(offset)];
+                    *key = Some(
+                        serde_json::from_str::<String>(raw)
+                            .unwrap_or_else(|_| raw.trim_matches('"').to_string()),
+                    );
+                }
+            }
+            b'{' => stack.push(Frame::Object {
+                key: None,
+                in_value: false,
+            }),
+            b'[' => stack.push(Frame::Array { index: 0 }),
+            b'}' | b']' => {
+                stack.pop();
+            }
+            b':' => {
+                if let Some(Frame::Object { in_value, .. }) = stack.last_mut() {
+                    *in_value = true;
+                }
+            }
+            b',' => match stack.last_mut() {
+                Some(Frame::Object { key, in_value }) => {
+                    *key = None;
+                    *in_value = false;
+                }
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    stack
+        .into_iter()
+        .filter_map(|frame| match frame {
+            Frame::Object { key, .. } => key,
+            Frame::Array { index } => Some(index.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(json: &str) -> ManifestParseError {
+        OxideManifest::from_json_str(json).unwrap_err()
+    }
+
+    #[test]
+    fn test_type_error_in_nested_field() {
+        let err = parse_err(
+            r#"{"nodes": {
+                "model.p.a": {"unique_id": "model.p.a", "name": "a", "resource_type": "model", "package_name": "p"},
+                "model.p.b": {"unique_id": "model.p.b", "name": "b", "resource_type": "model", "package_name": "p",
+                              "config": {"enabled": true, "materialized": 5}}
+            }}"#,
+        );
+        assert_eq!(err.kind, ParseErrorKind::Data);
+        assert_eq!(err.pointer(), "/nodes/model.p.b/config/materialized");
+        assert_eq!(err.collection(), Some("nodes"));
+        assert_eq!(err.unique_id(), Some("model.p.b"));
+        assert_eq!(err.field().as_deref(), Some("config.materialized"));
+        assert_eq!(err.expected.as_deref(), Some("a string"));
+        assert_eq!(err.found.as_deref(), Some("integer `5`"));
+    }
+
+    #[test]
+    fn test_missing_field_and_disabled_entries() {
+        let err = parse_err(
+            r#"{"disabled": {"model.p.x": [{"unique_id": "model.p.x", "resource_type": "model", "package_name": "p"}]}}"#,
+        );
+        assert_eq!(err.pointer(), "/disabled/model.p.x/0/name");
+        assert_eq!(err.field().as_deref(), Some("name"));
+        assert_eq!(err.expected.as_deref(), Some("field `name`"));
+
+        let err = parse_err(
+            r#"{"macros": {"macro.p.m": {"unique_id": "macro.p.m", "name": "m", "package_name": "p", "depends_on": {"macros": "x"}}}}"#,
+        );
+        assert_eq!(err.pointer(), "/macros/macro.p.m/depends_on/macros");
+        assert_eq!(err.found.as_deref(), Some("string \"x\""));
+    }
+
+    #[test]
+    fn test_wrong_collection_shape() {
+        let err = parse_err(r#"{"nodes": []}"#);
+        assert_eq!(err.pointer(), "/nodes");
+        assert_eq!(err.collection(), Some("nodes"));
+        assert_eq!(err.unique_id(), None);
+    }
+
+    #[test]
+    fn test_wrong_metadata_shape() {
+        let err = parse_err(r#"{"nodes": {}, "metadata": 5}"#);
+        assert_eq!(err.kind, ParseErrorKind::Data);
+        assert_eq!(err.pointer(), "/metadata");
+    }
+
+    #[test]
+    fn test_syntax_error_path() {
+        let err = parse_err(r#"{"nodes": {"model.p.a": {"name": "a", "fqn": ["p", "a",]}}}"#);
+        assert_eq!(err.kind, ParseErrorKind::Syntax);
+        assert_eq!(err.pointer(), "/nodes/model.p.a/fqn/2");
+        assert_eq!(err.position.map(|(line, _)| line), Some(1));
+        assert!(err.to_string().contains("at line 1 column"), "{}", err);
+    }
+
+    #[test]
+    fn test_version_error() {
+        let err = parse_err(
+            r#"{"metadata": {"dbt_schema_version": "https://schemas.getdbt.com/dbt/manifest/v2.json"}}"#,
+        );
+        assert_eq!(err.kind, ParseErrorKind::Version);
+        assert_eq!(err.pointer(), "/metadata/dbt_schema_version");
+    }
+}
@@ -0,0 +1,331 @@
+//! Applying partial-parse changes to a loaded manifest in place, so that an
+//! edit to one file does not cost a reload of the whole manifest.
+
+use crate::lossless::{self, Lossless};
+use crate::manifest::{OxideManifest, OxideNode};
+use crate::manifest_errors::ManifestParseError;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// A manifest collection keyed by unique_id.
+#[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchTarget {
+    Nodes,
+    Sources,
+    Macros,
+    Exposures,
+    Metrics,
+    Groups,
+    SemanticModels,
+    SavedQueries,
+    UnitTests,
+    /// Values are lists of disabled nodes.
+    Disabled,
+}
+
+impl PatchTarget {
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "nodes" => PatchTarget::Nodes,
+            "sources" => PatchTarget::Sources,
+            "macros" => PatchTarget::Macros,
+            "exposures" => PatchTarget::Exposures,
+            "metrics" => PatchTarget::Metrics,
+            "groups" => PatchTarget::Groups,
+            "semantic_models" => PatchTarget::SemanticModels,
+            "saved_queries" => PatchTarget::SavedQueries,
+            "unit_tests" => PatchTarget::UnitTests,
+            "disabled" => PatchTarget::Disabled,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PatchTarget::Nodes => "nodes",
+            PatchTarget::Sources => "sources",
+            PatchTarget::Macros => "macros",
+            PatchTarget::Exposures => "exposures",
+            PatchTarget::Metrics => "metrics",
+            PatchTarget::Groups => "groups",
+            PatchTarget::SemanticModels => "semantic_models",
+            PatchTarget::SavedQueries => "saved_queries",
+            PatchTarget::UnitTests => "unit_tests",
+            PatchTarget::Disabled => "disabled",
+        }
+    }
+}
+
+fn parse_entries(
+    target: PatchTarget,
+    json: &str,
+) -> Result<Map<String, Value>, ManifestParseError> {
+    let value: Value = serde_json::from_str(json).map_err(|e| {
+        let mut err = ManifestParseError::from_syntax_error(json, e);
+        err.path.insert(0, target.name().to_string());
+        err
+    })?;
+    deserialize_shape(vec![target.name().to_string()], &value)
+}
+
+/// Deserialize the containers around entities, which are small.
+fn deserialize_shape<T: DeserializeOwned>(
+    path: Vec<String>,
+    value: &Value,
+) -> Result<T, ManifestParseError> {
+    T::deserialize(value).map_err(|e| ManifestParseError::from_entity_error::<T>(path, value, e))
+}
+
+fn decode<T: Lossless>(path: Vec<String>, value: Value) -> Result<T, ManifestParseError> {
+    lossless::from_value(value.clone())
+        .map_err(|e| ManifestParseError::from_entity_error::<T>(path, &value, e))
+}
+
+fn decode_all<T: Lossless>(
+    target: PatchTarget,
+    entries: Map<String, Value>,
+) -> Result<Vec<(String, T)>, ManifestParseError> {
+    entries
+        .into_iter()
+        .map(|(unique_id, value)| {
+            let path = vec![target.name().to_string(), unique_id.clone()];
+            Ok((unique_id, decode(path, value)?))
+        })
+        .collect()
+}
+
+fn decode_disabled(
+    entries: Map<String, Value>,
+) -> Result<Vec<(String, Vec<OxideNode>)>, ManifestParseError> {
+    let name = PatchTarget::Disabled.name().to_string();
+    entries
+        .into_iter()
+        .map(|(unique_id, value)| {
+            let path = vec![name.clone(), unique_id.clone()];
+            let items: Vec<Value> = deserialize_shape(path.clone(), &value)?;
+            let nodes = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let mut path = path.clone();
+                    path.push(i.to_string());
+                    decode(path, item)
+                })
+                .collect::<Result<Vec<OxideNode>, _>>()?;
+            Ok((unique_id, nodes))
+        })
+        .collect()
+}
+
+fn replace<T>(map: &mut HashMap<String, T>, entries: Vec<(String, T)>) -> Vec<String> {
+    entries
+        .into_iter()
+        .map(|(unique_id, item)| {
+            map.insert(unique_id.clone(), item);
+            unique_id
+        })
+        .collect()
+}
+
+fn remove<T>(map: &mut HashMap<String, T>, unique_ids: &[String]) -> Vec<String> {
+    unique_ids
+        .iter()
+        .filter(|unique_id| map.remove(*unique_id).is_some())
+        .cloned()
+        .collect()
+}
+
+#[allow(dead_code)]
+impl OxideManifest {
+    /// Insert or replace entries of `target` from a JSON object mapping
+    /// unique_id to entity, as written in manifest.json. Nothing is applied
+    /// if any entry fails to parse. Returns the unique_ids written.
+    pub fn upsert_json(
+        &mut self,
+        target: PatchTarget,
+        json: &str,
+    ) -> Result<Vec<String>, ManifestParseError> {
+        let entries = parse_entries(target, json)?;
+        let written = match target {
+            PatchTarget::Nodes => replace(&mut self.nodes, decode_all(target, entries)?),
+            PatchTarget::Sources => replace(&mut self.sources, decode_all(target, entries)?),
+            PatchTarget::Macros => replace(&mut self.macros, decode_all(target, entries)?),
+            PatchTarget::Exposures => replace(&mut self.exposures, decode_all(target, entries)?),
+            PatchTarget::Metrics => replace(&mut self.metrics, decode_all(target, entries)?),
+            PatchTarget::Groups => replace(&mut self.groups, decode_all(target, entries)?),
+            PatchTarget::SemanticModels => {
+                replace(&mut self.semantic_models, decode_all(target, entries)?)
+            }
+            PatchTarget::SavedQueries => {
+                replace(&mut self.saved_queries, decode_all(target, entries)?)
+            }
+            PatchTarget::UnitTests => replace(&mut self.unit_tests, decode_all(target, entries)?),
+            PatchTarget::Disabled => replace(self.disabled_mut(), decode_disabled(entries)?),
+        };
+        if !written.is_empty() {
+            self.invalidate_indexes();
+        }
+        Ok(written)
+    }
+
+    /// Remove entries of `target`. Returns the unique_ids that were present.
+    pub fn remove_entries(&mut self, target: PatchTarget, unique_ids: &[String]) -> Vec<String> {
+        let removed = match target {
+            PatchTarget::Nodes => remove(&mut self.nodes, unique_ids),
+            PatchTarget::Sources => remove(&mut self.sources, unique_ids),
+            PatchTarget::Macros => remove(&mut self.macros, unique_ids),
+            PatchTarget::Exposures => remove(&mut self.exposures, unique_ids),
+            PatchTarget::Metrics => remove(&mut self.metrics, unique_ids),
+            PatchTarget::Groups => remove(&mut self.groups, unique_ids),
+            PatchTarget::SemanticModels => remove(&mut self.semantic_models, unique_ids),
+            PatchTarget::SavedQueries => remove(&mut self.saved_queries, unique_ids),
+            PatchTarget::UnitTests => remove(&mut self.unit_tests, unique_ids),
+            PatchTarget::Disabled => match &mut self.disabled {
+                Some(disabled) => remove(disabled, unique_ids),
+                None => Vec::new(),
+            },
+        };
+        if !removed.is_empty() {
+            self.invalidate_indexes();
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest_errors::ParseErrorKind;
+
+    const MANIFEST: &str = r#"{
+        "nodes": {
+            "model.p.a": {"unique_id": "model.p.a", "name": "a", "resource_type": "model", "package_name": "p"},
+            "model.p.b": {"unique_id": "model.p.b", "name": "b", "resource_type": "model", "package_name": "p",
+                          "depends_on": {"nodes": ["model.p.a"]}}
+        },
+        "macros": {
+            "macro.p.m": {"unique_id": "macro.p.m", "name": "m", "package_name": "p"}
+        }
+    }"#;
+
+    #[test]
+    fn test_upsert_and_remove() {
+        let mut manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let written = manifest
+            .upsert_json(
+                PatchTarget::Nodes,
+                r#"{"model.p.b": {"unique_id": "model.p.b", "name": "b", "resource_type": "model",
+                                  "package_name": "p", "depends_on": {"nodes": []}, "custom": 1}}"#,
+            )
+            .unwrap();
+        assert_eq!(written, vec!["model.p.b"]);
+        assert!(manifest.nodes["model.p.b"].depends_on.nodes.is_empty());
+        assert_eq!(manifest.nodes["model.p.b"].extra["custom"], 1);
+        assert_eq!(manifest.node_count(), 2);
+
+        let removed = manifest.remove_entries(
+            PatchTarget::Macros,
+            &["macro.p.m".to_string(), "macro.p.gone".to_string()],
+        );
+        assert_eq!(removed, vec!["macro.p.m"]);
+        assert!(manifest.macros.is_empty());
+    }
+
+    #[test]
+    fn test_upserted_entries_round_trip() {
+        let mut manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let entity = r#"{"unique_id": "model.p.c", "name": "c", "resource_type": "model", "package_name": "p"}"#;
+        manifest
+            .upsert_json(
+                PatchTarget::Nodes,
+                &format!(r#"{{"model.p.c": {}}}"#, entity),
+            )
+            .unwrap();
+        let written: Value = serde_json::from_str(&manifest.to_json_string().unwrap()).unwrap();
+        assert_eq!(
+            written["nodes"]["model.p.c"],
+            serde_json::from_str::<Value>(entity).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_upsert_invalidates_indexes() {
+        let internal = Vec::new();
+        let search_order = std::collections::HashMap::new();
+        let ctx = || crate::macro_resolution::MacroResolveContext {
+            root_project: "p",
+            internal_packages: &internal,
+            adapter_types: &[],
+            dispatch_search_order: &search_order,
+            node_package: None,
+        };
+        let mut manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        assert!(manifest.resolve_macro("m", Some("q"), ctx()).is_err());
+        manifest
+            .upsert_json(
+                PatchTarget::Macros,
+                r#"{"macro.q.m": {"unique_id": "macro.q.m", "name": "m", "package_name": "q"}}"#,
+            )
+            .unwrap();
+        let resolved = manifest.resolve_macro("m", Some("q"), ctx()).unwrap();
+        assert_eq!(resolved.unique_id, "macro.q.m");
+    }
+
+    #[test]
+    fn test_disabled_entries() {
+        let mut manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        manifest
+            .upsert_json(
+                PatchTarget::Disabled,
+                r#"{"model.p.x": [{"unique_id": "model.p.x", "name": "x", "resource_type": "model", "package_name": "p"}]}"#,
+            )
+            .unwrap();
+        assert_eq!(manifest.get_disabled("model.p.x").unwrap().len(), 1);
+
+        let err = manifest
+            .upsert_json(PatchTarget::Disabled, r#"{"model.p.y": {}}"#)
+            .unwrap_err();
+        assert_eq!(err.pointer(), "/disabled/model.p.y");
+
+        let mut manifest = OxideManifest::from_json_str(r#"{"disabled": null}"#).unwrap();
+        assert!(manifest
+            .remove_entries(PatchTarget::Disabled, &["model.p.x".to_string()])
+            .is_empty());
+        manifest
+            .upsert_json(
+                PatchTarget::Disabled,
+                r#"{"model.p.x": [{"unique_id": "model.p.x", "name": "x", "resource_type": "model", "package_name": "p"}]}"#,
+            )
+            .unwrap();
+        assert_eq!(manifest.get_disabled("model.p.x").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_entries_apply_nothing() {
+        let mut manifest = OxideManifest::from_json_str(MANIFEST).unwrap();
+        let err = manifest
+            .upsert_json(
+                PatchTarget::Nodes,
+                r#"{"model.p.a": {"unique_id": "model.p.a", "name": "a2", "resource_type": "model", "package_name": "p"},
+                    "model.p.z": {"unique_id": "model.p.z", "name": "z", "resource_type": "model", "package_name": "p",
+                                  "depends_on": {"nodes": "model.p.a"}}}"#,
+            )
+            .unwrap_err();
+        assert_eq!(err.pointer(), "/nodes/model.p.z/depends_on/nodes");
+        assert_eq!(manifest.nodes["model.p.a"].name, "a");
+
+        let err = manifest
+            .upsert_json(PatchTarget::Sources, r#"{"source.p.s.t": {"#)
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Syntax);
+        assert_eq!(err.pointer(), "/sources/source.p.s.t");
+
+        let err = manifest.upsert_json(PatchTarget::Nodes, "[]").unwrap_err();
+        assert_eq!(err.pointer(), "/nodes");
+        assert_eq!(err.expected.as_deref(), Some("a map"));
+        assert_eq!(err.found.as_deref(), Some("sequence"));
+    }
+}
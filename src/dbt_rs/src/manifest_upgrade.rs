@@ -0,0 +1,388 @@
+//! Upgrade older manifest.json schema versions to the current one, matching
+//! dbt's `upgrade_manifest_json`.
+
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+pub const CURRENT_SCHEMA_VERSION: u32 = 12;
+/// Oldest version dbt can still read as a `--state` manifest.
+pub const MIN_SCHEMA_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaVersionError {
+    Invalid(String),
+    Unsupported(u32),
+}
+
+impl fmt::Display for SchemaVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaVersionError::Invalid(version) => {
+                write!(f, "Invalid manifest schema version: {}", version)
+            }
+            SchemaVersionError::Unsupported(version) => write!(
+                f,
+                "Manifest schema version v{} is not supported, expected v{} to v{}",
+                version, MIN_SCHEMA_VERSION, CURRENT_SCHEMA_VERSION
+            ),
+        }
+    }
+}
+
+pub fn schema_url(version: u32) -> String {
+    format!("https://schemas.getdbt.com/dbt/manifest/v{}.json", version)
+}
+
+/// Parse `https://schemas.getdbt.com/dbt/manifest/v10.json` into `10`.
+pub fn parse_schema_version(url: &str) -> Result<u32, SchemaVersionError> {
+    url.rsplit('/')
+        .next()
+        .and_then(|file| file.split('.').next())
+        .and_then(|stem| stem.strip_prefix('v'))
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| SchemaVersionError::Invalid(url.to_string()))
+}
+
+pub fn check_supported(version: u32) -> Result<(), SchemaVersionError> {
+    if (MIN_SCHEMA_VERSION..=CURRENT_SCHEMA_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(SchemaVersionError::Unsupported(version))
+    }
+}
+
+/// The schema version declared by a manifest's `metadata.dbt_schema_version`
+/// value, if any.
+pub fn schema_version_field(field: Option<&Value>) -> Result<Option<u32>, SchemaVersionError> {
+    match field {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(url)) => parse_schema_version(url).map(Some),
+        Some(other) => Err(SchemaVersionError::Invalid(other.to_string())),
+    }
+}
+
+fn objects_mut<'a>(
+    manifest: &'a mut Value,
+    key: &str,
+) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    manifest
+        .get_mut(key)
+        .and_then(Value::as_object_mut)
+        .into_iter()
+        .flat_map(|map| map.values_mut())
+        .filter_map(Value::as_object_mut)
+}
+
+fn disabled_entries_mut(manifest: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    manifest
+        .get_mut("disabled")
+        .and_then(Value::as_object_mut)
+        .into_iter()
+        .flat_map(|map| map.values_mut())
+        .filter_map(Value::as_array_mut)
+        .flat_map(|entries| entries.iter_mut())
+        .filter_map(Value::as_object_mut)
+}
+
+fn rename_sql_attr(node: &mut Map<String, Value>) {
+    if let Some(raw) = node.shift_remove("raw_sql") {
+        node.insert("raw_code".to_string(), raw);
+    }
+    if let Some(compiled) = node.shift_remove("compiled_sql") {
+        node.insert("compiled_code".to_string(), compiled);
+    }
+    node.insert("language".to_string(), json!("sql"));
+}
+
+/// dbt 1.5 turned `refs` from `[[package?, name]]` into
+/// `[{package, name, version}]`.
+fn upgrade_ref_content(node: &mut Map<String, Value>) {
+    let Some(Value::Array(refs)) = node.get_mut("refs") else {
+        return;
+    };
+    let upgraded = refs
+        .iter()
+        .filter_map(|r| match r.as_array().map(Vec::as_slice) {
+            Some([name]) => Some(json!({"package": null, "name": name, "version": null})),
+            Some([package, name, ..]) => {
+                Some(json!({"package": package, "name": name, "version": null}))
+            }
+            // dbt fails with an IndexError on an empty ref; drop it instead.
+            Some(_) => None,
+            // Unlike dbt, which drops them, keep refs already in the new format.
+            None => Some(r.clone()),
+        })
+        .collect();
+    *refs = upgraded;
+}
+
+fn upgrade_node_content(node: &mut Map<String, Value>) {
+    rename_sql_attr(node);
+    upgrade_ref_content(node);
+    if node.get("resource_type").and_then(Value::as_str) != Some("seed") {
+        node.shift_remove("root_path");
+    }
+}
+
+const SEED_COMPILATION_ATTRS: [&str; 10] = [
+    "language",
+    "refs",
+    "sources",
+    "metrics",
+    "compiled_path",
+    "compiled",
+    "compiled_code",
+    "extra_ctes_injected",
+    "extra_ctes",
+    "relation_name",
+];
+
+fn upgrade_seed_content(node: &mut Map<String, Value>) {
+    for attr in SEED_COMPILATION_ATTRS {
+        node.shift_remove(attr);
+    }
+    // dbt 1.4 switched seeds to `MacroDependsOn`.
+    if let Some(Value::Object(depends_on)) = node.get_mut("depends_on") {
+        depends_on.shift_remove("nodes");
+    }
+}
+
+fn upgrade_node_or_seed(node: &mut Map<String, Value>) {
+    upgrade_node_content(node);
+    if node.get("resource_type").and_then(Value::as_str) == Some("seed") {
+        upgrade_seed_content(node);
+    }
+}
+
+/// Metrics before v10 predate the semantic layer and cannot be converted.
+fn drop_v9_and_prior_metrics(manifest: &mut Value) {
+    manifest["metrics"] = json!({});
+    if let Some(Value::Object(disabled)) = manifest.get_mut("disabled") {
+        for entries in disabled.values_mut() {
+            if let Value::Array(entries) = entries {
+                entries
+                    .retain(|e| e.get("resource_type").and_then(Value::as_str) != Some("metric"));
+            }
+        }
+    }
+}
+
+/// v11 wraps a single `filter` into `{"where_filters": [filter]}`.
+fn convert_filter(item: Option<&mut Value>) {
+    let Some(Value::Object(item)) = item else {
+        return;
+    };
+    if let Some(filter) = item.get_mut("filter").filter(|f| !f.is_null()) {
+        *filter = json!({"where_filters": [filter.take()]});
+    }
+}
+
+fn convert_v10_metric(metric: &mut Value) {
+    convert_filter(Some(metric));
+    let Some(Value::Object(type_params)) = metric.get_mut("type_params") else {
+        return;
+    };
+    for key in ["measure", "numerator", "denominator"] {
+        convert_filter(type_params.get_mut(key));
+    }
+    for key in ["input_measures", "metrics"] {
+        if let Some(Value::Array(items)) = type_params.get_mut(key) {
+            for item in items {
+                convert_filter(Some(item));
+            }
+        }
+    }
+}
+
+fn upgrade_v10_metric_filters(manifest: &mut Value) {
+    if let Some(Value::Object(metrics)) = manifest.get_mut("metrics") {
+        metrics.values_mut().for_each(convert_v10_metric);
+    }
+    if let Some(Value::Object(disabled)) = manifest.get_mut("disabled") {
+        for (unique_id, entries) in disabled.iter_mut() {
+            if unique_id.split('.').next() != Some("metric") {
+                continue;
+            }
+            if let Value::Array(entries) = entries {
+                entries.iter_mut().for_each(convert_v10_metric);
+            }
+        }
+    }
+}
+
+fn ensure_object(manifest: &mut Value, key: &str) {
+    if let Value::Object(map) = manifest {
+        map.entry(key).or_insert_with(|| json!({}));
+    }
+}
+
+/// Upgrade a manifest written with schema `version` in place and mark it as
+/// the current version.
+pub fn upgrade_manifest_json(manifest: &mut Value, version: u32) {
+    if version <= 9 {
+        drop_v9_and_prior_metrics(manifest);
+    } else if version == 10 {
+        upgrade_v10_metric_filters(manifest);
+    }
+
+    objects_mut(manifest, "nodes").for_each(upgrade_node_or_seed);
+    disabled_entries_mut(manifest).for_each(upgrade_node_or_seed);
+    ensure_object(manifest, "groups");
+    ensure_object(manifest, "group_map");
+    ensure_object(manifest, "unit_tests");
+    for key in ["metrics", "exposures"] {
+        for item in objects_mut(manifest, key) {
+            upgrade_ref_content(item);
+            item.shift_remove("root_path");
+        }
+    }
+    for key in ["sources", "macros"] {
+        for item in objects_mut(manifest, key) {
+            item.shift_remove("root_path");
+        }
+    }
+    for doc in objects_mut(manifest, "docs") {
+        doc.shift_remove("root_path");
+        doc.insert("resource_type".to_string(), json!("doc"));
+    }
+    ensure_object(manifest, "semantic_models");
+    ensure_object(manifest, "saved_queries");
+
+    if let Some(Value::Object(metadata)) = manifest.get_mut("metadata") {
+        metadata.insert(
+            "dbt_schema_version".to_string(),
+            json!(schema_url(CURRENT_SCHEMA_VERSION)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::OxideManifest;
+
+    macro_rules! fixture {
+        ($version:literal) => {
+            include_str!(concat!(
+                "../../../tests/functional/artifacts/data/state/v",
+                $version,
+                "/manifest.json"
+            ))
+        };
+    }
+
+    const FIXTURES: [(u32, &str); 9] = [
+        (4, fixture!("4")),
+        (5, fixture!("5")),
+        (6, fixture!("6")),
+        (7, fixture!("7")),
+        (8, fixture!("8")),
+        (9, fixture!("9")),
+        (10, fixture!("10")),
+        (11, fixture!("11")),
+        (12, fixture!("12")),
+    ];
+
+    #[test]
+    fn test_parse_schema_version() {
+        assert_eq!(parse_schema_version(&schema_url(10)), Ok(10));
+        assert!(parse_schema_version("https://example.com/manifest.json").is_err());
+    }
+
+    #[test]
+    fn test_load_every_supported_version() {
+        for (version, json) in FIXTURES {
+            let original: Value = serde_json::from_str(json).unwrap();
+            assert_eq!(
+                schema_version_field(original.pointer("/metadata/dbt_schema_version")).unwrap(),
+                Some(version)
+            );
+            let manifest = OxideManifest::from_json_str(json)
+                .unwrap_or_else(|e| panic!("v{}: {}", version, e));
+
+            assert_eq!(
+                manifest.node_count(),
+                original["nodes"].as_object().unwrap().len(),
+                "v{}",
+                version
+            );
+            let written: Value = serde_json::from_str(&manifest.to_json_string().unwrap()).unwrap();
+            assert_eq!(
+                written["metadata"]["dbt_schema_version"],
+                json!(schema_url(CURRENT_SCHEMA_VERSION))
+            );
+            for node in written["nodes"].as_object().unwrap().values() {
+                assert!(node.get("raw_sql").is_none(), "v{}", version);
+                assert!(node.get("raw_code").is_some(), "v{}", version);
+                if node["resource_type"] == "seed" {
+                    assert!(node["depends_on"].get("nodes").is_none(), "v{}", version);
+                    assert!(node.get("compiled_code").is_none(), "v{}", version);
+                }
+            }
+            for key in ["groups", "unit_tests", "semantic_models", "saved_queries"] {
+                assert!(written[key].is_object(), "v{} {}", version, key);
+            }
+            if version <= 9 {
+                assert_eq!(written["metrics"], json!({}));
+            }
+        }
+    }
+
+    #[test]
+    fn test_current_version_is_untouched() {
+        let json = FIXTURES[8].1;
+        let manifest = OxideManifest::from_json_str(json).unwrap();
+        let written: Value = serde_json::from_str(&manifest.to_json_string().unwrap()).unwrap();
+        assert_eq!(written, serde_json::from_str::<Value>(json).unwrap());
+    }
+
+    #[test]
+    fn test_unsupported_versions() {
+        let old = include_str!("../../../tests/functional/artifacts/data/state/v3/manifest.json");
+        let err = OxideManifest::from_json_str(old).unwrap_err();
+        assert!(err.to_string().contains("v3 is not supported"), "{}", err);
+
+        let future = format!(
+            r#"{{"metadata": {{"dbt_schema_version": "{}"}}}}"#,
+            schema_url(13)
+        );
+        assert!(OxideManifest::from_json_str(&future).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_refs_and_v10_filters() {
+        let mut manifest = json!({
+            "metadata": {"dbt_schema_version": schema_url(10)},
+            "nodes": {
+                "model.p.m": {"resource_type": "model", "raw_sql": "select 1", "root_path": "/p",
+                              "refs": [["a"], ["pkg", "b"]]}
+            },
+            "metrics": {
+                "metric.p.m": {"filter": {"where_sql_template": "x"},
+                               "type_params": {"input_measures": [{"filter": null}, {"filter": {"where_sql_template": "y"}}]}}
+            }
+        });
+        upgrade_manifest_json(&mut manifest, 10);
+
+        let node = &manifest["nodes"]["model.p.m"];
+        assert_eq!(node["raw_code"], json!("select 1"));
+        assert_eq!(node["language"], json!("sql"));
+        assert!(node.get("root_path").is_none());
+        assert_eq!(
+            node["refs"],
+            json!([
+                {"package": null, "name": "a", "version": null},
+                {"package": "pkg", "name": "b", "version": null}
+            ])
+        );
+        let metric = &manifest["metrics"]["metric.p.m"];
+        assert_eq!(
+            metric["filter"],
+            json!({"where_filters": [{"where_sql_template": "x"}]})
+        );
+        assert_eq!(
+            metric["type_params"]["input_measures"],
+            json!([{"filter": null}, {"filter": {"where_filters": [{"where_sql_template": "y"}]}}])
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! MessagePack encoding of JSON values, for the artifacts dbt writes with
+//! `msgpack.packb(..., use_bin_type=True)`.
+//!
+//! dbt's encoder stores `date` and `datetime` values found in untyped fields
+//! as extension types 1 and 2 holding the ISO format. JSON has no room for
+//! them, so an extension value decodes to the single-entry object
+//! `{"__msgpack_ext__": [type, data]}` (`data` a string when it is UTF-8, a
+//! list of bytes otherwise), which `encode` writes back as the extension.
+
+use rmpv::{Integer, Value as MsgpackValue};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+
+pub const EXT_DATE: i8 = 1;
+pub const EXT_DATETIME: i8 = 2;
+const EXT_KEY: &str = "__msgpack_ext__";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgpackError {
+    pub message: String,
+}
+
+impl fmt::Display for MsgpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MsgpackError {}
+
+fn error<T>(message: impl Into<String>) -> Result<T, MsgpackError> {
+    Err(MsgpackError {
+        message: message.into(),
+    })
+}
+
+/// The JSON stand-in for extension value `code` holding `data`.
+pub fn ext_value(code: i8, data: &[u8]) -> Value {
+    let data = match std::str::from_utf8(data) {
+        Ok(s) => Value::String(s.to_string()),
+        Err(_) => Value::Array(data.iter().map(|b| Value::from(*b)).collect()),
+    };
+    let mut map = Map::new();
+    map.insert(
+        EXT_KEY.to_string(),
+        Value::Array(vec![Value::from(code), data]),
+    );
+    Value::Object(map)
+}
+
+/// The type and data of an extension value decoded by `ext_value`.
+pub fn as_ext(value: &Value) -> Option<(i8, Vec<u8>)> {
+    let map = value.as_object().filter(|map| map.len() == 1)?;
+    let [code, data] = map.get(EXT_KEY)?.as_array()?.as_slice() else {
+        return None;
+    };
+    let code = i8::try_from(code.as_i64()?).ok()?;
+    let data = match data {
+        Value::String(s) => s.as_bytes().to_vec(),
+        Value::Array(bytes) => bytes
+            .iter()
+            .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    Some((code, data))
+}
+
+fn to_json(value: MsgpackValue) -> Result<Value, MsgpackError> {
+    Ok(match value {
+        MsgpackValue::Nil => Value::Null,
+        MsgpackValue::Boolean(b) => Value::Bool(b),
+        MsgpackValue::Integer(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => Value::from(u),
+            (None, Some(i)) => Value::from(i),
+            _ => return error(format!("integer {} out of range", n)),
+        },
+        MsgpackValue::F32(f) => float(f as f64)?,
+        MsgpackValue::F64(f) => float(f)?,
+        MsgpackValue::String(s) => match s.into_str() {
+            Some(s) => Value::String(s),
+            None => return error("invalid UTF-8 in string"),
+        },
+        MsgpackValue::Binary(_) => return error("binary values are not supported"),
+        MsgpackValue::Array(items) => {
+            Value::Array(items.into_iter().map(to_json).collect::<Result<_, _>>()?)
+        }
+        MsgpackValue::Map(entries) => {
+            let mut map = Map::new();
+            for (key, item) in entries {
+                let key = match key {
+                    MsgpackValue::String(s) => match s.into_str() {
+                        Some(s) => s,
+                        None => return error("invalid UTF-8 in map key"),
+                    },
+                    MsgpackValue::Integer(n) => n.to_string(),
+                    _ => return error("map keys must be strings or integers"),
+                };
+                map.insert(key, to_json(item)?);
+            }
+            Value::Object(map)
+        }
+        MsgpackValue::Ext(code, data) => ext_value(code, &data),
+    })
+}
+
+fn float(value: f64) -> Result<Value, MsgpackError> {
+    match Number::from_f64(value) {
+        Some(number) => Ok(Value::Number(number)),
+        None => error(format!("{} cannot be represented in JSON", value)),
+    }
+}
+
+fn from_json(value: &Value) -> MsgpackValue {
+    if let Some((code, data)) = as_ext(value) {
+        return MsgpackValue::Ext(code, data);
+    }
+    match value {
+        Value::Null => MsgpackValue::Nil,
+        Value::Bool(b) => MsgpackValue::Boolean(*b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => MsgpackValue::Integer(Integer::from(u)),
+            (None, Some(i)) => MsgpackValue::Integer(Integer::from(i)),
+            _ => MsgpackValue::F64(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => MsgpackValue::from(s.as_str()),
+        Value::Array(items) => MsgpackValue::Array(items.iter().map(from_json).collect()),
+        Value::Object(map) => MsgpackValue::Map(
+            map.iter()
+                .map(|(key, item)| (MsgpackValue::from(key.as_str()), from_json(item)))
+                .collect(),
+        ),
+    }
+}
+
+/// Decode one MessagePack value; trailing bytes are an error.
+pub fn decode(bytes: &[u8]) -> Result<Value, MsgpackError> {
+    let mut rest = bytes;
+    let value = match rmpv::decode::read_value(&mut rest) {
+        Ok(value) => value,
+        Err(e) => return error(e.to_string()),
+    };
+    if !rest.is_empty() {
+        return error(format!(
+            "trailing bytes after value at byte {}",
+            bytes.len() - rest.len()
+        ));
+    }
+    to_json(value)
+}
+
+/// Encode a value using the smallest representation for each element, as
+/// Python's msgpack does.
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    rmpv::encode::write_value(&mut out, &from_json(value)).expect("writing to a Vec cannot fail");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip() {
+        let long = "x".repeat(300);
+        let value = json!({
+            "ints": [0, 127, 128, 255, 256, 65535, 65536, 4294967296u64, -1, -32, -33, -128, -129, -40000, -3000000000i64],
+            "floats": [1.5, -0.25],
+            "strings": ["", "short", long],
+            "nested": {"a": null, "b": true, "c": false, "d": [[], {}]},
+        });
+        assert_eq!(decode(&encode(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn test_python_encoding() {
+        // msgpack.packb({"a": [1, -1, 1.5], "b": None}, use_bin_type=True)
+        let bytes = [
+            0x82, 0xa1, b'a', 0x93, 0x01, 0xff, 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0, 0xa1, b'b',
+            0xc0,
+        ];
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            json!({"a": [1, -1, 1.5], "b": null})
+        );
+        assert_eq!(encode(&json!({"a": [1, -1, 1.5], "b": null})), bytes);
+    }
+
+    #[test]
+    fn test_extension_types() {
+        // msgpack.packb([ExtType(1, b"2024-01-02"), ExtType(2, b"2024-01-02T03:04:05"),
+        //                ExtType(5, b"\xff")], use_bin_type=True)
+        let mut bytes = vec![0x93, 0xc7, 10, 1];
+        bytes.extend_from_slice(b"2024-01-02");
+        bytes.extend_from_slice(&[0xc7, 19, 2]);
+        bytes.extend_from_slice(b"2024-01-02T03:04:05");
+        bytes.extend_from_slice(&[0xd4, 5, 0xff]);
+        let value = decode(&bytes).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"__msgpack_ext__": [1, "2024-01-02"]},
+                {"__msgpack_ext__": [2, "2024-01-02T03:04:05"]},
+                {"__msgpack_ext__": [5, [255]]},
+            ])
+        );
+        assert_eq!(as_ext(&value[0]), Some((EXT_DATE, b"2024-01-02".to_vec())));
+        assert_eq!(as_ext(&json!({"__msgpack_ext__": [1, "x"], "y": 1})), None);
+        assert_eq!(encode(&value), bytes);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(decode(&[0x92, 0x01]).is_err());
+        assert_eq!(
+            decode(&[0xc4, 0x01, 0x00]).unwrap_err().message,
+            "binary values are not supported"
+        );
+        assert_eq!(
+            decode(&[0x01, 0x02]).unwrap_err().message,
+            "trailing bytes after value at byte 1"
+        );
+        assert!(decode(&[0x81, 0xc0, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,808 @@
+//! Construction of manifest nodes and sources from parsed files, replacing
+//! `_create_parsetime_node`, `update_parsed_node_config` and
+//! `SourcePatcher.parse_source`.
+//!
+//! Building happens in two steps, like in dbt: `build_node` and
+//! `build_sources` produce entries with their `refs`/`sources` recorded and
+//! `register_*` adds them to the manifest; once every file is in,
+//! `link_dependencies` fills `depends_on.nodes` by resolving those calls.
+//! Schema, database and alias follow dbt's built-in `generate_*_name`
+//! macros; projects that override them should set the names afterwards.
+
+use crate::config_resolution::{merge_config_dicts, ConfigError, ConfigResolver, ConfigTarget};
+use crate::file_scanner::{file_checksum, is_python_whitespace};
+use crate::lossless;
+use crate::manifest::{OxideManifest, OxideNode, OxideNodeVersion, OxideSource};
+use crate::properties::{ColumnProperties, NodeProperties, SourceProperties};
+use crate::resolution::{Referrer, ResolveContext, ResolveError};
+use crate::static_parser::StaticExtraction;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeBuildError {
+    UnsupportedResourceType(String),
+    Config {
+        unique_id: String,
+        error: ConfigError,
+    },
+    /// The assembled node does not validate, e.g. `enabled` is not a bool.
+    Invalid {
+        unique_id: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for NodeBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeBuildError::UnsupportedResourceType(resource_type) => {
+                write!(f, "Cannot build nodes of type '{}'", resource_type)
+            }
+            NodeBuildError::Config { unique_id, error } => write!(f, "{}: {}", unique_id, error),
+            NodeBuildError::Invalid { unique_id, message } => {
+                write!(f, "Invalid node {}: {}", unique_id, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeBuildError {}
+
+/// Settings shared by every node of a parse.
+#[derive(Debug, Clone, Copy)]
+pub struct BuildContext<'a> {
+    pub resolver: &'a ConfigResolver,
+    /// `target.database` and `target.schema`.
+    pub database: &'a str,
+    pub schema: &'a str,
+    /// Quote character for `relation_name`, e.g. `"` for Postgres. None
+    /// leaves `relation_name` unset for the adapter to fill in.
+    pub quote_char: Option<char>,
+}
+
+/// A file as read by dbt.
+#[derive(Debug, Clone, Copy)]
+pub struct FileInput<'a> {
+    pub package_name: &'a str,
+    /// Path relative to the directory it was found in, e.g.
+    /// `staging/stg_orders.sql`; it decides the fqn.
+    pub path: &'a str,
+    pub original_file_path: &'a str,
+    pub contents: &'a str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NodeInput<'a> {
+    /// `model`, `analysis`, `snapshot`, `seed` or `test` (singular tests).
+    pub resource_type: &'a str,
+    pub file: FileInput<'a>,
+    /// Defaults to the file name without its extension.
+    pub name: Option<&'a str>,
+    pub version: Option<&'a OxideNodeVersion>,
+    /// The node's properties entry, if any.
+    pub properties: Option<&'a NodeProperties>,
+    /// `ref()`, `source()`, `metric()` and `config()` calls of the file.
+    pub calls: &'a StaticExtraction,
+    /// Macros called while rendering, for `depends_on.macros`.
+    pub macros: &'a [String],
+}
+
+/// `os.path.splitext(path)[0]`.
+fn strip_extension(path: &str) -> &str {
+    let file_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[file_start..].rfind('.') {
+        Some(dot) if !path[file_start..][..dot].trim_start_matches('.').is_empty() => {
+            &path[..file_start + dot]
+        }
+        _ => path,
+    }
+}
+
+/// `get_fqn_prefix`: the package followed by the directories of `path`.
+pub fn fqn_prefix(package_name: &str, path: &str) -> Vec<String> {
+    let mut fqn = vec![package_name.to_string()];
+    let parts: Vec<&str> = strip_extension(path).split('/').collect();
+    fqn.extend(parts[..parts.len() - 1].iter().map(|part| part.to_string()));
+    fqn
+}
+
+/// The default `generate_schema_name`.
+pub fn generate_schema_name(default_schema: &str, custom_schema: Option<&str>) -> String {
+    match custom_schema {
+        Some(custom) => format!("{}_{}", default_schema, custom.trim()),
+        None => default_schema.to_string(),
+    }
+}
+
+fn config_str<'a>(config: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    config.get(key).and_then(Value::as_str)
+}
+
+fn relation_name(quote: char, config: &Map<String, Value>, parts: [(&str, &str); 3]) -> String {
+    let quoting = config.get("quoting").and_then(Value::as_object);
+    parts
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(part, value)| {
+            let quoted = quoting
+                .and_then(|quoting| quoting.get(*part))
+                .and_then(Value::as_bool)
+                .unwrap_or(true);
+            if quoted {
+                format!("{}{}{}", quote, value, quote)
+            } else {
+                value.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn columns_json(columns: &[ColumnProperties]) -> Value {
+    let columns: Map<String, Value> = columns
+        .iter()
+        .map(|column| {
+            let mut info = json!({
+                "name": column.name,
+                "description": column.description.clone().unwrap_or_default(),
+                "meta": column.extra.get("meta").cloned().unwrap_or(json!({})),
+                "data_type": column.data_type,
+                "constraints": column.extra.get("constraints").cloned().unwrap_or(json!([])),
+                "quote": column.extra.get("quote").cloned().unwrap_or(Value::Null),
+                "tags": column.extra.get("tags").cloned().unwrap_or(json!([])),
+                "granularity": column.extra.get("granularity").cloned().unwrap_or(Value::Null),
+            });
+            info["config"] = Value::Object(column.config.clone());
+            (column.name.clone(), info)
+        })
+        .collect();
+    Value::Object(columns)
+}
+
+fn version_string(version: &Value) -> String {
+    match version {
+        Value::String(version) => version.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Build a model, analysis, snapshot, seed or singular test node.
+pub fn build_node(ctx: BuildContext, input: NodeInput) -> Result<OxideNode, NodeBuildError> {
+    let resource_type = input.resource_type;
+    if !matches!(
+        resource_type,
+        "model" | "analysis" | "snapshot" | "seed" | "test"
+    ) {
+        return Err(NodeBuildError::UnsupportedResourceType(
+            resource_type.to_string(),
+        ));
+    }
+    let file = input.file;
+    let file_name = file.path.rsplit('/').next().unwrap_or(file.path);
+    let name = input
+        .name
+        .unwrap_or_else(|| strip_extension(file_name))
+        .to_string();
+    let version = input.version.map(|version| version.to_string());
+
+    let mut unique_id = format!("{}.{}.{}", resource_type, file.package_name, name);
+    let mut fqn = fqn_prefix(file.package_name, file.path);
+    fqn.push(name.clone());
+    if let Some(version) = &version {
+        unique_id.push_str(&format!(".v{}", version));
+        fqn.push(format!("v{}", version));
+    }
+    let config_error = |error| NodeBuildError::Config {
+        unique_id: unique_id.clone(),
+        error,
+    };
+
+    // Config calls are merged one option at a time, so repeated `tags`
+    // accumulate as they do across several `config()` calls.
+    let mut config_call = Map::new();
+    for (key, value) in &input.calls.configs {
+        let call = Map::from_iter([(key.clone(), value.clone())]);
+        merge_config_dicts(resource_type, &mut config_call, &call).map_err(config_error)?;
+    }
+    let language = if file.path.ends_with(".py") {
+        "python"
+    } else {
+        "sql"
+    };
+    let mut patch_config = input
+        .properties
+        .map(|properties| properties.config.clone())
+        .unwrap_or_default();
+    if resource_type == "model" && language == "python" {
+        patch_config.entry("materialized").or_insert(json!("table"));
+    }
+    let target = ConfigTarget {
+        package_name: file.package_name,
+        resource_type,
+        fqn: &fqn,
+    };
+    let resolved = ctx
+        .resolver
+        .resolve(target, Some(&patch_config), Some(&config_call))
+        .map_err(config_error)?;
+    let config = resolved.config;
+
+    let database = config_str(&config, "database")
+        .unwrap_or(ctx.database)
+        .to_string();
+    let schema = generate_schema_name(ctx.schema, config_str(&config, "schema"));
+    let alias = match (config_str(&config, "alias"), &version) {
+        (Some(alias), _) => alias.to_string(),
+        (None, Some(version)) => format!("{}_v{}", name, version),
+        (None, None) => name.clone(),
+    };
+    let (database, schema) = match resource_type {
+        // Snapshots keep their legacy target_database/target_schema.
+        "snapshot" => (
+            config_str(&config, "target_database")
+                .filter(|value| !value.is_empty())
+                .map_or(database, str::to_string),
+            config_str(&config, "target_schema")
+                .filter(|value| !value.is_empty())
+                .map_or(schema, str::to_string),
+        ),
+        _ => (database, schema),
+    };
+
+    let mut tags: Vec<Value> = Vec::new();
+    for tag in config
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    let refs: Vec<Value> = input
+        .calls
+        .refs
+        .iter()
+        .map(|r| json!({"name": r.name, "package": r.package, "version": r.version}))
+        .collect();
+    let Ok(checksum) = file_checksum(
+        resource_type,
+        file.original_file_path,
+        file.contents.len() as u64,
+        || Ok::<_, Infallible>(file.contents),
+    );
+    let raw_code = if resource_type == "seed" {
+        ""
+    } else {
+        file.contents.trim_matches(is_python_whitespace)
+    };
+
+    let mut node = json!({
+        "unique_id": unique_id,
+        "name": name,
+        "resource_type": resource_type,
+        "package_name": file.package_name,
+        "path": file.path,
+        "original_file_path": file.original_file_path,
+        "fqn": fqn,
+        "alias": alias,
+        "schema": schema,
+        "database": database,
+        "checksum": checksum,
+        "tags": tags,
+        "meta": config.get("meta").cloned().unwrap_or(json!({})),
+        "description": input
+            .properties
+            .and_then(|properties| properties.description.clone())
+            .unwrap_or_default(),
+        "columns": columns_json(input.properties.map_or(&[], |p| &p.columns)),
+        "raw_code": raw_code,
+        "language": language,
+        "refs": refs,
+        "sources": input.calls.sources,
+        "metrics": input.calls.metrics,
+        "depends_on": {"macros": input.macros, "nodes": []},
+        "config_call_dict": config_call,
+        "relation_name": null,
+    });
+    if let Some(docs) = config.get("docs") {
+        node["docs"] = docs.clone();
+    }
+    if let Some(group) = config.get("group").filter(|group| !group.is_null()) {
+        node["group"] = group.clone();
+    }
+    if resource_type == "model" {
+        node["access"] = config.get("access").cloned().unwrap_or(json!("protected"));
+        node["contract"] = config.get("contract").cloned().unwrap_or(json!({}));
+        node["version"] = json!(input.version);
+        node["latest_version"] = json!(input
+            .properties
+            .and_then(|properties| properties.latest_version.clone()));
+    }
+    let relational = match resource_type {
+        "model" => config_str(&config, "materialized") != Some("ephemeral"),
+        "seed" | "snapshot" => true,
+        _ => false,
+    };
+    if let (Some(quote), true) = (ctx.quote_char, relational) {
+        node["relation_name"] = json!(relation_name(
+            quote,
+            &config,
+            [
+                ("database", &database),
+                ("schema", &schema),
+                ("identifier", &alias),
+            ],
+        ));
+    }
+    node["config"] = Value::Object(config);
+
+    let unique_id = node["unique_id"].as_str().unwrap_or_default().to_string();
+    if let Some(access) = node.get("access").and_then(Value::as_str) {
+        if !matches!(access, "private" | "protected" | "public") {
+            return Err(NodeBuildError::Invalid {
+                unique_id,
+                message: format!("Invalid access type '{}'", access),
+            });
+        }
+    }
+    lossless::from_value(node).map_err(|e| NodeBuildError::Invalid {
+        unique_id,
+        message: e.to_string(),
+    })
+}
+
+/// Build the sources of one `sources:` entry of a properties file, one per
+/// table.
+pub fn build_sources(
+    ctx: BuildContext,
+    file: FileInput,
+    source: &SourceProperties,
+) -> Result<Vec<OxideSource>, NodeBuildError> {
+    let mut built = Vec::new();
+    for table in &source.tables {
+        let unique_id = format!(
+            "source.{}.{}.{}",
+            file.package_name, source.name, table.name
+        );
+        let mut fqn = fqn_prefix(file.package_name, file.path);
+        fqn.extend([source.name.clone(), table.name.clone()]);
+
+        // Table config wins over source config, key by key.
+        let mut patch_config = source.config.clone();
+        patch_config.extend(table.config.clone());
+        let target = ConfigTarget {
+            package_name: file.package_name,
+            resource_type: "source",
+            fqn: &fqn,
+        };
+        let config = ctx
+            .resolver
+            .resolve(target, Some(&patch_config), None)
+            .map_err(|error| NodeBuildError::Config {
+                unique_id: unique_id.clone(),
+                error,
+            })?
+            .config;
+
+        let mut quoting = source
+            .extra
+            .get("quoting")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(Value::Object(table_quoting)) = table.extra.get("quoting") {
+            quoting.extend(
+                table_quoting
+                    .iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
+        let mut source_meta = source
+            .extra
+            .get("meta")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(Value::Object(meta)) = source.config.get("meta") {
+            source_meta.extend(meta.clone());
+        }
+        let database = source
+            .database
+            .clone()
+            .unwrap_or_else(|| ctx.database.to_string());
+        let schema = source.schema.clone().unwrap_or_else(|| source.name.clone());
+        let identifier = table
+            .identifier
+            .clone()
+            .unwrap_or_else(|| table.name.clone());
+        let relation = ctx.quote_char.map(|quote| {
+            let quoting = Map::from_iter([("quoting".to_string(), Value::Object(quoting.clone()))]);
+            relation_name(
+                quote,
+                &quoting,
+                [
+                    ("database", &database),
+                    ("schema", &schema),
+                    ("identifier", &identifier),
+                ],
+            )
+        });
+
+        let value = json!({
+            "unique_id": unique_id,
+            "name": table.name,
+            "source_name": source.name,
+            "resource_type": "source",
+            "package_name": file.package_name,
+            "path": file.original_file_path,
+            "original_file_path": file.original_file_path,
+            "fqn": fqn,
+            "database": database,
+            "schema": schema,
+            "identifier": identifier,
+            "description": table.description.clone().unwrap_or_default(),
+            "source_description": source.description.clone().unwrap_or_default(),
+            "columns": columns_json(&table.columns),
+            "loader": source.extra.get("loader").cloned().unwrap_or(json!("")),
+            "quoting": quoting,
+            "external": table.extra.get("external").cloned().unwrap_or(Value::Null),
+            "source_meta": source_meta,
+            "meta": config.get("meta").cloned().unwrap_or(json!({})),
+            "tags": config.get("tags").cloned().unwrap_or(json!([])),
+            "loaded_at_field": config.get("loaded_at_field").cloned().unwrap_or(Value::Null),
+            "loaded_at_query": config.get("loaded_at_query").cloned().unwrap_or(Value::Null),
+            "freshness": config.get("freshness").cloned().unwrap_or(Value::Null),
+            "config": config,
+            "relation_name": relation,
+        });
+        built.push(
+            lossless::from_value(value).map_err(|e| NodeBuildError::Invalid {
+                unique_id,
+                message: e.to_string(),
+            })?,
+        );
+    }
+    Ok(built)
+}
+
+impl OxideManifest {
+    /// Add a built node; disabled nodes go to `disabled`.
+    pub fn register_node(&mut self, node: OxideNode) {
+        if node.config.enabled {
+            self.nodes.insert(node.unique_id.clone(), node);
+        } else {
+            self.disabled_mut()
+                .entry(node.unique_id.clone())
+                .or_default()
+                .push(node);
+        }
+        self.invalidate_indexes();
+    }
+
+    /// Add a built source; disabled sources go to `disabled`.
+    pub fn register_source(&mut self, source: OxideSource) -> Result<(), serde_json::Error> {
+        if source.config.enabled {
+            self.sources.insert(source.unique_id.clone(), source);
+        } else {
+            let entry = lossless::from_value(lossless::to_value(&source)?)?;
+            self.disabled_mut()
+                .entry(source.unique_id.clone())
+                .or_default()
+                .push(entry);
+        }
+        self.invalidate_indexes();
+        Ok(())
+    }
+}
+
+/// A `ref()` or `source()` that could not be linked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkError {
+    pub unique_id: String,
+    pub error: ResolveError,
+}
+
+/// The `depends_on.nodes` of `node`: its sources, then its refs, in call
+/// order without duplicates, as `process_sources` and `process_refs` add
+/// them.
+fn node_dependencies(
+    manifest: &OxideManifest,
+    node: &OxideNode,
+    ctx: ResolveContext,
+) -> (Vec<String>, Vec<ResolveError>) {
+    let mut nodes = Vec::new();
+    let mut errors = Vec::new();
+    let mut push = |result: Result<String, ResolveError>| match result {
+        Ok(unique_id) if !nodes.contains(&unique_id) => nodes.push(unique_id),
+        Ok(_) => {}
+        Err(error) => errors.push(error),
+    };
+    let sources = node.extra.get("sources").and_then(Value::as_array);
+    for source in sources.into_iter().flatten() {
+        if let Some([Value::String(source_name), Value::String(table_name)]) =
+            source.as_array().map(Vec::as_slice)
+        {
+            push(manifest.resolve_source(
+                source_name,
+                table_name,
+                &node.package_name,
+                ctx.current_project,
+            ));
+        }
+    }
+    let referrer = Referrer {
+        package_name: &node.package_name,
+        resource_type: &node.resource_type,
+        group: node.group.as_deref(),
+    };
+    let refs = node.extra.get("refs").and_then(Value::as_array);
+    for reference in refs.into_iter().flatten() {
+        let Some(name) = reference.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let package = reference.get("package").and_then(Value::as_str);
+        let version = reference
+            .get("version")
+            .filter(|version| !version.is_null())
+            .map(version_string);
+        push(manifest.resolve_ref(referrer, name, package, version.as_deref(), ctx));
+    }
+    (nodes, errors)
+}
+
+/// Fill `depends_on.nodes` of the given nodes (default: all) from their
+/// `sources` and `refs`. Returns the calls that did not resolve; the
+/// dependencies that did are still recorded.
+pub fn link_dependencies(
+    manifest: &mut OxideManifest,
+    unique_ids: Option<&[String]>,
+    current_project: &str,
+    restricted_packages: &HashSet<String>,
+) -> Vec<LinkError> {
+    let ctx = ResolveContext {
+        current_project,
+        restricted_packages,
+    };
+    let mut ids: Vec<String> = match unique_ids {
+        Some(ids) => ids.to_vec(),
+        None => manifest.nodes.keys().cloned().collect(),
+    };
+    ids.sort();
+    let mut updates = Vec::new();
+    let mut errors = Vec::new();
+    for unique_id in ids {
+        let Some(node) = manifest.nodes.get(&unique_id) else {
+            continue;
+        };
+        let (nodes, failed) = node_dependencies(manifest, node, ctx);
+        errors.extend(failed.into_iter().map(|error| LinkError {
+            unique_id: unique_id.clone(),
+            error,
+        }));
+        updates.push((unique_id, nodes));
+    }
+    for (unique_id, nodes) in updates {
+        if let Some(node) = manifest.nodes.get_mut(&unique_id) {
+            node.depends_on.nodes = nodes;
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::static_parser::extract_static_calls;
+
+    fn resolver() -> ConfigResolver {
+        let mut resolver = ConfigResolver::new("shop");
+        let project = json!({
+            "name": "shop",
+            "models": {"shop": {"marts": {"+materialized": "table", "+schema": "marts"}}},
+            "sources": {"+tags": ["raw"]},
+        });
+        let Value::Object(project) = project else {
+            unreachable!()
+        };
+        resolver.add_project("shop", project);
+        resolver
+    }
+
+    fn context(resolver: &ConfigResolver) -> BuildContext<'_> {
+        BuildContext {
+            resolver,
+            database: "analytics",
+            schema: "dbt_me",
+            quote_char: Some('"'),
+        }
+    }
+
+    #[test]
+    fn test_helpers_match_dbt() {
+        assert_eq!(
+            fqn_prefix("shop", "marts/core/orders.sql"),
+            vec!["shop", "marts", "core"]
+        );
+        assert_eq!(fqn_prefix("shop", "orders.sql"), vec!["shop"]);
+        assert_eq!(strip_extension("a.b/.hidden"), "a.b/.hidden");
+        assert_eq!(strip_extension("a/b.tar.gz"), "a/b.tar");
+        assert_eq!(generate_schema_name("dbt", Some(" marts ")), "dbt_marts");
+    }
+
+    #[test]
+    fn test_build_register_and_link() {
+        let resolver = resolver();
+        let ctx = context(&resolver);
+        let mut manifest = OxideManifest::from_json_str("{}").unwrap();
+
+        let yaml = "sources:\n  - name: raw\n    tables:\n      - name: orders\n        identifier: orders_v2\n";
+        let properties =
+            crate::properties::parse_properties("models/sources.yml", yaml, false).unwrap();
+        let file = FileInput {
+            package_name: "shop",
+            path: "sources.yml",
+            original_file_path: "models/sources.yml",
+            contents: yaml,
+        };
+        for source in build_sources(ctx, file, &properties.sources[0].value).unwrap() {
+            assert_eq!(source.unique_id, "source.shop.raw.orders");
+            assert_eq!(source.schema.as_deref(), Some("raw"));
+            assert_eq!(source.extra["fqn"], json!(["shop", "raw", "orders"]));
+            assert_eq!(source.extra["tags"], json!(["raw"]));
+            assert_eq!(
+                source.extra["relation_name"],
+                json!("\"analytics\".\"raw\".\"orders_v2\"")
+            );
+            manifest.register_source(source).unwrap();
+        }
+
+        let sql = "{{ config(tags=['daily']) }}\nselect * from {{ source('raw', 'orders') }}";
+        let calls = extract_static_calls(sql);
+        let staging = build_node(
+            ctx,
+            NodeInput {
+                resource_type: "model",
+                file: FileInput {
+                    package_name: "shop",
+                    path: "staging/stg_orders.sql",
+                    original_file_path: "models/staging/stg_orders.sql",
+                    contents: sql,
+                },
+                name: None,
+                version: None,
+                properties: None,
+                calls: &calls,
+                macros: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(staging.unique_id, "model.shop.stg_orders");
+        assert_eq!(staging.fqn, vec!["shop", "staging", "stg_orders"]);
+        assert_eq!(staging.extra["tags"], json!(["daily"]));
+        assert_eq!(staging.extra["schema"], json!("dbt_me"));
+        manifest.register_node(staging);
+
+        let sql = "select * from {{ ref('stg_orders') }} join {{ ref('stg_orders') }}";
+        let calls = extract_static_calls(sql);
+        let properties = crate::properties::parse_properties(
+            "models/marts/schema.yml",
+            "models:\n  - name: orders\n    description: Orders\n    columns:\n      - name: id\n",
+            false,
+        )
+        .unwrap();
+        let version = OxideNodeVersion::Int(2);
+        let orders = build_node(
+            ctx,
+            NodeInput {
+                resource_type: "model",
+                file: FileInput {
+                    package_name: "shop",
+                    path: "marts/orders_v2.sql",
+                    original_file_path: "models/marts/orders_v2.sql",
+                    contents: sql,
+                },
+                name: Some("orders"),
+                version: Some(&version),
+                properties: Some(&properties.models[0].value),
+                calls: &calls,
+                macros: &["macro.dbt.run_query".to_string()],
+            },
+        )
+        .unwrap();
+        assert_eq!(orders.unique_id, "model.shop.orders.v2");
+        assert_eq!(orders.fqn, vec!["shop", "marts", "orders", "v2"]);
+        assert_eq!(orders.config.materialized.as_deref(), Some("table"));
+        assert_eq!(orders.extra["alias"], json!("orders_v2"));
+        assert_eq!(orders.extra["schema"], json!("dbt_me_marts"));
+        assert_eq!(orders.extra["description"], json!("Orders"));
+        assert_eq!(orders.extra["columns"]["id"]["name"], json!("id"));
+        assert_eq!(
+            orders.extra["relation_name"],
+            json!("\"analytics\".\"dbt_me_marts\".\"orders_v2\"")
+        );
+        assert_eq!(orders.depends_on.macros, vec!["macro.dbt.run_query"]);
+        manifest.register_node(orders);
+
+        let errors = link_dependencies(&mut manifest, None, "shop", &HashSet::new());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            manifest.nodes["model.shop.orders.v2"].depends_on.nodes,
+            vec!["model.shop.stg_orders"]
+        );
+        assert_eq!(
+            manifest.nodes["model.shop.stg_orders"].depends_on.nodes,
+            vec!["source.shop.raw.orders"]
+        );
+
+        let calls = extract_static_calls("select * from {{ ref('missing') }}");
+        let broken = build_node(
+            ctx,
+            NodeInput {
+                resource_type: "analysis",
+                file: FileInput {
+                    package_name: "shop",
+                    path: "broken.sql",
+                    original_file_path: "analyses/broken.sql",
+                    contents: "",
+                },
+                name: None,
+                version: None,
+                properties: None,
+                calls: &calls,
+                macros: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(broken.extra["relation_name"], Value::Null);
+        manifest.register_node(broken);
+        let errors = link_dependencies(
+            &mut manifest,
+            Some(&["analysis.shop.broken".to_string()]),
+            "shop",
+            &HashSet::new(),
+        );
+        assert_eq!(errors[0].error.kind(), "not_found");
+    }
+
+    #[test]
+    fn test_invalid_nodes_are_rejected() {
+        let resolver = resolver();
+        let ctx = context(&resolver);
+        let calls = StaticExtraction {
+            configs: vec![("enabled".to_string(), json!("yes"))],
+            ..Default::default()
+        };
+        let input = NodeInput {
+            resource_type: "model",
+            file: FileInput {
+                package_name: "shop",
+                path: "a.sql",
+                original_file_path: "models/a.sql",
+                contents: "select 1",
+            },
+            name: None,
+            version: None,
+            properties: None,
+            calls: &calls,
+            macros: &[],
+        };
+        assert!(matches!(
+            build_node(ctx, input),
+            Err(NodeBuildError::Invalid { .. })
+        ));
+        let input = NodeInput {
+            resource_type: "exposure",
+            ..input
+        };
+        assert!(matches!(
+            build_node(ctx, input),
+            Err(NodeBuildError::UnsupportedResourceType(_))
+        ));
+    }
+}
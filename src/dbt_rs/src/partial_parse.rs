@@ -0,0 +1,315 @@
+//! Reading and writing dbt's `target/partial_parse.msgpack`.
+//!
+//! The file holds the msgpack-encoded `Manifest` dataclass: the collections
+//! of manifest.json plus the parse state (`files`, `state_check`,
+//! `env_vars`, ...), which is kept in `OxideManifest::extra` and exposed
+//! through `file_hashes` and `state_check`.
+
+use crate::manifest::OxideManifest;
+use crate::manifest_errors::ManifestParseError;
+use crate::manifest_upgrade::{parse_schema_version, CURRENT_SCHEMA_VERSION};
+use crate::msgpack::{self, MsgpackError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum PartialParseError {
+    Io(io::Error),
+    Msgpack(MsgpackError),
+    Manifest(ManifestParseError),
+}
+
+impl fmt::Display for PartialParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartialParseError::Io(e) => write!(f, "{}", e),
+            PartialParseError::Msgpack(e) => write!(f, "invalid msgpack: {}", e),
+            PartialParseError::Manifest(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PartialParseError {}
+
+impl From<io::Error> for PartialParseError {
+    fn from(e: io::Error) -> Self {
+        PartialParseError::Io(e)
+    }
+}
+
+impl From<MsgpackError> for PartialParseError {
+    fn from(e: MsgpackError) -> Self {
+        PartialParseError::Msgpack(e)
+    }
+}
+
+impl From<ManifestParseError> for PartialParseError {
+    fn from(e: ManifestParseError) -> Self {
+        PartialParseError::Manifest(e)
+    }
+}
+
+/// dbt's `FileHash`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct FileHash {
+    pub name: String,
+    pub checksum: String,
+}
+
+impl FileHash {
+    /// `FileHash.from_contents`: the sha256 of the contents.
+    pub fn from_contents(contents: &str) -> Self {
+        FileHash {
+            name: "sha256".to_string(),
+            checksum: format!("{:x}", Sha256::digest(contents.as_bytes())),
+        }
+    }
+}
+
+/// dbt's `ManifestStateCheck`: the hashes compared to decide whether the
+/// saved state can be reused at all.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct StateCheck {
+    #[serde(default)]
+    pub vars_hash: FileHash,
+    #[serde(default)]
+    pub project_env_vars_hash: FileHash,
+    #[serde(default)]
+    pub profile_env_vars_hash: FileHash,
+    #[serde(default)]
+    pub profile_hash: FileHash,
+    #[serde(default)]
+    pub project_hashes: BTreeMap<String, FileHash>,
+}
+
+#[derive(Deserialize)]
+struct FilePathProbe {
+    #[serde(default)]
+    searched_path: String,
+    #[serde(default)]
+    relative_path: String,
+}
+
+#[derive(Deserialize)]
+struct SourceFileProbe {
+    path: FilePathProbe,
+    checksum: FileHash,
+    #[serde(default)]
+    project_name: Option<String>,
+    #[serde(default)]
+    parse_file_type: Option<String>,
+}
+
+/// The hash dbt recorded for one parsed file, keyed by file_id
+/// (`project://path`) in `file_hashes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFileHash {
+    pub project_name: Option<String>,
+    pub original_file_path: String,
+    pub parse_file_type: Option<String>,
+    pub checksum: FileHash,
+}
+
+/// `os.path.join(searched_path, relative_path)`, which dbt uses as a file's
+/// `original_file_path`.
+pub(crate) fn join_path(searched_path: &str, relative_path: &str) -> String {
+    if searched_path.is_empty() || relative_path.starts_with('/') {
+        relative_path.to_string()
+    } else if searched_path.ends_with('/') {
+        format!("{}{}", searched_path, relative_path)
+    } else {
+        format!("{}/{}", searched_path, relative_path)
+    }
+}
+
+/// Build a manifest from the decoded partial-parse state. Saved state from a
+/// different schema version is rejected rather than upgraded: dbt reparses
+/// from scratch in that case.
+fn manifest_from_value(value: &Value) -> Result<OxideManifest, ManifestParseError> {
+    if let Some(url) = value
+        .pointer("/metadata/dbt_schema_version")
+        .and_then(Value::as_str)
+    {
+        let version = parse_schema_version(url)?;
+        if version != CURRENT_SCHEMA_VERSION {
+            return Err(ManifestParseError::from_version_error(format!(
+                "Saved partial parse state has schema v{}, expected v{}",
+                version, CURRENT_SCHEMA_VERSION
+            )));
+        }
+    }
+    OxideManifest::deserialize(value).map_err(|e| ManifestParseError::from_value_error(value, e))
+}
+
+#[allow(dead_code)]
+impl OxideManifest {
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, PartialParseError> {
+        let value = msgpack::decode(bytes)?;
+        Ok(manifest_from_value(&value)?)
+    }
+
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, serde_json::Error> {
+        Ok(msgpack::encode(&serde_json::to_value(self)?))
+    }
+
+    pub fn read_partial_parse(path: &Path) -> Result<Self, PartialParseError> {
+        OxideManifest::from_msgpack(&fs::read(path)?)
+    }
+
+    pub fn write_partial_parse(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_msgpack()?)
+    }
+
+    /// Hashes of the files the saved state was parsed from, keyed by
+    /// file_id. Empty for a manifest loaded from manifest.json.
+    pub fn file_hashes(&self) -> Result<BTreeMap<String, SourceFileHash>, serde_json::Error> {
+        let Some(files) = self.extra.get("files") else {
+            return Ok(BTreeMap::new());
+        };
+        BTreeMap::<String, SourceFileProbe>::deserialize(files).map(|files| {
+            files
+                .into_iter()
+                .map(|(file_id, file)| {
+                    let hash = SourceFileHash {
+                        project_name: file.project_name,
+                        original_file_path: join_path(
+                            &file.path.searched_path,
+                            &file.path.relative_path,
+                        ),
+                        parse_file_type: file.parse_file_type,
+                        checksum: file.checksum,
+                    };
+                    (file_id, hash)
+                })
+                .collect()
+        })
+    }
+
+    /// The saved `state_check`, if the manifest has one.
+    pub fn state_check(&self) -> Result<Option<StateCheck>, serde_json::Error> {
+        self.extra
+            .get("state_check")
+            .map(StateCheck::deserialize)
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest_upgrade::schema_url;
+    use serde_json::json;
+
+    fn saved_state() -> Value {
+        json!({
+            "nodes": {
+                "model.p.a": {"unique_id": "model.p.a", "name": "a", "resource_type": "model",
+                              "package_name": "p", "config_call_dict": {}},
+                "model.p.b": {"unique_id": "model.p.b", "name": "b", "resource_type": "model",
+                              "package_name": "p", "config_call_dict": {},
+                              "depends_on": {"nodes": ["model.p.a"], "macros": []}}
+            },
+            "metadata": {"dbt_schema_version": schema_url(CURRENT_SCHEMA_VERSION), "dbt_version": "1.10.0"},
+            "files": {
+                "p://models/a.sql": {
+                    "path": {"searched_path": "models", "relative_path": "a.sql",
+                             "modification_time": 0.0, "project_root": "/p"},
+                    "checksum": {"name": "sha256", "checksum": "abc"},
+                    "project_name": "p",
+                    "parse_file_type": "model",
+                    "nodes": ["model.p.a"]
+                }
+            },
+            "state_check": {
+                "vars_hash": {"name": "sha256", "checksum": "v"},
+                "project_hashes": {"p": {"name": "sha256", "checksum": "h"}}
+            },
+            "sources": {}, "macros": {}, "exposures": {}, "metrics": {}, "groups": {},
+            "semantic_models": {}, "saved_queries": {}, "unit_tests": {}, "disabled": {},
+            "env_vars": {},
+            "_doc_lookup": null
+        })
+    }
+
+    /// The document `bytes` decode to. Key order is left to the serializer,
+    /// so round trips are compared as values rather than bytes.
+    fn decoded(bytes: &[u8]) -> Value {
+        msgpack::decode(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_read_write_round_trip() {
+        let bytes = msgpack::encode(&saved_state());
+        let manifest = OxideManifest::from_msgpack(&bytes).unwrap();
+        assert_eq!(manifest.node_count(), 2);
+        assert_eq!(decoded(&manifest.to_msgpack().unwrap()), decoded(&bytes));
+
+        let path = std::env::temp_dir()
+            .join(format!("dbt_rs_pp_{}", std::process::id()))
+            .join("partial_parse.msgpack");
+        manifest.write_partial_parse(&path).unwrap();
+        let reread = OxideManifest::read_partial_parse(&path).unwrap();
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(
+            serde_json::to_value(&reread).unwrap(),
+            serde_json::to_value(&manifest).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip_keeps_dates() {
+        let mut state = saved_state();
+        state["nodes"]["model.p.a"]["meta"] = json!({
+            "since": msgpack::ext_value(msgpack::EXT_DATE, b"2024-01-02"),
+            "at": msgpack::ext_value(msgpack::EXT_DATETIME, b"2024-01-02T03:04:05"),
+        });
+        let bytes = msgpack::encode(&state);
+        assert!(bytes
+            .windows(13)
+            .any(|w| w == [&[0xc7, 10, 1][..], b"2024-01-02"].concat()));
+        let manifest = OxideManifest::from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded(&manifest.to_msgpack().unwrap()), decoded(&bytes));
+    }
+
+    #[test]
+    fn test_file_hashes_and_state_check() {
+        let manifest = OxideManifest::from_msgpack(&msgpack::encode(&saved_state())).unwrap();
+        let hashes = manifest.file_hashes().unwrap();
+        let file = &hashes["p://models/a.sql"];
+        assert_eq!(file.original_file_path, "models/a.sql");
+        assert_eq!(file.parse_file_type.as_deref(), Some("model"));
+        assert_eq!(file.checksum.checksum, "abc");
+
+        let state = manifest.state_check().unwrap().unwrap();
+        assert_eq!(state.vars_hash.checksum, "v");
+        assert_eq!(state.project_hashes["p"].checksum, "h");
+        assert_eq!(state.profile_hash, FileHash::default());
+    }
+
+    #[test]
+    fn test_rejects_other_versions_and_bad_input() {
+        let mut state = saved_state();
+        state["metadata"]["dbt_schema_version"] = json!(schema_url(11));
+        let err = OxideManifest::from_msgpack(&msgpack::encode(&state)).unwrap_err();
+        assert!(matches!(err, PartialParseError::Manifest(_)), "{}", err);
+
+        let err = OxideManifest::from_msgpack(&[0x81, 0xa5]).unwrap_err();
+        assert!(matches!(err, PartialParseError::Msgpack(_)), "{}", err);
+
+        state = saved_state();
+        state["nodes"]["model.p.a"]["name"] = json!(5);
+        match OxideManifest::from_msgpack(&msgpack::encode(&state)).unwrap_err() {
+            PartialParseError::Manifest(e) => assert_eq!(e.pointer(), "/nodes/model.p.a/name"),
+            other => panic!("{}", other),
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use crate::graph::{DenseView, OxideGraph};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// One CI shard: its nodes in a valid execution order and their total weight.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Shard {
+    pub nodes: Vec<String>,
+    pub weight: f64,
+}
+
+/// Result of splitting a graph into dependency-respecting shards.
+///
+/// Cross-shard edges always point from a lower to a higher shard index, so
+/// running shards in index order (or waiting on `shard_dependencies`) is safe.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphPartition {
+    pub shards: Vec<Shard>,
+    pub cross_shard_edges: Vec<(String, String)>,
+    pub shard_dependencies: Vec<(usize, usize)>,
+}
+
+impl GraphPartition {
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+impl OxideGraph {
+    /// Split `selected` (or the whole graph) into `shard_count` shards.
+    ///
+    /// Weakly connected components are bin-packed first; components heavier
+    /// than one shard are cut along a depth-first topological order at the
+    /// points crossed by the fewest edges. Nodes missing from `weights` get the
+    /// mean of the known weights (or 1.0 when no weights are given).
+    pub fn partition(
+        &self,
+        selected: Option<&HashSet<String>>,
+        shard_count: usize,
+        weights: &HashMap<String, f64>,
+    ) -> Result<GraphPartition, String> {
+        if shard_count == 0 {
+            return Err("shard_count must be at least 1".to_string());
+        }
+
+        let view = match selected {
+            Some(nodes) => self.get_subset_graph(nodes).dense_view(false),
+            None => self.dense_view(false),
+        };
+        if view.topological_order().is_none() {
+            return Err("Cycle detected in graph".to_string());
+        }
+
+        let known: Vec<f64> = view
+            .ids
+            .iter()
+            .filter_map(|id| weights.get(id).copied())
+            .collect();
+        let default_weight = if known.is_empty() {
+            1.0
+        } else {
+            known.iter().sum::<f64>() / known.len() as f64
+        };
+        let node_weight: Vec<f64> = view
+            .ids
+            .iter()
+            .map(|id| weights.get(id).copied().unwrap_or(default_weight).max(0.0))
+            .collect();
+
+        let total: f64 = node_weight.iter().sum();
+        let target = total / shard_count as f64;
+
+        let mut components = weakly_connected_components(&view);
+        let component_weight = |c: &Vec<usize>| -> f64 { c.iter().map(|&i| node_weight[i]).sum() };
+        components.sort_by(|a, b| {
+            component_weight(b)
+                .total_cmp(&component_weight(a))
+                .then(a[0].cmp(&b[0]))
+        });
+
+        let mut assignment = vec![0usize; view.len()];
+        let mut loads = vec![0f64; shard_count];
+
+        for component in &components {
+            let weight = component_weight(component);
+            let pieces = if target > 0.0 {
+                ((weight / target).ceil() as usize).clamp(1, shard_count.min(component.len()))
+            } else {
+                1
+            };
+
+            if pieces == 1 {
+                let shard = lightest_shards(&loads, 1)[0];
+                for &node in component {
+                    assignment[node] = shard;
+                }
+                loads[shard] += weight;
+                continue;
+            }
+
+            // Segments go to shards in increasing index order, so every
+            // cross-shard edge points from a lower to a higher index.
+            let mut shards = lightest_shards(&loads, pieces);
+            shards.sort_unstable();
+            let segments = split_component(&view, component, &node_weight, pieces);
+            for (segment, shard) in segments.iter().zip(shards) {
+                for &node in segment {
+                    assignment[node] = shard;
+                    loads[shard] += node_weight[node];
+                }
+            }
+        }
+
+        Ok(build_partition(
+            &view,
+            &assignment,
+            &node_weight,
+            shard_count,
+        ))
+    }
+}
+
+fn lightest_shards(loads: &[f64], count: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..loads.len()).collect();
+    order.sort_by(|a, b| loads[*a].total_cmp(&loads[*b]).then(a.cmp(b)));
+    order.truncate(count);
+    order
+}
+
+fn weakly_connected_components(view: &DenseView) -> Vec<Vec<usize>> {
+    let mut component = vec![usize::MAX; view.len()];
+    let mut components = Vec::new();
+
+    for start in 0..view.len() {
+        if component[start] != usize::MAX {
+            continue;
+        }
+        let id = components.len();
+        let mut members = Vec::new();
+        let mut stack = vec![start];
+        component[start] = id;
+        while let Some(node) = stack.pop() {
+            members.push(node);
+            for &next in view.children[node].iter().chain(&view.parents[node]) {
+                if component[next] == usize::MAX {
+                    component[next] = id;
+                    stack.push(next);
+                }
+            }
+        }
+        members.sort_unstable();
+        components.push(members);
+    }
+    components
+}
+
+/// Topological order of one component that releases children depth-first,
+/// keeping lineage chains adjacent so contiguous cuts cross few edges.
+fn depth_first_order(view: &DenseView, component: &[usize]) -> Vec<usize> {
+    let mut in_degree: HashMap<usize, usize> = component
+        .iter()
+        .map(|&n| (n, view.parents[n].len()))
+        .collect();
+    let mut stack: Vec<usize> = component
+        .iter()
+        .rev()
+        .copied()
+        .filter(|n| in_degree[n] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(component.len());
+
+    while let Some(node) = stack.pop() {
+        order.push(node);
+        for &child in view.children[node].iter().rev() {
+            let degree = in_degree.get_mut(&child).expect("child in component");
+            *degree -= 1;
+            if *degree == 0 {
+                stack.push(child);
+            }
+        }
+    }
+    order
+}
+
+/// Cut a component into `pieces` contiguous segments of its topological
+/// order. Each boundary is placed near its ideal weight split, at the position
+/// crossed by the fewest edges.
+fn split_component(
+    view: &DenseView,
+    component: &[usize],
+    node_weight: &[f64],
+    pieces: usize,
+) -> Vec<Vec<usize>> {
+    let order = depth_first_order(view, component);
+    let n = order.len();
+    let position: HashMap<usize, usize> = order.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+
+    // crossing[p] = number of edges (u, v) with pos(u) < p <= pos(v)
+    let mut diff = vec![0i64; n + 1];
+    for &u in &order {
+        for &v in &view.children[u] {
+            diff[position[&u] + 1] += 1;
+            diff[position[&v] + 1] -= 1;
+        }
+    }
+    let mut crossing = vec![0i64; n + 1];
+    let mut running = 0;
+    for p in 0..=n {
+        running += diff[p];
+        crossing[p] = running;
+    }
+
+    let mut prefix = vec![0f64; n + 1];
+    for (i, &v) in order.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + node_weight[v];
+    }
+    let total = prefix[n];
+    let window = total / pieces as f64 * 0.1;
+
+    let mut boundaries = Vec::with_capacity(pieces - 1);
+    let mut last = 0;
+    for k in 1..pieces {
+        let ideal = total * k as f64 / pieces as f64;
+        let remaining = pieces - k;
+        let candidates = (last + 1)..=(n - remaining);
+        let best = candidates
+            .filter(|&p| (prefix[p] - ideal).abs() <= window)
+            .min_by(|&a, &b| {
+                crossing[a].cmp(&crossing[b]).then(
+                    (prefix[a] - ideal)
+                        .abs()
+                        .total_cmp(&(prefix[b] - ideal).abs()),
+                )
+            })
+            .unwrap_or_else(|| {
+                ((last + 1)..=(n - remaining))
+                    .min_by(|&a, &b| {
+                        (prefix[a] - ideal)
+                            .abs()
+                            .total_cmp(&(prefix[b] - ideal).abs())
+                    })
+                    .unwrap_or(last + 1)
+            });
+        boundaries.push(best);
+        last = best;
+    }
+
+    let mut segments = Vec::with_capacity(pieces);
+    let mut start = 0;
+    for end in boundaries.into_iter().chain(std::iter::once(n)) {
+        segments.push(order[start..end].to_vec());
+        start = end;
+    }
+    segments
+}
+
+fn build_partition(
+    view: &DenseView,
+    assignment: &[usize],
+    node_weight: &[f64],
+    shard_count: usize,
+) -> GraphPartition {
+    let order = view.topological_order().unwrap_or_default();
+    let mut shards = vec![
+        Shard {
+            nodes: Vec::new(),
+            weight: 0.0,
+        };
+        shard_count
+    ];
+    for &node in &order {
+        let shard = &mut shards[assignment[node]];
+        shard.nodes.push(view.ids[node].clone());
+        shard.weight += node_weight[node];
+    }
+
+    let mut cross_shard_edges = Vec::new();
+    let mut shard_dependencies = HashSet::new();
+    for (source, children) in view.children.iter().enumerate() {
+        for &target in children {
+            let (from, to) = (assignment[source], assignment[target]);
+            if from != to {
+                cross_shard_edges.push((view.ids[source].clone(), view.ids[target].clone()));
+                shard_dependencies.insert((from, to));
+            }
+        }
+    }
+    cross_shard_edges.sort();
+    let mut shard_dependencies: Vec<(usize, usize)> = shard_dependencies.into_iter().collect();
+    shard_dependencies.sort_unstable();
+
+    GraphPartition {
+        shards,
+        cross_shard_edges,
+        shard_dependencies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard_of(partition: &GraphPartition, id: &str) -> usize {
+        partition
+            .shards
+            .iter()
+            .position(|s| s.nodes.iter().any(|n| n == id))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_partition_components_are_kept_whole() {
+        let mut g = OxideGraph::new();
+        g.add_edge("A", "B", None).unwrap();
+        g.add_edge("C", "D", None).unwrap();
+
+        let partition = g.partition(None, 2, &HashMap::new()).unwrap();
+        assert!(partition.cross_shard_edges.is_empty());
+        assert_eq!(shard_of(&partition, "A"), shard_of(&partition, "B"));
+        assert_eq!(shard_of(&partition, "C"), shard_of(&partition, "D"));
+        assert_ne!(shard_of(&partition, "A"), shard_of(&partition, "C"));
+    }
+
+    #[test]
+    fn test_partition_splits_large_component_forward_only() {
+        let mut g = OxideGraph::new();
+        // Two chains joined at the bottom: the cheapest cut is the join edge.
+        g.add_edge("a1", "a2", None).unwrap();
+        g.add_edge("a2", "a3", None).unwrap();
+        g.add_edge("b1", "b2", None).unwrap();
+        g.add_edge("b2", "b3", None).unwrap();
+        g.add_edge("a3", "z", None).unwrap();
+        g.add_edge("b3", "z", None).unwrap();
+
+        let partition = g.partition(None, 2, &HashMap::new()).unwrap();
+        assert_eq!(partition.shards.len(), 2);
+        assert!(partition.cross_shard_edges.len() <= 2);
+        for (source, target) in &partition.cross_shard_edges {
+            assert!(shard_of(&partition, source) < shard_of(&partition, target));
+        }
+        assert!(partition.shard_dependencies.iter().all(|(a, b)| a < b));
+        let total: usize = partition.shards.iter().map(|s| s.nodes.len()).sum();
+        assert_eq!(total, 7);
+    }
+
+    #[test]
+    fn test_partition_uses_weights() {
+        let mut g = OxideGraph::new();
+        for id in ["A", "B", "C"] {
+            g.add_node(id.to_string());
+        }
+        let weights: HashMap<String, f64> = [
+            ("A".to_string(), 10.0),
+            ("B".to_string(), 1.0),
+            ("C".to_string(), 1.0),
+        ]
+        .into_iter()
+        .collect();
+
+        let partition = g.partition(None, 2, &weights).unwrap();
+        assert_ne!(shard_of(&partition, "A"), shard_of(&partition, "B"));
+        assert_eq!(shard_of(&partition, "B"), shard_of(&partition, "C"));
+        assert_eq!(partition.shards[shard_of(&partition, "A")].weight, 10.0);
+    }
+
+    #[test]
+    fn test_partition_selected_preserves_transitive_order() {
+        let mut g = OxideGraph::new();
+        g.add_edge("A", "B", None).unwrap();
+        g.add_edge("B", "C", None).unwrap();
+
+        let selected: HashSet<String> = ["A".to_string(), "C".to_string()].into_iter().collect();
+        let partition = g.partition(Some(&selected), 2, &HashMap::new()).unwrap();
+        let total: usize = partition.shards.iter().map(|s| s.nodes.len()).sum();
+        assert_eq!(total, 2);
+        // A -> C is kept through the removed B, so they form one component.
+        assert_eq!(
+            partition.cross_shard_edges,
+            vec![("A".to_string(), "C".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_partition_heavy_single_node() {
+        let mut g = OxideGraph::new();
+        g.add_node("A".to_string());
+        g.add_node("B".to_string());
+        let weights: HashMap<String, f64> = [("A".to_string(), 100.0)].into_iter().collect();
+
+        let partition = g.partition(None, 3, &weights).unwrap();
+        assert_eq!(partition.shards.len(), 3);
+        assert_ne!(shard_of(&partition, "A"), shard_of(&partition, "B"));
+    }
+
+    #[test]
+    fn test_partition_rejects_zero_shards_and_cycles() {
+        let mut g = OxideGraph::new();
+        g.add_edge("A", "B", None).unwrap();
+        assert!(g.partition(None, 0, &HashMap::new()).is_err());
+
+        g.add_edge("B", "A", None).unwrap();
+        assert!(g.partition(None, 2, &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,557 @@
+//! Parsing of YAML properties files (`schema.yml` and friends) into typed
+//! structures, in parallel. Each file can be checked against the bundled
+//! properties schema (`core/dbt/jsonschemas/resources/latest.json`); like
+//! dbt, schema violations are reported as warnings, while YAML syntax
+//! errors and entries that cannot be read at all are errors. Every entry,
+//! warning and error carries the line it comes from.
+
+use crate::file_scanner::thread_pool;
+use crate::python_repr::python_type_name;
+use crate::schema_validation::{compile_schema, validate_with};
+use crate::yaml_loader::{escape_pointer, load_yaml, LoadedYaml, Location};
+use jsonschema::Validator;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+static PROPERTIES_SCHEMA: Lazy<Validator> = Lazy::new(|| {
+    let schema: Value = serde_json::from_str(include_str!(
+        "../../../core/dbt/jsonschemas/resources/latest.json"
+    ))
+    .expect("bundled properties schema is valid JSON");
+    compile_schema(&schema).expect("bundled properties schema compiles")
+});
+
+/// A missing key and an explicit `null` both mean the default.
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ColumnProperties {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub data_type: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(default, deserialize_with = "nullable", alias = "tests")]
+    pub data_tests: Vec<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An entry under `models`, `seeds`, `snapshots` or `analyses`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NodeProperties {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub columns: Vec<ColumnProperties>,
+    #[serde(default, deserialize_with = "nullable", alias = "tests")]
+    pub data_tests: Vec<Value>,
+    /// Model versions, kept as written.
+    #[serde(default, deserialize_with = "nullable")]
+    pub versions: Vec<Value>,
+    #[serde(default)]
+    pub latest_version: Option<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SourceTableProperties {
+    pub name: String,
+    #[serde(default)]
+    pub identifier: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub columns: Vec<ColumnProperties>,
+    #[serde(default, deserialize_with = "nullable", alias = "tests")]
+    pub data_tests: Vec<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SourceProperties {
+    pub name: String,
+    #[serde(default)]
+    pub database: Option<String>,
+    #[serde(default)]
+    pub schema: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub tables: Vec<SourceTableProperties>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExposureProperties {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub exposure_type: String,
+    pub owner: Value,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// `ref()`/`source()`/`metric()` calls, as written.
+    #[serde(default, deserialize_with = "nullable")]
+    pub depends_on: Vec<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MetricProperties {
+    pub name: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(rename = "type")]
+    pub metric_type: String,
+    #[serde(default)]
+    pub type_params: Value,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SemanticModelProperties {
+    pub name: String,
+    /// A `ref()` call, as written.
+    pub model: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub entities: Vec<Value>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub dimensions: Vec<Value>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub measures: Vec<Value>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SavedQueryProperties {
+    pub name: String,
+    pub query_params: Value,
+    #[serde(default, deserialize_with = "nullable")]
+    pub exports: Vec<Value>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UnitTestProperties {
+    pub name: String,
+    pub model: String,
+    #[serde(default, deserialize_with = "nullable")]
+    pub given: Vec<Value>,
+    pub expect: Value,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub config: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An entry together with where it starts in its file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Located<T> {
+    pub location: Location,
+    pub value: T,
+}
+
+/// A properties schema violation, reported as a warning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertiesWarning {
+    /// JSON pointer to the offending value, e.g. `/models/0/columns`.
+    pub path: String,
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PropertiesFile {
+    pub path: String,
+    pub version: Option<Value>,
+    pub models: Vec<Located<NodeProperties>>,
+    pub seeds: Vec<Located<NodeProperties>>,
+    pub snapshots: Vec<Located<NodeProperties>>,
+    pub analyses: Vec<Located<NodeProperties>>,
+    pub sources: Vec<Located<SourceProperties>>,
+    pub exposures: Vec<Located<ExposureProperties>>,
+    pub metrics: Vec<Located<MetricProperties>>,
+    pub semantic_models: Vec<Located<SemanticModelProperties>>,
+    pub saved_queries: Vec<Located<SavedQueryProperties>>,
+    pub unit_tests: Vec<Located<UnitTestProperties>>,
+    /// Top-level keys not modelled above (`groups`, `macros`, ...).
+    pub extra: Map<String, Value>,
+    pub warnings: Vec<PropertiesWarning>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertiesError {
+    pub path: String,
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl fmt::Display for PropertiesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some(location) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path, location.line, location.column, self.message
+            ),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl std::error::Error for PropertiesError {}
+
+/// A file to parse; `contents` is read from `path` when None.
+#[derive(Debug, Clone)]
+pub struct PropertiesInput {
+    pub path: String,
+    pub contents: Option<String>,
+}
+
+struct FileParser<'a> {
+    path: &'a str,
+    loaded: &'a LoadedYaml,
+    root: &'a Map<String, Value>,
+}
+
+impl FileParser<'_> {
+    fn error(&self, pointer: &str, message: String) -> PropertiesError {
+        PropertiesError {
+            path: self.path.to_string(),
+            location: self.loaded.location(pointer),
+            message,
+        }
+    }
+
+    fn entries<T: DeserializeOwned>(&self, key: &str) -> Result<Vec<Located<T>>, PropertiesError> {
+        let items = match self.root.get(key) {
+            None | Some(Value::Null) => return Ok(Vec::new()),
+            Some(Value::Array(items)) => items,
+            Some(other) => {
+                return Err(self.error(
+                    &format!("/{}", key),
+                    format!(
+                        "{} must be a list, got {} instead",
+                        key,
+                        python_type_name(other)
+                    ),
+                ))
+            }
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let pointer = format!("/{}/{}", key, index);
+                if !item.is_object() {
+                    return Err(self.error(
+                        &pointer,
+                        format!("Entry in {} must be a dict with string keys", key),
+                    ));
+                }
+                let value = serde_path_to_error::deserialize(item).map_err(|e| {
+                    let mut at = pointer.clone();
+                    for segment in e.path().iter() {
+                        match segment {
+                            serde_path_to_error::Segment::Seq { index } => {
+                                at.push_str(&format!("/{}", index))
+                            }
+                            serde_path_to_error::Segment::Map { key } => {
+                                at.push('/');
+                                at.push_str(&escape_pointer(key));
+                            }
+                            _ => {}
+                        }
+                    }
+                    self.error(&at, format!("{}: {}", key, e.inner()))
+                })?;
+                Ok(Located {
+                    location: self.loaded.location(&pointer).unwrap_or_default(),
+                    value,
+                })
+            })
+            .collect()
+    }
+}
+
+const MODELLED_KEYS: [&str; 11] = [
+    "version",
+    "models",
+    "seeds",
+    "snapshots",
+    "analyses",
+    "sources",
+    "exposures",
+    "metrics",
+    "semantic_models",
+    "saved_queries",
+    "unit_tests",
+];
+
+/// Parse one properties file. An empty file yields an empty result.
+pub fn parse_properties(
+    path: &str,
+    contents: &str,
+    validate: bool,
+) -> Result<PropertiesFile, PropertiesError> {
+    let loaded = load_yaml(contents).map_err(|e| PropertiesError {
+        path: path.to_string(),
+        location: Some(e.location),
+        message: e.message,
+    })?;
+    let root = match &loaded.value {
+        None => {
+            return Ok(PropertiesFile {
+                path: path.to_string(),
+                ..Default::default()
+            })
+        }
+        Some(Value::Object(root)) => root,
+        Some(other) => {
+            return Err(PropertiesError {
+                path: path.to_string(),
+                location: loaded.location(""),
+                message: format!(
+                    "properties file must be a dict, got {}",
+                    python_type_name(other)
+                ),
+            })
+        }
+    };
+    let parser = FileParser {
+        path,
+        loaded: &loaded,
+        root,
+    };
+    let warnings = if validate {
+        let document = loaded.value.as_ref().unwrap_or(&Value::Null);
+        validate_with(&PROPERTIES_SCHEMA, document)
+            .into_iter()
+            .map(|violation| PropertiesWarning {
+                location: loaded.location(&violation.path),
+                path: violation.path,
+                message: violation.message,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Ok(PropertiesFile {
+        path: path.to_string(),
+        version: root.get("version").cloned(),
+        models: parser.entries("models")?,
+        seeds: parser.entries("seeds")?,
+        snapshots: parser.entries("snapshots")?,
+        analyses: parser.entries("analyses")?,
+        sources: parser.entries("sources")?,
+        exposures: parser.entries("exposures")?,
+        metrics: parser.entries("metrics")?,
+        semantic_models: parser.entries("semantic_models")?,
+        saved_queries: parser.entries("saved_queries")?,
+        unit_tests: parser.entries("unit_tests")?,
+        extra: root
+            .iter()
+            .filter(|(key, _)| !MODELLED_KEYS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+        warnings,
+    })
+}
+
+/// Parse many properties files on up to `threads` threads, keeping input
+/// order. One file failing does not stop the others.
+pub fn parse_properties_files(
+    inputs: &[PropertiesInput],
+    threads: usize,
+    validate: bool,
+) -> Vec<Result<PropertiesFile, PropertiesError>> {
+    thread_pool(threads).install(|| {
+        inputs
+            .par_iter()
+            .map(|input| match &input.contents {
+                Some(contents) => parse_properties(&input.path, contents, validate),
+                None => std::fs::read_to_string(&input.path)
+                    .map_err(|e| PropertiesError {
+                        path: input.path.clone(),
+                        location: None,
+                        message: e.to_string(),
+                    })
+                    .and_then(|contents| parse_properties(&input.path, &contents, validate)),
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCHEMA_YML: &str = "\
+version: 2
+
+models:
+  - name: orders
+    description: All orders
+    config:
+      materialized: table
+    columns:
+      - name: id
+        tests: [unique, not_null]
+      - name: status
+        data_type: text
+
+sources:
+  - name: raw
+    schema: raw_data
+    tables:
+      - name: payments
+        identifier: stripe_payments
+
+exposures:
+  - name: dashboard
+    type: dashboard
+    owner: {email: a@b.c}
+    depends_on: [\"ref('orders')\"]
+
+unit_tests:
+  - name: test_orders
+    model: orders
+    given: []
+    expect: {rows: []}
+";
+
+    #[test]
+    fn test_parse_properties() {
+        let file = parse_properties("models/schema.yml", SCHEMA_YML, true).unwrap();
+        assert_eq!(file.version, Some(json!(2)));
+        assert!(file.warnings.is_empty(), "{:?}", file.warnings);
+
+        let orders = &file.models[0];
+        assert_eq!(orders.location.line, 4);
+        assert_eq!(orders.value.name, "orders");
+        assert_eq!(orders.value.config["materialized"], json!("table"));
+        assert_eq!(
+            orders.value.columns[0].data_tests,
+            vec![json!("unique"), json!("not_null")]
+        );
+        assert_eq!(orders.value.columns[1].data_type.as_deref(), Some("text"));
+
+        let raw = &file.sources[0];
+        assert_eq!(raw.location.line, 15);
+        assert_eq!(raw.value.schema.as_deref(), Some("raw_data"));
+        assert_eq!(
+            raw.value.tables[0].identifier.as_deref(),
+            Some("stripe_payments")
+        );
+        assert_eq!(file.exposures[0].value.depends_on, vec!["ref('orders')"]);
+        assert_eq!(file.unit_tests[0].value.model, "orders");
+        assert_eq!(file.unit_tests[0].location.line, 28);
+    }
+
+    #[test]
+    fn test_schema_violations_are_warnings_with_lines() {
+        let source = "\
+version: 2
+models:
+  - name: orders
+    colums:
+      - name: id
+";
+        let file = parse_properties("schema.yml", source, true).unwrap();
+        assert_eq!(
+            file.models[0].value.extra["colums"],
+            json!([{"name": "id"}])
+        );
+        assert_eq!(file.warnings.len(), 1);
+        assert_eq!(file.warnings[0].path, "/models/0/colums");
+        assert_eq!(file.warnings[0].location.map(|l| l.line), Some(4));
+        assert!(parse_properties("schema.yml", source, false)
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn test_errors_point_at_the_offending_line() {
+        let err = parse_properties(
+            "schema.yml",
+            "models:\n  - name: a\n    columns:\n      - description: x\n",
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err.location.map(|l| l.line), Some(4));
+        assert!(err.message.contains("missing field `name`"), "{}", err);
+
+        let err = parse_properties("schema.yml", "models:\n  name: a\n", false).unwrap_err();
+        assert_eq!(err.message, "models must be a list, got dict instead");
+        assert_eq!(err.location.map(|l| l.line), Some(1));
+
+        let err = parse_properties("schema.yml", "models: [\n", false).unwrap_err();
+        assert!(err.location.is_some());
+
+        let results = parse_properties_files(
+            &[
+                PropertiesInput {
+                    path: "a.yml".to_string(),
+                    contents: Some("models:\n  - name: a\n".to_string()),
+                },
+                PropertiesInput {
+                    path: "missing.yml".to_string(),
+                    contents: None,
+                },
+                PropertiesInput {
+                    path: "empty.yml".to_string(),
+                    contents: Some(String::new()),
+                },
+            ],
+            2,
+            true,
+        );
+        assert_eq!(results[0].as_ref().unwrap().models[0].value.name, "a");
+        assert_eq!(results[1].as_ref().unwrap_err().path, "missing.yml");
+        assert!(results[2].as_ref().unwrap().models.is_empty());
+    }
+}
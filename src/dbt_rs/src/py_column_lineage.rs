@@ -0,0 +1,112 @@
+use crate::column_lineage::{ColumnEdge, ColumnLineage};
+use crate::py_manifest::{read_manifest, DbtManifest};
+use crate::sql_analysis::Dialect;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+fn edges_to_list(py: Python, edges: Vec<ColumnEdge>) -> PyResult<PyObject> {
+    let rows = PyList::empty(py);
+    for edge in edges {
+        let row = PyDict::new(py);
+        row.set_item("source_unique_id", edge.source.unique_id)?;
+        row.set_item("source_column", edge.source.column)?;
+        row.set_item("target_unique_id", edge.target.unique_id)?;
+        row.set_item("target_column", edge.target.column)?;
+        row.set_item("kind", edge.kind.as_str())?;
+        rows.append(row)?;
+    }
+    Ok(rows.into())
+}
+
+/// Column-level lineage built by `build_column_lineage`. Column names are
+/// lowercased; `kind` is one of `passthrough`, `rename`, `expression` or
+/// `aggregate`.
+#[pyclass]
+pub struct DbtColumnLineage {
+    inner: ColumnLineage,
+}
+
+#[pymethods]
+impl DbtColumnLineage {
+    /// Output columns of a node: `[{"name", "sources": [{"unique_id",
+    /// "column", "kind"}]}]`, or None if the node has no compiled code.
+    pub fn columns(&self, py: Python, unique_id: &str) -> PyResult<Option<PyObject>> {
+        let Some(node) = self.inner.nodes.get(unique_id) else {
+            return Ok(None);
+        };
+        let rows = PyList::empty(py);
+        for column in &node.columns {
+            let sources = PyList::empty(py);
+            for source in &column.sources {
+                let item = PyDict::new(py);
+                item.set_item("unique_id", &source.column.unique_id)?;
+                item.set_item("column", &source.column.column)?;
+                item.set_item("kind", source.kind.as_str())?;
+                sources.append(item)?;
+            }
+            let row = PyDict::new(py);
+            row.set_item("name", &column.name)?;
+            row.set_item("sources", sources)?;
+            rows.append(row)?;
+        }
+        Ok(Some(rows.into()))
+    }
+
+    /// Whether every column of the node could be traced.
+    pub fn is_complete(&self, unique_id: &str) -> Option<bool> {
+        self.inner.nodes.get(unique_id).map(|node| node.complete)
+    }
+
+    /// Where a column comes from: the edges on all its upstream paths, as
+    /// dicts with `source_unique_id`, `source_column`, `target_unique_id`,
+    /// `target_column` and `kind`.
+    pub fn upstream(&self, py: Python, unique_id: &str, column: &str) -> PyResult<PyObject> {
+        edges_to_list(py, self.inner.upstream(unique_id, column))
+    }
+
+    /// What reads a column, e.g. what breaks if it is dropped: the edges on
+    /// all its downstream paths.
+    pub fn downstream(&self, py: Python, unique_id: &str, column: &str) -> PyResult<PyObject> {
+        edges_to_list(py, self.inner.downstream(unique_id, column))
+    }
+
+    /// Every edge of the column graph.
+    pub fn edges(&self, py: Python) -> PyResult<PyObject> {
+        edges_to_list(py, self.inner.edges())
+    }
+
+    /// `{"nodes": {unique_id: {"columns": [...], "complete": bool}}}` as JSON.
+    pub fn to_json(&self) -> PyResult<String> {
+        self.inner
+            .to_json()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// Trace the columns of every node with `compiled_code` to the upstream
+/// columns they read. `dialect` defaults to `metadata.adapter_type`.
+#[pyfunction]
+#[pyo3(signature = (manifest, dialect=None))]
+pub fn build_column_lineage(
+    py: Python,
+    manifest: &DbtManifest,
+    dialect: Option<&str>,
+) -> PyResult<DbtColumnLineage> {
+    let dialect: Option<Dialect> = dialect
+        .map(|dialect| dialect.parse())
+        .transpose()
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let manifest = read_manifest(manifest.shared())?;
+    let dialect = dialect.unwrap_or_else(|| {
+        let adapter_type = manifest.metadata.as_ref().map(|m| m.adapter_type.as_str());
+        Dialect::for_adapter(adapter_type.unwrap_or_default())
+    });
+    let inner = py.allow_threads(|| ColumnLineage::build(&manifest, dialect));
+    Ok(DbtColumnLineage { inner })
+}
+
+pub fn register_column_lineage_module(m: &PyModule) -> PyResult<()> {
+    m.add_class::<DbtColumnLineage>()?;
+    m.add_function(wrap_pyfunction!(build_column_lineage, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// pyo3 0.20 expands the `#[new]` text signature of the `#[pymethods]` block below
+// into an impl nested in a generated function, out of reach of an `allow` on the
+// block itself.
+#![allow(non_local_definitions)]
+
+use crate::config_resolution::{merge_config_dicts, ConfigResolver, ConfigTarget};
+use crate::py_manifest::{py_to_value, value_to_py};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use serde_json::{Map, Value};
+
+fn dict_to_map(dict: &PyDict, what: &str) -> PyResult<Map<String, Value>> {
+    match py_to_value(dict)? {
+        Value::Object(map) => Ok(map),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} must be a dict",
+            what
+        ))),
+    }
+}
+
+/// Resolves node configs from `dbt_project.yml`, properties and `config()`
+/// calls with dbt's precedence and merge rules.
+#[pyclass]
+pub struct DbtConfigResolver {
+    inner: ConfigResolver,
+}
+
+impl DbtConfigResolver {
+    pub(crate) fn inner(&self) -> &ConfigResolver {
+        &self.inner
+    }
+}
+
+#[pymethods]
+impl DbtConfigResolver {
+    /// `projects` maps each project name, the root project's included, to
+    /// its rendered `dbt_project.yml` dict.
+    #[new]
+    pub fn new(root_project: &str, projects: &PyDict) -> PyResult<Self> {
+        let mut inner = ConfigResolver::new(root_project);
+        for (name, project) in projects.iter() {
+            let name: &str = name.extract()?;
+            inner.add_project(name, dict_to_map(project.downcast()?, name)?);
+        }
+        Ok(DbtConfigResolver { inner })
+    }
+
+    /// The merged config of a node, as `calculate_node_config_dict` builds
+    /// it. `properties` is the `config:` of its properties entry and
+    /// `config_call` the options of its `config()` calls: one dict, or a list
+    /// of them merged in order.
+    ///
+    /// With `with_origins`, returns `(config, origins)` where `origins` maps
+    /// each key to the layers that set it, e.g. `["default", "jaffle
+    /// dbt_project.yml at jaffle.staging", "properties", "config()"]`.
+    /// Raises `ValueError` for an unknown package or a dict config given a
+    /// non-dict.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (package_name, resource_type, fqn, properties=None, config_call=None, with_origins=false))]
+    pub fn resolve(
+        &self,
+        py: Python,
+        package_name: &str,
+        resource_type: &str,
+        fqn: Vec<String>,
+        properties: Option<&PyDict>,
+        config_call: Option<&PyAny>,
+        with_origins: bool,
+    ) -> PyResult<PyObject> {
+        let properties = properties
+            .map(|dict| dict_to_map(dict, "properties"))
+            .transpose()?;
+        let config_call = match config_call {
+            None => None,
+            Some(calls) if calls.is_instance_of::<PyList>() => {
+                let mut merged = Map::new();
+                for call in calls.downcast::<PyList>()? {
+                    let call = dict_to_map(call.downcast()?, "config_call")?;
+                    merge_config_dicts(resource_type, &mut merged, &call)
+                        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+                }
+                Some(merged)
+            }
+            Some(call) => Some(dict_to_map(call.downcast()?, "config_call")?),
+        };
+        let target = ConfigTarget {
+            package_name,
+            resource_type,
+            fqn: &fqn,
+        };
+        let resolved = self
+            .inner
+            .resolve(target, properties.as_ref(), config_call.as_ref())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let config = value_to_py(py, &Value::Object(resolved.config))?;
+        if !with_origins {
+            return Ok(config);
+        }
+        let origins = PyDict::new(py);
+        for (key, layers) in resolved.origins {
+            let layers: Vec<String> = layers.iter().map(|layer| layer.to_string()).collect();
+            origins.set_item(key, layers)?;
+        }
+        Ok(PyTuple::new(py, [config, origins.into()]).into())
+    }
+}
+
+pub fn register_config_resolution_module(m: &PyModule) -> PyResult<()> {
+    m.add_class::<DbtConfigResolver>()?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use crate::cte_injection::{self, ephemeral_parents, CompiledSql, EphemeralModel, InjectedCte};
+use crate::py_graph::DbtGraph;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{BTreeMap, HashMap};
+
+fn ctes_to_list(py: Python, ctes: &[InjectedCte]) -> PyResult<Vec<PyObject>> {
+    ctes.iter()
+        .map(|cte| {
+            let row = PyDict::new(py);
+            row.set_item("id", &cte.id)?;
+            row.set_item("sql", &cte.sql)?;
+            Ok(row.into())
+        })
+        .collect()
+}
+
+/// Splice CTEs into compiled SQL, as `dbt.compilation.inject_ctes_into_sql`.
+/// `ctes` is a list of `(id, sql)` pairs.
+#[pyfunction]
+pub fn inject_ctes_into_sql(sql: &str, ctes: Vec<(String, String)>) -> String {
+    let ctes: Vec<InjectedCte> = ctes
+        .into_iter()
+        .map(|(id, sql)| InjectedCte { id, sql })
+        .collect();
+    cte_injection::inject_ctes_into_sql(sql, &ctes)
+}
+
+/// Inject the CTEs of ephemeral ancestors into compiled SQL, as
+/// `Compiler._recursively_prepend_ctes`.
+///
+/// `ephemeral` maps each ephemeral model's unique_id to `(identifier,
+/// compiled_code)`, its SQL before injection, and `compiled` maps the nodes
+/// to inject into to their `compiled_code`. The ephemeral models a node refs
+/// are its ephemeral parents in `graph`, by unique_id. dbt prepends them in
+/// `ref()` call order, which the graph does not record: to reproduce dbt's
+/// SQL exactly, pass each node's refs in call order (the `ephemeral_refs`
+/// returned by `compile_node_sql`) in `ephemeral_refs`.
+///
+/// Returns `{unique_id: {"compiled_code", "extra_ctes": [{"id", "sql"}]}}`.
+#[pyfunction]
+#[pyo3(signature = (graph, ephemeral, compiled, ephemeral_refs=None))]
+pub fn inject_ephemeral_ctes(
+    py: Python,
+    graph: &DbtGraph,
+    ephemeral: HashMap<String, (String, String)>,
+    compiled: BTreeMap<String, String>,
+    ephemeral_refs: Option<HashMap<String, Vec<String>>>,
+) -> PyResult<PyObject> {
+    let ephemeral_refs = ephemeral_refs.unwrap_or_default();
+    let graph = graph.oxide_graph();
+    let refs = |unique_id: &str| {
+        ephemeral_refs
+            .get(unique_id)
+            .cloned()
+            .unwrap_or_else(|| ephemeral_parents(graph, |id| ephemeral.contains_key(id), unique_id))
+    };
+    let models: HashMap<String, EphemeralModel> = ephemeral
+        .iter()
+        .map(|(unique_id, (identifier, compiled_code))| {
+            let model = EphemeralModel {
+                identifier: identifier.clone(),
+                compiled: CompiledSql {
+                    compiled_code: compiled_code.clone(),
+                    ephemeral_refs: refs(unique_id),
+                },
+            };
+            (unique_id.clone(), model)
+        })
+        .collect();
+    let compiled: BTreeMap<String, CompiledSql> = compiled
+        .into_iter()
+        .map(|(unique_id, compiled_code)| {
+            let node = CompiledSql {
+                compiled_code,
+                ephemeral_refs: refs(&unique_id),
+            };
+            (unique_id, node)
+        })
+        .collect();
+    let injected = py
+        .allow_threads(|| cte_injection::inject_ephemeral_ctes(&models, &compiled))
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+    let result = PyDict::new(py);
+    for (unique_id, node) in injected {
+        let row = PyDict::new(py);
+        row.set_item("compiled_code", node.compiled_code)?;
+        row.set_item("extra_ctes", ctes_to_list(py, &node.extra_ctes)?)?;
+        result.set_item(unique_id, row)?;
+    }
+    Ok(result.into())
+}
+
+pub fn register_cte_injection_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(inject_ctes_into_sql, m)?)?;
+    m.add_function(wrap_pyfunction!(inject_ephemeral_ctes, m)?)?;
+    Ok(())
+}
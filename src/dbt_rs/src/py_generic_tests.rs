@@ -0,0 +1,167 @@
+use crate::generic_tests::{
+    build_generic_tests as build, GenericTestContext, GenericTestInput, TestTarget,
+};
+use crate::lossless;
+use crate::macro_resolution::MacroResolveContext;
+use crate::manifest::OxideNode;
+use crate::node_construction::{link_dependencies, BuildContext};
+use crate::properties::{NodeProperties, SourceProperties};
+use crate::py_config_resolution::DbtConfigResolver;
+use crate::py_manifest::{py_to_value, read_manifest, value_to_py, write_lock, DbtManifest};
+use crate::py_node_construction::from_properties_dict;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Build the generic tests declared in one properties entry, as dbt's
+/// `SchemaGenericTestParser` does: `yaml_key` is `models`, `seeds`,
+/// `snapshots`, `analyses` or `sources`, and `entry` the entry from
+/// `parse_properties_files` (for sources, the tests of all its tables).
+///
+/// `render_context` holds the values that Jinja in test configs may use.
+/// With `register`, the tests are added to `manifest` and their
+/// `depends_on.nodes` linked. Returns `{"nodes": [...], "link_errors":
+/// [{"unique_id", "kind", "message"}]}`; raises `ValueError` for an invalid
+/// test definition.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (
+    manifest, resolver, entry, yaml_key, package_name, original_file_path, *,
+    database, schema, root_project, internal_packages=None, quote_char=None,
+    render_context=None, require_arguments_property=true, restricted_packages=None,
+    register=true
+))]
+pub fn build_generic_tests(
+    py: Python,
+    manifest: &DbtManifest,
+    resolver: &DbtConfigResolver,
+    entry: &PyDict,
+    yaml_key: &str,
+    package_name: &str,
+    original_file_path: &str,
+    database: &str,
+    schema: &str,
+    root_project: &str,
+    internal_packages: Option<Vec<String>>,
+    quote_char: Option<char>,
+    render_context: Option<&PyDict>,
+    require_arguments_property: bool,
+    restricted_packages: Option<HashSet<String>>,
+    register: bool,
+) -> PyResult<PyObject> {
+    let internal_packages = internal_packages.unwrap_or_else(|| vec!["dbt".to_string()]);
+    let dispatch_search_order = HashMap::new();
+    let render_context = match render_context.map(|dict| py_to_value(dict)).transpose()? {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    let ctx = GenericTestContext {
+        build: BuildContext {
+            resolver: resolver.inner(),
+            database,
+            schema,
+            quote_char,
+        },
+        macros: MacroResolveContext {
+            root_project,
+            internal_packages: &internal_packages,
+            adapter_types: &[],
+            dispatch_search_order: &dispatch_search_order,
+            node_package: None,
+        },
+        render_context: &render_context,
+        arguments_property: require_arguments_property,
+    };
+
+    let (source, node): (Option<SourceProperties>, Option<NodeProperties>) = match yaml_key {
+        "sources" => (Some(from_properties_dict(entry)?), None),
+        "models" | "seeds" | "snapshots" | "analyses" => (None, Some(from_properties_dict(entry)?)),
+        other => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Cannot build tests for '{}' entries",
+                other
+            )))
+        }
+    };
+    let targets: Vec<TestTarget> = match (&source, &node) {
+        (Some(source), _) => source
+            .tables
+            .iter()
+            .map(|table| TestTarget::Source { source, table })
+            .collect(),
+        (_, Some(properties)) => vec![TestTarget::Node {
+            yaml_key,
+            properties,
+        }],
+        (None, None) => Vec::new(),
+    };
+
+    let tests = {
+        let manifest = read_manifest(manifest.shared())?;
+        let mut tests = Vec::new();
+        for target in targets {
+            let input = GenericTestInput {
+                target,
+                package_name,
+                original_file_path,
+            };
+            tests.extend(
+                build(&ctx, &manifest, &input)
+                    .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?,
+            );
+        }
+        tests
+    };
+
+    let nodes = PyList::empty(py);
+    let link_errors = PyList::empty(py);
+    let to_py = |node: &OxideNode| -> PyResult<()> {
+        let value = lossless::to_value(node)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        nodes.append(value_to_py(py, &value)?)
+    };
+    if !register {
+        for test in &tests {
+            to_py(test)?;
+        }
+    } else {
+        let unique_ids: Vec<String> = tests.iter().map(|test| test.unique_id.clone()).collect();
+        let mut manifest = write_lock(manifest.shared())?;
+        for test in tests {
+            manifest.register_node(test);
+        }
+        let restricted_packages = restricted_packages.unwrap_or_default();
+        for error in link_dependencies(
+            &mut manifest,
+            Some(&unique_ids),
+            root_project,
+            &restricted_packages,
+        ) {
+            let row = PyDict::new(py);
+            row.set_item("unique_id", error.unique_id)?;
+            row.set_item("kind", error.error.kind())?;
+            row.set_item("message", error.error.to_string())?;
+            link_errors.append(row)?;
+        }
+        for unique_id in &unique_ids {
+            let registered = manifest.nodes.get(unique_id).or_else(|| {
+                manifest
+                    .get_disabled(unique_id)
+                    .and_then(|nodes| nodes.last())
+            });
+            if let Some(test) = registered {
+                to_py(test)?;
+            }
+        }
+    }
+    let result = PyDict::new(py);
+    result.set_item("nodes", nodes)?;
+    result.set_item("link_errors", link_errors)?;
+    Ok(result.into())
+}
+
+pub fn register_generic_tests_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(build_generic_tests, m)?)?;
+    Ok(())
+}
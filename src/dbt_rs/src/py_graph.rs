@@ -1,6 +1,17 @@
-use crate::graph::OxideGraph;
+// pyo3 0.20 expands the `#[new]` text signature of the `#[pymethods]` block below
+// into an impl nested in a generated function, out of reach of an `allow` on the
+// block itself.
+#![allow(non_local_definitions)]
+
+use crate::data_layer::update_graph_from_manifest;
+use crate::graph::{NodeOrder, OxideGraph};
+use crate::graph_diff::{self, GraphDiff};
+use crate::graph_metrics::{GraphMetrics, NodeMetrics, PackageMetrics};
+use crate::partition::GraphPartition;
+use crate::py_manifest::{read_manifest, DbtManifest};
 use pyo3::prelude::*;
-use std::collections::HashSet;
+use pyo3::types::PyDict;
+use std::collections::{HashMap, HashSet};
 
 #[pyclass]
 pub struct DbtGraph {
@@ -11,21 +22,164 @@ impl DbtGraph {
     pub fn from_oxide_graph(graph: OxideGraph) -> Self {
         DbtGraph { inner: graph }
     }
+
+    pub fn oxide_graph(&self) -> &OxideGraph {
+        &self.inner
+    }
+}
+
+fn node_metrics_to_dict(py: Python, m: &NodeMetrics) -> PyResult<PyObject> {
+    let row = PyDict::new(py);
+    row.set_item("unique_id", &m.unique_id)?;
+    row.set_item("package_name", &m.package_name)?;
+    row.set_item("fan_in", m.fan_in)?;
+    row.set_item("fan_out", m.fan_out)?;
+    row.set_item("depth", m.depth)?;
+    row.set_item("height", m.height)?;
+    row.set_item("ancestor_count", m.ancestor_count)?;
+    row.set_item("descendant_count", m.descendant_count)?;
+    row.set_item("betweenness", m.betweenness)?;
+    Ok(row.into())
+}
+
+fn package_metrics_to_dict(py: Python, m: &PackageMetrics) -> PyResult<PyObject> {
+    let row = PyDict::new(py);
+    row.set_item("package_name", &m.package_name)?;
+    row.set_item("node_count", m.node_count)?;
+    row.set_item("edges_in", m.edges_in)?;
+    row.set_item("edges_out", m.edges_out)?;
+    row.set_item("max_depth", m.max_depth)?;
+    row.set_item("max_height", m.max_height)?;
+    row.set_item("max_descendant_count", m.max_descendant_count)?;
+    row.set_item("max_betweenness", m.max_betweenness)?;
+    Ok(row.into())
+}
+
+fn partition_to_dict(py: Python, p: &GraphPartition) -> PyResult<PyObject> {
+    let shards = p
+        .shards
+        .iter()
+        .map(|shard| {
+            let row = PyDict::new(py);
+            row.set_item("nodes", &shard.nodes)?;
+            row.set_item("weight", shard.weight)?;
+            Ok(row.into())
+        })
+        .collect::<PyResult<Vec<PyObject>>>()?;
+
+    let result = PyDict::new(py);
+    result.set_item("shards", shards)?;
+    result.set_item("cross_shard_edges", &p.cross_shard_edges)?;
+    result.set_item("shard_dependencies", &p.shard_dependencies)?;
+    Ok(result.into())
+}
+
+fn graph_diff_to_dict(py: Python, d: &GraphDiff) -> PyResult<PyObject> {
+    let changed_edge_types = d
+        .changed_edge_types
+        .iter()
+        .map(|c| {
+            let row = PyDict::new(py);
+            row.set_item("source", &c.source)?;
+            row.set_item("target", &c.target)?;
+            row.set_item("old_type", &c.old_type)?;
+            row.set_item("new_type", &c.new_type)?;
+            Ok(row.into())
+        })
+        .collect::<PyResult<Vec<PyObject>>>()?;
+
+    let result = PyDict::new(py);
+    result.set_item("added_nodes", &d.added_nodes)?;
+    result.set_item("removed_nodes", &d.removed_nodes)?;
+    result.set_item("added_edges", &d.added_edges)?;
+    result.set_item("removed_edges", &d.removed_edges)?;
+    result.set_item("changed_edge_types", changed_edge_types)?;
+    result.set_item("lineage_changed", &d.lineage_changed)?;
+    Ok(result.into())
+}
+
+/// Compare two graphs, e.g. built from a `--state` manifest and the current one.
+#[pyfunction]
+pub fn diff_graphs(py: Python, old: &DbtGraph, new: &DbtGraph) -> PyResult<PyObject> {
+    graph_diff_to_dict(py, &graph_diff::diff_graphs(&old.inner, &new.inner))
+}
+
+/// Same as `diff_graphs`, serialized to JSON.
+#[pyfunction]
+pub fn diff_graphs_json(old: &DbtGraph, new: &DbtGraph) -> PyResult<String> {
+    graph_diff::diff_graphs(&old.inner, &new.inner)
+        .to_json()
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+fn parse_order(order: &str) -> PyResult<NodeOrder> {
+    match order {
+        "unique_id" => Ok(NodeOrder::UniqueId),
+        "topological" => Ok(NodeOrder::Topological),
+        other => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Unknown order '{}', expected 'unique_id' or 'topological'",
+            other
+        ))),
+    }
+}
+
+impl DbtGraph {
+    fn ordered(&self, nodes: HashSet<String>, order: &str) -> PyResult<Vec<String>> {
+        self.inner
+            .order_nodes(nodes, parse_order(order)?)
+            .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+    }
+
+    fn metrics(&self) -> PyResult<GraphMetrics> {
+        self.inner
+            .compute_metrics()
+            .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+    }
 }
 
 impl Default for DbtGraph {
     fn default() -> Self {
-        Self::new()
+        Self::new(false)
     }
 }
 
 #[pymethods]
 impl DbtGraph {
+    /// With `acyclic=True`, `add_edge` raises `ValueError` for edges that
+    /// would close a cycle and the topological order is kept up to date.
     #[new]
-    pub fn new() -> Self {
-        DbtGraph {
-            inner: OxideGraph::new(),
-        }
+    #[pyo3(signature = (acyclic=false))]
+    pub fn new(acyclic: bool) -> Self {
+        let inner = if acyclic {
+            OxideGraph::new_acyclic()
+        } else {
+            OxideGraph::new()
+        };
+        DbtGraph { inner }
+    }
+
+    /// Switch an existing graph to acyclic mode. Raises `ValueError` listing
+    /// the cycle edges if the graph already contains a cycle.
+    pub fn enable_acyclic_mode(&mut self) -> PyResult<()> {
+        self.inner.enable_acyclic_mode().map_err(|cycle| {
+            let path: Vec<String> = cycle
+                .iter()
+                .map(|(s, t)| format!("{} -> {}", s, t))
+                .collect();
+            pyo3::exceptions::PyValueError::new_err(format!(
+                "Graph contains a cycle: {}",
+                path.join(", ")
+            ))
+        })
+    }
+
+    pub fn disable_acyclic_mode(&mut self) {
+        self.inner.disable_acyclic_mode();
+    }
+
+    #[getter]
+    pub fn acyclic(&self) -> bool {
+        self.inner.is_acyclic_mode()
     }
 
     pub fn __len__(&self) -> usize {
@@ -90,6 +244,20 @@ impl DbtGraph {
             .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
     }
 
+    /// Apply a manifest patch to a graph built from that manifest: drop the
+    /// `removed` unique_ids and rebuild the incoming edges of `changed` ones.
+    #[pyo3(signature = (manifest, changed, removed=Vec::new()))]
+    pub fn update_from_manifest(
+        &mut self,
+        manifest: &DbtManifest,
+        changed: Vec<String>,
+        removed: Vec<String>,
+    ) -> PyResult<()> {
+        let manifest = read_manifest(manifest.shared())?;
+        update_graph_from_manifest(&mut self.inner, &manifest, &changed, &removed)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
     pub fn remove_node(&mut self, node: String) {
         self.inner.remove_node(&node);
     }
@@ -133,4 +301,127 @@ impl DbtGraph {
     pub fn find_cycle(&self) -> Option<Vec<(String, String)>> {
         self.inner.find_cycle()
     }
+
+    /// Per-node structure metrics as a list of row dicts, sorted by unique_id.
+    pub fn node_metrics(&self, py: Python) -> PyResult<Vec<PyObject>> {
+        self.metrics()?
+            .nodes
+            .iter()
+            .map(|m| node_metrics_to_dict(py, m))
+            .collect()
+    }
+
+    /// Per-package aggregates as a list of row dicts, sorted by package name.
+    pub fn package_metrics(&self, py: Python) -> PyResult<Vec<PyObject>> {
+        self.metrics()?
+            .packages
+            .iter()
+            .map(|m| package_metrics_to_dict(py, m))
+            .collect()
+    }
+
+    /// The `top_n` nodes with the largest downstream impact.
+    #[pyo3(signature = (top_n=20))]
+    pub fn hotspots(&self, py: Python, top_n: usize) -> PyResult<Vec<PyObject>> {
+        self.metrics()?
+            .hotspots(top_n)
+            .into_iter()
+            .map(|m| node_metrics_to_dict(py, m))
+            .collect()
+    }
+
+    pub fn metrics_json(&self) -> PyResult<String> {
+        self.metrics()?
+            .to_json()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Split the graph (or `selected` nodes) into dependency-respecting shards.
+    /// `weights` maps unique_id to historical runtime.
+    #[pyo3(signature = (shard_count, selected=None, weights=None))]
+    pub fn partition(
+        &self,
+        py: Python,
+        shard_count: usize,
+        selected: Option<HashSet<String>>,
+        weights: Option<HashMap<String, f64>>,
+    ) -> PyResult<PyObject> {
+        let partition = self
+            .inner
+            .partition(selected.as_ref(), shard_count, &weights.unwrap_or_default())
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        partition_to_dict(py, &partition)
+    }
+
+    // Ordered variants of the set-returning queries. `order` is "unique_id"
+    // or "topological" (ties broken by unique_id), so results are identical
+    // between runs regardless of insertion order.
+
+    #[pyo3(signature = (order="unique_id"))]
+    pub fn nodes_ordered(&self, order: &str) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.nodes(), order)
+    }
+
+    #[pyo3(signature = (order="unique_id"))]
+    pub fn edges_ordered(&self, order: &str) -> PyResult<Vec<(String, String)>> {
+        self.inner
+            .edges_ordered(parse_order(order)?)
+            .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+    }
+
+    pub fn stable_topological_order(&self) -> PyResult<Vec<String>> {
+        self.inner
+            .stable_topological_order()
+            .map_err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>)
+    }
+
+    #[pyo3(signature = (node, limit=None, order="unique_id"))]
+    pub fn descendants_ordered(
+        &self,
+        node: String,
+        limit: Option<usize>,
+        order: &str,
+    ) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.descendants(&node, limit), order)
+    }
+
+    #[pyo3(signature = (node, limit=None, order="unique_id"))]
+    pub fn ancestors_ordered(
+        &self,
+        node: String,
+        limit: Option<usize>,
+        order: &str,
+    ) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.ancestors(&node, limit), order)
+    }
+
+    #[pyo3(signature = (selected, limit=None, order="unique_id"))]
+    pub fn select_children_ordered(
+        &self,
+        selected: HashSet<String>,
+        limit: Option<usize>,
+        order: &str,
+    ) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.select_children(&selected, limit), order)
+    }
+
+    #[pyo3(signature = (selected, limit=None, order="unique_id"))]
+    pub fn select_parents_ordered(
+        &self,
+        selected: HashSet<String>,
+        limit: Option<usize>,
+        order: &str,
+    ) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.select_parents(&selected, limit), order)
+    }
+
+    #[pyo3(signature = (node, order="unique_id"))]
+    pub fn successors_ordered(&self, node: String, order: &str) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.successors(&node), order)
+    }
+
+    #[pyo3(signature = (node, order="unique_id"))]
+    pub fn predecessors_ordered(&self, node: String, order: &str) -> PyResult<Vec<String>> {
+        self.ordered(self.inner.predecessors(&node), order)
+    }
 }
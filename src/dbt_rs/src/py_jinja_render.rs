@@ -0,0 +1,139 @@
+use crate::jinja_render::{
+    compile_node, render_template, CompileOptions, FunctionCallback, RenderError,
+};
+use crate::py_manifest::{py_to_value, value_to_py, DbtManifest};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+pyo3::create_exception!(dbt_rs, JinjaRenderError, pyo3::exceptions::PyValueError);
+
+fn render_error(e: RenderError) -> PyErr {
+    JinjaRenderError::new_err(e.to_string())
+}
+
+fn dict_to_map(dict: Option<&PyDict>) -> PyResult<Map<String, Value>> {
+    match dict.map(|dict| py_to_value(dict)).transpose()? {
+        Some(Value::Object(map)) => Ok(map),
+        _ => Ok(Map::new()),
+    }
+}
+
+/// Render a Jinja template the way dbt's `get_rendered` does. With
+/// `native=True` a template that is a single `{{ ... | as_native }}` (or
+/// `as_bool`, `as_number`) returns the converted Python value; otherwise the
+/// result is a string. `context` must hold JSON-like values.
+#[pyfunction]
+#[pyo3(signature = (template, context=None, native=false))]
+pub fn render_jinja(
+    py: Python,
+    template: &str,
+    context: Option<&PyDict>,
+    native: bool,
+) -> PyResult<PyObject> {
+    let context = dict_to_map(context)?;
+    let rendered = py
+        .allow_threads(|| render_template(template, &context, native))
+        .map_err(render_error)?;
+    value_to_py(py, &rendered)
+}
+
+/// Compile a node's `raw_code` with `ref`, `source`, `var`, `env_var`,
+/// `config`, `is_incremental` and `this` resolved against the manifest.
+///
+/// `python_functions` maps names, e.g. adapter-bound macros, to callables
+/// invoked with the rendered arguments; their return value must be
+/// JSON-like. `context` adds globals such as `target`.
+///
+/// Returns `{"compiled_code", "refs", "sources", "ephemeral_refs",
+/// "env_vars"}`.
+#[pyfunction]
+#[pyo3(signature = (
+    manifest,
+    unique_id,
+    *,
+    current_project,
+    vars=None,
+    env_vars=None,
+    relation_exists=false,
+    full_refresh=false,
+    context=None,
+    python_functions=None,
+    restricted_packages=None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn compile_node_sql(
+    py: Python,
+    manifest: &DbtManifest,
+    unique_id: &str,
+    current_project: String,
+    vars: Option<&PyDict>,
+    env_vars: Option<BTreeMap<String, String>>,
+    relation_exists: bool,
+    full_refresh: bool,
+    context: Option<&PyDict>,
+    python_functions: Option<&PyDict>,
+    restricted_packages: Option<HashSet<String>>,
+) -> PyResult<PyObject> {
+    let mut functions: BTreeMap<String, PyObject> = BTreeMap::new();
+    if let Some(python_functions) = python_functions {
+        for (name, function) in python_functions {
+            functions.insert(name.extract()?, function.into_py(py));
+        }
+    }
+    let options = CompileOptions {
+        current_project,
+        restricted_packages: restricted_packages.unwrap_or_default(),
+        vars: dict_to_map(vars)?,
+        env_vars: env_vars.unwrap_or_default(),
+        relation_exists,
+        full_refresh,
+        context: dict_to_map(context)?,
+        callback_functions: functions.keys().cloned().collect(),
+    };
+    let callback: Option<FunctionCallback> = (!functions.is_empty()).then(|| {
+        let callback: FunctionCallback = Arc::new(move |name, args, kwargs| {
+            Python::with_gil(|py| {
+                let call = || -> PyResult<Value> {
+                    let args = args
+                        .iter()
+                        .map(|arg| value_to_py(py, arg))
+                        .collect::<PyResult<Vec<_>>>()?;
+                    let named = PyDict::new(py);
+                    for (key, value) in kwargs {
+                        named.set_item(key, value_to_py(py, value)?)?;
+                    }
+                    let result = functions[name].call(
+                        py,
+                        pyo3::types::PyTuple::new(py, args),
+                        Some(named),
+                    )?;
+                    py_to_value(result.as_ref(py))
+                };
+                call().map_err(|e| e.to_string())
+            })
+        });
+        callback
+    });
+    let shared = manifest.shared().clone();
+    let compiled = py
+        .allow_threads(|| compile_node(&shared, unique_id, options, callback))
+        .map_err(render_error)?;
+
+    let out = PyDict::new(py);
+    out.set_item("compiled_code", compiled.compiled_code)?;
+    out.set_item("refs", compiled.refs)?;
+    out.set_item("sources", compiled.sources)?;
+    out.set_item("ephemeral_refs", compiled.ephemeral_refs)?;
+    out.set_item("env_vars", compiled.env_vars)?;
+    Ok(out.into())
+}
+
+pub fn register_jinja_render_module(m: &PyModule) -> PyResult<()> {
+    m.add("JinjaRenderError", m.py().get_type::<JinjaRenderError>())?;
+    m.add_function(wrap_pyfunction!(render_jinja, m)?)?;
+    m.add_function(wrap_pyfunction!(compile_node_sql, m)?)?;
+    Ok(())
+}
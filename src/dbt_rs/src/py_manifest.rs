@@ -1,62 +1,606 @@
+use crate::data_layer::build_graph_from_manifest;
+use crate::macro_resolution::{MacroResolution, MacroResolveContext, MacroResolveError};
 use crate::manifest::OxideManifest;
-use once_cell::sync::OnceCell;
+use crate::manifest_errors::ManifestParseError as ManifestParseErrorInfo;
+use crate::manifest_patch::PatchTarget;
+use crate::msgpack;
+use crate::partial_parse::PartialParseError;
+use crate::py_graph::DbtGraph;
+use crate::py_views::{Collection, ManifestCollection};
+use crate::resolution::{Referrer, ResolveContext, ResolveError};
+use once_cell::sync::Lazy;
 use pyo3::prelude::*;
-use pyo3::types::PyModule;
-use std::sync::RwLock;
+use pyo3::types::{PyDict, PyList, PyModule};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-static MANIFEST: OnceCell<RwLock<OxideManifest>> = OnceCell::new();
+pyo3::create_exception!(
+    dbt_rs,
+    ResolutionError,
+    pyo3::exceptions::PyException,
+    "A ref(), source() or macro call could not be resolved. `kind` is one of \
+     not_found, disabled, ambiguous, private, protected or invalid_name; \
+     `unique_ids` lists the nodes involved."
+);
 
-/// Get reference to the global manifest (for internal use by other modules).
-pub fn get_global_manifest() -> PyResult<&'static RwLock<OxideManifest>> {
-    MANIFEST.get().ok_or_else(|| {
+pyo3::create_exception!(
+    dbt_rs,
+    ManifestParseError,
+    pyo3::exceptions::PyValueError,
+    "manifest.json could not be parsed. `kind` is syntax, data or version; \
+     `path` is a JSON pointer to the offending value, with `collection`, \
+     `unique_id` and `field` split out and `expected`/`found` describing the \
+     type mismatch. `line` and `column` are set for syntax errors."
+);
+
+pub(crate) fn manifest_parse_error(py: Python, e: ManifestParseErrorInfo) -> PyErr {
+    let err = ManifestParseError::new_err(e.to_string());
+    let value = err.value(py);
+    let set = || -> PyResult<()> {
+        value.setattr("kind", e.kind.as_str())?;
+        value.setattr("path", e.pointer())?;
+        value.setattr("collection", e.collection())?;
+        value.setattr("unique_id", e.unique_id())?;
+        value.setattr("field", e.field())?;
+        value.setattr("expected", e.expected.as_deref())?;
+        value.setattr("found", e.found.as_deref())?;
+        value.setattr("line", e.position.map(|(line, _)| line))?;
+        value.setattr("column", e.position.map(|(_, column)| column))?;
+        Ok(())
+    };
+    if let Err(attr_err) = set() {
+        return attr_err;
+    }
+    err
+}
+
+fn resolution_error(py: Python, e: ResolveError) -> PyErr {
+    let err = ResolutionError::new_err(e.to_string());
+    let value = err.value(py);
+    if let Err(attr_err) = value
+        .setattr("kind", e.kind())
+        .and_then(|_| value.setattr("unique_ids", e.unique_ids()))
+    {
+        return attr_err;
+    }
+    err
+}
+
+fn macro_resolution_error(py: Python, e: MacroResolveError) -> PyErr {
+    let err = ResolutionError::new_err(e.to_string());
+    let value = err.value(py);
+    if let Err(attr_err) = value
+        .setattr("kind", e.kind())
+        .and_then(|_| value.setattr("unique_ids", Vec::<String>::new()))
+    {
+        return attr_err;
+    }
+    err
+}
+
+fn macro_resolution_to_dict(py: Python, resolution: MacroResolution) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("unique_id", resolution.unique_id)?;
+    dict.set_item("shadowed", resolution.shadowed)?;
+    dict.set_item("searched", resolution.searched)?;
+    Ok(dict.into())
+}
+
+/// A manifest shared between its `DbtManifest` handles and, optionally, the
+/// global compatibility slot.
+pub type SharedManifest = Arc<RwLock<OxideManifest>>;
+
+// Compatibility shim for callers of the module-level functions: points at the
+// manifest most recently loaded with `set_global=True`.
+static MANIFEST: Lazy<RwLock<Option<SharedManifest>>> = Lazy::new(|| RwLock::new(None));
+
+pub fn read_manifest(manifest: &SharedManifest) -> PyResult<RwLockReadGuard<'_, OxideManifest>> {
+    manifest
+        .read()
+        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))
+}
+
+pub(crate) fn write_lock(
+    manifest: &SharedManifest,
+) -> PyResult<RwLockWriteGuard<'_, OxideManifest>> {
+    manifest
+        .write()
+        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))
+}
+
+fn patch_target(collection: &str) -> PyResult<PatchTarget> {
+    PatchTarget::from_name(collection).ok_or_else(|| {
+        pyo3::exceptions::PyValueError::new_err(format!("Unknown collection: {}", collection))
+    })
+}
+
+/// Get the global manifest (for internal use by other modules).
+pub fn get_global_manifest() -> PyResult<SharedManifest> {
+    let slot = MANIFEST
+        .read()
+        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))?;
+    slot.clone().ok_or_else(|| {
         pyo3::exceptions::PyRuntimeError::new_err(
             "Manifest not loaded. Call load_manifest() first.",
         )
     })
 }
 
-#[pyfunction]
-pub fn load_manifest(json_string: &str) -> PyResult<()> {
-    let manifest = OxideManifest::from_json_str(json_string)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
-
-    // Initialize or replace the manifest
-    match MANIFEST.get() {
-        Some(lock) => {
-            let mut guard = lock
-                .write()
-                .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))?;
-            *guard = manifest;
+fn set_global_manifest(manifest: SharedManifest) -> PyResult<()> {
+    let mut slot = MANIFEST
+        .write()
+        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))?;
+    *slot = Some(manifest);
+    Ok(())
+}
+
+pub(crate) fn parse_manifest(json_string: &str) -> PyResult<OxideManifest> {
+    OxideManifest::from_json_str(json_string)
+        .map_err(|e| Python::with_gil(|py| manifest_parse_error(py, e)))
+}
+
+fn partial_parse_error(py: Python, path: &str, e: PartialParseError) -> PyErr {
+    match e {
+        PartialParseError::Io(e) => {
+            pyo3::exceptions::PyOSError::new_err(format!("{}: {}", path, e))
         }
-        None => {
-            MANIFEST.set(RwLock::new(manifest)).map_err(|_| {
-                pyo3::exceptions::PyRuntimeError::new_err("Failed to initialize manifest")
-            })?;
+        PartialParseError::Msgpack(e) => {
+            pyo3::exceptions::PyValueError::new_err(format!("{}: invalid msgpack: {}", path, e))
         }
+        PartialParseError::Manifest(e) => manifest_parse_error(py, e),
     }
-    Ok(())
 }
 
-#[pyfunction]
-pub fn get_node_count() -> PyResult<usize> {
-    let lock = MANIFEST
-        .get()
-        .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err("Manifest not loaded"))?;
-    let manifest = lock
-        .read()
-        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))?;
-    Ok(manifest.node_count())
+pub(crate) fn file_hash_to_dict(py: Python, name: &str, checksum: &str) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("name", name)?;
+    dict.set_item("checksum", checksum)?;
+    Ok(dict.into())
 }
 
-#[pyfunction]
-pub fn get_node_dependencies(unique_id: &str) -> PyResult<Vec<String>> {
-    let lock = MANIFEST
-        .get()
-        .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err("Manifest not loaded"))?;
-    let manifest = lock
-        .read()
-        .map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Lock poisoned"))?;
+/// Convert a JSON value to the Python object `json.loads` would return;
+/// msgpack date and datetime extension values become `datetime` objects,
+/// as with dbt's `extended_msgpack_decoder`.
+pub(crate) fn value_to_py(py: Python, value: &Value) -> PyResult<PyObject> {
+    if let Some((code @ (msgpack::EXT_DATE | msgpack::EXT_DATETIME), data)) = msgpack::as_ext(value)
+    {
+        let class = if code == msgpack::EXT_DATE {
+            "date"
+        } else {
+            "datetime"
+        };
+        return Ok(py
+            .import("datetime")?
+            .getattr(class)?
+            .call_method1("fromisoformat", (String::from_utf8_lossy(&data),))?
+            .into());
+    }
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_py(py),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py(py),
+            (None, Some(u)) => u.into_py(py),
+            _ => n.as_f64().unwrap_or(f64::NAN).into_py(py),
+        },
+        Value::String(s) => s.into_py(py),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(value_to_py(py, item)?)?;
+            }
+            list.into()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, value_to_py(py, item)?)?;
+            }
+            dict.into()
+        }
+    })
+}
+
+/// Convert a Python object built from dicts, lists, tuples, strings, numbers,
+/// bools, None, dates and datetimes to JSON. Dict keys are stringified with
+/// `str()`; dates become msgpack extension values, as `value_to_py` reads them.
+pub(crate) fn py_to_value(obj: &PyAny) -> PyResult<Value> {
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    if let Ok(b) = obj.downcast::<pyo3::types::PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if let Ok(i) = obj.extract::<i64>() {
+        return Ok(Value::from(i));
+    }
+    if let Ok(f) = obj.downcast::<pyo3::types::PyFloat>() {
+        return Ok(serde_json::Number::from_f64(f.value()).map_or(Value::Null, Value::Number));
+    }
+    if let Ok(s) = obj.downcast::<pyo3::types::PyString>() {
+        return Ok(Value::String(s.to_str()?.to_string()));
+    }
+    if let Ok(date) = obj.downcast::<pyo3::types::PyDate>() {
+        let code = if date.downcast::<pyo3::types::PyDateTime>().is_ok() {
+            msgpack::EXT_DATETIME
+        } else {
+            msgpack::EXT_DATE
+        };
+        let iso: String = date.call_method0("isoformat")?.extract()?;
+        return Ok(msgpack::ext_value(code, iso.as_bytes()));
+    }
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut map = serde_json::Map::new();
+        for (key, item) in dict {
+            map.insert(key.str()?.to_string(), py_to_value(item)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if obj.downcast::<PyList>().is_ok() || obj.downcast::<pyo3::types::PyTuple>().is_ok() {
+        return obj
+            .iter()?
+            .map(|item| py_to_value(item?))
+            .collect::<PyResult<Vec<_>>>()
+            .map(Value::Array);
+    }
+    Err(pyo3::exceptions::PyTypeError::new_err(format!(
+        "cannot convert {} to a JSON value",
+        obj.get_type().name()?
+    )))
+}
+
+/// Handle to a Rust-owned manifest. Several handles can be alive at once,
+/// e.g. the current manifest and a `--state` manifest.
+#[pyclass]
+#[derive(Clone)]
+pub struct DbtManifest {
+    inner: SharedManifest,
+}
+
+impl DbtManifest {
+    pub fn from_oxide_manifest(manifest: OxideManifest) -> Self {
+        DbtManifest {
+            inner: Arc::new(RwLock::new(manifest)),
+        }
+    }
+
+    pub fn shared(&self) -> &SharedManifest {
+        &self.inner
+    }
+}
+
+#[pymethods]
+impl DbtManifest {
+    #[staticmethod]
+    pub fn from_json(json_string: &str) -> PyResult<Self> {
+        Ok(DbtManifest::from_oxide_manifest(parse_manifest(
+            json_string,
+        )?))
+    }
+
+    pub fn node_count(&self) -> PyResult<usize> {
+        Ok(read_manifest(&self.inner)?.node_count())
+    }
+
+    pub fn get_node_dependencies(&self, unique_id: &str) -> PyResult<Vec<String>> {
+        let manifest = read_manifest(&self.inner)?;
+        node_dependencies(&manifest, unique_id)
+    }
+
+    pub fn build_graph(&self) -> PyResult<DbtGraph> {
+        let manifest = read_manifest(&self.inner)?;
+        Ok(DbtGraph::from_oxide_graph(build_graph_from_manifest(
+            &manifest,
+        )))
+    }
+
+    /// Resolve a `ref()` call made from a node in `node_package` to a
+    /// unique_id. Raises `ResolutionError` when it cannot be resolved.
+    #[pyo3(signature = (
+        name,
+        package=None,
+        version=None,
+        *,
+        current_project,
+        node_package,
+        node_resource_type="model",
+        node_group=None,
+        restricted_packages=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve_ref(
+        &self,
+        py: Python,
+        name: &str,
+        package: Option<&str>,
+        version: Option<&PyAny>,
+        current_project: &str,
+        node_package: &str,
+        node_resource_type: &str,
+        node_group: Option<&str>,
+        restricted_packages: Option<HashSet<String>>,
+    ) -> PyResult<String> {
+        let version = version
+            .map(|v| v.str().map(|s| s.to_string()))
+            .transpose()?;
+        let restricted = restricted_packages.unwrap_or_default();
+        let referrer = Referrer {
+            package_name: node_package,
+            resource_type: node_resource_type,
+            group: node_group,
+        };
+        let ctx = ResolveContext {
+            current_project,
+            restricted_packages: &restricted,
+        };
+        read_manifest(&self.inner)?
+            .resolve_ref(referrer, name, package, version.as_deref(), ctx)
+            .map_err(|e| resolution_error(py, e))
+    }
+
+    /// Resolve a `source()` call made from a node in `node_package`.
+    #[pyo3(signature = (source_name, table_name, *, current_project, node_package))]
+    pub fn resolve_source(
+        &self,
+        py: Python,
+        source_name: &str,
+        table_name: &str,
+        current_project: &str,
+        node_package: &str,
+    ) -> PyResult<String> {
+        read_manifest(&self.inner)?
+            .resolve_source(source_name, table_name, node_package, current_project)
+            .map_err(|e| resolution_error(py, e))
+    }
+
+    /// Resolve a macro call `name()` (or `package.name()`) to a dict with the
+    /// winning `unique_id`, the `shadowed` candidates and the names `searched`.
+    /// `internal_packages` lists `dbt` and the adapter packages, highest
+    /// precedence first.
+    #[pyo3(signature = (
+        name,
+        package=None,
+        *,
+        root_project,
+        internal_packages,
+        node_package=None
+    ))]
+    pub fn resolve_macro(
+        &self,
+        py: Python,
+        name: &str,
+        package: Option<&str>,
+        root_project: &str,
+        internal_packages: Vec<String>,
+        node_package: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let dispatch_search_order = HashMap::new();
+        let ctx = MacroResolveContext {
+            root_project,
+            internal_packages: &internal_packages,
+            adapter_types: &[],
+            dispatch_search_order: &dispatch_search_order,
+            node_package,
+        };
+        let resolution = read_manifest(&self.inner)?
+            .resolve_macro(name, package, ctx)
+            .map_err(|e| macro_resolution_error(py, e))?;
+        macro_resolution_to_dict(py, resolution)
+    }
+
+    /// Resolve `adapter.dispatch(macro_name, macro_namespace)`. `adapter_types`
+    /// is the adapter type followed by its parents; `search_order` maps macro
+    /// namespaces to the project's `dispatch` search orders.
+    #[pyo3(signature = (
+        macro_name,
+        macro_namespace=None,
+        *,
+        root_project,
+        internal_packages,
+        adapter_types,
+        search_order=None,
+        node_package=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve_dispatch(
+        &self,
+        py: Python,
+        macro_name: &str,
+        macro_namespace: Option<&str>,
+        root_project: &str,
+        internal_packages: Vec<String>,
+        adapter_types: Vec<String>,
+        search_order: Option<HashMap<String, Vec<String>>>,
+        node_package: Option<&str>,
+    ) -> PyResult<PyObject> {
+        let dispatch_search_order = search_order.unwrap_or_default();
+        let ctx = MacroResolveContext {
+            root_project,
+            internal_packages: &internal_packages,
+            adapter_types: &adapter_types,
+            dispatch_search_order: &dispatch_search_order,
+            node_package,
+        };
+        let resolution = read_manifest(&self.inner)?
+            .resolve_dispatch(macro_name, macro_namespace, ctx)
+            .map_err(|e| macro_resolution_error(py, e))?;
+        macro_resolution_to_dict(py, resolution)
+    }
+
+    // Read-only, mapping-like collections of lazy views.
 
+    #[getter]
+    pub fn nodes(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::Nodes)
+    }
+
+    #[getter]
+    pub fn sources(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::Sources)
+    }
+
+    #[getter]
+    pub fn macros(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::Macros)
+    }
+
+    #[getter]
+    pub fn exposures(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::Exposures)
+    }
+
+    #[getter]
+    pub fn metrics(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::Metrics)
+    }
+
+    #[getter]
+    pub fn groups(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::Groups)
+    }
+
+    #[getter]
+    pub fn semantic_models(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::SemanticModels)
+    }
+
+    #[getter]
+    pub fn saved_queries(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::SavedQueries)
+    }
+
+    #[getter]
+    pub fn unit_tests(&self) -> ManifestCollection {
+        ManifestCollection::new(self.inner.clone(), Collection::UnitTests)
+    }
+
+    // Incremental updates after partial parsing. `json_string` maps
+    // unique_id to the entity as written in manifest.json; the unique_ids
+    // written or removed are returned so a graph can be updated with
+    // `DbtGraph.update_from_manifest`.
+
+    /// Insert or replace entries of `collection` ("nodes", "sources", ...,
+    /// or "disabled", whose values are lists). Raises `ManifestParseError`
+    /// and changes nothing if any entry is invalid.
+    pub fn upsert(&self, py: Python, collection: &str, json_string: &str) -> PyResult<Vec<String>> {
+        let target = patch_target(collection)?;
+        write_lock(&self.inner)?
+            .upsert_json(target, json_string)
+            .map_err(|e| manifest_parse_error(py, e))
+    }
+
+    /// Remove entries of `collection`; returns the ones that were present.
+    pub fn remove(&self, collection: &str, unique_ids: Vec<String>) -> PyResult<Vec<String>> {
+        let target = patch_target(collection)?;
+        Ok(write_lock(&self.inner)?.remove_entries(target, &unique_ids))
+    }
+
+    pub fn upsert_nodes(&self, py: Python, json_string: &str) -> PyResult<Vec<String>> {
+        self.upsert(py, "nodes", json_string)
+    }
+
+    pub fn remove_nodes(&self, unique_ids: Vec<String>) -> PyResult<Vec<String>> {
+        self.remove("nodes", unique_ids)
+    }
+
+    pub fn upsert_sources(&self, py: Python, json_string: &str) -> PyResult<Vec<String>> {
+        self.upsert(py, "sources", json_string)
+    }
+
+    pub fn remove_sources(&self, unique_ids: Vec<String>) -> PyResult<Vec<String>> {
+        self.remove("sources", unique_ids)
+    }
+
+    pub fn upsert_macros(&self, py: Python, json_string: &str) -> PyResult<Vec<String>> {
+        self.upsert(py, "macros", json_string)
+    }
+
+    pub fn remove_macros(&self, unique_ids: Vec<String>) -> PyResult<Vec<String>> {
+        self.remove("macros", unique_ids)
+    }
+
+    pub fn upsert_disabled(&self, py: Python, json_string: &str) -> PyResult<Vec<String>> {
+        self.upsert(py, "disabled", json_string)
+    }
+
+    pub fn remove_disabled(&self, unique_ids: Vec<String>) -> PyResult<Vec<String>> {
+        self.remove("disabled", unique_ids)
+    }
+
+    /// Write the manifest as manifest.json to `path`.
+    pub fn write_manifest(&self, path: &str) -> PyResult<()> {
+        write_manifest_to(&self.inner, path)
+    }
+
+    /// Write the manifest as dbt's partial_parse.msgpack to `path`.
+    pub fn write_partial_parse(&self, path: &str) -> PyResult<()> {
+        read_manifest(&self.inner)?
+            .write_partial_parse(Path::new(path))
+            .map_err(|e| pyo3::exceptions::PyOSError::new_err(format!("{}: {}", path, e)))
+    }
+
+    /// File hashes recorded in partial-parse state, keyed by file_id:
+    /// `{project_name, original_file_path, parse_file_type, checksum}` with
+    /// `checksum` a `{name, checksum}` dict. Empty for manifest.json input.
+    pub fn file_hashes(&self, py: Python) -> PyResult<PyObject> {
+        let hashes = read_manifest(&self.inner)?
+            .file_hashes()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("files: {}", e)))?;
+        let out = PyDict::new(py);
+        for (file_id, file) in hashes {
+            let dict = PyDict::new(py);
+            dict.set_item("project_name", file.project_name)?;
+            dict.set_item("original_file_path", file.original_file_path)?;
+            dict.set_item("parse_file_type", file.parse_file_type)?;
+            dict.set_item(
+                "checksum",
+                file_hash_to_dict(py, &file.checksum.name, &file.checksum.checksum)?,
+            )?;
+            out.set_item(file_id, dict)?;
+        }
+        Ok(out.into())
+    }
+
+    /// The saved `state_check` as a dict of `{name, checksum}` hashes, with
+    /// `project_hashes` keyed by project; `None` when absent.
+    pub fn state_check(&self, py: Python) -> PyResult<Option<PyObject>> {
+        let state = read_manifest(&self.inner)?
+            .state_check()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("state_check: {}", e)))?;
+        let Some(state) = state else {
+            return Ok(None);
+        };
+        let dict = PyDict::new(py);
+        for (key, hash) in [
+            ("vars_hash", &state.vars_hash),
+            ("project_env_vars_hash", &state.project_env_vars_hash),
+            ("profile_env_vars_hash", &state.profile_env_vars_hash),
+            ("profile_hash", &state.profile_hash),
+        ] {
+            dict.set_item(key, file_hash_to_dict(py, &hash.name, &hash.checksum)?)?;
+        }
+        let projects = PyDict::new(py);
+        for (project, hash) in &state.project_hashes {
+            projects.set_item(project, file_hash_to_dict(py, &hash.name, &hash.checksum)?)?;
+        }
+        dict.set_item("project_hashes", projects)?;
+        Ok(Some(dict.into()))
+    }
+
+    /// Make this manifest the one used by the module-level functions.
+    pub fn set_global(&self) -> PyResult<()> {
+        set_global_manifest(self.inner.clone())
+    }
+
+    /// True when both handles refer to the same underlying manifest.
+    pub fn is_same(&self, other: &DbtManifest) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+fn node_dependencies(manifest: &OxideManifest, unique_id: &str) -> PyResult<Vec<String>> {
     match manifest.get_node(unique_id) {
         Some(node) => Ok(node.depends_on.nodes.clone()),
         None => Err(pyo3::exceptions::PyKeyError::new_err(format!(
@@ -66,9 +610,75 @@ pub fn get_node_dependencies(unique_id: &str) -> PyResult<Vec<String>> {
     }
 }
 
-pub fn register_manifest_module(m: &PyModule) -> PyResult<()> {
+fn write_manifest_to(manifest: &SharedManifest, path: &str) -> PyResult<()> {
+    read_manifest(manifest)?
+        .write_json(Path::new(path))
+        .map_err(|e| pyo3::exceptions::PyOSError::new_err(format!("{}: {}", path, e)))
+}
+
+/// Parse a manifest and return a handle to it. With `set_global` (the
+/// default) it also becomes the global manifest used by the functions below.
+#[pyfunction]
+#[pyo3(signature = (json_string, set_global=true))]
+pub fn load_manifest(json_string: &str, set_global: bool) -> PyResult<DbtManifest> {
+    let handle = DbtManifest::from_json(json_string)?;
+    if set_global {
+        handle.set_global()?;
+    }
+    Ok(handle)
+}
+
+/// Read dbt's partial_parse.msgpack into a manifest handle without going
+/// through Python objects or JSON. `set_global` works as in `load_manifest`.
+#[pyfunction]
+#[pyo3(signature = (path, set_global=true))]
+pub fn load_partial_parse(py: Python, path: &str, set_global: bool) -> PyResult<DbtManifest> {
+    let manifest = OxideManifest::read_partial_parse(Path::new(path))
+        .map_err(|e| partial_parse_error(py, path, e))?;
+    let handle = DbtManifest::from_oxide_manifest(manifest);
+    if set_global {
+        handle.set_global()?;
+    }
+    Ok(handle)
+}
+
+/// Handle to the global manifest.
+#[pyfunction]
+pub fn get_global_manifest_handle() -> PyResult<DbtManifest> {
+    Ok(DbtManifest {
+        inner: get_global_manifest()?,
+    })
+}
+
+#[pyfunction]
+pub fn get_node_count() -> PyResult<usize> {
+    let manifest = get_global_manifest()?;
+    let manifest = read_manifest(&manifest)?;
+    Ok(manifest.node_count())
+}
+
+#[pyfunction]
+pub fn get_node_dependencies(unique_id: &str) -> PyResult<Vec<String>> {
+    let manifest = get_global_manifest()?;
+    let manifest = read_manifest(&manifest)?;
+    node_dependencies(&manifest, unique_id)
+}
+
+/// Write the global manifest as manifest.json to `path`.
+#[pyfunction]
+pub fn write_manifest(path: &str) -> PyResult<()> {
+    write_manifest_to(&get_global_manifest()?, path)
+}
+
+pub fn register_manifest_module(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("ResolutionError", py.get_type::<ResolutionError>())?;
+    m.add("ManifestParseError", py.get_type::<ManifestParseError>())?;
+    m.add_class::<DbtManifest>()?;
     m.add_function(wrap_pyfunction!(load_manifest, m)?)?;
+    m.add_function(wrap_pyfunction!(load_partial_parse, m)?)?;
+    m.add_function(wrap_pyfunction!(get_global_manifest_handle, m)?)?;
     m.add_function(wrap_pyfunction!(get_node_count, m)?)?;
     m.add_function(wrap_pyfunction!(get_node_dependencies, m)?)?;
+    m.add_function(wrap_pyfunction!(write_manifest, m)?)?;
     Ok(())
 }
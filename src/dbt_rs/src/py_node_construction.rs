@@ -0,0 +1,250 @@
+use crate::lossless::{self, Lossless};
+use crate::manifest::OxideNodeVersion;
+use crate::node_construction::{
+    build_node as build, build_sources as build_source_tables, link_dependencies as link,
+    BuildContext, FileInput, NodeBuildError, NodeInput,
+};
+use crate::properties::{NodeProperties, SourceProperties};
+use crate::py_config_resolution::DbtConfigResolver;
+use crate::py_manifest::{py_to_value, value_to_py, write_lock, DbtManifest};
+use crate::static_parser::{extract_static_calls, StaticRef};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashSet;
+
+fn build_error(e: NodeBuildError) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(e.to_string())
+}
+
+/// Deserialize a dict as returned by `parse_properties_files`, dropping the
+/// location keys it adds.
+pub(crate) fn from_properties_dict<T: DeserializeOwned>(dict: &PyDict) -> PyResult<T> {
+    let mut value = py_to_value(dict)?;
+    if let Value::Object(map) = &mut value {
+        map.shift_remove("line");
+        map.shift_remove("column");
+    }
+    serde_json::from_value(value)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("properties: {}", e)))
+}
+
+fn entry_to_py<T: Lossless>(py: Python, entry: &T) -> PyResult<PyObject> {
+    let value = lossless::to_value(entry)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    value_to_py(py, &value)
+}
+
+/// Build a model, analysis, snapshot, seed or singular test node the way
+/// dbt's parsers do: `unique_id`, `fqn`, `checksum`, `alias`, `schema`,
+/// `database` and the config merged by `resolver` from `dbt_project.yml`,
+/// `properties` (an entry from `parse_properties_files`) and the file's
+/// `config()` calls.
+///
+/// `path` is relative to the directory the file was found in. `refs`
+/// (`[{"name", "package", "version"}]`), `sources` (`[[source, table]]`) and
+/// `config_calls` (`[{...}]`, one dict per call) default to what is
+/// statically extracted from `contents`; pass them for files that needed
+/// rendering. `depends_on.nodes` stays empty until `link_dependencies`.
+/// With `register`, the node is added to `manifest` (to `disabled` if it is
+/// disabled). Returns the node as a dict; raises `ValueError` if it does not
+/// validate.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (
+    manifest, resolver, resource_type, package_name, path, original_file_path, contents, *,
+    database, schema, name=None, version=None, properties=None, refs=None, sources=None,
+    config_calls=None, macros=None, quote_char=None, register=true
+))]
+pub fn build_node(
+    py: Python,
+    manifest: &DbtManifest,
+    resolver: &DbtConfigResolver,
+    resource_type: &str,
+    package_name: &str,
+    path: &str,
+    original_file_path: &str,
+    contents: &str,
+    database: &str,
+    schema: &str,
+    name: Option<&str>,
+    version: Option<&PyAny>,
+    properties: Option<&PyDict>,
+    refs: Option<&PyList>,
+    sources: Option<Vec<[String; 2]>>,
+    config_calls: Option<Vec<&PyDict>>,
+    macros: Option<Vec<String>>,
+    quote_char: Option<char>,
+    register: bool,
+) -> PyResult<PyObject> {
+    let version: Option<OxideNodeVersion> = version
+        .map(|version| {
+            serde_json::from_value(py_to_value(version)?)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("version: {}", e)))
+        })
+        .transpose()?;
+    let properties: Option<NodeProperties> = properties.map(from_properties_dict).transpose()?;
+    let mut calls = extract_static_calls(contents);
+    if let Some(refs) = refs {
+        calls.refs = refs
+            .iter()
+            .map(|item| {
+                let item: &PyDict = item.downcast()?;
+                let field = |key: &str| -> PyResult<Option<Value>> {
+                    item.get_item(key)?.map(py_to_value).transpose()
+                };
+                let name = match field("name")? {
+                    Some(Value::String(name)) => name,
+                    _ => {
+                        return Err(pyo3::exceptions::PyValueError::new_err(
+                            "refs entries need a string 'name'",
+                        ))
+                    }
+                };
+                Ok(StaticRef {
+                    name,
+                    package: field("package")?.and_then(|p| p.as_str().map(str::to_string)),
+                    version: field("version")?.filter(|v| !v.is_null()),
+                })
+            })
+            .collect::<PyResult<_>>()?;
+    }
+    if let Some(sources) = sources {
+        calls.sources = sources;
+    }
+    if let Some(config_calls) = config_calls {
+        calls.configs.clear();
+        for call in config_calls {
+            if let Value::Object(call) = py_to_value(call)? {
+                calls.configs.extend(call);
+            }
+        }
+    }
+    let macros = macros.unwrap_or_default();
+
+    let ctx = BuildContext {
+        resolver: resolver.inner(),
+        database,
+        schema,
+        quote_char,
+    };
+    let input = NodeInput {
+        resource_type,
+        file: FileInput {
+            package_name,
+            path,
+            original_file_path,
+            contents,
+        },
+        name,
+        version: version.as_ref(),
+        properties: properties.as_ref(),
+        calls: &calls,
+        macros: &macros,
+    };
+    let node = py
+        .allow_threads(|| build(ctx, input))
+        .map_err(build_error)?;
+    let result = entry_to_py(py, &node)?;
+    if register {
+        write_lock(manifest.shared())?.register_node(node);
+    }
+    Ok(result)
+}
+
+/// Build one source per table of a `sources:` entry from
+/// `parse_properties_files`, found at `path` (relative to its search
+/// directory) in `package_name`. With `register`, the sources are added to
+/// `manifest`. Returns the sources as dicts.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (
+    manifest, resolver, source, package_name, path, original_file_path, *,
+    database, quote_char=None, register=true
+))]
+pub fn build_sources(
+    py: Python,
+    manifest: &DbtManifest,
+    resolver: &DbtConfigResolver,
+    source: &PyDict,
+    package_name: &str,
+    path: &str,
+    original_file_path: &str,
+    database: &str,
+    quote_char: Option<char>,
+    register: bool,
+) -> PyResult<PyObject> {
+    let source: SourceProperties = from_properties_dict(source)?;
+    let ctx = BuildContext {
+        resolver: resolver.inner(),
+        database,
+        schema: "",
+        quote_char,
+    };
+    let file = FileInput {
+        package_name,
+        path,
+        original_file_path,
+        contents: "",
+    };
+    let built = build_source_tables(ctx, file, &source).map_err(build_error)?;
+    let rows = PyList::empty(py);
+    for source in &built {
+        rows.append(entry_to_py(py, source)?)?;
+    }
+    if register {
+        let mut manifest = write_lock(manifest.shared())?;
+        for source in built {
+            manifest
+                .register_source(source)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        }
+    }
+    Ok(rows.into())
+}
+
+/// Fill `depends_on.nodes` of the given nodes (default: all) by resolving
+/// their `sources` and `refs` against `manifest`, as dbt's
+/// `process_sources` and `process_refs` do. Returns the calls that did not
+/// resolve as `{"unique_id", "kind", "message"}` dicts, `kind` being one of
+/// `not_found`, `disabled`, `ambiguous`, `private` or `protected`.
+#[pyfunction]
+#[pyo3(signature = (manifest, unique_ids=None, *, current_project, restricted_packages=None))]
+pub fn link_dependencies(
+    py: Python,
+    manifest: &DbtManifest,
+    unique_ids: Option<Vec<String>>,
+    current_project: &str,
+    restricted_packages: Option<HashSet<String>>,
+) -> PyResult<PyObject> {
+    let restricted_packages = restricted_packages.unwrap_or_default();
+    let errors = {
+        let mut guard = write_lock(manifest.shared())?;
+        let manifest = &mut *guard;
+        py.allow_threads(|| {
+            link(
+                manifest,
+                unique_ids.as_deref(),
+                current_project,
+                &restricted_packages,
+            )
+        })
+    };
+    let rows = PyList::empty(py);
+    for error in errors {
+        let row = PyDict::new(py);
+        row.set_item("unique_id", error.unique_id)?;
+        row.set_item("kind", error.error.kind())?;
+        row.set_item("message", error.error.to_string())?;
+        rows.append(row)?;
+    }
+    Ok(rows.into())
+}
+
+pub fn register_node_construction_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(build_node, m)?)?;
+    m.add_function(wrap_pyfunction!(build_sources, m)?)?;
+    m.add_function(wrap_pyfunction!(link_dependencies, m)?)?;
+    Ok(())
+}
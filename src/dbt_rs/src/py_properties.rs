@@ -0,0 +1,126 @@
+use crate::properties::{parse_properties_files as parse_files, Located, PropertiesInput};
+use crate::py_manifest::value_to_py;
+use crate::yaml_loader::Location;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use serde::Serialize;
+
+fn set_location(dict: &PyDict, location: Option<Location>) -> PyResult<()> {
+    dict.set_item("line", location.map(|l| l.line))?;
+    dict.set_item("column", location.map(|l| l.column))?;
+    Ok(())
+}
+
+fn entries_to_list<T: Serialize>(py: Python, entries: &[Located<T>]) -> PyResult<PyObject> {
+    let rows = PyList::empty(py);
+    for entry in entries {
+        let value = serde_json::to_value(&entry.value)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let row: &PyDict = value_to_py(py, &value)?.into_ref(py).downcast()?;
+        set_location(row, Some(entry.location))?;
+        rows.append(row)?;
+    }
+    Ok(rows.into())
+}
+
+fn properties_input(item: &PyAny) -> PyResult<PropertiesInput> {
+    if let Ok(path) = item.extract::<String>() {
+        return Ok(PropertiesInput {
+            path,
+            contents: None,
+        });
+    }
+    let pair: &PyTuple = item.downcast()?;
+    let (path, contents) = pair.extract::<(String, String)>()?;
+    Ok(PropertiesInput {
+        path,
+        contents: Some(contents),
+    })
+}
+
+/// Parse YAML properties files (`schema.yml`) on `threads` threads (default:
+/// one per CPU). Each file is a path to read or a `(path, contents)` tuple.
+/// Scalars resolve as with PyYAML's `SafeLoader`.
+///
+/// Returns one dict per file, in order, with `path`, `error`, `version`,
+/// `models`, `seeds`, `snapshots`, `analyses`, `sources`, `exposures`,
+/// `metrics`, `semantic_models`, `saved_queries`, `unit_tests`, `extra` (other
+/// top-level keys) and `warnings`. Entries are the dicts as written, with
+/// `data_tests` in place of `tests` and their `line` and `column` added.
+/// With `validate`, `warnings` lists violations of the properties JSON schema
+/// as `{"path", "line", "column", "message"}`. A file that cannot be read or
+/// parsed has `error` set to `{"message", "line", "column"}` and nothing else.
+#[pyfunction]
+#[pyo3(signature = (files, threads=None, validate=true))]
+pub fn parse_properties_files(
+    py: Python,
+    files: &PyList,
+    threads: Option<usize>,
+    validate: bool,
+) -> PyResult<PyObject> {
+    let inputs = files
+        .iter()
+        .map(properties_input)
+        .collect::<PyResult<Vec<_>>>()?;
+    let threads = threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let results = py.allow_threads(|| parse_files(&inputs, threads, validate));
+
+    let rows = PyList::empty(py);
+    for (input, result) in inputs.iter().zip(results) {
+        let row = PyDict::new(py);
+        row.set_item("path", &input.path)?;
+        let file = match result {
+            Ok(file) => file,
+            Err(e) => {
+                let error = PyDict::new(py);
+                error.set_item("message", e.message)?;
+                set_location(error, e.location)?;
+                row.set_item("error", error)?;
+                rows.append(row)?;
+                continue;
+            }
+        };
+        row.set_item("error", py.None())?;
+        row.set_item(
+            "version",
+            file.version
+                .as_ref()
+                .map(|version| value_to_py(py, version))
+                .transpose()?,
+        )?;
+        row.set_item("models", entries_to_list(py, &file.models)?)?;
+        row.set_item("seeds", entries_to_list(py, &file.seeds)?)?;
+        row.set_item("snapshots", entries_to_list(py, &file.snapshots)?)?;
+        row.set_item("analyses", entries_to_list(py, &file.analyses)?)?;
+        row.set_item("sources", entries_to_list(py, &file.sources)?)?;
+        row.set_item("exposures", entries_to_list(py, &file.exposures)?)?;
+        row.set_item("metrics", entries_to_list(py, &file.metrics)?)?;
+        row.set_item(
+            "semantic_models",
+            entries_to_list(py, &file.semantic_models)?,
+        )?;
+        row.set_item("saved_queries", entries_to_list(py, &file.saved_queries)?)?;
+        row.set_item("unit_tests", entries_to_list(py, &file.unit_tests)?)?;
+        row.set_item(
+            "extra",
+            value_to_py(py, &serde_json::Value::Object(file.extra))?,
+        )?;
+        let warnings = PyList::empty(py);
+        for warning in file.warnings {
+            let item = PyDict::new(py);
+            item.set_item("path", warning.path)?;
+            set_location(item, warning.location)?;
+            item.set_item("message", warning.message)?;
+            warnings.append(item)?;
+        }
+        row.set_item("warnings", warnings)?;
+        rows.append(row)?;
+    }
+    Ok(rows.into())
+}
+
+pub fn register_properties_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse_properties_files, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::dbtignore::IgnoreSpec;
+use crate::file_scanner::{diff_files, scan_projects, FileTypeSearch, ProjectSearch, ScanError};
+use crate::py_manifest::{file_hash_to_dict, read_manifest, DbtManifest};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::path::{Path, PathBuf};
+
+fn required<'a>(dict: &'a PyDict, key: &str) -> PyResult<&'a PyAny> {
+    dict.get_item(key)?.ok_or_else(|| {
+        pyo3::exceptions::PyKeyError::new_err(format!("project is missing '{}'", key))
+    })
+}
+
+fn project_search(project: &PyDict) -> PyResult<ProjectSearch> {
+    let project_root: PathBuf = required(project, "project_root")?
+        .extract::<String>()?
+        .into();
+    let mut file_types = Vec::new();
+    for (parse_file_type, info) in required(project, "file_types")?
+        .downcast::<PyDict>()?
+        .iter()
+    {
+        let info = info.downcast::<PyDict>()?;
+        file_types.push(FileTypeSearch {
+            parse_file_type: parse_file_type.str()?.to_string(),
+            paths: required(info, "paths")?.extract()?,
+            extensions: required(info, "extensions")?.extract()?,
+        });
+    }
+    let ignore = match project.get_item("ignore_lines")? {
+        Some(lines) if !lines.is_none() => {
+            let lines: Vec<String> = lines.extract()?;
+            Some(IgnoreSpec::from_lines(lines.iter().map(String::as_str)))
+        }
+        _ => IgnoreSpec::from_project_root(Path::new(&project_root))
+            .map_err(|e| pyo3::exceptions::PyOSError::new_err(e.to_string()))?,
+    };
+    Ok(ProjectSearch {
+        project_name: required(project, "project_name")?.extract()?,
+        project_root,
+        file_types,
+        ignore,
+    })
+}
+
+fn scan_error(e: ScanError) -> PyErr {
+    match e {
+        ScanError::Io { .. } => pyo3::exceptions::PyOSError::new_err(e.to_string()),
+        ScanError::Encoding { .. } => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+    }
+}
+
+/// Find and hash the files dbt would parse, walking directories and hashing
+/// contents on `threads` threads (default: one per CPU).
+///
+/// Each project is a dict with `project_name`, `project_root` and
+/// `file_types` shaped like `get_file_types_for_project`:
+/// `{parse_file_type: {"paths": [...], "extensions": [...]}}`. An optional
+/// `ignore_lines` list replaces reading `<project_root>/.dbtignore`.
+///
+/// Returns `{"files": [...], "added", "changed", "deleted", "unchanged"}`.
+/// Each file is a dict with `file_id`, `project_name`, `searched_path`,
+/// `relative_path`, `original_file_path`, `parse_file_type`,
+/// `modification_time` and `checksum` (`{name, checksum}`). The diff lists hold file_ids compared
+/// with the file hashes of `previous`, and are None without it.
+#[pyfunction]
+#[pyo3(signature = (projects, previous=None, threads=None))]
+pub fn scan_project_files(
+    py: Python,
+    projects: &PyList,
+    previous: Option<&DbtManifest>,
+    threads: Option<usize>,
+) -> PyResult<PyObject> {
+    let searches = projects
+        .iter()
+        .map(|project| project_search(project.downcast()?))
+        .collect::<PyResult<Vec<_>>>()?;
+    let previous = previous
+        .map(|manifest| {
+            read_manifest(manifest.shared())?
+                .file_hashes()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("files: {}", e)))
+        })
+        .transpose()?;
+    let threads = threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+
+    let files = py
+        .allow_threads(|| scan_projects(&searches, threads))
+        .map_err(scan_error)?;
+
+    let out = PyDict::new(py);
+    let rows = PyList::empty(py);
+    for file in &files {
+        let dict = PyDict::new(py);
+        dict.set_item("file_id", &file.file_id)?;
+        dict.set_item("project_name", &file.project_name)?;
+        dict.set_item("searched_path", &file.searched_path)?;
+        dict.set_item("relative_path", &file.relative_path)?;
+        dict.set_item("original_file_path", &file.original_file_path)?;
+        dict.set_item("parse_file_type", &file.parse_file_type)?;
+        dict.set_item("modification_time", file.modification_time)?;
+        dict.set_item(
+            "checksum",
+            file_hash_to_dict(py, &file.checksum.name, &file.checksum.checksum)?,
+        )?;
+        rows.append(dict)?;
+    }
+    out.set_item("files", rows)?;
+    match previous {
+        Some(previous) => {
+            let diff = diff_files(&searches, &files, &previous);
+            out.set_item("added", diff.added)?;
+            out.set_item("changed", diff.changed)?;
+            out.set_item("deleted", diff.deleted)?;
+            out.set_item("unchanged", diff.unchanged)?;
+        }
+        None => {
+            for key in ["added", "changed", "deleted", "unchanged"] {
+                out.set_item(key, py.None())?;
+            }
+        }
+    }
+    Ok(out.into())
+}
+
+pub fn register_scanner_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(scan_project_files, m)?)?;
+    Ok(())
+}
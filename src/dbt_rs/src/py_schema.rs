@@ -0,0 +1,63 @@
+use crate::py_manifest::{read_manifest, DbtManifest};
+use crate::schema_validation::{validate_against, validate_artifact, SchemaViolation};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+
+fn parse_artifact(json_string: &str) -> PyResult<Value> {
+    serde_json::from_str(json_string)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+fn violations_to_list(py: Python, violations: Vec<SchemaViolation>) -> PyResult<PyObject> {
+    let list = PyList::empty(py);
+    for violation in violations {
+        let dict = PyDict::new(py);
+        dict.set_item("path", violation.path)?;
+        dict.set_item("message", violation.message)?;
+        list.append(dict)?;
+    }
+    Ok(list.into())
+}
+
+/// Validate an artifact (manifest, run_results, catalog or sources JSON)
+/// against the bundled schema named by its `metadata.dbt_schema_version`.
+/// Returns a list of `{"path": <JSON pointer>, "message": ...}` dicts, empty
+/// when the artifact is valid.
+#[pyfunction]
+pub fn validate_artifact_json(py: Python, json_string: &str) -> PyResult<PyObject> {
+    let violations = validate_artifact(&parse_artifact(json_string)?)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    violations_to_list(py, violations)
+}
+
+/// Like `validate_artifact_json`, against an explicit schema such as
+/// `("run-results", 6)`.
+#[pyfunction]
+pub fn validate_artifact_json_against(
+    py: Python,
+    json_string: &str,
+    artifact: &str,
+    version: u32,
+) -> PyResult<PyObject> {
+    let violations = validate_against(&parse_artifact(json_string)?, artifact, version)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    violations_to_list(py, violations)
+}
+
+/// Validate a loaded manifest against the schema of its declared version.
+#[pyfunction]
+pub fn validate_manifest_handle(py: Python, manifest: &DbtManifest) -> PyResult<PyObject> {
+    let value = serde_json::to_value(&*read_manifest(manifest.shared())?)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    let violations = validate_artifact(&value)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    violations_to_list(py, violations)
+}
+
+pub fn register_schema_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(validate_artifact_json, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_artifact_json_against, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_manifest_handle, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use crate::py_manifest::{read_manifest, DbtManifest};
+use crate::sql_analysis::{
+    check_node_dependencies, display_relation, referenced_relations, Dialect, RelationIndex,
+};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+fn parse_dialect(dialect: &str) -> PyResult<Dialect> {
+    dialect
+        .parse()
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+/// The relations compiled SQL reads from, as lowercased dotted names, in
+/// order of first appearance. CTE names are left out. `dialect` is one of
+/// `ansi`, `postgres`, `snowflake` or `bigquery`. Raises `ValueError` if the
+/// SQL does not parse.
+#[pyfunction]
+#[pyo3(signature = (sql, dialect="ansi"))]
+pub fn extract_sql_relations(sql: &str, dialect: &str) -> PyResult<Vec<String>> {
+    let dialect = parse_dialect(dialect)?;
+    Ok(referenced_relations(sql, dialect)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?
+        .iter()
+        .map(|parts| display_relation(parts))
+        .collect())
+}
+
+/// Compare the tables each node's `compiled_code` reads with its
+/// `depends_on.nodes`, matching them through `relation_name`.
+///
+/// Returns one dict per node with findings, sorted by unique_id:
+/// `{"unique_id", "undeclared": [{"relation", "unique_id"}], "unused": [...],
+/// "parse_error"}`. `undeclared` lists tables read without
+/// `ref()`/`source()`, with the manifest resource they belong to when it is
+/// unambiguous; `unused` lists declared dependencies the SQL never reads;
+/// `parse_error` is set, and the lists empty, when the SQL does not parse.
+/// Defaults to every node with compiled code, and to the dialect of
+/// `metadata.adapter_type`.
+#[pyfunction]
+#[pyo3(signature = (manifest, unique_ids=None, dialect=None))]
+pub fn find_undeclared_dependencies(
+    py: Python,
+    manifest: &DbtManifest,
+    unique_ids: Option<Vec<String>>,
+    dialect: Option<&str>,
+) -> PyResult<PyObject> {
+    let dialect = dialect.map(parse_dialect).transpose()?;
+    let reports = {
+        let manifest = read_manifest(manifest.shared())?;
+        let dialect = dialect.unwrap_or_else(|| {
+            let adapter_type = manifest.metadata.as_ref().map(|m| m.adapter_type.as_str());
+            Dialect::for_adapter(adapter_type.unwrap_or_default())
+        });
+        let mut ids = unique_ids.unwrap_or_else(|| manifest.nodes.keys().cloned().collect());
+        ids.sort();
+        py.allow_threads(|| {
+            let index = RelationIndex::new(&manifest, dialect);
+            ids.iter()
+                .filter_map(|unique_id| {
+                    check_node_dependencies(&manifest, &index, unique_id, dialect)
+                })
+                .filter(|report| !report.is_empty())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let rows = PyList::empty(py);
+    for report in reports {
+        let row = PyDict::new(py);
+        row.set_item("unique_id", report.unique_id)?;
+        let undeclared = PyList::empty(py);
+        for relation in report.undeclared {
+            let item = PyDict::new(py);
+            item.set_item("relation", relation.relation)?;
+            item.set_item("unique_id", relation.unique_id)?;
+            undeclared.append(item)?;
+        }
+        row.set_item("undeclared", undeclared)?;
+        row.set_item("unused", report.unused)?;
+        row.set_item("parse_error", report.parse_error)?;
+        rows.append(row)?;
+    }
+    Ok(rows.into())
+}
+
+pub fn register_sql_analysis_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(extract_sql_relations, m)?)?;
+    m.add_function(wrap_pyfunction!(find_undeclared_dependencies, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use crate::py_manifest::{read_manifest, value_to_py, DbtManifest};
+use crate::static_parser::{extract_static_calls as extract, StaticExtraction};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+fn extraction_to_dict(py: Python, extraction: StaticExtraction) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    let refs = PyList::empty(py);
+    for r in extraction.refs {
+        let item = PyDict::new(py);
+        item.set_item("name", r.name)?;
+        item.set_item("package", r.package)?;
+        match r.version {
+            Some(version) => item.set_item("version", value_to_py(py, &version)?)?,
+            None => item.set_item("version", py.None())?,
+        }
+        refs.append(item)?;
+    }
+    dict.set_item("refs", refs)?;
+    dict.set_item(
+        "sources",
+        extraction
+            .sources
+            .into_iter()
+            .map(Vec::from)
+            .collect::<Vec<_>>(),
+    )?;
+    let configs = PyList::empty(py);
+    for (key, value) in extraction.configs {
+        configs.append(PyList::new(py, [key.into_py(py), value_to_py(py, &value)?]))?;
+    }
+    dict.set_item("configs", configs)?;
+    dict.set_item("metrics", extraction.metrics)?;
+    dict.set_item("simple", extraction.simple)?;
+    Ok(dict.into())
+}
+
+/// Statically extract the `ref`, `source`, `config` and `metric` calls of
+/// Jinja-SQL, in the shape of `dbt_extractor.py_extract_from_source`:
+/// `{"refs": [{name, package, version}], "sources": [[source, table]],
+/// "configs": [[key, value]], "metrics": [[package?, name]], "simple": bool}`.
+/// When `simple` is False the template needs rendering and the calls found
+/// are only those with literal arguments.
+#[pyfunction]
+pub fn extract_static_calls(py: Python, raw_code: &str) -> PyResult<PyObject> {
+    extraction_to_dict(py, py.allow_threads(|| extract(raw_code)))
+}
+
+/// `extract_static_calls` for the `raw_code` of manifest nodes, keyed by
+/// unique_id. Defaults to every node with raw code; unknown ids are skipped.
+#[pyfunction]
+#[pyo3(signature = (manifest, unique_ids=None))]
+pub fn extract_static_calls_for_nodes(
+    py: Python,
+    manifest: &DbtManifest,
+    unique_ids: Option<Vec<String>>,
+) -> PyResult<PyObject> {
+    let extractions: Vec<(String, StaticExtraction)> = {
+        let manifest = read_manifest(manifest.shared())?;
+        let ids = unique_ids.unwrap_or_else(|| manifest.nodes.keys().cloned().collect());
+        py.allow_threads(|| {
+            ids.into_iter()
+                .filter_map(|unique_id| {
+                    let raw_code = manifest.nodes.get(&unique_id)?.raw_code.as_deref()?;
+                    let extraction = extract(raw_code);
+                    Some((unique_id, extraction))
+                })
+                .collect()
+        })
+    };
+    let out = PyDict::new(py);
+    for (unique_id, extraction) in extractions {
+        out.set_item(unique_id, extraction_to_dict(py, extraction)?)?;
+    }
+    Ok(out.into())
+}
+
+pub fn register_static_parser_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(extract_static_calls, m)?)?;
+    m.add_function(wrap_pyfunction!(extract_static_calls_for_nodes, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,470 @@
+use crate::manifest::{OxideDependsOn, OxideMacro, OxideManifest, OxideNode, OxideSource};
+use crate::py_manifest::{read_manifest, SharedManifest};
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+
+/// Which `OxideManifest` collection a view or mapping reads from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collection {
+    Nodes,
+    Sources,
+    Macros,
+    Exposures,
+    Metrics,
+    Groups,
+    SemanticModels,
+    SavedQueries,
+    UnitTests,
+}
+
+impl Collection {
+    fn name(self) -> &'static str {
+        match self {
+            Collection::Nodes => "nodes",
+            Collection::Sources => "sources",
+            Collection::Macros => "macros",
+            Collection::Exposures => "exposures",
+            Collection::Metrics => "metrics",
+            Collection::Groups => "groups",
+            Collection::SemanticModels => "semantic_models",
+            Collection::SavedQueries => "saved_queries",
+            Collection::UnitTests => "unit_tests",
+        }
+    }
+
+    fn ids(self, m: &OxideManifest) -> Vec<String> {
+        let mut ids: Vec<String> = match self {
+            Collection::Nodes => m.nodes.keys().cloned().collect(),
+            Collection::Sources => m.sources.keys().cloned().collect(),
+            Collection::Macros => m.macros.keys().cloned().collect(),
+            Collection::Exposures => m.exposures.keys().cloned().collect(),
+            Collection::Metrics => m.metrics.keys().cloned().collect(),
+            Collection::Groups => m.groups.keys().cloned().collect(),
+            Collection::SemanticModels => m.semantic_models.keys().cloned().collect(),
+            Collection::SavedQueries => m.saved_queries.keys().cloned().collect(),
+            Collection::UnitTests => m.unit_tests.keys().cloned().collect(),
+        };
+        ids.sort();
+        ids
+    }
+
+    fn len(self, m: &OxideManifest) -> usize {
+        match self {
+            Collection::Nodes => m.nodes.len(),
+            Collection::Sources => m.sources.len(),
+            Collection::Macros => m.macros.len(),
+            Collection::Exposures => m.exposures.len(),
+            Collection::Metrics => m.metrics.len(),
+            Collection::Groups => m.groups.len(),
+            Collection::SemanticModels => m.semantic_models.len(),
+            Collection::SavedQueries => m.saved_queries.len(),
+            Collection::UnitTests => m.unit_tests.len(),
+        }
+    }
+
+    fn contains(self, m: &OxideManifest, id: &str) -> bool {
+        match self {
+            Collection::Nodes => m.nodes.contains_key(id),
+            Collection::Sources => m.sources.contains_key(id),
+            Collection::Macros => m.macros.contains_key(id),
+            Collection::Exposures => m.exposures.contains_key(id),
+            Collection::Metrics => m.metrics.contains_key(id),
+            Collection::Groups => m.groups.contains_key(id),
+            Collection::SemanticModels => m.semantic_models.contains_key(id),
+            Collection::SavedQueries => m.saved_queries.contains_key(id),
+            Collection::UnitTests => m.unit_tests.contains_key(id),
+        }
+    }
+
+    /// Name and dependencies of an entry in one of the "simple" collections.
+    fn name_and_depends_on<'a>(
+        self,
+        m: &'a OxideManifest,
+        id: &str,
+    ) -> Option<(&'a str, Option<&'a OxideDependsOn>)> {
+        match self {
+            Collection::Nodes => m
+                .nodes
+                .get(id)
+                .map(|n| (n.name.as_str(), Some(&n.depends_on))),
+            Collection::Sources => m.sources.get(id).map(|s| (s.name.as_str(), None)),
+            Collection::Macros => m
+                .macros
+                .get(id)
+                .map(|x| (x.name.as_str(), Some(&x.depends_on))),
+            Collection::Exposures => m
+                .exposures
+                .get(id)
+                .map(|x| (x.name.as_str(), Some(&x.depends_on))),
+            Collection::Metrics => m
+                .metrics
+                .get(id)
+                .map(|x| (x.name.as_str(), Some(&x.depends_on))),
+            Collection::Groups => m.groups.get(id).map(|x| (x.name.as_str(), None)),
+            Collection::SemanticModels => m
+                .semantic_models
+                .get(id)
+                .map(|x| (x.name.as_str(), Some(&x.depends_on))),
+            Collection::SavedQueries => m
+                .saved_queries
+                .get(id)
+                .map(|x| (x.name.as_str(), Some(&x.depends_on))),
+            Collection::UnitTests => m
+                .unit_tests
+                .get(id)
+                .map(|x| (x.name.as_str(), Some(&x.depends_on))),
+        }
+    }
+}
+
+fn missing(collection: Collection, unique_id: &str) -> PyErr {
+    pyo3::exceptions::PyKeyError::new_err(format!(
+        "{} not found in manifest.{}",
+        unique_id,
+        collection.name()
+    ))
+}
+
+/// Read-only view of an `OxideNode`. Fields are read from the Rust manifest
+/// on access; nothing is copied up front.
+#[pyclass]
+pub struct ManifestNodeView {
+    manifest: SharedManifest,
+    #[pyo3(get)]
+    unique_id: String,
+}
+
+impl ManifestNodeView {
+    fn with_node<R>(&self, f: impl FnOnce(&OxideNode) -> R) -> PyResult<R> {
+        let manifest = read_manifest(&self.manifest)?;
+        let node = manifest
+            .nodes
+            .get(&self.unique_id)
+            .ok_or_else(|| missing(Collection::Nodes, &self.unique_id))?;
+        Ok(f(node))
+    }
+}
+
+#[pymethods]
+impl ManifestNodeView {
+    #[getter]
+    fn name(&self) -> PyResult<String> {
+        self.with_node(|n| n.name.clone())
+    }
+
+    #[getter]
+    fn resource_type(&self) -> PyResult<String> {
+        self.with_node(|n| n.resource_type.clone())
+    }
+
+    #[getter]
+    fn package_name(&self) -> PyResult<String> {
+        self.with_node(|n| n.package_name.clone())
+    }
+
+    #[getter]
+    fn fqn(&self) -> PyResult<Vec<String>> {
+        self.with_node(|n| n.fqn.clone())
+    }
+
+    #[getter]
+    fn depends_on_nodes(&self) -> PyResult<Vec<String>> {
+        self.with_node(|n| n.depends_on.nodes.clone())
+    }
+
+    #[getter]
+    fn depends_on_macros(&self) -> PyResult<Vec<String>> {
+        self.with_node(|n| n.depends_on.macros.clone())
+    }
+
+    #[getter]
+    fn raw_code(&self) -> PyResult<Option<String>> {
+        self.with_node(|n| n.raw_code.clone())
+    }
+
+    #[getter]
+    fn compiled_code(&self) -> PyResult<Option<String>> {
+        self.with_node(|n| n.compiled_code.clone())
+    }
+
+    #[getter]
+    fn materialized(&self) -> PyResult<Option<String>> {
+        self.with_node(|n| n.config.materialized.clone())
+    }
+
+    #[getter]
+    fn enabled(&self) -> PyResult<bool> {
+        self.with_node(|n| n.config.enabled)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ManifestNodeView({})", self.unique_id)
+    }
+}
+
+/// Read-only view of an `OxideSource`.
+#[pyclass]
+pub struct SourceView {
+    manifest: SharedManifest,
+    #[pyo3(get)]
+    unique_id: String,
+}
+
+impl SourceView {
+    fn with_source<R>(&self, f: impl FnOnce(&OxideSource) -> R) -> PyResult<R> {
+        let manifest = read_manifest(&self.manifest)?;
+        let source = manifest
+            .sources
+            .get(&self.unique_id)
+            .ok_or_else(|| missing(Collection::Sources, &self.unique_id))?;
+        Ok(f(source))
+    }
+}
+
+#[pymethods]
+impl SourceView {
+    #[getter]
+    fn name(&self) -> PyResult<String> {
+        self.with_source(|s| s.name.clone())
+    }
+
+    #[getter]
+    fn source_name(&self) -> PyResult<String> {
+        self.with_source(|s| s.source_name.clone())
+    }
+
+    #[getter]
+    fn package_name(&self) -> PyResult<String> {
+        self.with_source(|s| s.package_name.clone())
+    }
+
+    #[getter]
+    fn database(&self) -> PyResult<Option<String>> {
+        self.with_source(|s| s.database.clone())
+    }
+
+    #[getter]
+    fn schema(&self) -> PyResult<Option<String>> {
+        self.with_source(|s| s.schema.clone())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SourceView({})", self.unique_id)
+    }
+}
+
+/// Read-only view of an `OxideMacro`.
+#[pyclass]
+pub struct MacroView {
+    manifest: SharedManifest,
+    #[pyo3(get)]
+    unique_id: String,
+}
+
+impl MacroView {
+    fn with_macro<R>(&self, f: impl FnOnce(&OxideMacro) -> R) -> PyResult<R> {
+        let manifest = read_manifest(&self.manifest)?;
+        let m = manifest
+            .macros
+            .get(&self.unique_id)
+            .ok_or_else(|| missing(Collection::Macros, &self.unique_id))?;
+        Ok(f(m))
+    }
+}
+
+#[pymethods]
+impl MacroView {
+    #[getter]
+    fn name(&self) -> PyResult<String> {
+        self.with_macro(|m| m.name.clone())
+    }
+
+    #[getter]
+    fn package_name(&self) -> PyResult<String> {
+        self.with_macro(|m| m.package_name.clone())
+    }
+
+    #[getter]
+    fn macro_sql(&self) -> PyResult<Option<String>> {
+        self.with_macro(|m| m.macro_sql.clone())
+    }
+
+    #[getter]
+    fn depends_on_macros(&self) -> PyResult<Vec<String>> {
+        self.with_macro(|m| m.depends_on.macros.clone())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MacroView({})", self.unique_id)
+    }
+}
+
+/// Read-only view of an exposure, metric, group, semantic model, saved query
+/// or unit test; these only carry a name and dependencies in `OxideManifest`.
+#[pyclass]
+pub struct ResourceView {
+    manifest: SharedManifest,
+    collection: Collection,
+    #[pyo3(get)]
+    unique_id: String,
+}
+
+impl ResourceView {
+    fn with_entry<R>(&self, f: impl FnOnce(&str, Option<&OxideDependsOn>) -> R) -> PyResult<R> {
+        let manifest = read_manifest(&self.manifest)?;
+        let (name, depends_on) = self
+            .collection
+            .name_and_depends_on(&manifest, &self.unique_id)
+            .ok_or_else(|| missing(self.collection, &self.unique_id))?;
+        Ok(f(name, depends_on))
+    }
+}
+
+#[pymethods]
+impl ResourceView {
+    #[getter]
+    fn name(&self) -> PyResult<String> {
+        self.with_entry(|name, _| name.to_string())
+    }
+
+    #[getter]
+    fn depends_on_nodes(&self) -> PyResult<Vec<String>> {
+        self.with_entry(|_, d| d.map(|d| d.nodes.clone()).unwrap_or_default())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ResourceView({})", self.unique_id)
+    }
+}
+
+/// Mapping-like, read-only access to one manifest collection:
+/// `manifest.nodes["model.x.y"]`, `len`, `in` and iteration over unique_ids.
+#[pyclass]
+pub struct ManifestCollection {
+    manifest: SharedManifest,
+    collection: Collection,
+}
+
+impl ManifestCollection {
+    pub fn new(manifest: SharedManifest, collection: Collection) -> Self {
+        ManifestCollection {
+            manifest,
+            collection,
+        }
+    }
+
+    fn view(&self, py: Python, unique_id: String) -> PyResult<PyObject> {
+        let manifest = self.manifest.clone();
+        let view = match self.collection {
+            Collection::Nodes => Py::new(
+                py,
+                ManifestNodeView {
+                    manifest,
+                    unique_id,
+                },
+            )?
+            .into_py(py),
+            Collection::Sources => Py::new(
+                py,
+                SourceView {
+                    manifest,
+                    unique_id,
+                },
+            )?
+            .into_py(py),
+            Collection::Macros => Py::new(
+                py,
+                MacroView {
+                    manifest,
+                    unique_id,
+                },
+            )?
+            .into_py(py),
+            collection => Py::new(
+                py,
+                ResourceView {
+                    manifest,
+                    collection,
+                    unique_id,
+                },
+            )?
+            .into_py(py),
+        };
+        Ok(view)
+    }
+
+    fn ids(&self) -> PyResult<Vec<String>> {
+        let manifest = read_manifest(&self.manifest)?;
+        Ok(self.collection.ids(&manifest))
+    }
+}
+
+#[pymethods]
+impl ManifestCollection {
+    fn __len__(&self) -> PyResult<usize> {
+        let manifest = read_manifest(&self.manifest)?;
+        Ok(self.collection.len(&manifest))
+    }
+
+    fn __contains__(&self, unique_id: &str) -> PyResult<bool> {
+        let manifest = read_manifest(&self.manifest)?;
+        Ok(self.collection.contains(&manifest, unique_id))
+    }
+
+    fn __getitem__(&self, py: Python, unique_id: String) -> PyResult<PyObject> {
+        if !self.__contains__(&unique_id)? {
+            return Err(missing(self.collection, &unique_id));
+        }
+        self.view(py, unique_id)
+    }
+
+    /// Iterates over unique_ids in sorted order, like iterating a dict's keys.
+    fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        let keys = PyList::new(py, self.ids()?);
+        Ok(keys.call_method0("__iter__")?.into_py(py))
+    }
+
+    fn keys(&self) -> PyResult<Vec<String>> {
+        self.ids()
+    }
+
+    fn values(&self, py: Python) -> PyResult<Vec<PyObject>> {
+        self.ids()?
+            .into_iter()
+            .map(|id| self.view(py, id))
+            .collect()
+    }
+
+    fn items(&self, py: Python) -> PyResult<Vec<(String, PyObject)>> {
+        self.ids()?
+            .into_iter()
+            .map(|id| Ok((id.clone(), self.view(py, id)?)))
+            .collect()
+    }
+
+    #[pyo3(signature = (unique_id, default=None))]
+    fn get(&self, py: Python, unique_id: String, default: Option<PyObject>) -> PyResult<PyObject> {
+        if self.__contains__(&unique_id)? {
+            self.view(py, unique_id)
+        } else {
+            Ok(default.unwrap_or_else(|| py.None()))
+        }
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "ManifestCollection({}, {} entries)",
+            self.collection.name(),
+            self.__len__()?
+        ))
+    }
+}
+
+pub fn register_views_module(m: &PyModule) -> PyResult<()> {
+    m.add_class::<ManifestNodeView>()?;
+    m.add_class::<SourceView>()?;
+    m.add_class::<MacroView>()?;
+    m.add_class::<ResourceView>()?;
+    m.add_class::<ManifestCollection>()?;
+    Ok(())
+}
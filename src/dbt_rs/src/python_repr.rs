@@ -0,0 +1,123 @@
+//! How Python prints the objects JSON values load as, for messages and
+//! names that must match dbt's: `repr()`, `str()` and `type()`.
+
+use serde_json::Value;
+
+/// The name of `type(value)`, e.g. `int`.
+pub fn python_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "NoneType",
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "str",
+        Value::Array(_) => "list",
+        Value::Object(_) => "dict",
+    }
+}
+
+/// `type(value)` as printed, e.g. `<class 'int'>`.
+pub fn python_type(value: &Value) -> String {
+    format!("<class '{}'>", python_type_name(value))
+}
+
+/// `repr()` of a string: single quotes unless only double quotes avoid
+/// escaping.
+pub fn python_repr_str(s: &str) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 || (0x7f..=0xa0).contains(&(c as u32)) => {
+                out.push_str(&format!("\\x{:02x}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+/// `repr()` of a float: shortest round-trip digits, in exponent notation
+/// outside `[1e-4, 1e16)`.
+fn python_float(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+        let formatted = format!("{:e}", value);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}e{}{:02}", mantissa, sign, exponent.abs());
+    }
+    format!("{:?}", value)
+}
+
+/// `repr()` of the Python object a JSON value loads as.
+pub fn python_repr(value: &Value) -> String {
+    match value {
+        Value::Null => "None".to_string(),
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => python_float(f),
+            _ => n.to_string(),
+        },
+        Value::String(s) => python_repr_str(s),
+        Value::Array(items) => format!(
+            "[{}]",
+            items.iter().map(python_repr).collect::<Vec<_>>().join(", ")
+        ),
+        Value::Object(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!("{}: {}", python_repr_str(key), python_repr(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// `str()` of the Python object a JSON value loads as.
+pub fn python_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => python_repr(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_python_repr() {
+        assert_eq!(
+            python_repr(&json!({"a": ["it's", "x\ny"], "b": 1.5, "c": null, "d": 1e20})),
+            r#"{'a': ["it's", 'x\ny'], 'b': 1.5, 'c': None, 'd': 1e+20}"#
+        );
+        assert_eq!(python_str(&json!("it's")), "it's");
+        assert_eq!(python_str(&json!([true, 1e-5])), "[True, 1e-05]");
+        assert_eq!(python_type(&json!(1)), "<class 'int'>");
+        assert_eq!(python_type_name(&json!(null)), "NoneType");
+    }
+}
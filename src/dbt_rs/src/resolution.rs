@@ -0,0 +1,561 @@
+use crate::manifest::{OxideManifest, OxideNode};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// Resource types that can be the target of `ref()`.
+const REFABLE_RESOURCE_TYPES: [&str; 3] = ["model", "seed", "snapshot"];
+
+type PackageIndex<T> = HashMap<String, BTreeMap<String, T>>;
+
+/// Lookup tables for `ref()` and `source()`, equivalent to dbt's
+/// `RefableLookup`, `SourceLookup` and `DisabledLookup`.
+///
+/// Keys are search names (`name`, `name.v<version>`, `source_name.table`),
+/// mapped to package name and then unique_id.
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionIndex {
+    refs: PackageIndex<String>,
+    sources: PackageIndex<String>,
+    disabled: PackageIndex<Vec<String>>,
+}
+
+impl ResolutionIndex {
+    pub fn build(manifest: &OxideManifest) -> Self {
+        let mut index = ResolutionIndex::default();
+
+        for node in manifest.nodes.values() {
+            index.add_node(node);
+        }
+        for source in manifest.sources.values() {
+            index
+                .sources
+                .entry(source.search_name())
+                .or_default()
+                .insert(source.package_name.clone(), source.unique_id.clone());
+        }
+        for entries in manifest.disabled.iter().flat_map(HashMap::values) {
+            for node in entries {
+                let key = if node.resource_type == "source" {
+                    disabled_source_search_name(node)
+                } else {
+                    node.search_name()
+                };
+                index
+                    .disabled
+                    .entry(key)
+                    .or_default()
+                    .entry(node.package_name.clone())
+                    .or_default()
+                    .push(node.unique_id.clone());
+            }
+        }
+        index
+    }
+
+    fn add_node(&mut self, node: &OxideNode) {
+        if !REFABLE_RESOURCE_TYPES.contains(&node.resource_type.as_str()) {
+            return;
+        }
+        let by_name = self.refs.entry(node.name.clone()).or_default();
+        if !node.is_versioned() {
+            by_name.insert(node.package_name.clone(), node.unique_id.clone());
+            return;
+        }
+        // Unpinned refs resolve to the latest version only.
+        if node.is_latest_version() {
+            by_name.insert(node.package_name.clone(), node.unique_id.clone());
+        }
+        self.refs
+            .entry(node.search_name())
+            .or_default()
+            .insert(node.package_name.clone(), node.unique_id.clone());
+    }
+
+    fn find_ref(&self, key: &str, package: Option<&str>) -> Vec<&String> {
+        find_in_package(&self.refs, key, package)
+    }
+
+    fn find_source(&self, key: &str, package: Option<&str>) -> Option<&String> {
+        // Like dbt's `find_unique_id_for_package`: without a package, the
+        // first match wins.
+        find_in_package(&self.sources, key, package)
+            .into_iter()
+            .next()
+    }
+
+    fn find_disabled(&self, key: &str, package: Option<&str>) -> Option<&String> {
+        find_in_package(&self.disabled, key, package)
+            .into_iter()
+            .flatten()
+            .next()
+    }
+}
+
+fn find_in_package<'a, T>(
+    index: &'a PackageIndex<T>,
+    key: &str,
+    package: Option<&str>,
+) -> Vec<&'a T> {
+    match (index.get(key), package) {
+        (None, _) => Vec::new(),
+        (Some(by_package), None) => by_package.values().collect(),
+        (Some(by_package), Some(package)) => by_package.get(package).into_iter().collect(),
+    }
+}
+
+/// Disabled sources are stored with node fields only, so recover
+/// `source_name` from `source.<package>.<source_name>.<table>`.
+fn disabled_source_search_name(node: &OxideNode) -> String {
+    let prefix = format!("source.{}.", node.package_name);
+    let suffix = format!(".{}", node.name);
+    let source_name = node
+        .unique_id
+        .strip_prefix(&prefix)
+        .and_then(|rest| rest.strip_suffix(&suffix))
+        .unwrap_or_default();
+    format!("{}.{}", source_name, node.name)
+}
+
+/// dbt's `_packages_to_search`: an explicit package, otherwise the root
+/// project, then the calling node's package, then any package (`None`).
+fn packages_to_search<'a>(
+    current_project: &'a str,
+    node_package: &'a str,
+    target_package: Option<&'a str>,
+) -> Vec<Option<&'a str>> {
+    match target_package {
+        Some(package) => vec![Some(package)],
+        None if current_project == node_package => vec![Some(current_project), None],
+        None => vec![Some(current_project), Some(node_package), None],
+    }
+}
+
+/// The node that contains the `ref()` or `source()` call.
+#[derive(Debug, Clone, Copy)]
+pub struct Referrer<'a> {
+    pub package_name: &'a str,
+    pub resource_type: &'a str,
+    pub group: Option<&'a str>,
+}
+
+/// Project-level settings that affect resolution.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolveContext<'a> {
+    pub current_project: &'a str,
+    /// Packages that set `restrict-access: true`.
+    pub restricted_packages: &'a HashSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    NotFound {
+        target: String,
+    },
+    Disabled {
+        target: String,
+        unique_id: String,
+    },
+    Ambiguous {
+        target: String,
+        candidates: Vec<String>,
+    },
+    PrivateRef {
+        target: String,
+        unique_id: String,
+    },
+    ProtectedRef {
+        target: String,
+        unique_id: String,
+    },
+}
+
+impl ResolveError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ResolveError::NotFound { .. } => "not_found",
+            ResolveError::Disabled { .. } => "disabled",
+            ResolveError::Ambiguous { .. } => "ambiguous",
+            ResolveError::PrivateRef { .. } => "private",
+            ResolveError::ProtectedRef { .. } => "protected",
+        }
+    }
+
+    /// Unique_ids related to the error: the disabled or inaccessible node,
+    /// or every ambiguous candidate.
+    pub fn unique_ids(&self) -> Vec<String> {
+        match self {
+            ResolveError::NotFound { .. } => Vec::new(),
+            ResolveError::Ambiguous { candidates, .. } => candidates.clone(),
+            ResolveError::Disabled { unique_id, .. }
+            | ResolveError::PrivateRef { unique_id, .. }
+            | ResolveError::ProtectedRef { unique_id, .. } => vec![unique_id.clone()],
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::NotFound { target } => write!(f, "{} was not found", target),
+            ResolveError::Disabled { target, unique_id } => {
+                write!(f, "{} resolves to disabled node {}", target, unique_id)
+            }
+            ResolveError::Ambiguous { target, candidates } => write!(
+                f,
+                "{} is ambiguous, it matches: {}",
+                target,
+                candidates.join(", ")
+            ),
+            ResolveError::PrivateRef { target, unique_id } => write!(
+                f,
+                "{} resolves to private model {} outside of its group",
+                target, unique_id
+            ),
+            ResolveError::ProtectedRef { target, unique_id } => write!(
+                f,
+                "{} resolves to protected model {} in a package with restrict-access",
+                target, unique_id
+            ),
+        }
+    }
+}
+
+fn describe_ref(name: &str, package: Option<&str>, version: Option<&str>) -> String {
+    let mut args = Vec::new();
+    if let Some(package) = package {
+        args.push(format!("'{}'", package));
+    }
+    args.push(format!("'{}'", name));
+    if let Some(version) = version {
+        args.push(format!("v={}", version));
+    }
+    format!("ref({})", args.join(", "))
+}
+
+impl OxideManifest {
+    /// Resolve `ref(package?, name, version=?)` called from `referrer` to a
+    /// unique_id, applying package precedence and `access` rules.
+    pub fn resolve_ref(
+        &self,
+        referrer: Referrer,
+        name: &str,
+        package: Option<&str>,
+        version: Option<&str>,
+        ctx: ResolveContext,
+    ) -> Result<String, ResolveError> {
+        let target = describe_ref(name, package, version);
+        let key = match version {
+            Some(version) => format!("{}.v{}", name, version),
+            None => name.to_string(),
+        };
+        let index = self.resolution_index();
+        let mut disabled = None;
+
+        for pkg in packages_to_search(ctx.current_project, referrer.package_name, package) {
+            let candidates = index.find_ref(&key, pkg);
+            if candidates.len() > 1 {
+                let mut candidates: Vec<String> = candidates.into_iter().cloned().collect();
+                candidates.sort();
+                return Err(ResolveError::Ambiguous { target, candidates });
+            }
+            if let Some(unique_id) = candidates.first() {
+                if let Some(node) = self.nodes.get(*unique_id) {
+                    if node.config.enabled {
+                        check_access(referrer, node, ctx, &target)?;
+                        return Ok(node.unique_id.clone());
+                    }
+                }
+            }
+            if disabled.is_none() {
+                disabled = index.find_disabled(&key, pkg).cloned();
+            }
+        }
+
+        Err(match disabled {
+            Some(unique_id) => ResolveError::Disabled { target, unique_id },
+            None => ResolveError::NotFound { target },
+        })
+    }
+
+    /// `ref_lookup.get_unique_id`: the enabled node `name` (pinned to
+    /// `version`) in `package`, without precedence or access rules.
+    pub fn find_ref_unique_id(
+        &self,
+        name: &str,
+        package: Option<&str>,
+        version: Option<&str>,
+    ) -> Option<String> {
+        let key = match version {
+            Some(version) => format!("{}.v{}", name, version),
+            None => name.to_string(),
+        };
+        self.resolution_index()
+            .find_ref(&key, package)
+            .first()
+            .map(|unique_id| unique_id.to_string())
+    }
+
+    /// `disabled_lookup.find(name, package)`: the first disabled node found
+    /// under `name`.
+    pub fn find_disabled_unique_id(&self, name: &str, package: Option<&str>) -> Option<String> {
+        self.resolution_index()
+            .find_disabled(name, package)
+            .cloned()
+    }
+
+    /// Resolve `source(source_name, table_name)` called from a node in
+    /// `node_package` to a unique_id.
+    pub fn resolve_source(
+        &self,
+        source_name: &str,
+        table_name: &str,
+        node_package: &str,
+        current_project: &str,
+    ) -> Result<String, ResolveError> {
+        let target = format!("source('{}', '{}')", source_name, table_name);
+        let key = format!("{}.{}", source_name, table_name);
+        let index = self.resolution_index();
+        let mut disabled = None;
+
+        for pkg in packages_to_search(current_project, node_package, None) {
+            if let Some(unique_id) = index.find_source(&key, pkg) {
+                if let Some(source) = self.sources.get(unique_id) {
+                    if source.config.enabled {
+                        return Ok(source.unique_id.clone());
+                    }
+                }
+            }
+            if disabled.is_none() {
+                disabled = index.find_disabled(&key, pkg).cloned();
+            }
+        }
+
+        Err(match disabled {
+            Some(unique_id) => ResolveError::Disabled { target, unique_id },
+            None => ResolveError::NotFound { target },
+        })
+    }
+}
+
+/// dbt's `is_invalid_private_ref` / `is_invalid_protected_ref`.
+fn check_access(
+    referrer: Referrer,
+    target_node: &OxideNode,
+    ctx: ResolveContext,
+    target: &str,
+) -> Result<(), ResolveError> {
+    if target_node.resource_type != "model" || referrer.resource_type == "sql_operation" {
+        return Ok(());
+    }
+    let cross_package = referrer.package_name != target_node.package_name;
+    let restricted = ctx.restricted_packages.contains(&target_node.package_name);
+
+    match target_node.access.as_deref() {
+        Some("private") => {
+            let same_group = matches!(
+                (referrer.group, target_node.group.as_deref()),
+                (Some(a), Some(b)) if !a.is_empty() && a == b
+            );
+            if !same_group || (cross_package && restricted) {
+                return Err(ResolveError::PrivateRef {
+                    target: target.to_string(),
+                    unique_id: target_node.unique_id.clone(),
+                });
+            }
+        }
+        Some("protected") if cross_package && restricted => {
+            return Err(ResolveError::ProtectedRef {
+                target: target.to_string(),
+                unique_id: target_node.unique_id.clone(),
+            });
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "nodes": {
+            "model.root.orders": {"unique_id": "model.root.orders", "name": "orders", "resource_type": "model", "package_name": "root"},
+            "model.pkg.orders": {"unique_id": "model.pkg.orders", "name": "orders", "resource_type": "model", "package_name": "pkg"},
+            "model.pkg.helper": {"unique_id": "model.pkg.helper", "name": "helper", "resource_type": "model", "package_name": "pkg"},
+            "model.other.helper": {"unique_id": "model.other.helper", "name": "helper", "resource_type": "model", "package_name": "other"},
+            "model.root.dim.v1": {"unique_id": "model.root.dim.v1", "name": "dim", "resource_type": "model", "package_name": "root",
+                                  "version": 1, "latest_version": 2},
+            "model.root.dim.v2": {"unique_id": "model.root.dim.v2", "name": "dim", "resource_type": "model", "package_name": "root",
+                                  "version": 2, "latest_version": 2},
+            "model.pkg.secret": {"unique_id": "model.pkg.secret", "name": "secret", "resource_type": "model", "package_name": "pkg",
+                                 "access": "private", "group": "finance"},
+            "model.pkg.guarded": {"unique_id": "model.pkg.guarded", "name": "guarded", "resource_type": "model", "package_name": "pkg",
+                                  "access": "protected"},
+            "test.root.not_null": {"unique_id": "test.root.not_null", "name": "not_null", "resource_type": "test", "package_name": "root"}
+        },
+        "sources": {
+            "source.root.raw.events": {"unique_id": "source.root.raw.events", "source_name": "raw", "name": "events", "package_name": "root"},
+            "source.pkg.raw.events": {"unique_id": "source.pkg.raw.events", "source_name": "raw", "name": "events", "package_name": "pkg"}
+        },
+        "disabled": {
+            "model.root.old": [{"unique_id": "model.root.old", "name": "old", "resource_type": "model", "package_name": "root"}],
+            "source.root.raw.gone": [{"unique_id": "source.root.raw.gone", "name": "gone", "resource_type": "source", "package_name": "root"}]
+        }
+    }"#;
+
+    fn manifest() -> OxideManifest {
+        OxideManifest::from_json_str(MANIFEST).unwrap()
+    }
+
+    fn referrer(package_name: &str) -> Referrer<'_> {
+        Referrer {
+            package_name,
+            resource_type: "model",
+            group: None,
+        }
+    }
+
+    fn resolve(
+        m: &OxideManifest,
+        from: Referrer,
+        name: &str,
+        package: Option<&str>,
+        version: Option<&str>,
+        restricted: &HashSet<String>,
+    ) -> Result<String, ResolveError> {
+        let ctx = ResolveContext {
+            current_project: "root",
+            restricted_packages: restricted,
+        };
+        m.resolve_ref(from, name, package, version, ctx)
+    }
+
+    #[test]
+    fn test_ref_prefers_root_then_own_package() {
+        let m = manifest();
+        let none = HashSet::new();
+        assert_eq!(
+            resolve(&m, referrer("root"), "orders", None, None, &none).unwrap(),
+            "model.root.orders"
+        );
+        // Called from a package, the root project still wins
+        assert_eq!(
+            resolve(&m, referrer("pkg"), "orders", None, None, &none).unwrap(),
+            "model.root.orders"
+        );
+        assert_eq!(
+            resolve(&m, referrer("root"), "orders", Some("pkg"), None, &none).unwrap(),
+            "model.pkg.orders"
+        );
+        // Not in root, but in the calling package
+        assert_eq!(
+            resolve(&m, referrer("pkg"), "helper", None, None, &none).unwrap(),
+            "model.pkg.helper"
+        );
+    }
+
+    #[test]
+    fn test_ref_ambiguous_and_not_found() {
+        let m = manifest();
+        let none = HashSet::new();
+        let err = resolve(&m, referrer("root"), "helper", None, None, &none).unwrap_err();
+        assert_eq!(
+            err,
+            ResolveError::Ambiguous {
+                target: "ref('helper')".to_string(),
+                candidates: vec![
+                    "model.other.helper".to_string(),
+                    "model.pkg.helper".to_string()
+                ],
+            }
+        );
+
+        let err = resolve(&m, referrer("root"), "missing", None, None, &none).unwrap_err();
+        assert_eq!(err.kind(), "not_found");
+
+        // Tests are not refable
+        let err = resolve(&m, referrer("root"), "not_null", None, None, &none).unwrap_err();
+        assert_eq!(err.kind(), "not_found");
+    }
+
+    #[test]
+    fn test_ref_versions() {
+        let m = manifest();
+        let none = HashSet::new();
+        assert_eq!(
+            resolve(&m, referrer("root"), "dim", None, None, &none).unwrap(),
+            "model.root.dim.v2"
+        );
+        assert_eq!(
+            resolve(&m, referrer("root"), "dim", None, Some("1"), &none).unwrap(),
+            "model.root.dim.v1"
+        );
+        assert!(resolve(&m, referrer("root"), "dim", None, Some("3"), &none).is_err());
+    }
+
+    #[test]
+    fn test_ref_disabled() {
+        let m = manifest();
+        let err = resolve(&m, referrer("root"), "old", None, None, &HashSet::new()).unwrap_err();
+        assert_eq!(err.kind(), "disabled");
+        assert_eq!(err.unique_ids(), vec!["model.root.old".to_string()]);
+    }
+
+    #[test]
+    fn test_ref_access_rules() {
+        let m = manifest();
+        let none = HashSet::new();
+        let restricted: HashSet<String> = ["pkg".to_string()].into_iter().collect();
+
+        let err = resolve(&m, referrer("root"), "secret", None, None, &none).unwrap_err();
+        assert_eq!(err.kind(), "private");
+
+        let in_group = Referrer {
+            package_name: "pkg",
+            resource_type: "model",
+            group: Some("finance"),
+        };
+        assert!(resolve(&m, in_group, "secret", None, None, &restricted).is_ok());
+
+        let other_package_in_group = Referrer {
+            package_name: "root",
+            ..in_group
+        };
+        assert!(resolve(&m, other_package_in_group, "secret", None, None, &none).is_ok());
+        assert!(resolve(
+            &m,
+            other_package_in_group,
+            "secret",
+            None,
+            None,
+            &restricted
+        )
+        .is_err());
+
+        assert!(resolve(&m, referrer("root"), "guarded", None, None, &none).is_ok());
+        let err = resolve(&m, referrer("root"), "guarded", None, None, &restricted).unwrap_err();
+        assert_eq!(err.kind(), "protected");
+    }
+
+    #[test]
+    fn test_resolve_source() {
+        let m = manifest();
+        assert_eq!(
+            m.resolve_source("raw", "events", "pkg", "root").unwrap(),
+            "source.root.raw.events"
+        );
+        assert_eq!(
+            m.resolve_source("raw", "gone", "root", "root")
+                .unwrap_err()
+                .kind(),
+            "disabled"
+        );
+        assert_eq!(
+            m.resolve_source("raw", "nope", "root", "root")
+                .unwrap_err()
+                .kind(),
+            "not_found"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! Validation of dbt artifacts against the JSON schemas bundled in
+//! `schemas/dbt`, using the `jsonschema` crate. `format` is treated as an
+//! annotation.
+
+use jsonschema::error::{TypeKind, ValidationErrorKind};
+use jsonschema::{JsonType, PatternOptions, ValidationError, Validator};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+struct BundledSchema {
+    artifact: &'static str,
+    version: u32,
+    source: &'static str,
+    validator: OnceCell<Validator>,
+}
+
+macro_rules! bundled {
+    ($artifact:literal, $version:literal) => {
+        BundledSchema {
+            artifact: $artifact,
+            version: $version,
+            source: include_str!(concat!(
+                "../../../schemas/dbt/",
+                $artifact,
+                "/v",
+                $version,
+                ".json"
+            )),
+            validator: OnceCell::new(),
+        }
+    };
+}
+
+static BUNDLED_SCHEMAS: [BundledSchema; 13] = [
+    bundled!("manifest", 5),
+    bundled!("manifest", 6),
+    bundled!("manifest", 7),
+    bundled!("manifest", 8),
+    bundled!("manifest", 9),
+    bundled!("manifest", 10),
+    bundled!("manifest", 11),
+    bundled!("manifest", 12),
+    bundled!("run-results", 4),
+    bundled!("run-results", 5),
+    bundled!("run-results", 6),
+    bundled!("catalog", 1),
+    bundled!("sources", 3),
+];
+
+/// Compile `schema`, whose `$ref`s must resolve within it.
+pub fn compile_schema(schema: &Value) -> Result<Validator, ValidationError<'static>> {
+    jsonschema::options()
+        .should_validate_formats(false)
+        .with_pattern_options(PatternOptions::regex())
+        .build(schema)
+}
+
+/// The validator for the bundled schema for `artifact` (`manifest`,
+/// `run-results`, `catalog` or `sources`) at `version`.
+fn bundled_validator(artifact: &str, version: u32) -> Option<&'static Validator> {
+    BUNDLED_SCHEMAS
+        .iter()
+        .find(|s| s.artifact == artifact && s.version == version)
+        .map(|s| {
+            s.validator.get_or_init(|| {
+                let schema: Value =
+                    serde_json::from_str(s.source).expect("bundled schema is valid JSON");
+                compile_schema(&schema).expect("bundled schema compiles")
+            })
+        })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaLookupError {
+    MissingSchemaVersion,
+    InvalidSchemaVersion(String),
+    UnknownSchema { artifact: String, version: u32 },
+}
+
+impl fmt::Display for SchemaLookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaLookupError::MissingSchemaVersion => {
+                write!(f, "Artifact is missing metadata.dbt_schema_version")
+            }
+            SchemaLookupError::InvalidSchemaVersion(url) => {
+                write!(f, "Invalid artifact schema version: {}", url)
+            }
+            SchemaLookupError::UnknownSchema { artifact, version } => {
+                write!(f, "No bundled schema for {} v{}", artifact, version)
+            }
+        }
+    }
+}
+
+/// Split `https://schemas.getdbt.com/dbt/manifest/v12.json` into
+/// `("manifest", 12)`.
+pub fn parse_schema_url(url: &str) -> Option<(String, u32)> {
+    let mut parts = url.trim_end_matches(".json").rsplit('/');
+    let version = parts.next()?.strip_prefix('v')?.parse().ok()?;
+    let artifact = parts.next().filter(|a| !a.is_empty())?;
+    Some((artifact.to_string(), version))
+}
+
+/// A place where an artifact does not conform to its schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, e.g. `/nodes/model.x.y/config`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Validate `artifact` against the bundled schema named by its
+/// `metadata.dbt_schema_version`. Returns every violation found.
+pub fn validate_artifact(artifact: &Value) -> Result<Vec<SchemaViolation>, SchemaLookupError> {
+    let url = artifact
+        .pointer("/metadata/dbt_schema_version")
+        .and_then(Value::as_str)
+        .ok_or(SchemaLookupError::MissingSchemaVersion)?;
+    let (name, version) = parse_schema_url(url)
+        .ok_or_else(|| SchemaLookupError::InvalidSchemaVersion(url.to_string()))?;
+    validate_against(artifact, &name, version)
+}
+
+/// Validate `artifact` against a specific bundled schema.
+pub fn validate_against(
+    artifact: &Value,
+    name: &str,
+    version: u32,
+) -> Result<Vec<SchemaViolation>, SchemaLookupError> {
+    let validator =
+        bundled_validator(name, version).ok_or_else(|| SchemaLookupError::UnknownSchema {
+            artifact: name.to_string(),
+            version,
+        })?;
+    Ok(validate_with(validator, artifact))
+}
+
+/// Validate `value` with a compiled schema, sorted by path.
+pub fn validate_with(validator: &Validator, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    for error in validator.iter_errors(value) {
+        collect(&error, &mut violations);
+    }
+    let mut violations: Vec<SchemaViolation> = violations
+        .into_iter()
+        .map(|v| SchemaViolation {
+            message: v.kind.to_string(),
+            path: v.path,
+        })
+        .collect();
+    violations.sort_by(|a, b| a.path.cmp(&b.path));
+    violations
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Type {
+        expected: Vec<JsonType>,
+        found: JsonType,
+    },
+    Message(String),
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Type { expected, found } => {
+                let expected: Vec<&str> = expected.iter().map(|t| t.as_str()).collect();
+                write!(f, "expected {}, found {}", expected.join(" or "), found)
+            }
+            Kind::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Violation {
+    path: String,
+    kind: Kind,
+}
+
+fn type_of(value: &Value) -> JsonType {
+    match value {
+        Value::Number(n) if n.is_i64() || n.is_u64() => JsonType::Integer,
+        other => JsonType::from(other),
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn depth(path: &str) -> usize {
+    path.matches('/').count()
+}
+
+/// Turn a `jsonschema` error into violations: type errors keep the expected
+/// types so `anyOf` branches can be merged, unexpected properties are
+/// reported at their own path, and failed `anyOf` / `oneOf` are replaced by
+/// the errors of their closest branch.
+fn collect(error: &ValidationError, out: &mut Vec<Violation>) {
+    let path = error.instance_path().as_str().to_string();
+    match error.kind() {
+        ValidationErrorKind::Type { kind } => out.push(Violation {
+            path,
+            kind: Kind::Type {
+                expected: match kind {
+                    TypeKind::Single(t) => vec![*t],
+                    TypeKind::Multiple(ts) => ts.iter().collect(),
+                },
+                found: type_of(error.instance()),
+            },
+        }),
+        ValidationErrorKind::AdditionalProperties { unexpected } => {
+            out.extend(unexpected.iter().map(|key| Violation {
+                path: child_path(&path, key),
+                kind: Kind::Message("unexpected property".to_string()),
+            }))
+        }
+        ValidationErrorKind::AnyOf { context } | ValidationErrorKind::OneOfNotValid { context } => {
+            closest_branch(&path, error, context, out)
+        }
+        _ => out.push(Violation {
+            path,
+            kind: Kind::Message(error.to_string()),
+        }),
+    }
+}
+
+/// When no branch matches, report the branch that came closest so the
+/// error points at the real problem, or a merged type error when every
+/// branch fails on the type of the value itself.
+fn closest_branch(
+    path: &str,
+    error: &ValidationError,
+    branches: &[Vec<ValidationError<'static>>],
+    out: &mut Vec<Violation>,
+) {
+    let results: Vec<Vec<Violation>> = branches
+        .iter()
+        .map(|errors| {
+            let mut inner = Vec::new();
+            for error in errors {
+                collect(error, &mut inner);
+            }
+            inner
+        })
+        .collect();
+
+    let type_errors: Option<Vec<JsonType>> = results
+        .iter()
+        .map(|r| match r.as_slice() {
+            [Violation {
+                path: p,
+                kind: Kind::Type { expected, .. },
+            }] if p == path => Some(expected.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|all| all.into_iter().flatten().collect());
+    if let Some(mut expected) = type_errors {
+        let mut seen = std::collections::HashSet::new();
+        expected.retain(|t| seen.insert(*t));
+        out.push(Violation {
+            path: path.to_string(),
+            kind: Kind::Type {
+                expected,
+                found: type_of(error.instance()),
+            },
+        });
+        return;
+    }
+
+    let own_depth = depth(path);
+    if let Some(best) = results.into_iter().min_by_key(|r| {
+        let shallow = r.iter().filter(|v| depth(&v.path) == own_depth).count();
+        let deepest = r.iter().map(|v| depth(&v.path)).max().unwrap_or(0);
+        (shallow, r.len(), std::cmp::Reverse(deepest))
+    }) {
+        out.extend(best);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const V12_MANIFEST: &str =
+        include_str!("../../../tests/functional/artifacts/data/state/v12/manifest.json");
+
+    #[test]
+    fn test_parse_schema_url() {
+        assert_eq!(
+            parse_schema_url("https://schemas.getdbt.com/dbt/run-results/v6.json"),
+            Some(("run-results".to_string(), 6))
+        );
+        assert_eq!(parse_schema_url("v6.json"), None);
+    }
+
+    #[test]
+    fn test_valid_manifest_has_no_violations() {
+        let manifest: Value = serde_json::from_str(V12_MANIFEST).unwrap();
+        let violations = validate_artifact(&manifest).unwrap();
+        assert!(
+            violations.is_empty(),
+            "{:?}",
+            &violations[..violations.len().min(5)]
+        );
+    }
+
+    #[test]
+    fn test_violation_paths() {
+        let mut manifest: Value = serde_json::from_str(V12_MANIFEST).unwrap();
+        let (model_id, _) = manifest["nodes"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .find(|(_, n)| n["resource_type"] == "model")
+            .map(|(id, n)| (id.clone(), n.clone()))
+            .unwrap();
+        manifest["nodes"][&model_id]["config"]["materialized"] = json!(5);
+        manifest.as_object_mut().unwrap().remove("macros");
+
+        let violations = validate_artifact(&manifest).unwrap();
+        let rendered: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "/: \"macros\" is a required property".to_string(),
+                format!(
+                    "/nodes/{}/config/materialized: expected string, found integer",
+                    model_id
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keywords() {
+        let schema = json!({
+            "$defs": {"Name": {"type": "string", "pattern": "^[a-z]+$"}},
+            "type": "object",
+            "properties": {
+                "name": {"$ref": "#/$defs/Name"},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+                "kind": {"enum": ["a", "b"]},
+                "size": {"oneOf": [{"type": "integer"}, {"type": "null"}]}
+            },
+            "additionalProperties": false
+        });
+        let value =
+            json!({"name": "Abc", "tags": ["x", 1, "z"], "kind": "c", "size": "big", "extra": 1});
+        let validator = compile_schema(&schema).unwrap();
+        let rendered: Vec<String> = validate_with(&validator, &value)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "/extra: unexpected property",
+                "/kind: \"c\" is not one of \"a\" or \"b\"",
+                "/name: \"Abc\" does not match \"^[a-z]+$\"",
+                "/size: expected integer or null, found string",
+                "/tags: [\"x\",1,\"z\"] has more than 2 items",
+                "/tags/1: expected string, found integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_schema() {
+        let artifact = json!({"metadata": {"dbt_schema_version": "https://schemas.getdbt.com/dbt/manifest/v99.json"}});
+        assert_eq!(
+            validate_artifact(&artifact),
+            Err(SchemaLookupError::UnknownSchema {
+                artifact: "manifest".to_string(),
+                version: 99
+            })
+        );
+        assert_eq!(
+            validate_artifact(&json!({})),
+            Err(SchemaLookupError::MissingSchemaVersion)
+        );
+    }
+}
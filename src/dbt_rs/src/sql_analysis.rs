@@ -0,0 +1,490 @@
+//! Relations referenced by compiled SQL, compared with declared dependencies.
+//!
+//! Compiled SQL is parsed with `sqlparser` in the adapter's dialect, and the
+//! tables a query reads from are collected from its `FROM` and `JOIN`
+//! clauses, skipping table functions and the names of CTEs in scope where
+//! they are read. Identifiers are compared case-insensitively.
+
+use crate::manifest::OxideManifest;
+use sqlparser::ast::{Ident, ObjectName, Query, Statement, TableFactor, Visit, Visitor};
+use sqlparser::dialect::{BigQueryDialect, GenericDialect, PostgreSqlDialect, SnowflakeDialect};
+use sqlparser::parser::{Parser, ParserError};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    #[default]
+    Ansi,
+    Postgres,
+    Snowflake,
+    BigQuery,
+}
+
+impl Dialect {
+    /// The dialect for a manifest's `metadata.adapter_type`; unknown
+    /// adapters get ANSI.
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn for_adapter(adapter_type: &str) -> Self {
+        match adapter_type {
+            "postgres" | "redshift" => Dialect::Postgres,
+            "snowflake" => Dialect::Snowflake,
+            "bigquery" => Dialect::BigQuery,
+            _ => Dialect::Ansi,
+        }
+    }
+
+    /// The `sqlparser` dialect. ANSI uses the generic dialect, which also
+    /// accepts the common extensions of adapters without their own.
+    fn parser_dialect(self) -> Box<dyn sqlparser::dialect::Dialect> {
+        match self {
+            Dialect::Ansi => Box::new(GenericDialect {}),
+            Dialect::Postgres => Box::new(PostgreSqlDialect {}),
+            Dialect::Snowflake => Box::new(SnowflakeDialect {}),
+            Dialect::BigQuery => Box::new(BigQueryDialect {}),
+        }
+    }
+}
+
+/// Parse SQL, which may hold several statements.
+pub fn parse_sql(sql: &str, dialect: Dialect) -> Result<Vec<Statement>, ParserError> {
+    Parser::parse_sql(dialect.parser_dialect().as_ref(), sql)
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ansi" => Ok(Dialect::Ansi),
+            "postgres" => Ok(Dialect::Postgres),
+            "snowflake" => Ok(Dialect::Snowflake),
+            "bigquery" => Ok(Dialect::BigQuery),
+            other => Err(format!(
+                "Unknown SQL dialect '{}', expected 'ansi', 'postgres', 'snowflake' or 'bigquery'",
+                other
+            )),
+        }
+    }
+}
+
+/// A relation name split into lowercased parts, e.g. `["db", "schema", "t"]`.
+pub type RelationParts = Vec<String>;
+
+/// The parts of a parsed name. BigQuery's `` `project.dataset.table` `` is
+/// split into its parts.
+pub(crate) fn name_parts(name: &ObjectName, dialect: Dialect) -> RelationParts {
+    let mut parts = Vec::new();
+    for Ident {
+        value, quote_style, ..
+    } in &name.0
+    {
+        if dialect == Dialect::BigQuery && *quote_style == Some('`') {
+            parts.extend(value.split('.').map(str::to_lowercase));
+        } else {
+            parts.push(value.to_lowercase());
+        }
+    }
+    parts
+}
+
+/// Parse a relation name such as a manifest `relation_name`.
+pub fn parse_relation_name(name: &str, dialect: Dialect) -> Option<RelationParts> {
+    let parser_dialect = dialect.parser_dialect();
+    let mut parser = Parser::new(parser_dialect.as_ref())
+        .try_with_sql(name)
+        .ok()?;
+    let parsed = parser.parse_object_name(false).ok()?;
+    parser
+        .expect_token(&sqlparser::tokenizer::Token::EOF)
+        .ok()?;
+    Some(name_parts(&parsed, dialect))
+}
+
+/// The tables and CTEs of parsed statements.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryRelations {
+    /// Tables read, in order of first appearance.
+    pub relations: Vec<RelationParts>,
+    /// Names of every CTE defined anywhere, lowercased.
+    pub ctes: HashSet<String>,
+}
+
+/// Collects the tables read, resolving single-part names against the CTEs
+/// of the enclosing queries, innermost first.
+struct RelationCollector {
+    dialect: Dialect,
+    scopes: Vec<HashSet<String>>,
+    found: QueryRelations,
+}
+
+impl Visitor for RelationCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        let names: HashSet<String> = query
+            .with
+            .iter()
+            .flat_map(|with| &with.cte_tables)
+            .map(|cte| cte.alias.name.value.to_lowercase())
+            .collect();
+        self.found.ctes.extend(names.iter().cloned());
+        self.scopes.push(names);
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.scopes.pop();
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+        // Table functions such as `generate_series(...)` carry arguments.
+        if let TableFactor::Table {
+            name, args: None, ..
+        } = table_factor
+        {
+            let parts = name_parts(name, self.dialect);
+            let is_cte =
+                parts.len() == 1 && self.scopes.iter().any(|ctes| ctes.contains(&parts[0]));
+            if !is_cte && !self.found.relations.contains(&parts) {
+                self.found.relations.push(parts);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The tables `statements` read and the CTEs they define.
+pub fn query_relations(statements: &[Statement], dialect: Dialect) -> QueryRelations {
+    let mut collector = RelationCollector {
+        dialect,
+        scopes: Vec::new(),
+        found: QueryRelations::default(),
+    };
+    for statement in statements {
+        let _ = statement.visit(&mut collector);
+    }
+    collector.found
+}
+
+/// Relations a query reads from, in order of first appearance, without the
+/// CTEs they name.
+pub fn referenced_relations(
+    sql: &str,
+    dialect: Dialect,
+) -> Result<Vec<RelationParts>, ParserError> {
+    let statements = parse_sql(sql, dialect)?;
+    Ok(query_relations(&statements, dialect).relations)
+}
+
+/// Render relation parts as a dotted name.
+pub fn display_relation(parts: &[String]) -> String {
+    parts.join(".")
+}
+
+/// Whether one relation name is a suffix of the other, so that `orders`
+/// and `analytics.orders` both match `db.analytics.orders`.
+fn same_relation(a: &[String], b: &[String]) -> bool {
+    let n = a.len().min(b.len());
+    n > 0 && a[a.len() - n..] == b[b.len() - n..]
+}
+
+/// A table read by compiled SQL that is not a declared dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndeclaredRelation {
+    /// The relation as written, lowercased.
+    pub relation: String,
+    /// The manifest node or source with that relation, when exactly one
+    /// matches.
+    pub unique_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyReport {
+    pub unique_id: String,
+    /// Tables read without `ref()` or `source()`.
+    pub undeclared: Vec<UndeclaredRelation>,
+    /// `depends_on.nodes` entries whose relation the SQL never reads.
+    pub unused: Vec<String>,
+    /// Why the compiled SQL could not be parsed, in which case nothing was
+    /// compared.
+    pub parse_error: Option<String>,
+}
+
+impl DependencyReport {
+    #[cfg_attr(not(feature = "extension-module"), allow(dead_code))]
+    pub fn is_empty(&self) -> bool {
+        self.undeclared.is_empty() && self.unused.is_empty() && self.parse_error.is_none()
+    }
+}
+
+/// The relations of manifest nodes and sources, with ephemeral models
+/// named by their CTE.
+pub struct RelationIndex {
+    relations: HashMap<String, RelationParts>,
+    by_name: HashMap<String, Vec<String>>,
+}
+
+impl RelationIndex {
+    pub fn new(manifest: &OxideManifest, dialect: Dialect) -> Self {
+        let mut relations = HashMap::new();
+        for node in manifest.nodes.values() {
+            let parts = if node.config.materialized.as_deref() == Some("ephemeral") {
+                let identifier = node.extra.get("alias").and_then(|alias| alias.as_str());
+                let identifier = identifier.unwrap_or(&node.name);
+                Some(vec![format!("__dbt__cte__{}", identifier.to_lowercase())])
+            } else {
+                node.extra
+                    .get("relation_name")
+                    .and_then(|name| name.as_str())
+                    .and_then(|name| parse_relation_name(name, dialect))
+            };
+            if let Some(parts) = parts {
+                relations.insert(node.unique_id.clone(), parts);
+            }
+        }
+        for source in manifest.sources.values() {
+            let parts = source
+                .extra
+                .get("relation_name")
+                .and_then(|name| name.as_str())
+                .and_then(|name| parse_relation_name(name, dialect));
+            if let Some(parts) = parts {
+                relations.insert(source.unique_id.clone(), parts);
+            }
+        }
+        let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+        for (unique_id, parts) in &relations {
+            if let Some(last) = parts.last() {
+                by_name
+                    .entry(last.clone())
+                    .or_default()
+                    .push(unique_id.clone());
+            }
+        }
+        RelationIndex { relations, by_name }
+    }
+
+    pub fn relation(&self, unique_id: &str) -> Option<&RelationParts> {
+        self.relations.get(unique_id)
+    }
+
+    /// The only manifest resource whose relation matches `parts`.
+    pub fn resolve(&self, parts: &[String]) -> Option<String> {
+        let candidates = self.by_name.get(parts.last()?)?;
+        let mut matches = candidates
+            .iter()
+            .filter(|unique_id| same_relation(&self.relations[*unique_id], parts));
+        let first = matches.next()?;
+        matches.next().is_none().then(|| first.clone())
+    }
+}
+
+/// Compare the relations `unique_id`'s compiled SQL reads with its
+/// `depends_on.nodes`. Returns None for nodes without compiled code.
+pub fn check_node_dependencies(
+    manifest: &OxideManifest,
+    index: &RelationIndex,
+    unique_id: &str,
+    dialect: Dialect,
+) -> Option<DependencyReport> {
+    let node = manifest.nodes.get(unique_id)?;
+    let sql = node.compiled_code.as_deref()?;
+    let declared: Vec<(&String, &RelationParts)> = node
+        .depends_on
+        .nodes
+        .iter()
+        .filter_map(|dep| Some((dep, index.relation(dep)?)))
+        .collect();
+    let own = index.relation(unique_id);
+
+    let mut report = DependencyReport {
+        unique_id: unique_id.to_string(),
+        ..DependencyReport::default()
+    };
+    let found = match parse_sql(sql, dialect) {
+        Ok(statements) => query_relations(&statements, dialect),
+        Err(e) => {
+            report.parse_error = Some(e.to_string());
+            return Some(report);
+        }
+    };
+    let mut used: BTreeSet<&String> = BTreeSet::new();
+    // Injected ephemeral CTEs show up as CTE names rather than relations.
+    for (dep, parts) in &declared {
+        if parts.len() == 1 && found.ctes.contains(&parts[0]) {
+            used.insert(dep);
+        }
+    }
+
+    for parts in found.relations {
+        let matching: Vec<&String> = declared
+            .iter()
+            .filter(|(_, declared)| same_relation(declared, &parts))
+            .map(|(dep, _)| *dep)
+            .collect();
+        if !matching.is_empty() {
+            used.extend(matching);
+        } else if !own.is_some_and(|own| same_relation(own, &parts)) {
+            report.undeclared.push(UndeclaredRelation {
+                relation: display_relation(&parts),
+                unique_id: index.resolve(&parts),
+            });
+        }
+    }
+    report.unused = declared
+        .iter()
+        .map(|(dep, _)| *dep)
+        .filter(|dep| !used.contains(dep))
+        .cloned()
+        .collect();
+    Some(report)
+}
+
+impl fmt::Display for UndeclaredRelation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.unique_id {
+            Some(unique_id) => write!(f, "{} ({})", self.relation, unique_id),
+            None => write!(f, "{}", self.relation),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relations(sql: &str, dialect: Dialect) -> Vec<String> {
+        referenced_relations(sql, dialect)
+            .unwrap()
+            .iter()
+            .map(|parts| display_relation(parts))
+            .collect()
+    }
+
+    #[test]
+    fn test_referenced_relations() {
+        let sql = r#"
+            with recursive base (id) as (
+                select id from "DB"."Raw"."Orders" -- from fake.comment
+                where extract(year from created_at) > 2000
+                  and a is not distinct from b
+            ), lookups as materialized (select * from analytics.lookups)
+            select /* from fake.block */ 'from fake.string', *
+            from base, generate_series(1, 3) g,
+                 db.raw.customers as c, db.raw.extra e (x, y)
+            join lookups l on l.id = base.id
+            left join (select * from db.raw.payments) p using (id)
+            where exists (select 1 from db.raw.refunds r where r.id = base.id)
+        "#;
+        assert_eq!(
+            relations(sql, Dialect::Postgres),
+            vec![
+                "db.raw.orders",
+                "analytics.lookups",
+                "db.raw.customers",
+                "db.raw.extra",
+                "db.raw.payments",
+                "db.raw.refunds"
+            ]
+        );
+        assert_eq!(
+            relations(
+                "select $$ from x $$, * from t1, lateral flatten(input => t1.v) f \
+                 // from y\n join t2 on true",
+                Dialect::Snowflake
+            ),
+            vec!["t1", "t2"]
+        );
+        assert_eq!(
+            relations(
+                "select r'from x', `a`.b from `my-proj.ds.events_*` # from z\n \
+                 cross join unnest(arr) as a",
+                Dialect::BigQuery
+            ),
+            vec!["my-proj.ds.events_*"]
+        );
+        assert!(referenced_relations("select * from", Dialect::Ansi).is_err());
+        assert_eq!(
+            parse_relation_name("\"db\".\"main\".\"orders\"", Dialect::Postgres),
+            Some(vec![
+                "db".to_string(),
+                "main".to_string(),
+                "orders".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_cte_names_are_scoped() {
+        // The `orders` CTE of the subquery does not hide the table read by
+        // the outer query, and a CTE is visible in nested subqueries.
+        let sql = "
+            with customers as (select * from raw.customers)
+            select * from orders
+            join (
+                with orders as (select * from raw.orders)
+                select * from orders join customers using (id)
+            ) o using (id)
+            where exists (select 1 from customers)
+        ";
+        assert_eq!(
+            relations(sql, Dialect::Ansi),
+            vec!["raw.customers", "orders", "raw.orders"]
+        );
+    }
+
+    #[test]
+    fn test_check_node_dependencies() {
+        let manifest = OxideManifest::from_json_str(
+            r#"{
+            "metadata": {"adapter_type": "postgres"},
+            "nodes": {
+                "model.p.orders": {"unique_id": "model.p.orders", "name": "orders",
+                    "resource_type": "model", "package_name": "p",
+                    "relation_name": "\"db\".\"main\".\"orders\"",
+                    "depends_on": {"nodes": ["model.p.stg", "source.p.raw.pay", "model.p.unused", "model.p.eph"]},
+                    "compiled_code": "with __dbt__cte__eph as (select 1) select * from \"db\".\"main\".\"stg\" join __dbt__cte__eph on true join \"db\".\"raw\".\"pay\" on true join main.customers on true join raw.hardcoded on true where x > (select max(x) from \"db\".\"main\".\"orders\")"},
+                "model.p.stg": {"unique_id": "model.p.stg", "name": "stg", "resource_type": "model",
+                    "package_name": "p", "relation_name": "\"db\".\"main\".\"stg\""},
+                "model.p.unused": {"unique_id": "model.p.unused", "name": "unused", "resource_type": "model",
+                    "package_name": "p", "relation_name": "\"db\".\"main\".\"unused\""},
+                "model.p.customers": {"unique_id": "model.p.customers", "name": "customers",
+                    "resource_type": "model", "package_name": "p",
+                    "relation_name": "\"db\".\"main\".\"customers\""},
+                "model.p.eph": {"unique_id": "model.p.eph", "name": "eph", "resource_type": "model",
+                    "package_name": "p", "config": {"materialized": "ephemeral"}}
+            },
+            "sources": {
+                "source.p.raw.pay": {"unique_id": "source.p.raw.pay", "source_name": "raw", "name": "pay",
+                    "package_name": "p", "relation_name": "\"db\".\"raw\".\"pay\""}
+            }
+        }"#,
+        )
+        .unwrap();
+        let index = RelationIndex::new(&manifest, Dialect::Postgres);
+        let report =
+            check_node_dependencies(&manifest, &index, "model.p.orders", Dialect::Postgres)
+                .unwrap();
+        assert_eq!(
+            report.undeclared,
+            vec![
+                UndeclaredRelation {
+                    relation: "main.customers".to_string(),
+                    unique_id: Some("model.p.customers".to_string()),
+                },
+                UndeclaredRelation {
+                    relation: "raw.hardcoded".to_string(),
+                    unique_id: None,
+                },
+            ]
+        );
+        assert_eq!(report.unused, vec!["model.p.unused"]);
+        assert!(
+            check_node_dependencies(&manifest, &index, "model.p.stg", Dialect::Postgres).is_none()
+        );
+    }
+}
@@ -0,0 +1,677 @@
+//! Static extraction of `ref`, `source`, `config` and `metric` calls from a
+//! node's raw Jinja-SQL, without rendering it.
+//!
+//! A template is "simple" when it is plain text, comments and `{{ ... }}`
+//! tags that each hold exactly one of these calls with literal arguments.
+//! Rendering such a template at parse time records nothing beyond what is
+//! extracted here, so dbt can skip Jinja for it, as it does with the
+//! `dbt_extractor` static parser. Calls in other templates are still
+//! reported where their arguments are literals, but only as a hint.
+
+use serde_json::{Map, Number, Value};
+
+/// A piece of a template, split on Jinja's default delimiters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    /// The body of `{{ ... }}`, without whitespace-control markers.
+    Expression(&'a str),
+    /// The body of `{% ... %}`, without whitespace-control markers.
+    Statement(&'a str),
+    /// The contents of a `{% raw %}` block.
+    Raw(&'a str),
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    pub offset: usize,
+    pub message: String,
+}
+
+/// Find the end of a tag body starting at `from`: the offset of `close`,
+/// skipping over string literals.
+fn find_close(source: &str, from: usize, close: &str, strings: bool) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut i = from;
+    let mut quote = None;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match quote {
+            Some(_) if b == b'\\' => i += 1,
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None if strings && (b == b'\'' || b == b'"') => quote = Some(b),
+            None if source[i..].starts_with(close) => return Some(i),
+            None => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn strip_markers(body: &str) -> &str {
+    let body = body.strip_prefix(['-', '+']).unwrap_or(body);
+    body.strip_suffix(['-', '+']).unwrap_or(body)
+}
+
+/// Find `{% endraw %}` (with optional whitespace control) at or after
+/// `from`; returns its start and end.
+fn find_endraw(source: &str, from: usize) -> Option<(usize, usize)> {
+    let mut search = from;
+    while let Some(found) = source[search..].find("{%") {
+        let start = search + found;
+        let body_start = start + 2;
+        if let Some(close) = source[body_start..].find("%}") {
+            let body = strip_markers(&source[body_start..body_start + close]);
+            if body.trim() == "endraw" {
+                return Some((start, body_start + close + 2));
+            }
+        }
+        search = body_start;
+    }
+    None
+}
+
+/// Split a template into text, tags and comments.
+pub fn split_template(source: &str) -> Result<Vec<Segment<'_>>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    while pos < source.len() {
+        let next = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|open| source[pos..].find(open).map(|i| pos + i))
+            .min();
+        let Some(start) = next else {
+            segments.push(Segment::Text(&source[pos..]));
+            break;
+        };
+        if start > pos {
+            segments.push(Segment::Text(&source[pos..start]));
+        }
+        let body_start = start + 2;
+        let (close, strings) = match &source[start..body_start] {
+            "{{" => ("}}", true),
+            "{%" => ("%}", true),
+            _ => ("#}", false),
+        };
+        let end = find_close(source, body_start, close, strings).ok_or_else(|| TemplateError {
+            offset: start,
+            message: format!("unclosed tag, expected '{}'", close),
+        })?;
+        let body = strip_markers(&source[body_start..end]);
+        pos = end + 2;
+        match close {
+            "}}" => segments.push(Segment::Expression(body)),
+            "#}" => segments.push(Segment::Comment),
+            _ if body.trim() == "raw" => {
+                let (raw_end, after) = find_endraw(source, pos).ok_or_else(|| TemplateError {
+                    offset: start,
+                    message: "unclosed raw block, expected '{% endraw %}'".to_string(),
+                })?;
+                segments.push(Segment::Raw(&source[pos..raw_end]));
+                pos = after;
+            }
+            _ => segments.push(Segment::Statement(body)),
+        }
+    }
+    Ok(segments)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Name(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 26] = [
+    "**", "//", "==", "!=", ">=", "<=", "(", ")", "[", "]", "{", "}", ",", ":", ".", "|", "=", "+",
+    "-", "*", "/", "%", "~", "<", ">", ";",
+];
+
+fn read_string(chars: &[char], quote_at: usize) -> Result<(String, usize), String> {
+    let quote = chars[quote_at];
+    let mut out = String::new();
+    let mut i = quote_at + 1;
+    while i < chars.len() {
+        match chars[i] {
+            c if c == quote => return Ok((out, i + 1)),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                match chars[i] {
+                    'n' => out.push('\n'),
+                    't' => out.push('\t'),
+                    'r' => out.push('\r'),
+                    '0' => out.push('\0'),
+                    c @ ('\\' | '\'' | '"') => out.push(c),
+                    c => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    Err("unterminated string".to_string())
+}
+
+fn read_number(chars: &[char], start: usize) -> Result<(Token, usize), String> {
+    let mut i = start;
+    let mut float = false;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_ascii_digit() || c == '_' {
+            i += 1;
+        } else if c == '.' && !float && chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+            float = true;
+            i += 1;
+        } else if (c == 'e' || c == 'E')
+            && chars
+                .get(i + 1)
+                .is_some_and(|c| c.is_ascii_digit() || *c == '-' || *c == '+')
+        {
+            float = true;
+            i += 2;
+        } else {
+            break;
+        }
+    }
+    let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+    let token = if float {
+        Token::Float(
+            text.parse()
+                .map_err(|_| format!("invalid number {}", text))?,
+        )
+    } else {
+        match text.parse() {
+            Ok(n) => Token::Int(n),
+            Err(_) => Token::Float(
+                text.parse()
+                    .map_err(|_| format!("invalid number {}", text))?,
+            ),
+        }
+    };
+    Ok((token, i))
+}
+
+/// Split a Jinja expression or statement body into tokens.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    'outer: while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let (s, end) = read_string(&chars, i)?;
+            tokens.push(Token::Str(s));
+            i = end;
+        } else if c.is_ascii_digit() {
+            let (token, end) = read_number(&chars, i)?;
+            tokens.push(token);
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            for op in OPERATORS {
+                let len = op.chars().count();
+                if chars[i..].iter().take(len).copied().eq(op.chars()) {
+                    tokens.push(Token::Op(op));
+                    i += len;
+                    continue 'outer;
+                }
+            }
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a literal at `pos`: strings (adjacent ones concatenate), numbers,
+/// booleans, none, and lists, tuples and string-keyed dicts of literals.
+pub fn parse_literal(tokens: &[Token], pos: &mut usize) -> Option<Value> {
+    let value = match tokens.get(*pos)? {
+        Token::Str(s) => {
+            let mut s = s.clone();
+            while let Some(Token::Str(next)) = tokens.get(*pos + 1) {
+                s.push_str(next);
+                *pos += 1;
+            }
+            Value::String(s)
+        }
+        Token::Int(n) => Value::from(*n),
+        Token::Float(f) => Value::Number(Number::from_f64(*f)?),
+        Token::Op("-") => {
+            *pos += 1;
+            match tokens.get(*pos)? {
+                Token::Int(n) => Value::from(-*n),
+                Token::Float(f) => Value::Number(Number::from_f64(-*f)?),
+                _ => return None,
+            }
+        }
+        Token::Name(name) => match name.as_str() {
+            "true" | "True" => Value::Bool(true),
+            "false" | "False" => Value::Bool(false),
+            "none" | "None" => Value::Null,
+            _ => return None,
+        },
+        Token::Op(open @ ("[" | "(")) => {
+            let close = if *open == "[" { "]" } else { ")" };
+            *pos += 1;
+            let mut items = Vec::new();
+            while tokens.get(*pos)? != &Token::Op(close) {
+                items.push(parse_literal(tokens, pos)?);
+                match tokens.get(*pos)? {
+                    Token::Op(",") => *pos += 1,
+                    Token::Op(op) if *op == close => {}
+                    _ => return None,
+                }
+            }
+            Value::Array(items)
+        }
+        Token::Op("{") => {
+            *pos += 1;
+            let mut map = Map::new();
+            while tokens.get(*pos)? != &Token::Op("}") {
+                let Value::String(key) = parse_literal(tokens, pos)? else {
+                    return None;
+                };
+                if tokens.get(*pos)? != &Token::Op(":") {
+                    return None;
+                }
+                *pos += 1;
+                map.insert(key, parse_literal(tokens, pos)?);
+                match tokens.get(*pos)? {
+                    Token::Op(",") => *pos += 1,
+                    Token::Op("}") => {}
+                    _ => return None,
+                }
+            }
+            Value::Object(map)
+        }
+        Token::Op(_) => return None,
+    };
+    *pos += 1;
+    Some(value)
+}
+
+/// A call to one of the extracted functions. `dynamic` calls have at least
+/// one argument that is not a literal, and no arguments recorded.
+#[derive(Debug, Clone, PartialEq)]
+struct Call {
+    function: String,
+    args: Vec<Value>,
+    kwargs: Vec<(String, Value)>,
+    dynamic: bool,
+    /// Token index just past the closing parenthesis.
+    end: usize,
+}
+
+const FUNCTIONS: [&str; 4] = ["ref", "source", "config", "metric"];
+
+/// Skip to just past the parenthesis closing the one before `pos`.
+fn skip_call(tokens: &[Token], mut pos: usize) -> usize {
+    let mut depth = 1;
+    while let Some(token) = tokens.get(pos) {
+        pos += 1;
+        match token {
+            Token::Op("(" | "[" | "{") => depth += 1,
+            Token::Op(")" | "]" | "}") => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    pos
+}
+
+/// Parse the arguments of the call whose name is at `start`.
+fn parse_call(tokens: &[Token], start: usize) -> Call {
+    let Token::Name(function) = &tokens[start] else {
+        unreachable!("calls start at a name")
+    };
+    let mut call = Call {
+        function: function.clone(),
+        args: Vec::new(),
+        kwargs: Vec::new(),
+        dynamic: false,
+        end: 0,
+    };
+    let mut pos = start + 2;
+    loop {
+        if tokens.get(pos) == Some(&Token::Op(")")) {
+            call.end = pos + 1;
+            return call;
+        }
+        let keyword = match (tokens.get(pos), tokens.get(pos + 1)) {
+            (Some(Token::Name(name)), Some(Token::Op("="))) => {
+                pos += 2;
+                Some(name.clone())
+            }
+            _ => None,
+        };
+        let value = parse_literal(tokens, &mut pos);
+        let separator = tokens.get(pos);
+        match (value, separator) {
+            (Some(value), Some(Token::Op("," | ")"))) => {
+                match keyword {
+                    Some(keyword) => call.kwargs.push((keyword, value)),
+                    None => call.args.push(value),
+                }
+                if separator == Some(&Token::Op(",")) {
+                    pos += 1;
+                }
+            }
+            _ => {
+                call.args.clear();
+                call.kwargs.clear();
+                call.dynamic = true;
+                call.end = skip_call(tokens, start + 2);
+                return call;
+            }
+        }
+    }
+}
+
+fn find_calls(tokens: &[Token]) -> Vec<Call> {
+    let mut calls = Vec::new();
+    let mut i = 0;
+    while i + 1 < tokens.len() {
+        let is_call = matches!(&tokens[i], Token::Name(name) if FUNCTIONS.contains(&name.as_str()))
+            && tokens[i + 1] == Token::Op("(")
+            && (i == 0 || tokens[i - 1] != Token::Op("."));
+        if is_call {
+            let call = parse_call(tokens, i);
+            calls.push(call);
+            // Arguments are literals or skipped, so no calls are nested.
+        }
+        i += 1;
+    }
+    calls
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticRef {
+    pub name: String,
+    pub package: Option<String>,
+    /// A string or number, as written.
+    pub version: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StaticExtraction {
+    pub refs: Vec<StaticRef>,
+    /// `[source_name, table_name]`
+    pub sources: Vec<[String; 2]>,
+    /// Config keys and values in call order; later calls override earlier
+    /// ones when merged.
+    pub configs: Vec<(String, Value)>,
+    /// `[name]` or `[package, name]`, as dbt stores `node.metrics`.
+    pub metrics: Vec<Vec<String>>,
+    /// True when rendering the template is not needed to parse it.
+    pub simple: bool,
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn strings(args: &[Value]) -> Option<Vec<String>> {
+    args.iter()
+        .map(|arg| arg.as_str().map(str::to_string))
+        .collect()
+}
+
+fn kwarg<'a>(call: &'a Call, name: &str) -> Option<&'a Value> {
+    call.kwargs
+        .iter()
+        .rev()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+impl StaticExtraction {
+    /// Record a call the way the matching dbt context function would.
+    /// Returns false for calls that would fail or that cannot be read
+    /// statically.
+    fn record(&mut self, call: &Call) -> bool {
+        if call.dynamic {
+            return false;
+        }
+        match call.function.as_str() {
+            "ref" => {
+                let Some(mut args) = strings(&call.args) else {
+                    return false;
+                };
+                let (package, name) = match args.len() {
+                    1 => (None, args.remove(0)),
+                    2 => (Some(args.remove(0)), args.remove(0)),
+                    _ => return false,
+                };
+                // kwargs.get("version") or kwargs.get("v")
+                let version = kwarg(call, "version")
+                    .filter(|v| is_truthy(v))
+                    .or_else(|| kwarg(call, "v"))
+                    .filter(|v| !v.is_null())
+                    .cloned();
+                if version
+                    .as_ref()
+                    .is_some_and(|v| !v.is_string() && !v.is_number())
+                {
+                    return false;
+                }
+                self.refs.push(StaticRef {
+                    name,
+                    package,
+                    version,
+                });
+            }
+            "source" => match strings(&call.args).as_deref() {
+                Some([source_name, table_name]) if call.kwargs.is_empty() => {
+                    self.sources.push([source_name.clone(), table_name.clone()]);
+                }
+                _ => return false,
+            },
+            "metric" => match strings(&call.args) {
+                Some(args) if (1..=2).contains(&args.len()) && call.kwargs.is_empty() => {
+                    self.metrics.push(args);
+                }
+                _ => return false,
+            },
+            _ => match (call.args.as_slice(), call.kwargs.is_empty()) {
+                ([Value::Object(opts)], true) => self
+                    .configs
+                    .extend(opts.iter().map(|(k, v)| (k.clone(), v.clone()))),
+                ([], false) => self.configs.extend(call.kwargs.iter().cloned()),
+                _ => return false,
+            },
+        }
+        true
+    }
+}
+
+/// Extract the `ref`, `source`, `config` and `metric` calls of a template.
+pub fn extract_static_calls(raw_code: &str) -> StaticExtraction {
+    let mut extraction = StaticExtraction {
+        simple: true,
+        ..StaticExtraction::default()
+    };
+    let segments = match split_template(raw_code) {
+        Ok(segments) => segments,
+        Err(_) => {
+            extraction.simple = false;
+            return extraction;
+        }
+    };
+    for segment in segments {
+        let (body, is_expression) = match segment {
+            Segment::Text(_) | Segment::Comment => continue,
+            Segment::Raw(_) => {
+                extraction.simple = false;
+                continue;
+            }
+            Segment::Expression(body) => (body, true),
+            Segment::Statement(body) => (body, false),
+        };
+        let Ok(tokens) = tokenize(body) else {
+            extraction.simple = false;
+            continue;
+        };
+        let calls = find_calls(&tokens);
+        let whole_call = is_expression
+            && calls.len() == 1
+            && calls[0].end == tokens.len()
+            && !tokens.is_empty()
+            && matches!(&tokens[0], Token::Name(name) if *name == calls[0].function);
+        let mut recorded = true;
+        for call in &calls {
+            recorded &= extraction.record(call);
+        }
+        if !(whole_call && recorded) {
+            extraction.simple = false;
+        }
+    }
+    extraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_simple_model() {
+        let sql = r#"
+            {{ config(materialized='incremental', tags=["a", "b"], meta={'owner': 'x'}, enabled=true) }}
+            {# a comment with {{ ref('ignored') }} #}
+            select * from {{ ref('orders') }}
+            join {{- ref("pkg", 'customers', v=2) -}} using (id)
+            join {{ source('raw', 'payments') }} using (id)
+            where {{ metric('revenue') }} is not null
+        "#;
+        let extracted = extract_static_calls(sql);
+        assert!(extracted.simple);
+        assert_eq!(
+            extracted.refs,
+            vec![
+                StaticRef {
+                    name: "orders".into(),
+                    package: None,
+                    version: None
+                },
+                StaticRef {
+                    name: "customers".into(),
+                    package: Some("pkg".into()),
+                    version: Some(json!(2))
+                },
+            ]
+        );
+        assert_eq!(
+            extracted.sources,
+            vec![["raw".to_string(), "payments".to_string()]]
+        );
+        assert_eq!(extracted.metrics, vec![vec!["revenue".to_string()]]);
+        assert_eq!(
+            extracted.configs,
+            vec![
+                ("materialized".to_string(), json!("incremental")),
+                ("tags".to_string(), json!(["a", "b"])),
+                ("meta".to_string(), json!({"owner": "x"})),
+                ("enabled".to_string(), json!(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_templates_that_need_rendering() {
+        for sql in [
+            "{% set x = 1 %}select {{ ref('a') }}",
+            "select {{ var('x') }}",
+            "select * from {{ ref('a') | lower }}",
+            "select * from {{ ref(model_name) }}",
+            "select * from {{ ref('a', 'b', 'c') }}",
+            "select * from {{ source('only_one') }}",
+            "{{ config() }}",
+            "{{ config({'a': 1}, b=2) }}",
+            "{{ this.ref('a') }}",
+            "{% raw %}{{ ref('a') }}{% endraw %}",
+            "select {{ ref('a') ",
+        ] {
+            assert!(!extract_static_calls(sql).simple, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_calls_in_complex_templates() {
+        let sql = "{% if target.name == 'prod' %}{{ ref('a', version='1') }}\
+                   {% else %}{{ ref(x) }}{{ ref('b', version=0, v=3) }}{% endif %}\
+                   {% set s = source('s', 't') %}{{ config({'schema': 'x'}) }}";
+        let extracted = extract_static_calls(sql);
+        assert!(!extracted.simple);
+        let refs: Vec<_> = extracted
+            .refs
+            .iter()
+            .map(|r| (r.name.as_str(), r.version.clone()))
+            .collect();
+        assert_eq!(refs, vec![("a", Some(json!("1"))), ("b", Some(json!(3)))]);
+        assert_eq!(extracted.sources, vec![["s".to_string(), "t".to_string()]]);
+        assert_eq!(extracted.configs, vec![("schema".to_string(), json!("x"))]);
+    }
+
+    #[test]
+    fn test_split_and_tokenize() {
+        let segments =
+            split_template("a {{- '}}' -}} b {%+ if x %}{# c #}{% raw %}{{ y }}{%- endraw %}")
+                .unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("a "),
+                Segment::Expression(" '}}' "),
+                Segment::Text(" b "),
+                Segment::Statement(" if x "),
+                Segment::Comment,
+                Segment::Raw("{{ y }}"),
+            ]
+        );
+        assert_eq!(
+            tokenize(r#"f('a\'b', -1.5e2, x.y) ~ 10"#).unwrap(),
+            vec![
+                Token::Name("f".into()),
+                Token::Op("("),
+                Token::Str("a'b".into()),
+                Token::Op(","),
+                Token::Op("-"),
+                Token::Float(150.0),
+                Token::Op(","),
+                Token::Name("x".into()),
+                Token::Op("."),
+                Token::Name("y".into()),
+                Token::Op(")"),
+                Token::Op("~"),
+                Token::Int(10),
+            ]
+        );
+        assert!(split_template("{% if %").is_err());
+    }
+}
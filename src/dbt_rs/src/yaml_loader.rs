@@ -0,0 +1,531 @@
+//! YAML loading with source locations, resolving scalars like PyYAML's
+//! `SafeLoader` (YAML 1.1): `yes`/`no`/`on`/`off` are booleans, `~` is null,
+//! `0o`-less leading-zero integers are octal and floats need a dot. Merge
+//! keys (`<<`) and aliases are expanded. Timestamps stay strings, which is
+//! what dbt's JSON schema validation accepts for them anyway.
+//!
+//! The result is a `serde_json::Value` plus the line and column of every
+//! node, keyed by JSON pointer.
+
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::fmt;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser, Tag};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+/// A 1-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<Marker> for Location {
+    fn from(mark: Marker) -> Self {
+        Location {
+            line: mark.line(),
+            column: mark.col() + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YamlError {
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.location.line, self.location.column
+        )
+    }
+}
+
+impl std::error::Error for YamlError {}
+
+/// A loaded document. `value` is None for an empty document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadedYaml {
+    pub value: Option<Value>,
+    locations: HashMap<String, Location>,
+}
+
+impl LoadedYaml {
+    /// The location of the node at `pointer` (of its key, for a mapping
+    /// value), or of its nearest ancestor with one: nodes copied by an alias
+    /// or merge key have none.
+    pub fn location(&self, pointer: &str) -> Option<Location> {
+        let mut pointer = pointer;
+        loop {
+            if let Some(location) = self.locations.get(pointer) {
+                return Some(*location);
+            }
+            pointer = &pointer[..pointer.rfind('/')?];
+        }
+    }
+}
+
+pub(crate) fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn is_digits(s: &str, radix: u32) -> bool {
+    !s.is_empty() && s.chars().all(|c| c == '_' || c.is_digit(radix))
+}
+
+fn strip_sign(s: &str) -> (bool, &str) {
+    match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    }
+}
+
+/// `a:b:c` in base 60, as YAML 1.1 sexagesimal numbers.
+fn sexagesimal(s: &str) -> Option<f64> {
+    let mut parts = s.split(':');
+    let first = parts.next()?.replace('_', "");
+    let mut total: f64 = first.parse().ok()?;
+    for part in parts {
+        if part.is_empty() || part.len() > 2 || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        total = total * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(total)
+}
+
+fn int_value(negative: bool, magnitude: i128) -> Value {
+    let n = if negative { -magnitude } else { magnitude };
+    match i64::try_from(n) {
+        Ok(n) => Value::from(n),
+        Err(_) => Number::from_f64(n as f64).map_or(Value::Null, Value::Number),
+    }
+}
+
+fn resolve_int(s: &str) -> Option<Value> {
+    let (negative, body) = strip_sign(s);
+    let parse = |digits: &str, radix: u32| {
+        i128::from_str_radix(&digits.replace('_', ""), radix)
+            .ok()
+            .map(|n| int_value(negative, n))
+    };
+    if let Some(bin) = body.strip_prefix("0b") {
+        return is_digits(bin, 2).then(|| parse(bin, 2)).flatten();
+    }
+    if let Some(hex) = body.strip_prefix("0x") {
+        return is_digits(hex, 16).then(|| parse(hex, 16)).flatten();
+    }
+    if body == "0" {
+        return Some(Value::from(0));
+    }
+    if let Some(octal) = body.strip_prefix('0') {
+        return is_digits(octal, 8).then(|| parse(octal, 8)).flatten();
+    }
+    if body.starts_with(|c: char| c.is_ascii_digit() && c != '0') {
+        if is_digits(body, 10) {
+            return parse(body, 10);
+        }
+        if body.contains(':') && body.split(':').next().is_some_and(|h| is_digits(h, 10)) {
+            let total = sexagesimal(body)?;
+            return Some(int_value(negative, total as i128));
+        }
+    }
+    None
+}
+
+fn resolve_float(s: &str) -> Option<Value> {
+    let (negative, body) = strip_sign(s);
+    let sign = if negative { -1.0 } else { 1.0 };
+    if matches!(body, ".inf" | ".Inf" | ".INF") || matches!(s, ".nan" | ".NaN" | ".NAN") {
+        // JSON has no infinities or NaN; keep the text.
+        return Some(Value::String(s.to_string()));
+    }
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(e) => (&body[..e], Some(&body[e + 1..])),
+        None => (body, None),
+    };
+    if let Some(exponent) = exponent {
+        // YAML 1.1 requires a signed exponent and a dot in the mantissa.
+        let (_, digits) = strip_sign(exponent);
+        if !exponent.starts_with(['+', '-']) || !is_digits(digits, 10) || digits.contains('_') {
+            return None;
+        }
+    }
+    let (int_part, frac_part) = mantissa.split_once('.')?;
+    let valid = if int_part.is_empty() {
+        is_digits(frac_part, 10)
+    } else if int_part.contains(':') {
+        exponent.is_none()
+            && int_part.starts_with(|c: char| c.is_ascii_digit())
+            && frac_part.chars().all(|c| c == '_' || c.is_ascii_digit())
+    } else {
+        int_part.starts_with(|c: char| c.is_ascii_digit())
+            && is_digits(int_part, 10)
+            && frac_part.chars().all(|c| c == '_' || c.is_ascii_digit())
+    };
+    if !valid {
+        return None;
+    }
+    let value = if int_part.contains(':') {
+        sexagesimal(int_part)?
+            + format!("0.{}", frac_part.replace('_', ""))
+                .parse::<f64>()
+                .ok()?
+    } else {
+        body.replace('_', "").parse::<f64>().ok()?
+    };
+    Number::from_f64(sign * value).map(Value::Number)
+}
+
+/// Resolve a scalar the way PyYAML's `SafeLoader` does.
+fn resolve_scalar(text: String, style: TScalarStyle, tag: Option<&Tag>) -> Value {
+    if let Some(tag) = tag {
+        if tag.handle == "!!" || tag.handle == "tag:yaml.org,2002:" {
+            match tag.suffix.as_str() {
+                "str" => return Value::String(text),
+                "null" => return Value::Null,
+                _ => {}
+            }
+        } else {
+            return Value::String(text);
+        }
+    }
+    if style != TScalarStyle::Plain {
+        return Value::String(text);
+    }
+    match text.as_str() {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Null,
+        "yes" | "Yes" | "YES" | "true" | "True" | "TRUE" | "on" | "On" | "ON" => {
+            return Value::Bool(true)
+        }
+        "no" | "No" | "NO" | "false" | "False" | "FALSE" | "off" | "Off" | "OFF" => {
+            return Value::Bool(false)
+        }
+        _ => {}
+    }
+    if text.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.')) {
+        if let Some(value) = resolve_int(&text).or_else(|| resolve_float(&text)) {
+            return value;
+        }
+    }
+    Value::String(text)
+}
+
+/// A key as PyYAML would stringify it for a JSON-like dict.
+fn key_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        Value::Null => "None".to_string(),
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        other => other.to_string(),
+    }
+}
+
+enum Frame {
+    Sequence {
+        pointer: String,
+        anchor: usize,
+        items: Vec<Value>,
+    },
+    Mapping {
+        pointer: String,
+        anchor: usize,
+        entries: Map<String, Value>,
+        merged: Vec<Value>,
+        key: Option<String>,
+    },
+}
+
+#[derive(Default)]
+struct Builder {
+    stack: Vec<Frame>,
+    anchors: HashMap<usize, Value>,
+    locations: HashMap<String, Location>,
+    documents: Vec<Option<Value>>,
+    document_marks: Vec<Marker>,
+    error: Option<YamlError>,
+}
+
+impl Builder {
+    /// The pointer of the next node, or None if it is a mapping key.
+    fn next_pointer(&self) -> Option<String> {
+        match self.stack.last() {
+            None => Some(String::new()),
+            Some(Frame::Sequence { pointer, items, .. }) => {
+                Some(format!("{}/{}", pointer, items.len()))
+            }
+            Some(Frame::Mapping { key: None, .. }) => None,
+            Some(Frame::Mapping {
+                pointer,
+                key: Some(key),
+                ..
+            }) => Some(format!("{}/{}", pointer, escape_pointer(key))),
+        }
+    }
+
+    fn fail(&mut self, mark: Marker, message: impl Into<String>) {
+        if self.error.is_none() {
+            self.error = Some(YamlError {
+                location: mark.into(),
+                message: message.into(),
+            });
+        }
+    }
+
+    fn finish_node(&mut self, value: Value, anchor: usize, mark: Marker) {
+        if anchor != 0 {
+            self.anchors.insert(anchor, value.clone());
+        }
+        match self.stack.last_mut() {
+            None => {
+                if let Some(document) = self.documents.last_mut() {
+                    *document = Some(value);
+                }
+            }
+            Some(Frame::Sequence { items, .. }) => items.push(value),
+            Some(Frame::Mapping {
+                key: key @ None,
+                pointer,
+                entries,
+                merged,
+                ..
+            }) => match value {
+                Value::Array(_) | Value::Object(_) => {
+                    self.fail(mark, "found unhashable key");
+                }
+                value => {
+                    let location = Location::from(mark);
+                    // A block mapping starts at the `:` after its first key;
+                    // point at the key instead, as a flow mapping's `{` does.
+                    if entries.is_empty() && merged.is_empty() {
+                        if let Some(start) = self.locations.get_mut(pointer.as_str()) {
+                            if (location.line, location.column) < (start.line, start.column) {
+                                *start = location;
+                            }
+                        }
+                    }
+                    let name = key_string(&value);
+                    let child = format!("{}/{}", pointer, escape_pointer(&name));
+                    self.locations.insert(child, location);
+                    *key = Some(name);
+                }
+            },
+            Some(Frame::Mapping {
+                key: key @ Some(_),
+                entries,
+                merged,
+                ..
+            }) => {
+                let key = key.take().unwrap_or_default();
+                if key == "<<" {
+                    merged.push(value);
+                } else {
+                    entries.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for Builder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        if !matches!(
+            event,
+            Event::StreamStart
+                | Event::StreamEnd
+                | Event::DocumentStart
+                | Event::DocumentEnd
+                | Event::MappingEnd
+                | Event::SequenceEnd
+        ) {
+            if let Some(pointer) = self.next_pointer() {
+                self.locations.entry(pointer).or_insert(mark.into());
+            }
+        }
+        match event {
+            Event::DocumentStart => {
+                self.documents.push(None);
+                self.document_marks.push(mark);
+            }
+            Event::Scalar(text, style, anchor, tag) => {
+                let value = resolve_scalar(text, style, tag.as_ref());
+                self.finish_node(value, anchor, mark);
+            }
+            Event::Alias(anchor) => match self.anchors.get(&anchor).cloned() {
+                Some(value) => self.finish_node(value, 0, mark),
+                None => self.fail(mark, "found undefined alias"),
+            },
+            Event::SequenceStart(anchor, _) => {
+                let pointer = self.next_pointer().unwrap_or_default();
+                self.stack.push(Frame::Sequence {
+                    pointer,
+                    anchor,
+                    items: Vec::new(),
+                });
+            }
+            Event::MappingStart(anchor, _) => {
+                let pointer = self.next_pointer().unwrap_or_default();
+                self.stack.push(Frame::Mapping {
+                    pointer,
+                    anchor,
+                    entries: Map::new(),
+                    merged: Vec::new(),
+                    key: None,
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => match self.stack.pop() {
+                Some(Frame::Sequence { anchor, items, .. }) => {
+                    self.finish_node(Value::Array(items), anchor, mark)
+                }
+                Some(Frame::Mapping {
+                    anchor,
+                    entries,
+                    merged,
+                    ..
+                }) => {
+                    // Explicit keys win over merged ones, and earlier merge
+                    // sources over later ones.
+                    let mut result = Map::new();
+                    let sources = merged.into_iter().flat_map(|value| match value {
+                        Value::Array(items) => items,
+                        value => vec![value],
+                    });
+                    for source in sources {
+                        let Value::Object(source) = source else {
+                            self.fail(mark, "expected a mapping or list of mappings for merging");
+                            return;
+                        };
+                        for (key, value) in source {
+                            result.entry(key).or_insert(value);
+                        }
+                    }
+                    result.extend(entries);
+                    self.finish_node(Value::Object(result), anchor, mark);
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Load a single YAML document.
+pub fn load_yaml(contents: &str) -> Result<LoadedYaml, YamlError> {
+    let mut builder = Builder::default();
+    let mut parser = Parser::new_from_str(contents);
+    parser.load(&mut builder, true).map_err(|e| YamlError {
+        location: (*e.marker()).into(),
+        message: e.info().to_string(),
+    })?;
+    if let Some(error) = builder.error {
+        return Err(error);
+    }
+    if builder.documents.len() > 1 {
+        return Err(YamlError {
+            location: builder.document_marks[1].into(),
+            message: "expected a single document in the stream".to_string(),
+        });
+    }
+    Ok(LoadedYaml {
+        value: builder.documents.pop().flatten(),
+        locations: builder.locations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scalars_resolve_like_pyyaml() {
+        let loaded = load_yaml(
+            "a: yes\nb: Off\nc: ~\nd: 010\ne: 0x1f\nf: 1_000\ng: 1.5\nh: 1e5\ni: 1.0e+2\n\
+             j: '1'\nk: 2024-01-01\nl: 1:30\nm: .5\nn: -7\no: !!str 3\np:\nq: +.inf\n",
+        )
+        .unwrap();
+        assert_eq!(
+            loaded.value.unwrap(),
+            json!({
+                "a": true, "b": false, "c": null, "d": 8, "e": 31, "f": 1000, "g": 1.5,
+                "h": "1e5", "i": 100.0, "j": "1", "k": "2024-01-01", "l": 90, "m": 0.5,
+                "n": -7, "o": "3", "p": null, "q": "+.inf"
+            })
+        );
+    }
+
+    #[test]
+    fn test_anchors_merge_keys_and_locations() {
+        let source = "\
+defaults: &defaults
+  materialized: view
+  tags: [a]
+models:
+  - name: one
+    config:
+      <<: *defaults
+      materialized: table
+  - name: two
+    config: *defaults
+";
+        let loaded = load_yaml(source).unwrap();
+        let value = loaded.value.clone().unwrap();
+        assert_eq!(
+            value["models"][0]["config"],
+            json!({"materialized": "table", "tags": ["a"]})
+        );
+        assert_eq!(
+            value["models"][1]["config"],
+            json!({"materialized": "view", "tags": ["a"]})
+        );
+        assert_eq!(
+            loaded.location("/models/1/name"),
+            Some(Location { line: 9, column: 5 })
+        );
+        assert_eq!(loaded.location("/models/0/config/tags/0").unwrap().line, 6);
+    }
+
+    #[test]
+    fn test_mapping_locations_point_at_their_first_key() {
+        let source = "\
+models:
+  - name: one
+  -
+    name: two
+  - {name: three}
+";
+        let loaded = load_yaml(source).unwrap();
+        assert_eq!(loaded.location(""), Some(Location { line: 1, column: 1 }));
+        assert_eq!(
+            loaded.location("/models/0"),
+            Some(Location { line: 2, column: 5 })
+        );
+        assert_eq!(
+            loaded.location("/models/1"),
+            Some(Location { line: 4, column: 5 })
+        );
+        assert_eq!(
+            loaded.location("/models/2"),
+            Some(Location { line: 5, column: 5 })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = load_yaml("models:\n  - name: x\n   bad: [\n").unwrap_err();
+        assert_eq!(err.location.line, 3);
+        let err = load_yaml("a: 1\n---\nb: 2\n").unwrap_err();
+        assert_eq!(err.message, "expected a single document in the stream");
+        assert_eq!(load_yaml("").unwrap().value, None);
+        assert_eq!(load_yaml("# only a comment\n").unwrap().value, None);
+    }
+}
@@ -0,0 +1,107 @@
+import json
+
+import dbt_rs
+import pytest
+
+
+def make_node(name, compiled_code, depends_on=()):
+    return {
+        "unique_id": f"model.p.{name}",
+        "name": name,
+        "resource_type": "model",
+        "package_name": "p",
+        "relation_name": f'"db"."main"."{name}"',
+        "depends_on": {"nodes": list(depends_on), "macros": []},
+        "compiled_code": compiled_code,
+    }
+
+
+@pytest.fixture
+def lineage():
+    nodes = [
+        make_node(
+            "stg_customers",
+            "select id as customer_id, email, first_name || ' ' || last_name as full_name"
+            ' from "db"."raw"."customers"',
+            ["source.p.raw.customers"],
+        ),
+        make_node(
+            "customers",
+            'select customer_id, email as contact, upper(full_name) as name, count(*) as n'
+            ' from "db"."main"."stg_customers" group by 1, 2, 3',
+            ["model.p.stg_customers"],
+        ),
+        make_node("opaque", "select * from unknown_table"),
+    ]
+    manifest = dbt_rs.load_manifest(
+        json.dumps(
+            {
+                "metadata": {"adapter_type": "postgres"},
+                "nodes": {node["unique_id"]: node for node in nodes},
+                "sources": {
+                    "source.p.raw.customers": {
+                        "unique_id": "source.p.raw.customers",
+                        "source_name": "raw",
+                        "name": "customers",
+                        "package_name": "p",
+                        "relation_name": '"db"."raw"."customers"',
+                        "columns": {"id": {}, "email": {}, "first_name": {}, "last_name": {}},
+                    }
+                },
+            }
+        ),
+        set_global=False,
+    )
+    return dbt_rs.build_column_lineage(manifest)
+
+
+def staging(column, kind):
+    return {"unique_id": "model.p.stg_customers", "column": column, "kind": kind}
+
+
+class TestColumnLineage:
+    def test_columns(self, lineage):
+        assert lineage.columns("model.p.customers") == [
+            {"name": "customer_id", "sources": [staging("customer_id", "passthrough")]},
+            {"name": "contact", "sources": [staging("email", "rename")]},
+            {"name": "name", "sources": [staging("full_name", "expression")]},
+            {"name": "n", "sources": []},
+        ]
+        assert lineage.is_complete("model.p.customers")
+        assert not lineage.is_complete("model.p.opaque")
+        assert lineage.columns("model.p.missing") is None
+        assert lineage.is_complete("model.p.missing") is None
+
+    def test_upstream_and_downstream(self, lineage):
+        upstream = lineage.upstream("model.p.customers", "CONTACT")
+        assert [(edge["source_unique_id"], edge["source_column"]) for edge in upstream] == [
+            ("model.p.stg_customers", "email"),
+            ("source.p.raw.customers", "email"),
+        ]
+        assert upstream[0] == {
+            "source_unique_id": "model.p.stg_customers",
+            "source_column": "email",
+            "target_unique_id": "model.p.customers",
+            "target_column": "contact",
+            "kind": "rename",
+        }
+
+        downstream = lineage.downstream("source.p.raw.customers", "first_name")
+        targets = sorted((edge["target_unique_id"], edge["target_column"]) for edge in downstream)
+        assert targets == [
+            ("model.p.customers", "name"),
+            ("model.p.stg_customers", "full_name"),
+        ]
+
+    def test_edges_and_json(self, lineage):
+        assert len(lineage.edges()) == 7
+        written = json.loads(lineage.to_json())
+        assert written["nodes"]["model.p.customers"]["columns"][1]["sources"] == [
+            staging("email", "rename")
+        ]
+
+    def test_dialect(self):
+        manifest = dbt_rs.load_manifest(json.dumps({"nodes": {}}), set_global=False)
+        assert dbt_rs.build_column_lineage(manifest, "snowflake").edges() == []
+        with pytest.raises(ValueError):
+            dbt_rs.build_column_lineage(manifest, "oracle")
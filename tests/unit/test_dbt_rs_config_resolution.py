@@ -0,0 +1,84 @@
+import dbt_rs
+import pytest
+
+
+@pytest.fixture
+def resolver():
+    return dbt_rs.DbtConfigResolver(
+        "jaffle",
+        {
+            "jaffle": {
+                "name": "jaffle",
+                "models": {
+                    "+tags": "root",
+                    "+meta": {"owner": "data", "tier": 1},
+                    "jaffle": {
+                        "+materialized": "table",
+                        "staging": {
+                            "+materialized": "view",
+                            "+tags": ["staging"],
+                            "pre_hook": "select 1",
+                        },
+                    },
+                    "utils": {"+schema": "utils"},
+                },
+            },
+            "utils": {
+                "name": "utils",
+                "models": {"utils": {"+materialized": "ephemeral", "+tags": ["pkg"]}},
+            },
+        },
+    )
+
+
+class TestDbtConfigResolver:
+    def test_resolve(self, resolver):
+        config = resolver.resolve(
+            "jaffle",
+            "model",
+            ["jaffle", "staging", "stg_orders"],
+            properties={"meta": {"tier": 2}, "tags": "props"},
+            config_call=[{"tags": ["call"]}, {"tags": "call2", "materialized": "incremental"}],
+        )
+
+        assert config["materialized"] == "incremental"
+        assert config["tags"] == ["root", "staging", "props", "call", "call2"]
+        assert config["meta"] == {"owner": "data", "tier": 2}
+        assert config["pre-hook"] == [{"sql": "select 1", "transaction": True, "index": None}]
+        assert "pre_hook" not in config
+
+    def test_origins(self, resolver):
+        config, origins = resolver.resolve(
+            "jaffle",
+            "model",
+            ["jaffle", "staging", "stg_orders"],
+            properties={"meta": {"tier": 2}},
+            config_call={"materialized": "incremental"},
+            with_origins=True,
+        )
+
+        assert config["materialized"] == "incremental"
+        assert origins["materialized"] == ["config()"]
+        assert origins["meta"] == ["default", "jaffle dbt_project.yml", "properties"]
+
+    def test_package_nodes_get_root_project_configs_last(self, resolver):
+        config, origins = resolver.resolve(
+            "utils", "model", ["utils", "dates"], config_call={"schema": "mine"}, with_origins=True
+        )
+
+        assert config["materialized"] == "ephemeral"
+        assert config["schema"] == "utils"
+        assert config["tags"] == ["pkg", "root"]
+        assert origins["schema"][0] == "root project jaffle dbt_project.yml at utils"
+
+    def test_errors(self, resolver):
+        with pytest.raises(ValueError):
+            resolver.resolve("missing", "model", ["missing", "a"])
+        with pytest.raises(ValueError):
+            resolver.resolve(
+                "jaffle",
+                "model",
+                ["jaffle", "a"],
+                properties={"meta": 1},
+                config_call={"meta": {}},
+            )
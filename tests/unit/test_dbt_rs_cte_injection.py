@@ -0,0 +1,98 @@
+import dbt_rs
+
+
+def normalize(sql):
+    return " ".join(sql.split())
+
+
+class TestInjectCtesIntoSql:
+    def test_inject(self):
+        sql = dbt_rs.inject_ctes_into_sql(
+            "with internal_cte as (select * from sessions) select * from internal_cte",
+            [
+                ("model.test.ephemeral", "__dbt__cte__ephemeral as (select * from table)"),
+                ("model.test.events", "__dbt__cte__events as (select id from events)"),
+            ],
+        )
+
+        assert normalize(sql) == (
+            "with __dbt__cte__ephemeral as (select * from table), "
+            "__dbt__cte__events as (select id from events), "
+            "internal_cte as (select * from sessions) select * from internal_cte"
+        )
+
+    def test_no_ctes(self):
+        assert dbt_rs.inject_ctes_into_sql("select 1", []) == "select 1"
+
+
+class TestInjectEphemeralCtes:
+    def make_graph(self):
+        graph = dbt_rs.DbtGraph()
+        graph.add_edge("model.root.level_two", "model.root.ephemeral")
+        graph.add_edge("model.root.ephemeral", "model.root.view")
+        graph.add_edge("model.root.other", "model.root.view")
+        return graph
+
+    def test_refs_from_graph(self):
+        injected = dbt_rs.inject_ephemeral_ctes(
+            self.make_graph(),
+            {
+                "model.root.ephemeral": ("ephemeral", "select * from __dbt__cte__level_two"),
+                "model.root.level_two": ("level_two", "select * from source_table"),
+            },
+            {
+                "model.root.view": "select * from __dbt__cte__ephemeral",
+                "model.root.ephemeral": "select * from __dbt__cte__level_two",
+            },
+        )
+
+        view = injected["model.root.view"]
+        assert normalize(view["compiled_code"]) == (
+            "with __dbt__cte__level_two as ( select * from source_table ), "
+            "__dbt__cte__ephemeral as ( select * from __dbt__cte__level_two ) "
+            "select * from __dbt__cte__ephemeral"
+        )
+        assert [cte["id"] for cte in view["extra_ctes"]] == [
+            "model.root.level_two",
+            "model.root.ephemeral",
+        ]
+        assert normalize(injected["model.root.ephemeral"]["compiled_code"]) == (
+            "with __dbt__cte__level_two as ( select * from source_table ) "
+            "select * from __dbt__cte__level_two"
+        )
+
+    def test_refs_in_call_order(self):
+        graph = dbt_rs.DbtGraph()
+        for parent in ["model.root.a", "model.root.b"]:
+            graph.add_edge(parent, "model.root.view")
+            graph.add_edge(parent, "model.root.ephemeral")
+        ephemeral = {
+            "model.root.a": ("a", "select 1"),
+            "model.root.b": ("b", "select 2"),
+            "model.root.ephemeral": ("ephemeral", "select * from __dbt__cte__b, __dbt__cte__a"),
+        }
+        compiled = {
+            "model.root.view": "select * from __dbt__cte__b, __dbt__cte__a",
+            "model.root.ephemeral": "select * from __dbt__cte__b, __dbt__cte__a",
+        }
+
+        by_graph = dbt_rs.inject_ephemeral_ctes(graph, ephemeral, compiled)
+        by_call_order = dbt_rs.inject_ephemeral_ctes(
+            graph,
+            ephemeral,
+            compiled,
+            {
+                "model.root.view": ["model.root.b", "model.root.a"],
+                "model.root.ephemeral": ["model.root.b", "model.root.a"],
+            },
+        )
+
+        assert [cte["id"] for cte in by_graph["model.root.view"]["extra_ctes"]] == [
+            "model.root.a",
+            "model.root.b",
+        ]
+        for unique_id in compiled:
+            assert [cte["id"] for cte in by_call_order[unique_id]["extra_ctes"]] == [
+                "model.root.b",
+                "model.root.a",
+            ]
@@ -0,0 +1,170 @@
+import json
+
+import dbt_rs
+import pytest
+
+PROPERTIES = """
+version: 2
+models:
+  - name: orders
+    columns:
+      - name: id
+        data_tests:
+          - unique
+          - not_null:
+              config:
+                severity: warn
+      - name: customer_id
+        data_tests:
+          - relationships:
+              arguments:
+                to: ref('customers')
+                field: id
+sources:
+  - name: raw
+    tables:
+      - name: payments
+        columns:
+          - name: id
+            quote: true
+            data_tests: [unique]
+"""
+
+
+def make_macro(package_name, name):
+    return {
+        "unique_id": f"macro.{package_name}.{name}",
+        "name": name,
+        "package_name": package_name,
+    }
+
+
+def make_node(name):
+    return {
+        "unique_id": f"model.shop.{name}",
+        "name": name,
+        "resource_type": "model",
+        "package_name": "shop",
+    }
+
+
+@pytest.fixture
+def manifest():
+    macros = [
+        make_macro("dbt", name)
+        for name in ["test_unique", "test_not_null", "test_relationships", "get_where_subquery"]
+    ]
+    return dbt_rs.load_manifest(
+        json.dumps(
+            {
+                "nodes": {
+                    "model.shop.orders": make_node("orders"),
+                    "model.shop.customers": make_node("customers"),
+                },
+                "sources": {
+                    "source.shop.raw.payments": {
+                        "unique_id": "source.shop.raw.payments",
+                        "name": "payments",
+                        "source_name": "raw",
+                        "resource_type": "source",
+                        "package_name": "shop",
+                    }
+                },
+                "macros": {macro["unique_id"]: macro for macro in macros},
+            }
+        ),
+        set_global=False,
+    )
+
+
+@pytest.fixture
+def resolver():
+    return dbt_rs.DbtConfigResolver("shop", {"shop": {"name": "shop"}})
+
+
+@pytest.fixture
+def properties():
+    [result] = dbt_rs.parse_properties_files([("models/staging/schema.yml", PROPERTIES)])
+    return result
+
+
+def build(manifest, resolver, entry, yaml_key, **kwargs):
+    return dbt_rs.build_generic_tests(
+        manifest,
+        resolver,
+        entry,
+        yaml_key,
+        "shop",
+        "models/staging/schema.yml",
+        database="analytics",
+        schema="dbt_me",
+        root_project="shop",
+        quote_char='"',
+        **kwargs,
+    )
+
+
+class TestBuildGenericTests:
+    def test_model_tests(self, manifest, resolver, properties):
+        result = build(manifest, resolver, properties["models"][0], "models", register=False)
+
+        assert result["link_errors"] == []
+        assert [test["unique_id"] for test in result["nodes"]] == [
+            "test.shop.unique_orders_id.1360ecc70e",
+            "test.shop.not_null_orders_id.84ef11d8fe",
+            "test.shop.relationships_orders_customer_id__id__ref_customers_.4282b0c525",
+        ]
+        not_null = result["nodes"][1]
+        assert not_null["config"]["severity"] == "warn"
+        assert not_null["depends_on"]["macros"] == ["macro.dbt.test_not_null"]
+        assert not_null["attached_node"] == "model.shop.orders"
+        assert not_null["test_metadata"] == {
+            "name": "not_null",
+            "namespace": None,
+            "kwargs": {"column_name": "id", "model": "{{ get_where_subquery(ref('orders')) }}"},
+        }
+        assert manifest.node_count() == 2
+
+    def test_register_and_link(self, manifest, resolver, properties):
+        result = build(manifest, resolver, properties["models"][0], "models")
+
+        assert result["link_errors"] == []
+        relationships = result["nodes"][2]
+        assert relationships["depends_on"]["nodes"] == [
+            "model.shop.customers",
+            "model.shop.orders",
+        ]
+        assert relationships["unique_id"] in manifest.nodes
+        assert manifest.node_count() == 5
+
+    def test_source_tests(self, manifest, resolver, properties):
+        [test] = build(manifest, resolver, properties["sources"][0], "sources")["nodes"]
+
+        assert test["unique_id"] == "test.shop.source_unique_raw_payments__id_.97427b24dc"
+        assert test["column_name"] == '"id"'
+        assert test["depends_on"]["nodes"] == ["source.shop.raw.payments"]
+
+    def test_link_errors(self, manifest, resolver):
+        [properties] = dbt_rs.parse_properties_files(
+            [
+                (
+                    "models/staging/schema.yml",
+                    "models:\n  - name: missing\n    data_tests: [unique]\n",
+                )
+            ]
+        )
+
+        result = build(manifest, resolver, properties["models"][0], "models")
+
+        [error] = result["link_errors"]
+        assert error["unique_id"] == result["nodes"][0]["unique_id"]
+        assert error["kind"] == "not_found"
+
+    def test_invalid_definitions(self, manifest, resolver):
+        yaml = "models:\n  - name: orders\n    data_tests:\n      - unique:\n          model: x\n"
+        [properties] = dbt_rs.parse_properties_files([("models/schema.yml", yaml)])
+
+        with pytest.raises(ValueError, match="reserved argument"):
+            build(manifest, resolver, properties["models"][0], "models")
+        with pytest.raises(ValueError, match="Cannot build tests for 'exposures' entries"):
+            build(manifest, resolver, properties["models"][0], "exposures")
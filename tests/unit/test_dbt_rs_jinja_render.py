@@ -0,0 +1,127 @@
+import json
+
+import dbt_rs
+import pytest
+
+RAW_CODE = (
+    "{{ config(materialized='incremental') }}"
+    "select * from {{ ref('stg') }} join {{ source('raw', 'pay') }}"
+    "{% if is_incremental() %} where x > (select max(x) from {{ this }}){% endif %}"
+    " -- {{ var('v') }} {{ env_var('DBT_RS_TEST_VAR', 'd') }} {{ audit(1, k='x') }}"
+)
+
+
+@pytest.fixture
+def manifest():
+    return dbt_rs.load_manifest(
+        json.dumps(
+            {
+                "nodes": {
+                    "model.p.orders": {
+                        "unique_id": "model.p.orders",
+                        "name": "orders",
+                        "resource_type": "model",
+                        "package_name": "p",
+                        "relation_name": '"db"."main"."orders"',
+                        "config": {"materialized": "incremental"},
+                        "raw_code": RAW_CODE,
+                    },
+                    "model.p.stg": {
+                        "unique_id": "model.p.stg",
+                        "name": "stg",
+                        "resource_type": "model",
+                        "package_name": "p",
+                        "config": {"materialized": "ephemeral"},
+                    },
+                    "model.p.bad": {
+                        "unique_id": "model.p.bad",
+                        "name": "bad",
+                        "resource_type": "model",
+                        "package_name": "p",
+                        "raw_code": "select * from {{ ref('nope') }}",
+                    },
+                },
+                "sources": {
+                    "source.p.raw.pay": {
+                        "unique_id": "source.p.raw.pay",
+                        "source_name": "raw",
+                        "name": "pay",
+                        "package_name": "p",
+                        "database": "db",
+                        "schema": "raw_data",
+                    }
+                },
+            }
+        ),
+        set_global=False,
+    )
+
+
+class TestRenderJinja:
+    @pytest.mark.parametrize(
+        "template,text,native",
+        [
+            ("{{ 1 | as_number }}", "1", 1),
+            ("{{ '1' | as_native }}", "1", 1),
+            ("{{ True | as_bool }}", "True", True),
+            ("{{ none | as_native }}", "None", None),
+            ("{{ (a_int + 100) | as_native }}", "200", 200),
+            ("'{{ a_int | as_native }}'", "'100'", "'100'"),
+        ],
+    )
+    def test_native_rendering(self, template, text, native):
+        context = {"a_int": 100}
+        assert dbt_rs.render_jinja(template, context) == text
+        assert dbt_rs.render_jinja(template, context, native=True) == native
+
+    def test_render_errors(self):
+        with pytest.raises(dbt_rs.JinjaRenderError):
+            dbt_rs.render_jinja("{{ 'bar' | as_bool }}", native=True)
+        with pytest.raises(ValueError):
+            dbt_rs.render_jinja("{{ missing }}")
+
+
+class TestCompileNodeSql:
+    def test_compile(self, manifest):
+        calls = []
+
+        def audit(*args, **kwargs):
+            calls.append((args, kwargs))
+            return "audited"
+
+        compiled = dbt_rs.compile_node_sql(
+            manifest,
+            "model.p.orders",
+            current_project="p",
+            vars={"v": 7},
+            env_vars={"DBT_RS_TEST_VAR": "e"},
+            relation_exists=True,
+            python_functions={"audit": audit},
+        )
+
+        assert compiled["compiled_code"] == (
+            'select * from __dbt__cte__stg join "db"."raw_data"."pay"'
+            ' where x > (select max(x) from "db"."main"."orders") -- 7 e audited'
+        )
+        assert compiled["refs"] == ["model.p.stg"]
+        assert compiled["ephemeral_refs"] == ["model.p.stg"]
+        assert compiled["sources"] == ["source.p.raw.pay"]
+        assert compiled["env_vars"] == {"DBT_RS_TEST_VAR": "e"}
+        assert calls == [((1,), {"k": "x"})]
+
+    def test_full_refresh_skips_incremental_branch(self, manifest):
+        compiled = dbt_rs.compile_node_sql(
+            manifest,
+            "model.p.orders",
+            current_project="p",
+            vars={"v": 7},
+            relation_exists=True,
+            full_refresh=True,
+            python_functions={"audit": lambda *args, **kwargs: ""},
+        )
+
+        assert "max(x)" not in compiled["compiled_code"]
+
+    def test_compile_errors(self, manifest):
+        with pytest.raises(dbt_rs.JinjaRenderError, match="ref\\('nope'\\) was not found"):
+            dbt_rs.compile_node_sql(manifest, "model.p.bad", current_project="p")
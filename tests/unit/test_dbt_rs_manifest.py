@@ -0,0 +1,551 @@
+import json
+from pathlib import Path
+
+import dbt_rs
+import pytest
+
+STATE_DIR = Path(__file__).parents[1] / "functional" / "artifacts" / "data" / "state"
+
+
+def make_node(name, depends_on=()):
+    return {
+        "unique_id": f"model.test_package.{name}",
+        "name": name,
+        "resource_type": "model",
+        "package_name": "test_package",
+        "fqn": ["test_package", name],
+        "depends_on": {"nodes": list(depends_on), "macros": []},
+        "raw_code": "select 1",
+        "config": {"materialized": "view", "enabled": True},
+    }
+
+
+def make_manifest_json(*nodes):
+    manifest = {
+        "nodes": {node["unique_id"]: node for node in nodes},
+        "sources": {},
+        "macros": {},
+    }
+    return json.dumps(manifest)
+
+
+class TestManifestHandles:
+    def test_handle_without_set_global_leaves_global_alone(self):
+        current = dbt_rs.load_manifest(make_manifest_json(make_node("upstream")))
+        other = dbt_rs.load_manifest(
+            make_manifest_json(
+                make_node("upstream"),
+                make_node("downstream", ["model.test_package.upstream"]),
+            ),
+            set_global=False,
+        )
+
+        assert other.node_count() == 2
+        assert dbt_rs.get_node_count() == 1
+        assert dbt_rs.get_global_manifest_handle().is_same(current)
+        assert not dbt_rs.get_global_manifest_handle().is_same(other)
+
+    def test_handles_are_independent(self):
+        first = dbt_rs.load_manifest(make_manifest_json(make_node("upstream")), set_global=False)
+        second = dbt_rs.load_manifest(make_manifest_json(make_node("upstream")), set_global=False)
+
+        second.upsert_nodes(json.dumps({"model.test_package.other": make_node("other")}))
+
+        assert not first.is_same(second)
+        assert first.node_count() == 1
+        assert second.node_count() == 2
+
+    def test_set_global(self):
+        dbt_rs.load_manifest(make_manifest_json(make_node("upstream")))
+        handle = dbt_rs.DbtManifest.from_json(
+            make_manifest_json(
+                make_node("upstream"),
+                make_node("downstream", ["model.test_package.upstream"]),
+            )
+        )
+        assert dbt_rs.get_node_count() == 1
+
+        handle.set_global()
+
+        assert dbt_rs.get_global_manifest_handle().is_same(handle)
+        assert dbt_rs.get_node_count() == 2
+        assert dbt_rs.get_node_dependencies("model.test_package.downstream") == [
+            "model.test_package.upstream"
+        ]
+
+    def test_global_functions_see_updates_through_the_handle(self):
+        handle = dbt_rs.load_manifest(make_manifest_json(make_node("upstream")))
+
+        handle.upsert_nodes(json.dumps({"model.test_package.other": make_node("other")}))
+
+        assert dbt_rs.get_node_count() == 2
+        assert dbt_rs.get_global_manifest_handle().node_count() == 2
+
+
+class TestManifestViews:
+    @pytest.fixture
+    def manifest(self):
+        return dbt_rs.load_manifest(
+            make_manifest_json(
+                make_node("upstream"),
+                make_node("downstream", ["model.test_package.upstream"]),
+            ),
+            set_global=False,
+        )
+
+    def test_node_view(self, manifest):
+        node = manifest.nodes["model.test_package.downstream"]
+
+        assert node.unique_id == "model.test_package.downstream"
+        assert node.name == "downstream"
+        assert node.resource_type == "model"
+        assert node.package_name == "test_package"
+        assert node.fqn == ["test_package", "downstream"]
+        assert node.depends_on_nodes == ["model.test_package.upstream"]
+        assert node.depends_on_macros == []
+        assert node.raw_code == "select 1"
+        assert node.compiled_code is None
+        assert node.materialized == "view"
+        assert node.enabled
+        assert repr(node) == "ManifestNodeView(model.test_package.downstream)"
+
+    def test_view_reads_updates(self, manifest):
+        node = manifest.nodes["model.test_package.upstream"]
+        updated = dict(make_node("upstream"), raw_code="select 2")
+
+        manifest.upsert_nodes(json.dumps({"model.test_package.upstream": updated}))
+
+        assert node.raw_code == "select 2"
+
+    def test_view_of_removed_node_raises(self, manifest):
+        node = manifest.nodes["model.test_package.upstream"]
+        nodes = manifest.nodes
+
+        assert manifest.remove_nodes(["model.test_package.upstream"]) == [
+            "model.test_package.upstream"
+        ]
+
+        assert "model.test_package.upstream" not in nodes
+        assert len(nodes) == 1
+        with pytest.raises(KeyError):
+            node.name
+
+    def test_mapping_protocol(self, manifest):
+        nodes = manifest.nodes
+
+        assert len(nodes) == 2
+        assert "model.test_package.upstream" in nodes
+        assert "model.test_package.missing" not in nodes
+        assert list(nodes) == ["model.test_package.downstream", "model.test_package.upstream"]
+        assert nodes.keys() == list(nodes)
+        assert [node.name for node in nodes.values()] == ["downstream", "upstream"]
+        assert [(key, node.unique_id) for key, node in nodes.items()] == [
+            ("model.test_package.downstream", "model.test_package.downstream"),
+            ("model.test_package.upstream", "model.test_package.upstream"),
+        ]
+        assert nodes.get("model.test_package.upstream").name == "upstream"
+        assert nodes.get("model.test_package.missing") is None
+        assert nodes.get("model.test_package.missing", "default") == "default"
+        assert repr(nodes) == "ManifestCollection(nodes, 2 entries)"
+        with pytest.raises(KeyError):
+            nodes["model.test_package.missing"]
+
+    def test_empty_collection(self, manifest):
+        assert len(manifest.sources) == 0
+        assert list(manifest.sources) == []
+        assert manifest.sources.get("source.test_package.raw.orders") is None
+
+
+def make_resolution_manifest():
+    return dbt_rs.load_manifest(
+        json.dumps(
+            {
+                "nodes": {
+                    "model.test_package.orders": make_node("orders"),
+                    "model.pkg.orders": dict(
+                        make_node("orders"), unique_id="model.pkg.orders", package_name="pkg"
+                    ),
+                    "model.pkg.secret": dict(
+                        make_node("secret"),
+                        unique_id="model.pkg.secret",
+                        package_name="pkg",
+                        access="private",
+                        group="finance",
+                    ),
+                },
+                "sources": {
+                    "source.test_package.raw.events": {
+                        "unique_id": "source.test_package.raw.events",
+                        "source_name": "raw",
+                        "name": "events",
+                        "package_name": "test_package",
+                    }
+                },
+                "macros": {},
+                "disabled": {
+                    "model.test_package.old": [
+                        dict(make_node("old"), config={"enabled": False})
+                    ]
+                },
+            }
+        ),
+        set_global=False,
+    )
+
+
+class TestResolution:
+    @pytest.fixture
+    def manifest(self):
+        return make_resolution_manifest()
+
+    def test_resolve_ref(self, manifest):
+        assert (
+            manifest.resolve_ref(
+                "orders", current_project="test_package", node_package="pkg"
+            )
+            == "model.test_package.orders"
+        )
+        assert (
+            manifest.resolve_ref(
+                "orders", "pkg", current_project="test_package", node_package="test_package"
+            )
+            == "model.pkg.orders"
+        )
+
+    def test_resolve_ref_errors(self, manifest):
+        with pytest.raises(dbt_rs.ResolutionError) as excinfo:
+            manifest.resolve_ref(
+                "missing", current_project="test_package", node_package="test_package"
+            )
+        assert excinfo.value.kind == "not_found"
+
+        with pytest.raises(dbt_rs.ResolutionError) as excinfo:
+            manifest.resolve_ref(
+                "old", current_project="test_package", node_package="test_package"
+            )
+        assert excinfo.value.kind == "disabled"
+        assert excinfo.value.unique_ids == ["model.test_package.old"]
+
+        with pytest.raises(dbt_rs.ResolutionError) as excinfo:
+            manifest.resolve_ref(
+                "secret", current_project="test_package", node_package="test_package"
+            )
+        assert excinfo.value.kind == "private"
+
+    def test_resolve_source(self, manifest):
+        assert (
+            manifest.resolve_source(
+                "raw", "events", current_project="test_package", node_package="test_package"
+            )
+            == "source.test_package.raw.events"
+        )
+        with pytest.raises(dbt_rs.ResolutionError) as excinfo:
+            manifest.resolve_source(
+                "raw", "missing", current_project="test_package", node_package="test_package"
+            )
+        assert excinfo.value.kind == "not_found"
+
+
+def make_macro(package_name, name):
+    return {
+        "unique_id": f"macro.{package_name}.{name}",
+        "name": name,
+        "package_name": package_name,
+        "macro_sql": "",
+    }
+
+
+class TestMacroResolution:
+    @pytest.fixture
+    def manifest(self):
+        macros = [
+            make_macro("test_package", "foo"),
+            make_macro("pkg", "foo"),
+            make_macro("dbt", "foo"),
+            make_macro("dbt", "default__cast"),
+            make_macro("dbt_postgres", "postgres__cast"),
+            make_macro("utils", "default__pivot"),
+            make_macro("shim", "default__pivot"),
+        ]
+        return dbt_rs.load_manifest(
+            json.dumps(
+                {
+                    "nodes": {},
+                    "sources": {},
+                    "macros": {macro["unique_id"]: macro for macro in macros},
+                }
+            ),
+            set_global=False,
+        )
+
+    def resolve_macro(self, manifest, name, package=None, **kwargs):
+        return manifest.resolve_macro(
+            name,
+            package,
+            root_project="test_package",
+            internal_packages=["dbt_postgres", "dbt"],
+            **kwargs,
+        )
+
+    def resolve_dispatch(self, manifest, name, namespace=None, **kwargs):
+        return manifest.resolve_dispatch(
+            name,
+            namespace,
+            root_project="test_package",
+            internal_packages=["dbt_postgres", "dbt"],
+            adapter_types=["postgres"],
+            **kwargs,
+        )
+
+    def test_resolve_macro(self, manifest):
+        assert self.resolve_macro(manifest, "foo") == {
+            "unique_id": "macro.test_package.foo",
+            "shadowed": ["macro.pkg.foo", "macro.dbt.foo"],
+            "searched": ["foo"],
+        }
+        assert self.resolve_macro(manifest, "foo", node_package="pkg")["unique_id"] == (
+            "macro.pkg.foo"
+        )
+        assert self.resolve_macro(manifest, "foo", "dbt")["unique_id"] == "macro.dbt.foo"
+
+        with pytest.raises(dbt_rs.ResolutionError) as excinfo:
+            self.resolve_macro(manifest, "missing")
+        assert excinfo.value.kind == "not_found"
+        assert excinfo.value.unique_ids == []
+
+    def test_resolve_dispatch(self, manifest):
+        resolution = self.resolve_dispatch(manifest, "cast")
+        assert resolution["unique_id"] == "macro.dbt_postgres.postgres__cast"
+        assert resolution["shadowed"] == ["macro.dbt.default__cast"]
+        assert resolution["searched"] == ["postgres__cast", "default__cast"]
+
+        assert self.resolve_dispatch(manifest, "pivot", "utils")["unique_id"] == (
+            "macro.utils.default__pivot"
+        )
+        resolution = self.resolve_dispatch(
+            manifest, "pivot", "utils", search_order={"utils": ["shim", "utils"]}
+        )
+        assert resolution["unique_id"] == "macro.shim.default__pivot"
+        assert resolution["shadowed"] == ["macro.utils.default__pivot"]
+
+    def test_resolve_dispatch_errors(self, manifest):
+        with pytest.raises(dbt_rs.ResolutionError) as excinfo:
+            self.resolve_dispatch(manifest, "utils.pivot")
+        assert excinfo.value.kind == "invalid_name"
+
+
+class TestWriteManifest:
+    def test_round_trip_is_lossless(self, tmp_path):
+        original = (STATE_DIR / "v12" / "manifest.json").read_text()
+        manifest = dbt_rs.load_manifest(original, set_global=False)
+
+        manifest.write_manifest(str(tmp_path / "manifest.json"))
+
+        assert json.loads((tmp_path / "manifest.json").read_text()) == json.loads(original)
+
+    def test_write_global_manifest(self, tmp_path):
+        original = json.loads(make_manifest_json(make_node("upstream")))
+        original["disabled"] = None
+        dbt_rs.load_manifest(json.dumps(original))
+
+        dbt_rs.write_manifest(str(tmp_path / "manifest.json"))
+
+        written = json.loads((tmp_path / "manifest.json").read_text())
+        assert written["disabled"] is None
+        assert written["nodes"] == original["nodes"]
+
+
+class TestSchemaUpgrade:
+    @pytest.mark.parametrize("version", [4, 8, 10])
+    def test_load_older_schema(self, version, tmp_path):
+        original = json.loads((STATE_DIR / f"v{version}" / "manifest.json").read_text())
+        manifest = dbt_rs.load_manifest(json.dumps(original), set_global=False)
+
+        assert manifest.node_count() == len(original["nodes"])
+        manifest.write_manifest(str(tmp_path / "manifest.json"))
+        written = json.loads((tmp_path / "manifest.json").read_text())
+        assert written["metadata"]["dbt_schema_version"] == (
+            "https://schemas.getdbt.com/dbt/manifest/v12.json"
+        )
+        for node in written["nodes"].values():
+            assert "raw_sql" not in node
+            assert "raw_code" in node
+
+    def test_unsupported_schema(self):
+        old = (STATE_DIR / "v3" / "manifest.json").read_text()
+        with pytest.raises(dbt_rs.ManifestParseError, match="v3 is not supported") as excinfo:
+            dbt_rs.load_manifest(old, set_global=False)
+        assert excinfo.value.kind == "version"
+
+
+class TestManifestParseError:
+    def test_data_error(self):
+        node = dict(make_node("downstream"), config={"enabled": True, "materialized": 5})
+
+        with pytest.raises(dbt_rs.ManifestParseError) as excinfo:
+            dbt_rs.load_manifest(make_manifest_json(make_node("upstream"), node), set_global=False)
+
+        error = excinfo.value
+        assert isinstance(error, ValueError)
+        assert error.kind == "data"
+        assert error.path == "/nodes/model.test_package.downstream/config/materialized"
+        assert error.collection == "nodes"
+        assert error.unique_id == "model.test_package.downstream"
+        assert error.field == "config.materialized"
+        assert error.expected == "a string"
+        assert error.found == "integer `5`"
+        assert error.line is None
+        assert error.column is None
+
+    def test_syntax_error(self):
+        invalid = '{"nodes": {"model.p.a": {"fqn": ["p", "a",]}}}'
+        with pytest.raises(dbt_rs.ManifestParseError, match="at line 1 column") as excinfo:
+            dbt_rs.load_manifest(invalid, set_global=False)
+
+        error = excinfo.value
+        assert error.kind == "syntax"
+        assert error.path == "/nodes/model.p.a/fqn/2"
+        assert error.line == 1
+        assert error.column is not None
+
+
+class TestManifestPatch:
+    @pytest.fixture
+    def manifest(self):
+        return dbt_rs.load_manifest(
+            make_manifest_json(
+                make_node("upstream"),
+                make_node("downstream", ["model.test_package.upstream"]),
+            ),
+            set_global=False,
+        )
+
+    def test_upsert_and_update_graph(self, manifest):
+        graph = dbt_rs.build_graph_from_manifest_handle(manifest)
+        added = make_node("added", ["model.test_package.downstream"])
+        moved = make_node("downstream", ["model.test_package.added"])
+
+        changed = manifest.upsert_nodes(
+            json.dumps({"model.test_package.added": added, "model.test_package.downstream": moved})
+        )
+        graph.update_from_manifest(manifest, changed)
+
+        assert sorted(changed) == ["model.test_package.added", "model.test_package.downstream"]
+        assert manifest.node_count() == 3
+        assert graph.predecessors("model.test_package.downstream") == {"model.test_package.added"}
+        assert graph.successors("model.test_package.upstream") == set()
+
+    def test_remove_and_update_graph(self, manifest):
+        graph = dbt_rs.build_graph_from_manifest_handle(manifest)
+
+        removed = manifest.remove_nodes(
+            ["model.test_package.upstream", "model.test_package.missing"]
+        )
+        graph.update_from_manifest(manifest, [], removed)
+
+        assert removed == ["model.test_package.upstream"]
+        # downstream still depends on upstream, so a full rebuild keeps it
+        rebuilt = dbt_rs.build_graph_from_manifest_handle(manifest)
+        assert graph.nodes() == rebuilt.nodes()
+        assert sorted(graph.edges()) == sorted(rebuilt.edges())
+
+        manifest.remove_nodes(["model.test_package.downstream"])
+        graph.update_from_manifest(manifest, [], ["model.test_package.downstream"])
+        assert graph.nodes() == set()
+
+    def test_invalid_upsert_changes_nothing(self, manifest):
+        valid = make_node("valid")
+        invalid = dict(make_node("invalid"), fqn="not a list")
+
+        nodes = {"model.test_package.valid": valid, "model.test_package.invalid": invalid}
+        with pytest.raises(dbt_rs.ManifestParseError) as excinfo:
+            manifest.upsert_nodes(json.dumps(nodes))
+
+        assert excinfo.value.unique_id == "model.test_package.invalid"
+        assert manifest.node_count() == 2
+
+    def test_disabled_and_unknown_collections(self, manifest):
+        old = dict(make_node("old"), config={"enabled": False})
+
+        assert manifest.upsert("disabled", json.dumps({"model.test_package.old": [old]})) == [
+            "model.test_package.old"
+        ]
+        assert manifest.remove_disabled(["model.test_package.old"]) == ["model.test_package.old"]
+        with pytest.raises(ValueError, match="Unknown collection: seeds"):
+            manifest.upsert("seeds", "{}")
+
+
+class TestPartialParse:
+    @pytest.fixture
+    def saved_state(self):
+        manifest = json.loads(
+            make_manifest_json(
+                make_node("upstream"),
+                make_node("downstream", ["model.test_package.upstream"]),
+            )
+        )
+        manifest["metadata"] = {
+            "dbt_schema_version": "https://schemas.getdbt.com/dbt/manifest/v12.json",
+        }
+        manifest["files"] = {
+            "test_package://models/upstream.sql": {
+                "path": {
+                    "searched_path": "models",
+                    "relative_path": "upstream.sql",
+                    "modification_time": 0.0,
+                    "project_root": "/project",
+                },
+                "checksum": {"name": "sha256", "checksum": "abc"},
+                "project_name": "test_package",
+                "parse_file_type": "model",
+                "nodes": ["model.test_package.upstream"],
+            }
+        }
+        manifest["state_check"] = {
+            "vars_hash": {"name": "sha256", "checksum": "v"},
+            "project_hashes": {"test_package": {"name": "sha256", "checksum": "h"}},
+        }
+        return dbt_rs.load_manifest(json.dumps(manifest), set_global=False)
+
+    def test_write_and_load(self, saved_state, tmp_path):
+        path = str(tmp_path / "target" / "partial_parse.msgpack")
+        saved_state.write_partial_parse(path)
+
+        manifest = dbt_rs.load_partial_parse(path, set_global=False)
+
+        assert manifest.node_count() == 2
+        assert manifest.nodes["model.test_package.downstream"].depends_on_nodes == [
+            "model.test_package.upstream"
+        ]
+        assert not dbt_rs.get_global_manifest_handle().is_same(manifest)
+        dbt_rs.load_partial_parse(path)
+        assert dbt_rs.get_node_count() == 2
+
+    def test_file_hashes_and_state_check(self, saved_state, tmp_path):
+        path = str(tmp_path / "partial_parse.msgpack")
+        saved_state.write_partial_parse(path)
+        manifest = dbt_rs.load_partial_parse(path, set_global=False)
+
+        assert manifest.file_hashes() == {
+            "test_package://models/upstream.sql": {
+                "project_name": "test_package",
+                "original_file_path": "models/upstream.sql",
+                "parse_file_type": "model",
+                "checksum": {"name": "sha256", "checksum": "abc"},
+            }
+        }
+        state = manifest.state_check()
+        assert state["vars_hash"] == {"name": "sha256", "checksum": "v"}
+        assert state["project_hashes"] == {"test_package": {"name": "sha256", "checksum": "h"}}
+
+        plain = dbt_rs.load_manifest(make_manifest_json(make_node("upstream")), set_global=False)
+        assert plain.file_hashes() == {}
+        assert plain.state_check() is None
+
+    def test_load_errors(self, tmp_path):
+        with pytest.raises(OSError):
+            dbt_rs.load_partial_parse(str(tmp_path / "missing.msgpack"), set_global=False)
+
+        (tmp_path / "bad.msgpack").write_bytes(b"\x81\xa5")
+        with pytest.raises(ValueError, match="invalid msgpack"):
+            dbt_rs.load_partial_parse(str(tmp_path / "bad.msgpack"), set_global=False)
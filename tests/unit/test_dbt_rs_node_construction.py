@@ -0,0 +1,133 @@
+import json
+
+import dbt_rs
+import pytest
+
+
+@pytest.fixture
+def manifest():
+    return dbt_rs.load_manifest(json.dumps({}), set_global=False)
+
+
+@pytest.fixture
+def resolver():
+    return dbt_rs.DbtConfigResolver(
+        "shop",
+        {
+            "shop": {
+                "name": "shop",
+                "models": {"shop": {"marts": {"+materialized": "table", "+schema": "marts"}}},
+                "sources": {"+tags": ["raw"]},
+            }
+        },
+    )
+
+
+def build_model(manifest, resolver, path, contents, **kwargs):
+    return dbt_rs.build_node(
+        manifest,
+        resolver,
+        "model",
+        "shop",
+        path,
+        f"models/{path}",
+        contents,
+        database="analytics",
+        schema="dbt_me",
+        quote_char='"',
+        **kwargs,
+    )
+
+
+class TestNodeConstruction:
+    def test_build_register_and_link(self, manifest, resolver):
+        yaml = (
+            "sources:\n  - name: raw\n    tables:\n"
+            "      - name: orders\n        identifier: orders_v2\n"
+        )
+        [properties] = dbt_rs.parse_properties_files([("models/sources.yml", yaml)])
+        [source] = dbt_rs.build_sources(
+            manifest,
+            resolver,
+            properties["sources"][0],
+            "shop",
+            "sources.yml",
+            "models/sources.yml",
+            database="analytics",
+            quote_char='"',
+        )
+        assert source["unique_id"] == "source.shop.raw.orders"
+        assert source["fqn"] == ["shop", "raw", "orders"]
+        assert source["tags"] == ["raw"]
+        assert source["relation_name"] == '"analytics"."raw"."orders_v2"'
+
+        staging = build_model(
+            manifest,
+            resolver,
+            "staging/stg_orders.sql",
+            "{{ config(tags=['daily']) }}\nselect * from {{ source('raw', 'orders') }}",
+        )
+        assert staging["unique_id"] == "model.shop.stg_orders"
+        assert staging["fqn"] == ["shop", "staging", "stg_orders"]
+        assert staging["tags"] == ["daily"]
+        assert staging["schema"] == "dbt_me"
+        assert staging["depends_on"]["nodes"] == []
+
+        [schema] = dbt_rs.parse_properties_files(
+            [("models/marts/schema.yml", "models:\n  - name: orders\n    description: Orders\n")]
+        )
+        orders = build_model(
+            manifest,
+            resolver,
+            "marts/orders_v2.sql",
+            "select * from {{ ref('stg_orders') }}",
+            name="orders",
+            version=2,
+            properties=schema["models"][0],
+            macros=["macro.dbt.run_query"],
+        )
+        assert orders["unique_id"] == "model.shop.orders.v2"
+        assert orders["fqn"] == ["shop", "marts", "orders", "v2"]
+        assert orders["config"]["materialized"] == "table"
+        assert orders["alias"] == "orders_v2"
+        assert orders["schema"] == "dbt_me_marts"
+        assert orders["description"] == "Orders"
+        assert orders["relation_name"] == '"analytics"."dbt_me_marts"."orders_v2"'
+
+        assert dbt_rs.link_dependencies(manifest, current_project="shop") == []
+        assert manifest.nodes["model.shop.orders.v2"].depends_on_nodes == ["model.shop.stg_orders"]
+        assert manifest.nodes["model.shop.stg_orders"].depends_on_nodes == [
+            "source.shop.raw.orders"
+        ]
+        assert "source.shop.raw.orders" in manifest.sources
+
+    def test_explicit_calls_and_register(self, manifest, resolver):
+        node = build_model(
+            manifest,
+            resolver,
+            "rendered.sql",
+            "select * from {{ ref(model_name) }}",
+            refs=[{"name": "missing", "package": None, "version": None}],
+            config_calls=[{"materialized": "incremental"}],
+            register=False,
+        )
+        assert node["config"]["materialized"] == "incremental"
+        assert manifest.node_count() == 0
+
+        build_model(manifest, resolver, "rendered.sql", "select 1", refs=[{"name": "missing"}])
+        [error] = dbt_rs.link_dependencies(
+            manifest, ["model.shop.rendered"], current_project="shop"
+        )
+        assert error["unique_id"] == "model.shop.rendered"
+        assert error["kind"] == "not_found"
+        assert error["message"]
+
+    def test_disabled_nodes_are_registered_as_disabled(self, manifest, resolver):
+        build_model(manifest, resolver, "off.sql", "{{ config(enabled=false) }}select 1")
+
+        assert manifest.node_count() == 0
+        assert manifest.remove_disabled(["model.shop.off"]) == ["model.shop.off"]
+
+    def test_invalid_node(self, manifest, resolver):
+        with pytest.raises(ValueError):
+            build_model(manifest, resolver, "a.sql", "select 1", config_calls=[{"enabled": "yes"}])
@@ -0,0 +1,82 @@
+import dbt_rs
+
+SCHEMA_YML = """\
+version: 2
+
+models:
+  - name: orders
+    config:
+      materialized: table
+      meta: {flag: yes, since: 2024-01-02}
+    columns:
+      - name: id
+        tests: [unique, not_null]
+
+sources:
+  - name: raw
+    tables:
+      - name: payments
+"""
+
+
+class TestParsePropertiesFiles:
+    def test_parse(self):
+        [result] = dbt_rs.parse_properties_files([("models/schema.yml", SCHEMA_YML)], threads=2)
+
+        assert result["path"] == "models/schema.yml"
+        assert result["error"] is None
+        assert result["version"] == 2
+        assert result["warnings"] == []
+        assert result["seeds"] == []
+
+        [orders] = result["models"]
+        assert (orders["line"], orders["column"]) == (4, 5)
+        assert orders["name"] == "orders"
+        assert orders["config"]["meta"]["flag"] is True
+        assert str(orders["config"]["meta"]["since"]) == "2024-01-02"
+        assert orders["columns"][0]["data_tests"] == ["unique", "not_null"]
+        assert "tests" not in orders["columns"][0]
+
+        [raw] = result["sources"]
+        assert (raw["line"], raw["column"]) == (13, 5)
+        assert raw["tables"][0]["name"] == "payments"
+
+    def test_schema_violations_are_warnings(self):
+        source = "version: 2\nmodels:\n  - name: orders\n    colums:\n      - name: id\n"
+
+        [result] = dbt_rs.parse_properties_files([("schema.yml", source)])
+        [warning] = result["warnings"]
+        assert warning["path"] == "/models/0/colums"
+        assert warning["line"] == 4
+        assert warning["message"]
+
+        [result] = dbt_rs.parse_properties_files([("schema.yml", source)], validate=False)
+        assert result["warnings"] == []
+
+    def test_extra_top_level_keys(self):
+        [result] = dbt_rs.parse_properties_files([("schema.yml", "version: 2\ncustom_key: 1\n")])
+
+        assert result["extra"] == {"custom_key": 1}
+        assert [warning["path"] for warning in result["warnings"]] == ["/custom_key"]
+
+    def test_errors(self, tmp_path):
+        (tmp_path / "a.yml").write_text("models:\n  - name: a\n")
+        results = dbt_rs.parse_properties_files(
+            [
+                str(tmp_path / "a.yml"),
+                str(tmp_path / "missing.yml"),
+                ("bad.yml", "models:\n  name: a\n"),
+            ]
+        )
+
+        assert results[0]["models"][0]["name"] == "a"
+        assert results[1]["error"]["message"]
+        assert results[1]["error"]["line"] is None
+        assert results[2] == {
+            "path": "bad.yml",
+            "error": {
+                "message": "models must be a list, got dict instead",
+                "line": 1,
+                "column": 1,
+            },
+        }
@@ -0,0 +1,98 @@
+import hashlib
+import json
+from pathlib import Path
+
+import dbt_rs
+import pytest
+
+
+def sha256(text):
+    return {"name": "sha256", "checksum": hashlib.sha256(text.encode()).hexdigest()}
+
+
+def saved_file(relative_path, checksum):
+    return {
+        "path": {
+            "searched_path": "models",
+            "relative_path": relative_path,
+            "modification_time": 0.0,
+            "project_root": "/project",
+        },
+        "checksum": checksum,
+        "project_name": "test_package",
+        "parse_file_type": "model",
+    }
+
+
+class TestScanProjectFiles:
+    @pytest.fixture
+    def project(self, tmp_path):
+        (tmp_path / "models" / "staging").mkdir(parents=True)
+        (tmp_path / "models" / "scratch").mkdir()
+        (tmp_path / "models" / "a.sql").write_text("select 1")
+        (tmp_path / "models" / "staging" / "b.sql").write_text("select 2")
+        (tmp_path / "models" / "notes.md").write_text("not a model")
+        (tmp_path / "models" / "scratch" / "c.sql").write_text("select 3")
+        (tmp_path / ".dbtignore").write_text("models/scratch/\n")
+        return {
+            "project_name": "test_package",
+            "project_root": str(tmp_path),
+            "file_types": {"model": {"paths": ["models"], "extensions": [".sql"]}},
+        }
+
+    def test_scan(self, project):
+        result = dbt_rs.scan_project_files([project], threads=2)
+
+        assert [file["file_id"] for file in result["files"]] == [
+            "test_package://models/a.sql",
+            "test_package://models/staging/b.sql",
+        ]
+        file = result["files"][1]
+        assert file["project_name"] == "test_package"
+        assert file["searched_path"] == "models"
+        assert file["relative_path"] == "staging/b.sql"
+        assert file["original_file_path"] == "models/staging/b.sql"
+        assert file["parse_file_type"] == "model"
+        assert file["modification_time"] > 0
+        assert file["checksum"] == sha256("select 2")
+        assert result["added"] is None
+
+    def test_ignore_lines_replace_dbtignore(self, project):
+        project["ignore_lines"] = ["staging/"]
+
+        result = dbt_rs.scan_project_files([project])
+
+        assert [file["relative_path"] for file in result["files"]] == [
+            "a.sql",
+            "scratch/c.sql",
+        ]
+
+    def test_diff_against_previous(self, project):
+        previous = dbt_rs.load_manifest(
+            json.dumps(
+                {
+                    "nodes": {},
+                    "files": {
+                        "test_package://models/a.sql": saved_file("a.sql", sha256("select 1")),
+                        "test_package://models/staging/b.sql": saved_file(
+                            "staging/b.sql", sha256("select 0")
+                        ),
+                        "test_package://models/gone.sql": saved_file("gone.sql", sha256("")),
+                    },
+                }
+            ),
+            set_global=False,
+        )
+        (Path(project["project_root"]) / "models" / "new.sql").write_text("select 4")
+
+        result = dbt_rs.scan_project_files([project], previous)
+
+        assert result["added"] == ["test_package://models/new.sql"]
+        assert result["changed"] == ["test_package://models/staging/b.sql"]
+        assert result["deleted"] == ["test_package://models/gone.sql"]
+        assert result["unchanged"] == ["test_package://models/a.sql"]
+
+    def test_missing_key(self, project):
+        del project["file_types"]
+        with pytest.raises(KeyError, match="project is missing 'file_types'"):
+            dbt_rs.scan_project_files([project])
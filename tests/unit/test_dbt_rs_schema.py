@@ -0,0 +1,52 @@
+import json
+from pathlib import Path
+
+import dbt_rs
+import pytest
+
+STATE_DIR = Path(__file__).parents[1] / "functional" / "artifacts" / "data" / "state"
+
+
+@pytest.fixture
+def manifest():
+    return json.loads((STATE_DIR / "v12" / "manifest.json").read_text())
+
+
+def first_model_id(manifest):
+    return next(
+        unique_id
+        for unique_id, node in manifest["nodes"].items()
+        if node["resource_type"] == "model"
+    )
+
+
+class TestValidateArtifact:
+    def test_valid_manifest(self, manifest):
+        assert dbt_rs.validate_artifact_json(json.dumps(manifest)) == []
+
+    def test_violations(self, manifest):
+        model_id = first_model_id(manifest)
+        manifest["nodes"][model_id]["config"]["materialized"] = 5
+        del manifest["macros"]
+
+        violations = dbt_rs.validate_artifact_json(json.dumps(manifest))
+
+        assert [violation["message"] for violation in violations] == [
+            '"macros" is a required property',
+            "expected string, found integer",
+        ]
+        assert violations[1]["path"] == f"/nodes/{model_id}/config/materialized"
+
+    def test_validate_against(self, manifest):
+        del manifest["metadata"]["dbt_schema_version"]
+        with pytest.raises(ValueError, match="missing metadata.dbt_schema_version"):
+            dbt_rs.validate_artifact_json(json.dumps(manifest))
+
+        assert dbt_rs.validate_artifact_json_against(json.dumps(manifest), "manifest", 12) == []
+        assert dbt_rs.validate_artifact_json_against(json.dumps({}), "run-results", 6) != []
+        with pytest.raises(ValueError, match="No bundled schema for manifest v99"):
+            dbt_rs.validate_artifact_json_against(json.dumps(manifest), "manifest", 99)
+
+    def test_validate_manifest_handle(self, manifest):
+        handle = dbt_rs.load_manifest(json.dumps(manifest), set_global=False)
+        assert dbt_rs.validate_manifest_handle(handle) == []
@@ -0,0 +1,95 @@
+import json
+
+import dbt_rs
+import pytest
+
+
+def make_node(name, relation_name=None, **fields):
+    node = {
+        "unique_id": f"model.p.{name}",
+        "name": name,
+        "resource_type": "model",
+        "package_name": "p",
+    }
+    if relation_name:
+        node["relation_name"] = relation_name
+    node.update(fields)
+    return node
+
+
+class TestExtractSqlRelations:
+    def test_relations(self):
+        sql = """
+            with base as (select id from "DB"."Raw"."Orders")
+            select * from base
+            join db.raw.customers c using (id)
+            where exists (select 1 from db.raw.refunds r where r.id = base.id)
+        """
+        assert dbt_rs.extract_sql_relations(sql, "postgres") == [
+            "db.raw.orders",
+            "db.raw.customers",
+            "db.raw.refunds",
+        ]
+
+    def test_dialects(self):
+        assert dbt_rs.extract_sql_relations(
+            "select * from `my-proj.ds.events_*`", dialect="bigquery"
+        ) == ["my-proj.ds.events_*"]
+        assert dbt_rs.extract_sql_relations("select * from t1") == ["t1"]
+
+    def test_errors(self):
+        with pytest.raises(ValueError):
+            dbt_rs.extract_sql_relations("select * from", "ansi")
+        with pytest.raises(ValueError):
+            dbt_rs.extract_sql_relations("select 1", "oracle")
+
+
+class TestFindUndeclaredDependencies:
+    @pytest.fixture
+    def manifest(self):
+        orders = make_node(
+            "orders",
+            '"db"."main"."orders"',
+            depends_on={"nodes": ["model.p.stg", "model.p.unused"], "macros": []},
+            compiled_code=(
+                'select * from "db"."main"."stg"'
+                " join main.customers using (id) join raw.hardcoded using (id)"
+            ),
+        )
+        broken = make_node("broken", compiled_code="select * from")
+        nodes = [
+            orders,
+            broken,
+            make_node("stg", '"db"."main"."stg"'),
+            make_node("unused", '"db"."main"."unused"'),
+            make_node("customers", '"db"."main"."customers"'),
+        ]
+        return dbt_rs.load_manifest(
+            json.dumps(
+                {
+                    "metadata": {"adapter_type": "postgres"},
+                    "nodes": {node["unique_id"]: node for node in nodes},
+                }
+            ),
+            set_global=False,
+        )
+
+    def test_report(self, manifest):
+        reports = dbt_rs.find_undeclared_dependencies(manifest)
+
+        assert [report["unique_id"] for report in reports] == ["model.p.broken", "model.p.orders"]
+        assert reports[0]["undeclared"] == []
+        assert reports[0]["parse_error"] is not None
+        assert reports[1] == {
+            "unique_id": "model.p.orders",
+            "undeclared": [
+                {"relation": "main.customers", "unique_id": "model.p.customers"},
+                {"relation": "raw.hardcoded", "unique_id": None},
+            ],
+            "unused": ["model.p.unused"],
+            "parse_error": None,
+        }
+
+    def test_selected_nodes(self, manifest):
+        reports = dbt_rs.find_undeclared_dependencies(manifest, ["model.p.orders"], "postgres")
+        assert [report["unique_id"] for report in reports] == ["model.p.orders"]
@@ -0,0 +1,74 @@
+import json
+
+import dbt_rs
+
+SIMPLE_MODEL = """
+{{ config(materialized='incremental', tags=["a", "b"], meta={'owner': 'x'}, enabled=true) }}
+select * from {{ ref('orders') }}
+join {{ ref("pkg", 'customers', v=2) }} using (id)
+join {{ source('raw', 'payments') }} using (id)
+where {{ metric('revenue') }} is not null
+"""
+
+
+def make_node(name, raw_code):
+    return {
+        "unique_id": f"model.test_package.{name}",
+        "name": name,
+        "resource_type": "model",
+        "package_name": "test_package",
+        "raw_code": raw_code,
+    }
+
+
+class TestExtractStaticCalls:
+    def test_simple_model(self):
+        assert dbt_rs.extract_static_calls(SIMPLE_MODEL) == {
+            "refs": [
+                {"name": "orders", "package": None, "version": None},
+                {"name": "customers", "package": "pkg", "version": 2},
+            ],
+            "sources": [["raw", "payments"]],
+            "configs": [
+                ["materialized", "incremental"],
+                ["tags", ["a", "b"]],
+                ["meta", {"owner": "x"}],
+                ["enabled", True],
+            ],
+            "metrics": [["revenue"]],
+            "simple": True,
+        }
+
+    def test_template_that_needs_rendering(self):
+        extracted = dbt_rs.extract_static_calls(
+            "{% if target.name == 'prod' %}{{ ref('a', version='1') }}"
+            "{% else %}{{ ref(x) }}{% endif %}"
+        )
+
+        assert not extracted["simple"]
+        assert extracted["refs"] == [{"name": "a", "package": None, "version": "1"}]
+
+    def test_for_nodes(self):
+        manifest = dbt_rs.load_manifest(
+            json.dumps(
+                {
+                    "nodes": {
+                        "model.test_package.a": make_node("a", "select 1"),
+                        "model.test_package.b": make_node("b", "select * from {{ ref('a') }}"),
+                    }
+                }
+            ),
+            set_global=False,
+        )
+
+        extracted = dbt_rs.extract_static_calls_for_nodes(manifest)
+        assert sorted(extracted) == ["model.test_package.a", "model.test_package.b"]
+        assert extracted["model.test_package.b"]["refs"] == [
+            {"name": "a", "package": None, "version": None}
+        ]
+
+        extracted = dbt_rs.extract_static_calls_for_nodes(
+            manifest, ["model.test_package.a", "model.test_package.missing"]
+        )
+        assert list(extracted) == ["model.test_package.a"]
+        assert extracted["model.test_package.a"]["simple"]